@@ -0,0 +1,233 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::diff;
+use crate::dynamic;
+use crate::loader;
+use crate::symbol;
+
+//whether consumers built against the old library keep working with the new one
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Severity {
+    Compatible,
+    Breaking,
+}
+
+//one difference in the exported interface
+#[derive(Debug)]
+pub struct AbiChange {
+    pub severity:    Severity,
+    pub description: String,
+}
+
+impl fmt::Display for AbiChange {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.severity {
+            Severity::Compatible => write!(f, "compatible: {}", self.description),
+            Severity::Breaking   => write!(f, "BREAKING:   {}", self.description),
+        }
+    }
+}
+
+//a symbol other modules can bind to
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub name:    String,
+
+    //version name, None for unversioned symbols
+    pub version: Option<String>,
+
+    //true for name@VERSION, which only binds for consumers linked against that version
+    pub hidden:  bool,
+
+    //STT_* type and STB_* binding of the symbol
+    pub symType: u8,
+    pub bind:    u8,
+    pub size:    usize,
+}
+
+impl Export {
+
+    //name@VERSION or name@@VERSION as the linker prints it
+    pub fn label(&self) -> String {
+        match (&self.version, self.hidden) {
+            (Some(v), true)  => format!("{}@{}", self.name, v),
+            (Some(v), false) => format!("{}@@{}", self.name, v),
+            (None, _)        => self.name.clone(),
+        }
+    }
+}
+
+//returns every defined global or weak dynamic symbol with default or protected visibility
+//the absolute symbols the linker emits for each version name are left out
+pub fn exports(loader: &loader::Loader) -> Vec<Export> {
+    let mut exports = vec![];
+    for (i, s) in loader.dynamicSymbols.iter().enumerate() {
+        let bind = s.getBind();
+        if s.isUndefined() || s.name.is_empty() {continue;}
+        if bind != symbol::STB_GLOBAL && bind != symbol::STB_WEAK && bind != symbol::STB_GNU_UNIQUE {continue;}
+        let visibility = s.getVisibility();
+        if visibility != symbol::STV_DEFAULT && visibility != symbol::STV_PROTECTED {continue;}
+        let version = loader.symbolVersion(i);
+        if s.st_shndx == symbol::SHN_ABS && version.as_ref().map(|v| v.name == s.name).unwrap_or(false) {continue;}
+        exports.push(Export {
+            name:    s.name.clone(),
+            hidden:  version.as_ref().map(|v| v.hidden).unwrap_or(false),
+            version: version.map(|v| v.name),
+            symType: s.getType(),
+            bind:    bind,
+            size:    s.st_size,
+        });
+    }
+    exports
+}
+
+//the interface changes between two versions of a shared object
+pub struct AbiReport {
+    pub changes: Vec<AbiChange>,
+}
+
+impl AbiReport {
+
+    //compares the SONAME, version definitions and exported symbols of two versions of a library
+    pub fn new(old: &loader::Loader, new: &loader::Loader) -> Self {
+        let mut report = Self{changes: vec![]};
+
+        let oldSoname = old.getDynamic(dynamic::DT_SONAME).and_then(|o| old.dynamicString(o));
+        let newSoname = new.getDynamic(dynamic::DT_SONAME).and_then(|o| new.dynamicString(o));
+        if oldSoname != newSoname {
+            //a new SONAME is how a library announces a break, consumers keep asking for the old name
+            report.push(Severity::Breaking, format!("SONAME changed from {} to {}",
+                oldSoname.as_deref().unwrap_or("(none)"), newSoname.as_deref().unwrap_or("(none)")));
+        }
+
+        let versions = |l: &loader::Loader| -> Vec<String> {
+            l.versionDefinitions.iter()
+                .filter(|d| d.vd_flags & crate::version::VER_FLG_BASE == 0)
+                .map(|d| d.name().to_string())
+                .collect()
+        };
+        let oldVersions = versions(old);
+        let newVersions = versions(new);
+        for v in oldVersions.iter().filter(|v| !newVersions.contains(v)) {
+            report.push(Severity::Breaking, format!("version {} removed", v));
+        }
+        for v in newVersions.iter().filter(|v| !oldVersions.contains(v)) {
+            report.push(Severity::Compatible, format!("version {} added", v));
+        }
+
+        //consumers record name and version, so that pair is what has to survive
+        let byKey = |l: &loader::Loader| -> BTreeMap<(String, Option<String>), Export> {
+            exports(l).into_iter().map(|e| ((e.name.clone(), e.version.clone()), e)).collect()
+        };
+        let oldExports = byKey(old);
+        let newExports = byKey(new);
+        let mut matched = vec![];
+        for (key, a) in oldExports.iter() {
+            //consumers of an unversioned symbol bind to the default version once the library has one
+            let b = match newExports.get(key) {
+                Some(b) => b,
+                None    => match newExports.values().find(|b| a.version.is_none() && b.name == a.name && b.version.is_some() && !b.hidden) {
+                    Some(b) => {
+                        report.push(Severity::Compatible, format!("{} became {}", a.label(), b.label()));
+                        b
+                    },
+                    None => {
+                        report.push(Severity::Breaking, format!("{} removed", a.label()));
+                        continue;
+                    },
+                },
+            };
+            matched.push((b.name.clone(), b.version.clone()));
+            if a.symType != b.symType && isFunction(a.symType) && isFunction(b.symType) {
+                //an IFUNC resolves to a function before anyone calls it
+                report.push(Severity::Compatible, format!("{} changed type from {} to {}",
+                    a.label(), typeName(a.symType), typeName(b.symType)));
+            } else if a.symType != b.symType {
+                report.push(Severity::Breaking, format!("{} changed type from {} to {}",
+                    a.label(), typeName(a.symType), typeName(b.symType)));
+            } else if isData(a.symType) && a.size != b.size {
+                //copy relocations reserve the old size in the executable
+                report.push(Severity::Breaking, format!("{} changed size from {} to {}", a.label(), a.size, b.size));
+            }
+            if a.hidden != b.hidden {
+                let severity = if b.hidden {Severity::Breaking} else {Severity::Compatible};
+                report.push(severity, format!("{} became {}", a.label(), b.label()));
+            }
+            if a.bind != b.bind {
+                //a weak definition can be silently preempted, going the other way is harmless
+                let severity = if b.bind == symbol::STB_WEAK {Severity::Breaking} else {Severity::Compatible};
+                report.push(severity, format!("{} changed binding from {} to {}", a.label(), bindName(a.bind), bindName(b.bind)));
+            }
+        }
+        for (key, b) in newExports.iter() {
+            if !matched.contains(key) {
+                report.push(Severity::Compatible, format!("{} added", b.label()));
+            }
+        }
+        report
+    }
+
+    fn push(&mut self, severity: Severity, description: String) {
+        self.changes.push(AbiChange{severity: severity, description: description});
+    }
+
+    //true when no change breaks existing consumers
+    pub fn isCompatible(&self) -> bool {
+        self.changes.iter().all(|c| c.severity == Severity::Compatible)
+    }
+
+    //formats the report as a JSON object for CI tooling
+    pub fn toJson(&self) -> String {
+        let mut out = format!("{{\n  \"compatible\": {},\n  \"changes\": [", self.isCompatible());
+        for (i, c) in self.changes.iter().enumerate() {
+            if i > 0 {out.push(',');}
+            let severity = match c.severity {
+                Severity::Compatible => "compatible",
+                Severity::Breaking   => "breaking",
+            };
+            out.push_str(&format!("\n    {{\"severity\": \"{}\", \"description\": {}}}", severity, diff::jsonString(&c.description)));
+        }
+        out.push_str(if self.changes.is_empty() {"]\n}"} else {"\n  ]\n}"});
+        out
+    }
+}
+
+impl fmt::Display for AbiReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.changes.iter() {
+            writeln!(f, "{}", c)?;
+        }
+        write!(f, "{}", if self.isCompatible() {"result: compatible"} else {"result: BREAKING"})
+    }
+}
+
+//types whose size is part of the interface
+fn isData(symType: u8) -> bool {
+    symType == symbol::STT_OBJECT || symType == symbol::STT_TLS || symType == symbol::STT_COMMON
+}
+
+//types callers reach through a plain call, whatever resolves it
+fn isFunction(symType: u8) -> bool {
+    symType == symbol::STT_FUNC || symType == symbol::STT_GNU_IFUNC
+}
+
+fn typeName(symType: u8) -> String {
+    match symType {
+        symbol::STT_NOTYPE    => "notype".to_string(),
+        symbol::STT_OBJECT    => "object".to_string(),
+        symbol::STT_FUNC      => "func".to_string(),
+        symbol::STT_COMMON    => "common".to_string(),
+        symbol::STT_TLS       => "tls".to_string(),
+        symbol::STT_GNU_IFUNC => "ifunc".to_string(),
+        t                     => format!("type {}", t),
+    }
+}
+
+fn bindName(bind: u8) -> String {
+    match bind {
+        symbol::STB_GLOBAL     => "global".to_string(),
+        symbol::STB_WEAK       => "weak".to_string(),
+        symbol::STB_GNU_UNIQUE => "unique".to_string(),
+        b                      => format!("binding {}", b),
+    }
+}
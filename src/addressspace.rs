@@ -0,0 +1,408 @@
+use std::collections::HashMap;
+use crate::loader;
+use crate::programheader;
+
+//granularity of snapshot tracking
+pub const PAGE_SIZE: usize = 4096;
+
+//a single contiguous mapping in the address space
+#[derive(Debug)]
+pub struct Region {
+
+    //virtual address of the first byte of the region
+    pub start:  usize,
+
+    //backing bytes of the region, its length is the size of the mapping
+    pub data:   Vec<u8>,
+
+    //permission bits using the same PF_X, PF_W, PF_R values as program headers
+    pub flags:  u32,
+}
+
+impl Region {
+
+    //returns the address one past the last byte of the region
+    pub fn end(&self) -> usize {
+        self.start + self.data.len()
+    }
+
+    //returns true if addr falls inside of the region
+    pub fn contains(&self, addr: usize) -> bool {
+        addr >= self.start && addr < self.end()
+    }
+}
+
+//state captured by AddressSpace::snapshot
+//memory is copy-on-write, a page is only copied the first time it changes after the snapshot
+pub struct Snapshot {
+    //start, size and flags of every region when the snapshot was taken
+    pub layout:        Vec<(usize, usize, u32)>,
+
+    //original contents of every page modified since the snapshot, keyed by page address
+    //a page is saved as (address, bytes) pieces, one for each region covering part of it
+    pub saved:         HashMap<usize, Vec<(usize, Vec<u8>)>>,
+
+    //set once regions were mapped, unmapped or had their flags changed
+    pub layoutChanged: bool,
+}
+
+//segment based memory image of a program
+//regions are kept sorted by start address and never overlap
+pub struct AddressSpace {
+    pub regions:  Vec<Region>,
+
+    //endianness used by the word helpers, same values as e_ident.Data
+    pub data:     u8,
+
+    //copy-on-write state used by restore, None until snapshot is called
+    pub snapshot: Option<Snapshot>,
+}
+
+impl AddressSpace {
+
+    //creates an empty little endian address space
+    pub fn new() -> Self {
+        Self {
+            regions:  vec![],
+            data:     1,
+            snapshot: None,
+        }
+    }
+
+    //creates an address space containing every PT_LOAD segment of the loaded ELF
+    //bias is added to each segment address, which is how PIE executables and shared objects get
+    //placed. bytes past p_filesz are zero filled like the kernel does for .bss
+    pub fn fromLoader(loader: &loader::Loader, bias: usize) -> Option<Self> {
+        let mut space = Self::new();
+        space.data = loader.header.e_ident.Data;
+
+        for pHeader in loader.programHeaders.iter() {
+            if pHeader.getTYPE() != programheader::PT_LOAD {continue;}
+            let start = bias.checked_add(pHeader.getVADDR())?;
+            space.mapOrExtend(start, pHeader.getMEMSZ(), pHeader.getFLAGS())?;
+
+            let fileBytes = loader.fileVec.get(pHeader.getOFFSET()..pHeader.getOFFSET().checked_add(pHeader.getFILESZ())?)?;
+            space.write(start, fileBytes)?;
+        }
+        Some(space)
+    }
+
+    //maps a new zeroed region, returns None if it would overlap an existing one
+    pub fn map(&mut self, start: usize, size: usize, flags: u32) -> Option<()> {
+        let end = start.checked_add(size)?;
+        if size == 0 {return None;}
+        if self.regions.iter().any(|r| start < r.end() && r.start < end) {return None;}
+        if let Some(snapshot) = self.snapshot.as_mut() {snapshot.layoutChanged = true;}
+
+        let index = self.regions.iter().position(|r| r.start > start).unwrap_or(self.regions.len());
+        self.regions.insert(index, Region{start: start, data: vec![0; size], flags: flags});
+        Some(())
+    }
+
+    //maps a region like map but instead of failing on overlap it grows the existing
+    //regions so the whole range is covered. segments commonly share a page so this
+    //is what loading PT_LOAD segments needs. returns None if the range runs past the address space
+    pub fn mapOrExtend(&mut self, start: usize, size: usize, flags: u32) -> Option<()> {
+        if size == 0 {return Some(());}
+        let end = start.checked_add(size)?;
+        self.preserveLayout(start, size);
+
+        //take every region that touches the new range out and merge them
+        let mut mergedStart = start;
+        let mut mergedEnd = end;
+        let mut mergedFlags = flags;
+        let mut touching = vec![];
+        let mut i = 0;
+        while i < self.regions.len() {
+            if self.regions[i].start < end && start < self.regions[i].end() {
+                let r = self.regions.remove(i);
+                mergedStart = mergedStart.min(r.start);
+                mergedEnd = mergedEnd.max(r.end());
+                mergedFlags |= r.flags;
+                touching.push(r);
+            } else {
+                i += 1;
+            }
+        }
+
+        let mut data = vec![0; mergedEnd - mergedStart];
+        for r in touching.iter() {
+            let offset = r.start - mergedStart;
+            data[offset..offset + r.data.len()].copy_from_slice(&r.data);
+        }
+        let index = self.regions.iter().position(|r| r.start > mergedStart).unwrap_or(self.regions.len());
+        self.regions.insert(index, Region{start: mergedStart, data: data, flags: mergedFlags});
+        Some(())
+    }
+
+    //removes the range from the address space, splitting regions when needed
+    pub fn unmap(&mut self, start: usize, size: usize) {
+        let end = start.saturating_add(size);
+        self.preserveLayout(start, size);
+        let mut kept = vec![];
+        for r in self.regions.drain(..) {
+            if r.end() <= start || r.start >= end {
+                kept.push(r);
+                continue;
+            }
+            if r.start < start {
+                kept.push(Region{start: r.start, data: r.data[..start - r.start].to_vec(), flags: r.flags});
+            }
+            if r.end() > end {
+                kept.push(Region{start: end, data: r.data[end - r.start..].to_vec(), flags: r.flags});
+            }
+        }
+        self.regions = kept;
+    }
+
+    //changes the permission bits of every region inside the range, splitting regions when needed
+    pub fn protect(&mut self, start: usize, size: usize, flags: u32) {
+        let end = start.saturating_add(size);
+        self.preserveLayout(start, size);
+        let mut result = vec![];
+        for r in self.regions.drain(..) {
+            if r.end() <= start || r.start >= end {
+                result.push(r);
+                continue;
+            }
+            let lo = start.max(r.start);
+            let hi = end.min(r.end());
+            if r.start < lo {
+                result.push(Region{start: r.start, data: r.data[..lo - r.start].to_vec(), flags: r.flags});
+            }
+            result.push(Region{start: lo, data: r.data[lo - r.start..hi - r.start].to_vec(), flags: flags});
+            if r.end() > hi {
+                result.push(Region{start: hi, data: r.data[hi - r.start..].to_vec(), flags: r.flags});
+            }
+        }
+        self.regions = result;
+    }
+
+    //returns the region containing addr
+    pub fn region(&self, addr: usize) -> Option<&Region> {
+        let index = self.regions.partition_point(|r| r.end() <= addr);
+        self.regions.get(index).filter(|r| r.contains(addr))
+    }
+
+    //returns the region containing addr as mutable
+    pub fn regionMut(&mut self, addr: usize) -> Option<&mut Region> {
+        let index = self.regions.partition_point(|r| r.end() <= addr);
+        self.regions.get_mut(index).filter(|r| r.contains(addr))
+    }
+
+    //returns true if every byte of the range is mapped, the range may cross adjacent regions
+    pub fn isMapped(&self, addr: usize, len: usize) -> bool {
+        let end = match addr.checked_add(len) {
+            Some(e) => e,
+            None    => return false,
+        };
+        let mut cur = addr;
+        while cur < end {
+            cur = match self.region(cur) {
+                Some(r) => r.end(),
+                None    => return false,
+            };
+        }
+        true
+    }
+
+    //copies len bytes starting at addr, the range may cross adjacent regions
+    pub fn read(&self, addr: usize, len: usize) -> Option<Vec<u8>> {
+        //the length often comes from the guest, so nothing is allocated for an unmapped range
+        if !self.isMapped(addr, len) {return None;}
+        let mut out = Vec::with_capacity(len);
+        let mut cur = addr;
+        let end = addr + len;
+        while cur < end {
+            let r = self.region(cur)?;
+            let take = (end - cur).min(r.end() - cur);
+            out.extend_from_slice(&r.data[cur - r.start..cur - r.start + take]);
+            cur += take;
+        }
+        Some(out)
+    }
+
+    //writes bytes starting at addr ignoring permissions, the range may cross adjacent regions
+    pub fn write(&mut self, addr: usize, bytes: &[u8]) -> Option<()> {
+        let end = addr.checked_add(bytes.len())?;
+        //check the whole range first so a failed write leaves memory untouched
+        if !self.isMapped(addr, bytes.len()) {return None;}
+        self.preserve(addr, bytes.len());
+        let mut cur = addr;
+        while cur < end {
+            let r = self.regionMut(cur)?;
+            let take = (end - cur).min(r.end() - cur);
+            let offset = cur - r.start;
+            r.data[offset..offset + take].copy_from_slice(&bytes[cur - addr..cur - addr + take]);
+            cur += take;
+        }
+        Some(())
+    }
+
+    //reads an unsigned value of size bytes using the endianness of the address space
+    pub fn readUValue(&self, addr: usize, size: usize) -> Option<u64> {
+        let bytes = self.read(addr, size)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = match self.data {
+                2 => bytes[i],
+                _ => bytes[size - 1 - i],
+            };
+            value = (value << 8) | b as u64;
+        }
+        Some(value)
+    }
+
+    //writes the low size bytes of value using the endianness of the address space
+    pub fn writeUValue(&mut self, addr: usize, size: usize, value: u64) -> Option<()> {
+        let mut bytes = vec![0u8; size];
+        for i in 0..size {
+            let b = (value >> (8 * i)) as u8;
+            match self.data {
+                2 => bytes[size - 1 - i] = b,
+                _ => bytes[i] = b,
+            }
+        }
+        self.write(addr, &bytes)
+    }
+
+    //reads a nul terminated string starting at addr
+    pub fn readCString(&self, addr: usize) -> Option<Vec<u8>> {
+        let mut out = vec![];
+        let mut cur = addr;
+        loop {
+            let r = self.region(cur)?;
+            let slice = &r.data[cur - r.start..];
+            match slice.iter().position(|b| *b == 0) {
+                Some(n) => {
+                    out.extend_from_slice(&slice[..n]);
+                    return Some(out);
+                },
+                None => {
+                    out.extend_from_slice(slice);
+                    cur = r.end();
+                },
+            }
+        }
+    }
+
+    //saves the current layout as the state restore goes back to
+    //no memory is copied here, pages are copied lazily the first time they are modified
+    pub fn snapshot(&mut self) {
+        self.snapshot = Some(Snapshot {
+            layout:        self.regions.iter().map(|r| (r.start, r.data.len(), r.flags)).collect(),
+            saved:         HashMap::new(),
+            layoutChanged: false,
+        });
+    }
+
+    //puts memory back to how it was when snapshot was called and keeps the snapshot so it can
+    //be restored again. only pages dirtied since then are copied back. when the layout changed,
+    //regions that still have their old bounds are kept as they are and only the reshaped ones
+    //are rebuilt, from the current memory for pages that were never modified
+    //returns false if no snapshot was taken
+    pub fn restore(&mut self) -> bool {
+        let snapshot = match self.snapshot.take() {
+            Some(s) => s,
+            None    => return false,
+        };
+
+        if snapshot.layoutChanged {
+            let mut current = std::mem::take(&mut self.regions);
+            for (start, size, flags) in snapshot.layout.iter() {
+                //regions never overlap, so one with the same bounds holds nothing another needs
+                let same = current.binary_search_by_key(start, |r| r.start).ok().filter(|i| current[*i].data.len() == *size);
+                if let Some(i) = same {
+                    let mut r = current.remove(i);
+                    r.flags = *flags;
+                    self.regions.push(r);
+                    continue;
+                }
+                let end = start + size;
+                let mut data = vec![0u8; *size];
+                let first = current.partition_point(|r| r.end() <= *start);
+                for r in current[first..].iter() {
+                    if r.start >= end {break;}
+                    let lo = r.start.max(*start);
+                    let hi = r.end().min(end);
+                    data[lo - start..hi - start].copy_from_slice(&r.data[lo - r.start..hi - r.start]);
+                }
+                self.regions.push(Region{start: *start, data: data, flags: *flags});
+            }
+        }
+
+        //put back the original contents of every page modified since the snapshot
+        for (pieceStart, bytes) in snapshot.saved.values().flatten() {
+            let pieceEnd = pieceStart + bytes.len();
+            let first = self.regions.partition_point(|r| r.end() <= *pieceStart);
+            for r in self.regions[first..].iter_mut() {
+                if r.start >= pieceEnd {break;}
+                let lo = r.start.max(*pieceStart);
+                let hi = r.end().min(pieceEnd);
+                r.data[lo - r.start..hi - r.start].copy_from_slice(&bytes[lo - pieceStart..hi - pieceStart]);
+            }
+        }
+
+        self.snapshot = Some(Snapshot {
+            layout:        snapshot.layout,
+            saved:         HashMap::new(),
+            layoutChanged: false,
+        });
+        true
+    }
+
+    //returns the sorted addresses of the pages modified since the last snapshot or restore
+    pub fn dirtyPages(&self) -> Vec<usize> {
+        let mut pages: Vec<usize> = match self.snapshot.as_ref() {
+            Some(s) => s.saved.keys().cloned().collect(),
+            None    => vec![],
+        };
+        pages.sort();
+        pages
+    }
+
+    //copies the original contents of every page in the range that has not been saved since
+    //the last snapshot, called before memory in the range is modified. the whole page is saved
+    //the first time so later layout changes on it can not mix modified bytes into the copy
+    fn preserve(&mut self, addr: usize, len: usize) {
+        let snapshot = match self.snapshot.as_mut() {
+            Some(s) => s,
+            None    => return,
+        };
+        if len == 0 {return;}
+        let end = addr.saturating_add(len);
+        //only pages holding memory have anything to save, so the pages are taken from the regions
+        //overlapping the range instead of walking a range that can span the whole address space
+        let mut pages = vec![];
+        let first = self.regions.partition_point(|r| r.end() <= addr);
+        for r in self.regions[first..].iter() {
+            if r.start >= end {break;}
+            let last = end.min(r.end());
+            let mut page = addr.max(r.start) & !(PAGE_SIZE - 1);
+            while page < last {
+                if pages.last() != Some(&page) {pages.push(page);}
+                page = page.saturating_add(PAGE_SIZE);
+            }
+        }
+        for page in pages {
+            if snapshot.saved.contains_key(&page) {continue;}
+            let pageEnd = page.saturating_add(PAGE_SIZE);
+            let mut pieces = vec![];
+            let first = self.regions.partition_point(|r| r.end() <= page);
+            for r in self.regions[first..].iter() {
+                if r.start >= pageEnd {break;}
+                let lo = page.max(r.start);
+                let hi = pageEnd.min(r.end());
+                pieces.push((lo, r.data[lo - r.start..hi - r.start].to_vec()));
+            }
+            snapshot.saved.insert(page, pieces);
+        }
+    }
+
+    //preserves a range whose regions are about to be reshaped and marks the layout as changed
+    fn preserveLayout(&mut self, addr: usize, len: usize) {
+        if self.snapshot.is_none() {return;}
+        self.preserve(addr, len);
+        if let Some(snapshot) = self.snapshot.as_mut() {snapshot.layoutChanged = true;}
+    }
+}
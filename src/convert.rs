@@ -0,0 +1,256 @@
+use crate::loader;
+use crate::programheader;
+use crate::sectionheader;
+
+//data bytes per Intel HEX or S-record line and the line ending, as objcopy writes them
+const RECORD_LENGTH: usize = 16;
+const LINE_END:      &str = "\r\n";
+
+//a flat image larger than this is almost always a VMA gap between flash and RAM
+const MAX_BINARY_SIZE: u64 = 1 << 28;
+
+//output formats for firmware images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Binary,
+    IntelHex,
+    Srec,
+}
+
+impl Format {
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "binary"                       => Some(Format::Binary),
+            "ihex" | "hex"                 => Some(Format::IntelHex),
+            "srec" | "s19" | "s28" | "s37" => Some(Format::Srec),
+            _                              => None,
+        }
+    }
+
+    //guesses the format from the extension of the output file, flat binary when unknown
+    pub fn fromPath(path: &str) -> Self {
+        let extension = path.rsplit_once('.').map(|(_, e)| e.to_lowercase()).unwrap_or_default();
+        match extension.as_str() {
+            "hex" | "ihex" | "ihx"                  => Format::IntelHex,
+            "srec" | "s19" | "s28" | "s37" | "mot"  => Format::Srec,
+            _                                       => Format::Binary,
+        }
+    }
+}
+
+//bytes placed at one address, a section, a segment or the fill between two of them
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chunk {
+    pub name:    String,
+    pub address: u64,
+    pub data:    Vec<u8>,
+}
+
+//turns the loadable contents of an ELF file into the images flash programmers take
+pub struct Converter {
+    pub loader:     loader::Loader,
+
+    //place contents at their virtual addresses instead of their load addresses (p_paddr)
+    pub useVirtual: bool,
+
+    //fills the gaps between sections with this byte, flat binaries use 0 without it
+    pub gapFill:    Option<u8>,
+
+    //section name patterns with * and ?, only matching sections are kept when not empty
+    pub only:       Vec<String>,
+    pub remove:     Vec<String>,
+}
+
+impl Converter {
+
+    pub fn new(loader: loader::Loader) -> Self {
+        Self{loader: loader, useVirtual: false, gapFill: None, only: vec![], remove: vec![]}
+    }
+
+    //the address a section is written to, its virtual address with useVirtual
+    pub fn loadAddress(&self, index: usize) -> u64 {
+        if self.useVirtual {return self.loader.sectionHeaders[index].sh_addr as u64;}
+        loadAddress(&self.loader, index)
+    }
+
+    //the selected contents sorted by address with the gap fill between them, overlaps are an error
+    pub fn chunks(&self) -> Result<Vec<Chunk>, String> {
+        let mut chunks = vec![];
+        if self.loader.sectionHeaders.is_empty() {
+            //without section headers the segments are all there is
+            for (i, p) in self.loader.programHeaders.iter().enumerate() {
+                if p.getTYPE() != programheader::PT_LOAD || p.getFILESZ() == 0 {continue;}
+                let data = self.loader.fileVec.get(p.getOFFSET()..p.getOFFSET().saturating_add(p.getFILESZ())).ok_or("segment extends past the end of the file")?;
+                let address = if self.useVirtual {p.getVADDR()} else {p.getPADDR()};
+                chunks.push(Chunk{name: format!("segment {}", i), address: address as u64, data: data.to_vec()});
+            }
+        }
+        for (i, s) in self.loader.sectionHeaders.iter().enumerate() {
+            if s.sh_flags & sectionheader::SHF_ALLOC == 0 || s.sh_type == sectionheader::SHT_NOBITS || s.sh_size == 0 {continue;}
+            let name = self.loader.sectionName(i).unwrap_or_default();
+            if !self.only.is_empty() && !self.only.iter().any(|p| wildcard(p, &name)) {continue;}
+            if self.remove.iter().any(|p| wildcard(p, &name)) {continue;}
+            let data = self.loader.sectionData(i).ok_or_else(|| format!("{} extends past the end of the file", name))?;
+            chunks.push(Chunk{name: name, address: self.loadAddress(i), data: data.to_vec()});
+        }
+        chunks.sort_by_key(|c| c.address);
+
+        let mut result: Vec<Chunk> = vec![];
+        for c in chunks {
+            if c.address.checked_add(c.data.len() as u64).is_none() {
+                return Err(format!("{} at {:#x} runs past the end of the address space", c.name, c.address));
+            }
+            if let Some(last) = result.last() {
+                let end = last.address + last.data.len() as u64;
+                if c.address < end {
+                    return Err(format!("{} at {:#x} overlaps {} which ends at {:#x}", c.name, c.address, last.name, end));
+                }
+                if let Some(fill) = self.gapFill.filter(|_| c.address > end) {
+                    let data = vec![fill; (c.address - end) as usize];
+                    result.push(Chunk{name: "fill".to_string(), address: end, data: data});
+                }
+            }
+            result.push(c);
+        }
+        Ok(result)
+    }
+
+    //the image as the bytes from the lowest address to the end of the highest content
+    pub fn binary(&self) -> Result<Vec<u8>, String> {
+        let chunks = self.chunks()?;
+        let (start, end) = match (chunks.first(), chunks.last()) {
+            (Some(f), Some(l)) => (f.address, l.address + l.data.len() as u64),
+            _ => return Ok(vec![]),
+        };
+        if end - start > MAX_BINARY_SIZE {
+            return Err(format!("the image spans {:#x}..{:#x}, use Intel HEX or S-records instead", start, end));
+        }
+        let mut image = vec![self.gapFill.unwrap_or(0); (end - start) as usize];
+        for c in chunks.iter() {
+            let at = (c.address - start) as usize;
+            image[at..at + c.data.len()].copy_from_slice(&c.data);
+        }
+        Ok(image)
+    }
+
+    //the image as Intel HEX with extended linear address records and the entry point as start address
+    pub fn intelHex(&self) -> Result<String, String> {
+        let mut text = String::new();
+        let mut base = 0;
+        for c in self.chunks()?.iter() {
+            if c.address + c.data.len() as u64 > 1 << 32 {
+                return Err(format!("{} at {:#x} is beyond the 4 GiB Intel HEX can address", c.name, c.address));
+            }
+            let mut address = c.address;
+            let mut data = &c.data[..];
+            while !data.is_empty() {
+                if address >> 16 != base {
+                    base = address >> 16;
+                    text.push_str(&hexRecord(0, 4, &(base as u16).to_be_bytes()));
+                }
+                //a record may not cross into the next 64 KiB
+                let length = RECORD_LENGTH.min(data.len()).min((0x10000 - (address & 0xffff)) as usize);
+                text.push_str(&hexRecord(address as u16, 0, &data[..length]));
+                address += length as u64;
+                data = &data[length..];
+            }
+        }
+        let entry = self.loader.header.e_entry as u64;
+        if entry != 0 {
+            if entry > 0xffffffff {return Err(format!("entry point {:#x} does not fit a start address record", entry));}
+            text.push_str(&hexRecord(0, 5, &(entry as u32).to_be_bytes()));
+        }
+        text.push_str(&hexRecord(0, 1, &[]));
+        Ok(text)
+    }
+
+    //the image as Motorola S-records, S1, S2 or S3 by the highest address, after an S0 header
+    //holding the given name and ending with the entry point
+    pub fn srec(&self, header: &str) -> Result<String, String> {
+        let chunks = self.chunks()?;
+        let end = chunks.iter().map(|c| c.address + c.data.len() as u64 - 1).max().unwrap_or(0).max(self.loader.header.e_entry as u64);
+        let (data, termination, width) = match end {
+            0..=0xffff             => (1, 9, 2),
+            0x10000..=0xffffff     => (2, 8, 3),
+            0x1000000..=0xffffffff => (3, 7, 4),
+            _ => return Err(format!("{:#x} is beyond the 4 GiB S-records can address", end)),
+        };
+        let mut text = srecRecord(0, 0, 2, header.as_bytes());
+        for c in chunks.iter() {
+            for (i, line) in c.data.chunks(RECORD_LENGTH).enumerate() {
+                text.push_str(&srecRecord(data, c.address + (i * RECORD_LENGTH) as u64, width, line));
+            }
+        }
+        text.push_str(&srecRecord(termination, self.loader.header.e_entry as u64, width, &[]));
+        Ok(text)
+    }
+
+    pub fn convert(&self, format: Format, header: &str) -> Result<Vec<u8>, String> {
+        match format {
+            Format::Binary   => self.binary(),
+            Format::IntelHex => self.intelHex().map(String::into_bytes),
+            Format::Srec     => self.srec(header).map(String::into_bytes),
+        }
+    }
+}
+
+//the load address (LMA) of a section: the physical address of the PT_LOAD segment holding its
+//contents plus its distance into that segment, the virtual address when no segment holds it
+pub fn loadAddress(loader: &loader::Loader, index: usize) -> u64 {
+    let s = &loader.sectionHeaders[index];
+    if s.sh_type == sectionheader::SHT_NOBITS {return s.sh_addr as u64;}
+    loader.programHeaders.iter()
+        .find(|p| p.getTYPE() == programheader::PT_LOAD && p.getOFFSET() <= s.sh_offset && s.sh_offset.saturating_add(s.sh_size) <= p.getOFFSET().saturating_add(p.getFILESZ()))
+        .map(|p| p.getPADDR().wrapping_add(s.sh_offset - p.getOFFSET()) as u64)
+        .unwrap_or(s.sh_addr as u64)
+}
+
+//one Intel HEX line, the checksum makes all bytes of the record sum to zero
+fn hexRecord(address: u16, kind: u8, data: &[u8]) -> String {
+    let mut bytes = vec![data.len() as u8];
+    bytes.extend_from_slice(&address.to_be_bytes());
+    bytes.push(kind);
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+    bytes.push(sum.wrapping_neg());
+    format!(":{}{}", upperHex(&bytes), LINE_END)
+}
+
+//one S-record line, the count covers address, data and checksum, which is the ones' complement of their sum
+fn srecRecord(kind: u8, address: u64, width: usize, data: &[u8]) -> String {
+    let mut bytes = vec![(width + data.len() + 1) as u8];
+    bytes.extend_from_slice(&address.to_be_bytes()[8 - width..]);
+    bytes.extend_from_slice(data);
+    let sum = bytes.iter().fold(0u8, |s, b| s.wrapping_add(*b));
+    bytes.push(!sum);
+    format!("S{}{}{}", kind, upperHex(&bytes), LINE_END)
+}
+
+fn upperHex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+//matches a name against a pattern where * is any run of characters and ? any one character
+pub fn wildcard(pattern: &str, name: &str) -> bool {
+    let (pattern, name): (Vec<char>, Vec<char>) = (pattern.chars().collect(), name.chars().collect());
+    //position after the last * and where in the name it started matching
+    let (mut p, mut n, mut star, mut from) = (0, 0, None, 0);
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == '?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == '*' {
+            star = Some(p + 1);
+            from = n;
+            p += 1;
+        } else if let Some(s) = star {
+            p = s;
+            from += 1;
+            n = from;
+        } else {
+            return false;
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
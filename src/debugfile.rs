@@ -0,0 +1,141 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use crate::deps;
+use crate::loader;
+use crate::note;
+use crate::strip;
+use crate::xz;
+
+//where distributions install separate debug files
+pub const DEFAULT_DEBUG_DIRECTORY: &str = "/usr/lib/debug";
+
+//how a debug file was found
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Method {
+    BuildId,
+    Debuglink,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Method::BuildId   => write!(f, "build-id"),
+            Method::Debuglink => write!(f, "debuglink"),
+        }
+    }
+}
+
+//a separate debug file that matched its binary
+pub struct DebugFile {
+    //path of the file on this machine
+    pub path:   PathBuf,
+    pub method: Method,
+    pub loader: loader::Loader,
+}
+
+//finds the separate debug file of a binary in the order GDB uses
+pub struct DebugLocator {
+    //directory treated as / when opening files
+    pub sysroot:          PathBuf,
+
+    //global debug directories like /usr/lib/debug, as seen from inside the sysroot
+    pub debugDirectories: Vec<String>,
+}
+
+impl DebugLocator {
+
+    pub fn new(sysroot: &Path) -> Self {
+        Self{sysroot: sysroot.to_path_buf(), debugDirectories: vec![DEFAULT_DEBUG_DIRECTORY.to_string()]}
+    }
+
+    //translates a path inside the sysroot to a path on this machine, None when its symlinks loop
+    //links are followed inside the sysroot, see deps::sysrootPath
+    pub fn hostPath(&self, path: &str) -> Option<PathBuf> {
+        if !path.starts_with('/') {return Some(PathBuf::from(path));}
+        deps::sysrootPath(&self.sysroot, path)
+    }
+
+    //returns the paths to try for a binary at path inside the sysroot, in order:
+    //  DIR/.build-id/xx/yyyy.debug for every global debug directory
+    //  the debuglink name next to the binary, in its .debug directory, then below every global
+    //  debug directory followed by the binary's directory
+    pub fn candidates(&self, binary: &loader::Loader, path: &str) -> Vec<(String, Method)> {
+        let mut candidates = vec![];
+        if let Some(id) = buildId(binary).filter(|id| id.len() > 2) {
+            for dir in self.debugDirectories.iter() {
+                candidates.push((format!("{}/.build-id/{}/{}.debug", dir.trim_end_matches('/'), &id[..2], &id[2..]), Method::BuildId));
+            }
+        }
+        if let Some((name, _)) = debuglink(binary) {
+            let dir = match path.rfind('/') {
+                Some(i) => &path[..i],
+                None    => ".",
+            };
+            candidates.push((format!("{}/{}", dir, name), Method::Debuglink));
+            candidates.push((format!("{}/.debug/{}", dir, name), Method::Debuglink));
+            //the global directories mirror absolute paths only
+            for global in self.debugDirectories.iter().filter(|_| dir.starts_with('/')) {
+                candidates.push((format!("{}{}/{}", global.trim_end_matches('/'), dir, name), Method::Debuglink));
+            }
+        }
+        candidates
+    }
+
+    //returns the first candidate that really belongs to the binary: a build id lookup must find
+    //the same build id and a debuglink lookup the same CRC-32, and the build ids must agree when
+    //both files have one
+    pub fn locate(&self, binary: &loader::Loader, path: &str) -> Option<DebugFile> {
+        let id = buildId(binary);
+        let link = debuglink(binary);
+        let itself = self.hostPath(path).and_then(|p| std::fs::canonicalize(p).ok());
+        for (candidate, method) in self.candidates(binary, path) {
+            let host = match self.hostPath(&candidate) {
+                Some(h) => h,
+                None    => continue,
+            };
+            if itself.is_some() && std::fs::canonicalize(&host).ok() == itself {continue;}
+            let bytes = match std::fs::read(&host) {
+                Ok(b) if loader::Loader::validate(&b).is_ok() => b,
+                _ => continue,
+            };
+            if method == Method::Debuglink && link.as_ref().map(|(_, crc)| *crc) != Some(strip::crc32(&bytes)) {continue;}
+            let mut parser = loader::Loader::fromBytes(bytes);
+            parser.load();
+            let candidateId = buildId(&parser);
+            if method == Method::BuildId && candidateId != id {continue;}
+            if id.is_some() && candidateId.is_some() && candidateId != id {continue;}
+            return Some(DebugFile{path: host, method: method, loader: parser});
+        }
+        None
+    }
+}
+
+//returns the GNU build id of a file as lowercase hex
+pub fn buildId(loader: &loader::Loader) -> Option<String> {
+    loader.notes.iter().find(|n| n.n_type == note::NT_GNU_BUILD_ID).and_then(|n| n.buildId())
+}
+
+//returns the file name and CRC-32 from .gnu_debuglink
+pub fn debuglink(loader: &loader::Loader) -> Option<(String, u32)> {
+    let data = loader.sectionData(loader.findSection(".gnu_debuglink")?)?;
+    strip::debuglink(data, loader.header.e_ident.Data == 2)
+}
+
+//true if the file has DWARF line or unit information of its own
+pub fn hasDebugInfo(loader: &loader::Loader) -> bool {
+    loader.findSection(".debug_info").map(|i| loader.sectionHeaders[i].sh_type != crate::sectionheader::SHT_NOBITS).unwrap_or(false)
+}
+
+//decompresses the MiniDebugInfo in .gnu_debugdata, the small ELF file with the function
+//symbols that Fedora style distributions leave in stripped binaries
+pub fn miniDebugInfo(loader: &loader::Loader) -> Result<Option<loader::Loader>, String> {
+    let data = match loader.findSection(".gnu_debugdata").and_then(|i| loader.sectionData(i)) {
+        Some(d) => d,
+        None    => return Ok(None),
+    };
+    let bytes = xz::decompress(data, xz::MAX_OUTPUT)?;
+    loader::Loader::validate(&bytes).map_err(|e| format!(".gnu_debugdata: {}", e))?;
+    let mut parser = loader::Loader::fromBytes(bytes);
+    parser.load();
+    Ok(Some(parser))
+}
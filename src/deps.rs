@@ -0,0 +1,461 @@
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::{Component, Path, PathBuf};
+use crate::abi;
+use crate::dynamic;
+use crate::header;
+use crate::ldcache;
+use crate::loader;
+use crate::stack;
+use crate::symbol;
+use crate::version;
+
+//most symlinks followed while resolving one path inside a sysroot
+pub const MAX_SYMLINKS: usize = 40;
+
+//an object pulled into the process image, either the root or one of its libraries
+pub struct Object {
+    //DT_NEEDED name the object was loaded for, the path given for the root
+    pub name:         String,
+
+    //path of the object as the runtime linker sees it, inside the sysroot
+    pub path:         String,
+
+    pub loader:       loader::Loader,
+
+    //index of the object whose DT_NEEDED entry loaded this one, None for the root
+    pub parent:       Option<usize>,
+
+    //every DT_NEEDED name with the index of the object it resolved to
+    pub dependencies: Vec<(String, Option<usize>)>,
+}
+
+impl Object {
+
+    //SONAME of the object if it has one
+    pub fn soname(&self) -> Option<String> {
+        self.loader.getDynamic(dynamic::DT_SONAME).and_then(|o| self.loader.dynamicString(o))
+    }
+}
+
+//finds libraries the way the runtime linker does, but inside a sysroot and without running anything
+pub struct Resolver {
+    //directory treated as / when opening files
+    pub sysroot:      PathBuf,
+
+    //directories searched after RPATH and RUNPATH, None picks the usual ones for the root's machine
+    pub defaultPaths: Option<Vec<String>>,
+
+    //simulated LD_LIBRARY_PATH, searched after RPATH and before RUNPATH
+    pub libraryPath:  Vec<String>,
+
+    //the sysroot's /etc/ld.so.cache, searched after RUNPATH
+    pub cache:        Option<ldcache::LdCache>,
+
+    //expansion of $LIB, None uses lib64 for 64 bit programs and lib otherwise
+    pub lib:          Option<String>,
+}
+
+impl Resolver {
+
+    //returns a resolver that searches below sysroot, using the sysroot's ld.so.cache if it has one
+    pub fn new(sysroot: &Path) -> Self {
+        let cache = sysrootPath(sysroot, "/etc/ld.so.cache").and_then(|p| std::fs::read(p).ok()).and_then(|b| ldcache::LdCache::parse(&b));
+        Self {
+            sysroot:      sysroot.to_path_buf(),
+            defaultPaths: None,
+            libraryPath:  vec![],
+            cache:        cache,
+            lib:          None,
+        }
+    }
+
+    //replaces $ORIGIN, $LIB and $PLATFORM, also written as ${ORIGIN} and so on, in a search path entry
+    //origin is the directory of the object the entry came from, None if a token cannot be expanded
+    pub fn expand(&self, entry: &str, origin: &str, root: &loader::Loader) -> Option<String> {
+        let lib = match self.lib.as_ref() {
+            Some(l) => l.clone(),
+            None    => if root.header.e_ident.Class == 2 {"lib64".to_string()} else {"lib".to_string()},
+        };
+        let platform = stack::InitialStack::platformFor(root.header.e_machine);
+        let mut out = String::new();
+        let mut rest = entry;
+        while let Some(i) = rest.find('$') {
+            out.push_str(&rest[..i]);
+            rest = &rest[i + 1..];
+            let (token, length) = match rest.strip_prefix('{') {
+                Some(r) => (r.split('}').next()?, r.find('}')? + 2),
+                None    => {
+                    let length = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '_').unwrap_or(rest.len());
+                    (&rest[..length], length)
+                },
+            };
+            match token {
+                "ORIGIN"   => out.push_str(origin),
+                "LIB"      => out.push_str(&lib),
+                "PLATFORM" if !platform.is_empty() => out.push_str(platform),
+                _          => return None,
+            }
+            rest = &rest[length..];
+        }
+        out.push_str(rest);
+        Some(out)
+    }
+
+    //translates a path seen by the runtime linker to a path on this machine, None when its
+    //symlinks loop. relative paths are taken from the current directory like the runtime linker does
+    pub fn hostPath(&self, path: &str) -> Option<PathBuf> {
+        if !path.starts_with('/') {return Some(PathBuf::from(path));}
+        sysrootPath(&self.sysroot, path)
+    }
+
+    //opens an ELF below the sysroot, None if it is missing or not an ELF file
+    pub fn open(&self, path: &str) -> Option<loader::Loader> {
+        let bytes = std::fs::read(self.hostPath(path)?).ok()?;
+        loader::Loader::validate(&bytes).ok()?;
+        let mut parser = loader::Loader::fromBytes(bytes);
+        parser.load();
+        Some(parser)
+    }
+
+    //loads the root and every library in its DT_NEEDED closure in breadth first order
+    pub fn closure(&self, path: &str) -> Option<Vec<Object>> {
+        let root = self.open(path)?;
+        let mut objects = vec![Object {
+            name:         path.to_string(),
+            path:         path.to_string(),
+            loader:       root,
+            parent:       None,
+            dependencies: vec![],
+        }];
+
+        let mut next = 0;
+        while next < objects.len() {
+            let needed = objects[next].loader.dynamicStrings(dynamic::DT_NEEDED);
+            let mut dependencies = vec![];
+            for name in needed {
+                //a library already loaded under the same name or SONAME is reused
+                let existing = objects.iter().position(|o| o.name == name || o.soname().as_deref() == Some(name.as_str()));
+                let index = match existing {
+                    Some(i) => Some(i),
+                    None    => self.find(&name, &objects, next).map(|(path, parser)| {
+                        objects.push(Object {
+                            name:         name.clone(),
+                            path:         path,
+                            loader:       parser,
+                            parent:       Some(next),
+                            dependencies: vec![],
+                        });
+                        objects.len() - 1
+                    }),
+                };
+                dependencies.push((name, index));
+            }
+            objects[next].dependencies = dependencies;
+            next += 1;
+        }
+        Some(objects)
+    }
+
+    //searches for the library name requested by objects[requester]
+    //order: DT_RPATH of the requester and the objects that loaded it unless the requester has DT_RUNPATH,
+    //LD_LIBRARY_PATH, DT_RUNPATH of the requester, ld.so.cache and the default directories,
+    //the last two are skipped when the requester is linked with -z nodeflib
+    pub fn find(&self, name: &str, objects: &[Object], requester: usize) -> Option<(String, loader::Loader)> {
+        let root = &objects[0].loader;
+        if name.contains('/') {
+            let path = self.expand(name, &origin(&objects[requester].path), root)?;
+            return self.open(&path).filter(|l| isCompatible(root, l)).map(|l| (path, l));
+        }
+
+        let mut candidates: Vec<String> = vec![];
+        let mut search = |list: &[String], from: &str| {
+            for entry in list.iter().flat_map(|l| splitPath(l)) {
+                if let Some(directory) = self.expand(&entry, &origin(from), root) {
+                    candidates.push(format!("{}/{}", directory.trim_end_matches('/'), name));
+                }
+            }
+        };
+        let runpath = objects[requester].loader.dynamicStrings(dynamic::DT_RUNPATH);
+        if runpath.is_empty() {
+            let mut current = Some(requester);
+            while let Some(i) = current {
+                search(&objects[i].loader.dynamicStrings(dynamic::DT_RPATH), &objects[i].path);
+                current = objects[i].parent;
+            }
+        }
+        search(&self.libraryPath, &objects[0].path);
+        search(&runpath, &objects[requester].path);
+
+        let flags1 = objects[requester].loader.getDynamic(dynamic::DT_FLAGS_1).unwrap_or(0);
+        if flags1 & dynamic::DF_1_NODEFLIB == 0 {
+            if let Some(cache) = self.cache.as_ref() {
+                candidates.extend(cache.lookup(name, ldcache::requiredFlags(root)));
+            }
+            match self.defaultPaths.as_ref() {
+                Some(d) => candidates.extend(d.iter().map(|d| format!("{}/{}", d.trim_end_matches('/'), name))),
+                None    => candidates.extend(defaultPaths(root).iter().map(|d| format!("{}/{}", d, name))),
+            }
+        }
+
+        for path in candidates {
+            //the runtime linker skips libraries built for another machine and keeps looking
+            if let Some(parser) = self.open(&path).filter(|l| isCompatible(root, l)) {
+                return Some((path, parser));
+            }
+        }
+        None
+    }
+}
+
+//the directory a path is in, what $ORIGIN expands to for the object at that path
+pub fn origin(path: &str) -> String {
+    match path.rfind('/') {
+        Some(0) => "/".to_string(),
+        Some(i) => path[..i].to_string(),
+        None    => ".".to_string(),
+    }
+}
+
+//splits a colon separated search path, dropping empty entries
+pub fn splitPath(list: &str) -> Vec<String> {
+    list.split(':').filter(|d| !d.is_empty()).map(|d| d.to_string()).collect()
+}
+
+//true when a library can be loaded into a process started from root
+pub fn isCompatible(root: &loader::Loader, library: &loader::Loader) -> bool {
+    root.header.e_ident.Class == library.header.e_ident.Class
+        && root.header.e_ident.Data == library.header.e_ident.Data
+        && root.header.e_machine == library.header.e_machine
+        && library.header.e_type == header::ET_DYN
+}
+
+//the directories glibc searches last, with the Debian multiarch ones first
+pub fn defaultPaths(root: &loader::Loader) -> Vec<String> {
+    let triplet = match (root.header.e_machine, root.header.e_ident.Class) {
+        (header::EM_X86_64, 2)  => Some("x86_64-linux-gnu"),
+        (header::EM_X86_64, _)  => Some("x86_64-linux-gnux32"),
+        (header::EM_386, _)     => Some("i386-linux-gnu"),
+        (header::EM_AARCH64, _) => Some("aarch64-linux-gnu"),
+        (header::EM_ARM, _)     => Some("arm-linux-gnueabihf"),
+        (header::EM_RISCV, 2)   => Some("riscv64-linux-gnu"),
+        _                       => None,
+    };
+    let mut paths = vec![];
+    if let Some(t) = triplet {
+        paths.push(format!("/lib/{}", t));
+        paths.push(format!("/usr/lib/{}", t));
+    }
+    if root.header.e_ident.Class == 2 {
+        paths.push("/lib64".to_string());
+        paths.push("/usr/lib64".to_string());
+    }
+    paths.push("/lib".to_string());
+    paths.push("/usr/lib".to_string());
+    paths
+}
+
+//how dependencies are printed by formatDependencies
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    //indented tree, libraries are only expanded the first time they appear
+    Tree,
+    //ldd style list in load order
+    Flat,
+    //Graphviz digraph
+    Dot,
+}
+
+//prints the dependencies found by Resolver::closure
+pub fn formatDependencies(objects: &[Object], format: Format) -> String {
+    let mut out = String::new();
+    match format {
+        Format::Tree => {
+            out.push_str(&format!("{}\n", objects[0].path));
+            let mut expanded = vec![false; objects.len()];
+            expanded[0] = true;
+            treeLines(objects, 0, 1, &mut expanded, &mut out);
+        },
+        Format::Flat => {
+            for o in objects.iter().skip(1) {
+                out.push_str(&format!("\t{} => {}\n", o.name, o.path));
+            }
+            for o in objects.iter() {
+                for (name, _) in o.dependencies.iter().filter(|(_, i)| i.is_none()) {
+                    out.push_str(&format!("\t{} => not found\n", name));
+                }
+            }
+        },
+        Format::Dot => {
+            out.push_str("digraph dependencies {\n");
+            for (i, o) in objects.iter().enumerate() {
+                out.push_str(&format!("  n{} [label={}];\n", i, crate::diff::jsonString(&o.path)));
+            }
+            let mut missing = 0;
+            for (i, o) in objects.iter().enumerate() {
+                for (name, index) in o.dependencies.iter() {
+                    match index {
+                        Some(j) => out.push_str(&format!("  n{} -> n{};\n", i, j)),
+                        None    => {
+                            out.push_str(&format!("  missing{} [label={}, color=red];\n", missing, crate::diff::jsonString(name)));
+                            out.push_str(&format!("  n{} -> missing{};\n", i, missing));
+                            missing += 1;
+                        },
+                    }
+                }
+            }
+            out.push_str("}\n");
+        },
+    }
+    out
+}
+
+fn treeLines(objects: &[Object], index: usize, depth: usize, expanded: &mut Vec<bool>, out: &mut String) {
+    for (name, dependency) in objects[index].dependencies.iter() {
+        let indent = "    ".repeat(depth);
+        match dependency {
+            None    => out.push_str(&format!("{}{} => not found\n", indent, name)),
+            Some(j) if expanded[*j] => out.push_str(&format!("{}{} => {} (already listed)\n", indent, name, objects[*j].path)),
+            Some(j) => {
+                out.push_str(&format!("{}{} => {}\n", indent, name, objects[*j].path));
+                expanded[*j] = true;
+                treeLines(objects, *j, depth + 1, expanded, out);
+            },
+        }
+    }
+}
+
+//something that makes the runtime linker refuse to start the program
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    //a DT_NEEDED entry could not be found
+    MissingLibrary{object: String, name: String},
+
+    //a dependency does not define a version the object was linked against
+    MissingVersion{object: String, file: String, version: String},
+
+    //no object in the closure defines a symbol, expected is the library named by the version requirement
+    MissingSymbol{object: String, symbol: String, version: Option<String>, expected: Option<String>},
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Problem::MissingLibrary{object, name} => write!(f, "{}: library {} not found", object, name),
+            Problem::MissingVersion{object, file, version} => write!(f, "{}: version {} not found in {}", object, version, file),
+            Problem::MissingSymbol{object, symbol, version, expected} => {
+                write!(f, "{}: undefined symbol {}", object, symbol)?;
+                if let Some(v) = version {write!(f, "@{}", v)?;}
+                if let Some(e) = expected {write!(f, " (expected in {})", e)?;}
+                Ok(())
+            },
+        }
+    }
+}
+
+//checks that every library, required version and non-weak undefined dynamic symbol in the closure is provided
+pub fn checkClosure(objects: &[Object]) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    //every definition in the closure, by name
+    let mut definitions: HashMap<String, Vec<abi::Export>> = HashMap::new();
+    for o in objects.iter() {
+        for e in abi::exports(&o.loader) {
+            definitions.entry(e.name.clone()).or_default().push(e);
+        }
+    }
+
+    for o in objects.iter() {
+        for (name, index) in o.dependencies.iter() {
+            if index.is_none() {
+                problems.push(Problem::MissingLibrary{object: o.path.clone(), name: name.clone()});
+            }
+        }
+
+        for need in o.loader.versionNeeds.iter() {
+            if need.vna_flags & version::VER_FLG_WEAK != 0 {continue;}
+            let provider = o.dependencies.iter().find(|(n, _)| *n == need.file).and_then(|(_, i)| *i);
+            let provider = match provider {
+                Some(i) => &objects[i],
+                None    => continue,
+            };
+            if !provider.loader.versionDefinitions.iter().any(|d| d.name() == need.name) {
+                problems.push(Problem::MissingVersion{object: o.path.clone(), file: need.file.clone(), version: need.name.clone()});
+            }
+        }
+
+        for (i, s) in o.loader.dynamicSymbols.iter().enumerate() {
+            if !s.isUndefined() || s.name.is_empty() {continue;}
+            if s.getBind() != symbol::STB_GLOBAL {continue;}
+            let wanted = o.loader.symbolVersion(i);
+            //symbols expected from a library that is missing were already reported with it
+            let expected = wanted.as_ref().and_then(|w| w.file.as_ref());
+            if o.dependencies.iter().any(|(n, i)| Some(n) == expected && i.is_none()) {continue;}
+            let found = definitions.get(&s.name).map(|candidates| candidates.iter().any(|e| {
+                match (&wanted, &e.version) {
+                    //a library without versioning satisfies any version
+                    (_, None)          => true,
+                    (Some(w), Some(v)) => w.name == *v,
+                    (None, Some(_))    => !e.hidden,
+                }
+            })).unwrap_or(false);
+            if !found {
+                problems.push(Problem::MissingSymbol {
+                    object:   o.path.clone(),
+                    symbol:   s.name.clone(),
+                    expected: wanted.as_ref().and_then(|w| w.file.clone()),
+                    version:  wanted.map(|w| w.name),
+                });
+            }
+        }
+    }
+    problems
+}
+
+//maps an absolute path inside a sysroot to the host path it names
+//symlinks are resolved here one component at a time as if the sysroot were the root directory:
+//absolute targets start again at the sysroot and .. stops at it, so a link like
+///lib/libc.so.6 -> /usr/lib/libc.so.6 is read from the sysroot and never from this machine.
+//None when more than MAX_SYMLINKS links are followed
+pub fn sysrootPath(sysroot: &Path, path: &str) -> Option<PathBuf> {
+    //components still to walk, the next one last
+    let mut pending = pathComponents(Path::new(path));
+    let mut host = sysroot.to_path_buf();
+    let mut depth = 0;
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        if part == ".." {
+            if depth > 0 {
+                host.pop();
+                depth -= 1;
+            }
+            continue;
+        }
+        host.push(&part);
+        let isLink = std::fs::symlink_metadata(&host).map(|m| m.file_type().is_symlink()).unwrap_or(false);
+        if !isLink {
+            depth += 1;
+            continue;
+        }
+        links += 1;
+        if links > MAX_SYMLINKS {return None;}
+        let target = std::fs::read_link(&host).ok()?;
+        host.pop();
+        if target.is_absolute() {
+            host = sysroot.to_path_buf();
+            depth = 0;
+        }
+        pending.extend(pathComponents(&target));
+    }
+    Some(host)
+}
+
+//the names and .. of a path in reverse order, . and the root are dropped
+fn pathComponents(path: &Path) -> Vec<OsString> {
+    path.components().rev().filter_map(|c| match c {
+        Component::Normal(p) => Some(p.to_os_string()),
+        Component::ParentDir => Some(OsString::from("..")),
+        _                    => None,
+    }).collect()
+}
@@ -0,0 +1,369 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use crate::loader;
+
+//what happened to an item between the old and new file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    Changed,
+}
+
+//a single difference between two files
+#[derive(Debug)]
+pub struct Change {
+    //header, segment, section, symbol, dynamic symbol, dynamic or note
+    pub category: &'static str,
+
+    //name of the item, for example a section name or a segment index
+    pub item:     String,
+
+    //the property that changed, empty for added and removed items
+    pub field:    String,
+
+    pub kind:     ChangeKind,
+
+    //old and new values formatted for display
+    pub old:      Option<String>,
+    pub new:      Option<String>,
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.kind {
+            ChangeKind::Added   => write!(f, "+ {} {} {}", self.category, self.item, self.new.as_deref().unwrap_or("")),
+            ChangeKind::Removed => write!(f, "- {} {} {}", self.category, self.item, self.old.as_deref().unwrap_or("")),
+            ChangeKind::Changed => write!(f, "~ {} {} {}: {} -> {}", self.category, self.item, self.field,
+                self.old.as_deref().unwrap_or(""), self.new.as_deref().unwrap_or("")),
+        }
+    }
+}
+
+//every difference between two files in the order headers, segments, sections, symbols,
+//dynamic entries and notes
+pub struct ElfDiff {
+    pub changes: Vec<Change>,
+}
+
+impl ElfDiff {
+
+    //compares two loaded files
+    pub fn new(old: &loader::Loader, new: &loader::Loader) -> Self {
+        let mut diff = Self{changes: vec![]};
+        diff.compareHeaders(old, new);
+        diff.compareSegments(old, new);
+        diff.compareSections(old, new);
+        diff.compareSymbols("symbol", &old.symbols, &new.symbols);
+        diff.compareSymbols("dynamic symbol", &old.dynamicSymbols, &new.dynamicSymbols);
+        diff.compareDynamic(old, new);
+        diff.compareNotes(old, new);
+        diff
+    }
+
+    //true when nothing differs
+    pub fn isEmpty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    fn changed(&mut self, category: &'static str, item: &str, field: &str, old: String, new: String) {
+        if old == new {return;}
+        self.changes.push(Change {
+            category: category,
+            item:     item.to_string(),
+            field:    field.to_string(),
+            kind:     ChangeKind::Changed,
+            old:      Some(old),
+            new:      Some(new),
+        });
+    }
+
+    fn added(&mut self, category: &'static str, item: &str, value: String) {
+        self.changes.push(Change {
+            category: category,
+            item:     item.to_string(),
+            field:    String::new(),
+            kind:     ChangeKind::Added,
+            old:      None,
+            new:      Some(value),
+        });
+    }
+
+    fn removed(&mut self, category: &'static str, item: &str, value: String) {
+        self.changes.push(Change {
+            category: category,
+            item:     item.to_string(),
+            field:    String::new(),
+            kind:     ChangeKind::Removed,
+            old:      Some(value),
+            new:      None,
+        });
+    }
+
+    fn compareHeaders(&mut self, old: &loader::Loader, new: &loader::Loader) {
+        let fields = |l: &loader::Loader| -> Vec<(&'static str, String)> {
+            let h = &l.header;
+            vec![
+                ("file size",   format!("{:#x}", l.fileVec.len())),
+                ("class",       format!("{}", h.e_ident.Class)),
+                ("data",        format!("{}", h.e_ident.Data)),
+                ("os abi",      format!("{}", h.e_ident.OS_ABI)),
+                ("abi version", format!("{}", h.e_ident.ABI_Version)),
+                ("e_type",      format!("{}", h.e_type)),
+                ("e_machine",   format!("{}", h.e_machine)),
+                ("e_version",   format!("{}", h.e_version)),
+                ("e_entry",     format!("{:#x}", h.e_entry)),
+                ("e_phoff",     format!("{:#x}", h.e_phoff)),
+                ("e_shoff",     format!("{:#x}", h.e_shoff)),
+                ("e_flags",     format!("{:#x}", h.e_flags)),
+                ("e_ehsize",    format!("{}", h.e_ehsize)),
+                ("e_phentsize", format!("{}", h.e_phentsize)),
+                ("e_phnum",     format!("{}", h.e_phnum)),
+                ("e_shentsize", format!("{}", h.e_shentsize)),
+                ("e_shnum",     format!("{}", h.e_shnum)),
+                ("e_shstrndx",  format!("{}", h.e_shstrndx)),
+            ]
+        };
+        for ((name, a), (_, b)) in fields(old).into_iter().zip(fields(new).into_iter()) {
+            self.changed("header", "elf header", name, a, b);
+        }
+    }
+
+    //segments are matched by their index in the program header table
+    fn compareSegments(&mut self, old: &loader::Loader, new: &loader::Loader) {
+        let describe = |l: &loader::Loader, i: usize| -> Vec<(&'static str, String)> {
+            let p = &l.programHeaders[i];
+            vec![
+                ("type",   format!("{:#x}", p.getTYPE())),
+                ("offset", format!("{:#x}", p.getOFFSET())),
+                ("vaddr",  format!("{:#x}", p.getVADDR())),
+                ("paddr",  format!("{:#x}", p.getPADDR())),
+                ("filesz", format!("{:#x}", p.getFILESZ())),
+                ("memsz",  format!("{:#x}", p.getMEMSZ())),
+                ("flags",  format!("{:#x}", p.getFLAGS())),
+                ("align",  format!("{:#x}", p.getALIGN())),
+            ]
+        };
+        let summary = |fields: Vec<(&'static str, String)>| -> String {
+            fields.iter().map(|(k, v)| format!("{}={}", k, v)).collect::<Vec<_>>().join(" ")
+        };
+        let count = old.programHeaders.len().max(new.programHeaders.len());
+        for i in 0..count {
+            let item = format!("{}", i);
+            match (i < old.programHeaders.len(), i < new.programHeaders.len()) {
+                (true, true) => {
+                    for ((name, a), (_, b)) in describe(old, i).into_iter().zip(describe(new, i).into_iter()) {
+                        self.changed("segment", &item, name, a, b);
+                    }
+                },
+                (true, false) => self.removed("segment", &item, summary(describe(old, i))),
+                _             => self.added("segment", &item, summary(describe(new, i))),
+            }
+        }
+    }
+
+    //sections are matched by name, repeated names are matched in order
+    fn compareSections(&mut self, old: &loader::Loader, new: &loader::Loader) {
+        let byName = |l: &loader::Loader| -> BTreeMap<String, Vec<usize>> {
+            let mut map: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+            for i in 0..l.sectionHeaders.len() {
+                map.entry(l.sectionName(i).unwrap_or_default()).or_default().push(i);
+            }
+            map
+        };
+        let describe = |l: &loader::Loader, i: usize| -> Vec<(&'static str, String)> {
+            let s = &l.sectionHeaders[i];
+            vec![
+                ("type",    format!("{:#x}", s.sh_type)),
+                ("flags",   format!("{:#x}", s.sh_flags)),
+                ("address", format!("{:#x}", s.sh_addr)),
+                ("offset",  format!("{:#x}", s.sh_offset)),
+                ("size",    format!("{:#x}", s.sh_size)),
+                ("content", format!("{:016x}", fnv1a(l.sectionData(i).unwrap_or(&[])))),
+            ]
+        };
+        let oldNames = byName(old);
+        let newNames = byName(new);
+        let mut names: Vec<&String> = oldNames.keys().chain(newNames.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let empty = vec![];
+            let a = oldNames.get(name).unwrap_or(&empty);
+            let b = newNames.get(name).unwrap_or(&empty);
+            let label = if name.is_empty() {"(unnamed)".to_string()} else {name.clone()};
+            for n in 0..a.len().max(b.len()) {
+                match (a.get(n), b.get(n)) {
+                    (Some(i), Some(j)) => {
+                        for ((field, x), (_, y)) in describe(old, *i).into_iter().zip(describe(new, *j).into_iter()) {
+                            self.changed("section", &label, field, x, y);
+                        }
+                    },
+                    (Some(i), None) => self.removed("section", &label, format!("size={:#x}", old.sectionHeaders[*i].sh_size)),
+                    (None, Some(j)) => self.added("section", &label, format!("size={:#x}", new.sectionHeaders[*j].sh_size)),
+                    (None, None)    => {},
+                }
+            }
+        }
+    }
+
+    //symbols are matched by name, unnamed symbols are skipped
+    fn compareSymbols(&mut self, category: &'static str, old: &[crate::symbol::Symbol], new: &[crate::symbol::Symbol]) {
+        let byName = |symbols: &[crate::symbol::Symbol]| -> BTreeMap<String, Vec<(usize, usize, u8)>> {
+            let mut map: BTreeMap<String, Vec<(usize, usize, u8)>> = BTreeMap::new();
+            for s in symbols.iter().filter(|s| !s.name.is_empty()) {
+                map.entry(s.name.clone()).or_default().push((s.st_value, s.st_size, s.st_info));
+            }
+            map
+        };
+        let oldNames = byName(old);
+        let newNames = byName(new);
+        let mut names: Vec<&String> = oldNames.keys().chain(newNames.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        for name in names {
+            let empty = vec![];
+            let a = oldNames.get(name).unwrap_or(&empty);
+            let b = newNames.get(name).unwrap_or(&empty);
+            for n in 0..a.len().max(b.len()) {
+                match (a.get(n), b.get(n)) {
+                    (Some(x), Some(y)) => {
+                        self.changed(category, name, "value", format!("{:#x}", x.0), format!("{:#x}", y.0));
+                        self.changed(category, name, "size", format!("{:#x}", x.1), format!("{:#x}", y.1));
+                        self.changed(category, name, "info", format!("{:#x}", x.2), format!("{:#x}", y.2));
+                    },
+                    (Some(x), None) => self.removed(category, name, format!("value={:#x} size={:#x}", x.0, x.1)),
+                    (None, Some(y)) => self.added(category, name, format!("value={:#x} size={:#x}", y.0, y.1)),
+                    (None, None)    => {},
+                }
+            }
+        }
+    }
+
+    //dynamic entries are compared as a multiset of tag and value, string values are resolved
+    fn compareDynamic(&mut self, old: &loader::Loader, new: &loader::Loader) {
+        let entries = |l: &loader::Loader| -> Vec<(String, String)> {
+            l.dynamic.iter().map(|d| {
+                let value = if d.isString() {
+                    l.dynamicString(d.d_val).unwrap_or_else(|| format!("{:#x}", d.d_val))
+                } else {
+                    format!("{:#x}", d.d_val)
+                };
+                (format!("{:#x}", d.d_tag), value)
+            }).collect()
+        };
+        let a = entries(old);
+        let mut b = entries(new);
+
+        //entries whose tag appears once on both sides are reported as changed values
+        let mut removed = vec![];
+        for entry in a.into_iter() {
+            match b.iter().position(|e| *e == entry) {
+                Some(i) => {b.remove(i);},
+                None    => removed.push(entry),
+            }
+        }
+        for (tag, value) in removed.into_iter() {
+            let single = b.iter().filter(|(t, _)| *t == tag).count() == 1
+                && old.dynamic.iter().filter(|d| format!("{:#x}", d.d_tag) == tag).count() == 1;
+            match b.iter().position(|(t, _)| *t == tag) {
+                Some(i) if single => {
+                    let (_, newValue) = b.remove(i);
+                    self.changed("dynamic", &tag, "value", value, newValue);
+                },
+                _ => self.removed("dynamic", &tag, value),
+            }
+        }
+        for (tag, value) in b.into_iter() {
+            self.added("dynamic", &tag, value);
+        }
+    }
+
+    //notes are compared as a multiset of owner, type and descriptor
+    fn compareNotes(&mut self, old: &loader::Loader, new: &loader::Loader) {
+        let describe = |l: &loader::Loader| -> Vec<(String, String)> {
+            l.notes.iter().map(|n| (format!("{} {:#x}", n.name, n.n_type), n.desc.iter().map(|b| format!("{:02x}", b)).collect())).collect()
+        };
+        let a = describe(old);
+        let mut b = describe(new);
+        let mut removed = vec![];
+        for entry in a.into_iter() {
+            match b.iter().position(|e| *e == entry) {
+                Some(i) => {b.remove(i);},
+                None    => removed.push(entry),
+            }
+        }
+        for (item, desc) in removed.into_iter() {
+            match b.iter().position(|(i, _)| *i == item) {
+                Some(i) => {
+                    let (_, newDesc) = b.remove(i);
+                    self.changed("note", &item, "descriptor", desc, newDesc);
+                },
+                None => self.removed("note", &item, desc),
+            }
+        }
+        for (item, desc) in b.into_iter() {
+            self.added("note", &item, desc);
+        }
+    }
+
+    //formats the changes as a JSON array of objects
+    pub fn toJson(&self) -> String {
+        let mut out = String::from("[");
+        for (i, c) in self.changes.iter().enumerate() {
+            if i > 0 {out.push(',');}
+            let kind = match c.kind {
+                ChangeKind::Added   => "added",
+                ChangeKind::Removed => "removed",
+                ChangeKind::Changed => "changed",
+            };
+            let optional = |v: &Option<String>| match v {
+                Some(s) => jsonString(s),
+                None    => "null".to_string(),
+            };
+            out.push_str(&format!("\n  {{\"category\": {}, \"item\": {}, \"field\": {}, \"kind\": \"{}\", \"old\": {}, \"new\": {}}}",
+                jsonString(c.category), jsonString(&c.item), jsonString(&c.field), kind, optional(&c.old), optional(&c.new)));
+        }
+        out.push_str(if self.changes.is_empty() {"]"} else {"\n]"});
+        out
+    }
+}
+
+impl fmt::Display for ElfDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for c in self.changes.iter() {
+            writeln!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+//64 bit FNV-1a hash, enough to tell whether section contents changed
+pub fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for b in bytes {
+        hash ^= *b as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+//quotes and escapes a string for JSON output
+pub fn jsonString(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c    => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
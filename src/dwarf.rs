@@ -0,0 +1,925 @@
+use std::collections::HashMap;
+use crate::loader;
+use crate::sectionheader;
+use crate::symbolize::IntervalIndex;
+
+//tags of the debugging information entries that matter for symbolization
+pub const DW_TAG_lexical_block:      u64 = 0x0b;
+pub const DW_TAG_compile_unit:       u64 = 0x11;
+pub const DW_TAG_inlined_subroutine: u64 = 0x1d;
+pub const DW_TAG_subprogram:         u64 = 0x2e;
+pub const DW_TAG_partial_unit:       u64 = 0x3c;
+
+//attributes read from those entries
+pub const DW_AT_name:              u64 = 0x03;
+pub const DW_AT_stmt_list:         u64 = 0x10;
+pub const DW_AT_low_pc:            u64 = 0x11;
+pub const DW_AT_high_pc:           u64 = 0x12;
+pub const DW_AT_comp_dir:          u64 = 0x1b;
+pub const DW_AT_abstract_origin:   u64 = 0x31;
+pub const DW_AT_specification:     u64 = 0x47;
+pub const DW_AT_ranges:            u64 = 0x55;
+pub const DW_AT_call_column:       u64 = 0x57;
+pub const DW_AT_call_file:         u64 = 0x58;
+pub const DW_AT_call_line:         u64 = 0x59;
+pub const DW_AT_linkage_name:      u64 = 0x6e;
+pub const DW_AT_str_offsets_base:  u64 = 0x72;
+pub const DW_AT_addr_base:         u64 = 0x73;
+pub const DW_AT_rnglists_base:     u64 = 0x74;
+pub const DW_AT_MIPS_linkage_name: u64 = 0x2007;
+
+//attribute forms
+pub const DW_FORM_addr:           u64 = 0x01;
+pub const DW_FORM_block2:         u64 = 0x03;
+pub const DW_FORM_block4:         u64 = 0x04;
+pub const DW_FORM_data2:          u64 = 0x05;
+pub const DW_FORM_data4:          u64 = 0x06;
+pub const DW_FORM_data8:          u64 = 0x07;
+pub const DW_FORM_string:         u64 = 0x08;
+pub const DW_FORM_block:          u64 = 0x09;
+pub const DW_FORM_block1:         u64 = 0x0a;
+pub const DW_FORM_data1:          u64 = 0x0b;
+pub const DW_FORM_flag:           u64 = 0x0c;
+pub const DW_FORM_sdata:          u64 = 0x0d;
+pub const DW_FORM_strp:           u64 = 0x0e;
+pub const DW_FORM_udata:          u64 = 0x0f;
+pub const DW_FORM_ref_addr:       u64 = 0x10;
+pub const DW_FORM_ref1:           u64 = 0x11;
+pub const DW_FORM_ref2:           u64 = 0x12;
+pub const DW_FORM_ref4:           u64 = 0x13;
+pub const DW_FORM_ref8:           u64 = 0x14;
+pub const DW_FORM_ref_udata:      u64 = 0x15;
+pub const DW_FORM_indirect:       u64 = 0x16;
+pub const DW_FORM_sec_offset:     u64 = 0x17;
+pub const DW_FORM_exprloc:        u64 = 0x18;
+pub const DW_FORM_flag_present:   u64 = 0x19;
+pub const DW_FORM_strx:           u64 = 0x1a;
+pub const DW_FORM_addrx:          u64 = 0x1b;
+pub const DW_FORM_ref_sup4:       u64 = 0x1c;
+pub const DW_FORM_strp_sup:       u64 = 0x1d;
+pub const DW_FORM_data16:         u64 = 0x1e;
+pub const DW_FORM_line_strp:      u64 = 0x1f;
+pub const DW_FORM_ref_sig8:       u64 = 0x20;
+pub const DW_FORM_implicit_const: u64 = 0x21;
+pub const DW_FORM_loclistx:       u64 = 0x22;
+pub const DW_FORM_rnglistx:       u64 = 0x23;
+pub const DW_FORM_ref_sup8:       u64 = 0x24;
+pub const DW_FORM_strx1:          u64 = 0x25;
+pub const DW_FORM_strx2:          u64 = 0x26;
+pub const DW_FORM_strx3:          u64 = 0x27;
+pub const DW_FORM_strx4:          u64 = 0x28;
+pub const DW_FORM_addrx1:         u64 = 0x29;
+pub const DW_FORM_addrx2:         u64 = 0x2a;
+pub const DW_FORM_addrx3:         u64 = 0x2b;
+pub const DW_FORM_addrx4:         u64 = 0x2c;
+pub const DW_FORM_GNU_addr_index: u64 = 0x1f01;
+pub const DW_FORM_GNU_str_index:  u64 = 0x1f02;
+pub const DW_FORM_GNU_ref_alt:    u64 = 0x1f20;
+pub const DW_FORM_GNU_strp_alt:   u64 = 0x1f21;
+
+//unit types of DWARF 5 unit headers
+pub const DW_UT_compile:       u8 = 0x01;
+pub const DW_UT_type:          u8 = 0x02;
+pub const DW_UT_partial:       u8 = 0x03;
+pub const DW_UT_skeleton:      u8 = 0x04;
+pub const DW_UT_split_compile: u8 = 0x05;
+pub const DW_UT_split_type:    u8 = 0x06;
+
+//line number program opcodes
+pub const DW_LNS_copy:               u8 = 1;
+pub const DW_LNS_advance_pc:         u8 = 2;
+pub const DW_LNS_advance_line:       u8 = 3;
+pub const DW_LNS_set_file:           u8 = 4;
+pub const DW_LNS_set_column:         u8 = 5;
+pub const DW_LNS_negate_stmt:        u8 = 6;
+pub const DW_LNS_set_basic_block:    u8 = 7;
+pub const DW_LNS_const_add_pc:       u8 = 8;
+pub const DW_LNS_fixed_advance_pc:   u8 = 9;
+pub const DW_LNE_end_sequence:       u8 = 1;
+pub const DW_LNE_set_address:        u8 = 2;
+pub const DW_LNE_define_file:        u8 = 3;
+
+//content types of DWARF 5 directory and file name entries
+pub const DW_LNCT_path:            u64 = 1;
+pub const DW_LNCT_directory_index: u64 = 2;
+
+//entry kinds of .debug_rnglists
+pub const DW_RLE_end_of_list:   u8 = 0;
+pub const DW_RLE_base_addressx: u8 = 1;
+pub const DW_RLE_startx_endx:   u8 = 2;
+pub const DW_RLE_startx_length: u8 = 3;
+pub const DW_RLE_offset_pair:   u8 = 4;
+pub const DW_RLE_base_address:  u8 = 5;
+pub const DW_RLE_start_end:     u8 = 6;
+pub const DW_RLE_start_length:  u8 = 7;
+
+//a cursor over the bytes of one debug section
+pub struct Reader<'a> {
+    pub bytes: &'a [u8],
+    pub pos:   usize,
+    pub big:   bool,
+}
+
+impl<'a> Reader<'a> {
+
+    pub fn new(bytes: &'a [u8], pos: usize, big: bool) -> Self {
+        Self{bytes: bytes, pos: pos, big: big}
+    }
+
+    pub fn isEmpty(&self) -> bool {
+        self.pos >= self.bytes.len()
+    }
+
+    //reads an unsigned value of size bytes in the file's byte order
+    pub fn read(&mut self, size: usize) -> Option<u64> {
+        let bytes = self.bytes.get(self.pos..self.pos.checked_add(size)?)?;
+        self.pos += size;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = if self.big {bytes[i]} else {bytes[size - 1 - i]};
+            value = (value << 8) | b as u64;
+        }
+        Some(value)
+    }
+
+    pub fn u8(&mut self) -> Option<u8> {
+        self.read(1).map(|v| v as u8)
+    }
+
+    pub fn uleb(&mut self) -> Option<u64> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {value |= ((b & 0x7f) as u64) << shift;}
+            shift += 7;
+            if b & 0x80 == 0 {return Some(value);}
+        }
+    }
+
+    pub fn sleb(&mut self) -> Option<i64> {
+        let mut value = 0i64;
+        let mut shift = 0;
+        loop {
+            let b = self.u8()?;
+            if shift < 64 {value |= ((b & 0x7f) as i64) << shift;}
+            shift += 7;
+            if b & 0x80 == 0 {
+                if shift < 64 && b & 0x40 != 0 {value |= -1i64 << shift;}
+                return Some(value);
+            }
+        }
+    }
+
+    //reads a nul terminated string
+    pub fn string(&mut self) -> Option<String> {
+        let rest = self.bytes.get(self.pos..)?;
+        let end = rest.iter().position(|b| *b == 0)?;
+        self.pos += end + 1;
+        Some(String::from_utf8_lossy(&rest[..end]).to_string())
+    }
+
+    //reads an initial length field, returning the length and the offset size, 4 or 8 for DWARF64
+    pub fn initialLength(&mut self) -> Option<(usize, usize)> {
+        let length = self.read(4)?;
+        if length == 0xffffffff {
+            Some((self.read(8)? as usize, 8))
+        } else {
+            Some((length as usize, 4))
+        }
+    }
+
+    pub fn skip(&mut self, size: usize) -> Option<()> {
+        self.pos = self.pos.checked_add(size)?;
+        if self.pos > self.bytes.len() {return None;}
+        Some(())
+    }
+}
+
+//returns the nul terminated string at an offset into a string section
+fn stringAt(section: &[u8], offset: usize) -> Option<String> {
+    Reader::new(section, offset, false).string()
+}
+
+//the value of an attribute, offsets and indexes are resolved later against the unit
+#[derive(Debug, Clone)]
+pub enum AttributeValue {
+    Address(u64),
+    Unsigned(u64),
+    Signed(i64),
+    String(String),
+    //offset into .debug_str, .debug_line_str is resolved straight away
+    StringOffset(u64),
+    StringIndex(u64),
+    AddressIndex(u64),
+    RangeIndex(u64),
+    //offset of the referenced entry from the start of .debug_info
+    Reference(usize),
+    SectionOffset(u64),
+    Other,
+}
+
+impl AttributeValue {
+
+    //the value as an unsigned constant
+    pub fn unsigned(&self) -> Option<u64> {
+        match self {
+            AttributeValue::Unsigned(v)      => Some(*v),
+            AttributeValue::Signed(v)        => Some(*v as u64),
+            AttributeValue::SectionOffset(v) => Some(*v),
+            _                                => None,
+        }
+    }
+}
+
+//an abbreviation declaration from .debug_abbrev
+struct Abbreviation {
+    tag:         u64,
+    hasChildren: bool,
+    //attribute name, form and the value of DW_FORM_implicit_const
+    attributes:  Vec<(u64, u64, i64)>,
+}
+
+fn readAbbreviations(section: &[u8], offset: usize) -> HashMap<u64, Abbreviation> {
+    let mut table = HashMap::new();
+    let mut r = Reader::new(section, offset, false);
+    while let Some(code) = r.uleb() {
+        if code == 0 {break;}
+        let (tag, children) = match (r.uleb(), r.u8()) {
+            (Some(t), Some(c)) => (t, c),
+            _ => break,
+        };
+        let mut attributes = vec![];
+        loop {
+            let (name, form) = match (r.uleb(), r.uleb()) {
+                (Some(n), Some(f)) => (n, f),
+                _ => break,
+            };
+            if name == 0 && form == 0 {break;}
+            let implicit = if form == DW_FORM_implicit_const {r.sleb().unwrap_or(0)} else {0};
+            attributes.push((name, form, implicit));
+        }
+        table.insert(code, Abbreviation{tag: tag, hasChildren: children != 0, attributes: attributes});
+    }
+    table
+}
+
+//the header fields of a unit that decide how its forms are read
+#[derive(Clone, Copy)]
+struct UnitHeader {
+    offset:      usize,
+    version:     u16,
+    offsetSize:  usize,
+    addressSize: usize,
+}
+
+//the debug sections of one file, SHT_NOBITS and compressed sections count as missing
+struct Sections<'a> {
+    info:       &'a [u8],
+    abbrev:     &'a [u8],
+    line:       &'a [u8],
+    str:        &'a [u8],
+    lineStr:    &'a [u8],
+    ranges:     &'a [u8],
+    rnglists:   &'a [u8],
+    addr:       &'a [u8],
+    strOffsets: &'a [u8],
+}
+
+impl<'a> Sections<'a> {
+    fn new(loader: &'a loader::Loader) -> Self {
+        let get = |name: &str| -> &'a [u8] {
+            match loader.findSection(name) {
+                Some(i) if loader.sectionHeaders[i].sh_flags & sectionheader::SHF_COMPRESSED == 0 => loader.sectionData(i).unwrap_or(&[]),
+                _ => &[],
+            }
+        };
+        Self {
+            info:       get(".debug_info"),
+            abbrev:     get(".debug_abbrev"),
+            line:       get(".debug_line"),
+            str:        get(".debug_str"),
+            lineStr:    get(".debug_line_str"),
+            ranges:     get(".debug_ranges"),
+            rnglists:   get(".debug_rnglists"),
+            addr:       get(".debug_addr"),
+            strOffsets: get(".debug_str_offsets"),
+        }
+    }
+}
+
+fn readAttribute(r: &mut Reader, form: u64, implicit: i64, unit: &UnitHeader, sections: &Sections) -> Option<AttributeValue> {
+    //DW_FORM_indirect puts the real form in front of the value, naming indirect again is not allowed
+    let form = if form == DW_FORM_indirect {r.uleb()?} else {form};
+    if form == DW_FORM_indirect {return None;}
+    let value = match form {
+        DW_FORM_addr => AttributeValue::Address(r.read(unit.addressSize)?),
+        DW_FORM_data1 | DW_FORM_ref1 | DW_FORM_flag => {
+            let v = r.read(1)?;
+            if form == DW_FORM_ref1 {AttributeValue::Reference(unit.offset.wrapping_add(v as usize))} else {AttributeValue::Unsigned(v)}
+        },
+        DW_FORM_data2 | DW_FORM_ref2 => {
+            let v = r.read(2)?;
+            if form == DW_FORM_ref2 {AttributeValue::Reference(unit.offset.wrapping_add(v as usize))} else {AttributeValue::Unsigned(v)}
+        },
+        DW_FORM_data4 | DW_FORM_ref4 => {
+            let v = r.read(4)?;
+            if form == DW_FORM_ref4 {AttributeValue::Reference(unit.offset.wrapping_add(v as usize))} else {AttributeValue::Unsigned(v)}
+        },
+        DW_FORM_data8 | DW_FORM_ref8 => {
+            let v = r.read(8)?;
+            if form == DW_FORM_ref8 {AttributeValue::Reference(unit.offset.wrapping_add(v as usize))} else {AttributeValue::Unsigned(v)}
+        },
+        DW_FORM_ref_udata => AttributeValue::Reference(unit.offset.wrapping_add(r.uleb()? as usize)),
+        DW_FORM_udata => AttributeValue::Unsigned(r.uleb()?),
+        DW_FORM_sdata => AttributeValue::Signed(r.sleb()?),
+        DW_FORM_implicit_const => AttributeValue::Signed(implicit),
+        DW_FORM_string => AttributeValue::String(r.string()?),
+        DW_FORM_strp => AttributeValue::StringOffset(r.read(unit.offsetSize)?),
+        DW_FORM_line_strp => {
+            let offset = r.read(unit.offsetSize)? as usize;
+            AttributeValue::String(stringAt(sections.lineStr, offset).unwrap_or_default())
+        },
+        DW_FORM_strx | DW_FORM_GNU_str_index => AttributeValue::StringIndex(r.uleb()?),
+        DW_FORM_strx1 => AttributeValue::StringIndex(r.read(1)?),
+        DW_FORM_strx2 => AttributeValue::StringIndex(r.read(2)?),
+        DW_FORM_strx3 => AttributeValue::StringIndex(r.read(3)?),
+        DW_FORM_strx4 => AttributeValue::StringIndex(r.read(4)?),
+        DW_FORM_addrx | DW_FORM_GNU_addr_index => AttributeValue::AddressIndex(r.uleb()?),
+        DW_FORM_addrx1 => AttributeValue::AddressIndex(r.read(1)?),
+        DW_FORM_addrx2 => AttributeValue::AddressIndex(r.read(2)?),
+        DW_FORM_addrx3 => AttributeValue::AddressIndex(r.read(3)?),
+        DW_FORM_addrx4 => AttributeValue::AddressIndex(r.read(4)?),
+        DW_FORM_rnglistx => AttributeValue::RangeIndex(r.uleb()?),
+        DW_FORM_loclistx => AttributeValue::Unsigned(r.uleb()?),
+        DW_FORM_ref_addr => {
+            let size = if unit.version <= 2 {unit.addressSize} else {unit.offsetSize};
+            AttributeValue::Reference(r.read(size)? as usize)
+        },
+        DW_FORM_sec_offset => AttributeValue::SectionOffset(r.read(unit.offsetSize)?),
+        DW_FORM_strp_sup | DW_FORM_GNU_strp_alt | DW_FORM_GNU_ref_alt => {
+            r.read(unit.offsetSize)?;
+            AttributeValue::Other
+        },
+        DW_FORM_ref_sup4 => {r.read(4)?; AttributeValue::Other},
+        DW_FORM_ref_sup8 | DW_FORM_ref_sig8 => {r.read(8)?; AttributeValue::Other},
+        DW_FORM_data16 => {r.skip(16)?; AttributeValue::Other},
+        DW_FORM_flag_present => AttributeValue::Unsigned(1),
+        DW_FORM_block1 => {let n = r.read(1)? as usize; r.skip(n)?; AttributeValue::Other},
+        DW_FORM_block2 => {let n = r.read(2)? as usize; r.skip(n)?; AttributeValue::Other},
+        DW_FORM_block4 => {let n = r.read(4)? as usize; r.skip(n)?; AttributeValue::Other},
+        DW_FORM_block | DW_FORM_exprloc => {let n = r.uleb()? as usize; r.skip(n)?; AttributeValue::Other},
+        //an unknown form has an unknown size so the rest of the unit cannot be read
+        _ => return None,
+    };
+    Some(value)
+}
+
+//a row of the line number table
+#[derive(Debug, Clone, Copy)]
+pub struct LineRow {
+    pub address:     u64,
+    //index into DebugInfo::files
+    pub file:        usize,
+    pub line:        u32,
+    pub column:      u32,
+    pub endSequence: bool,
+}
+
+//a subprogram or inlined subroutine
+#[derive(Debug, Clone)]
+pub struct Function {
+    pub name:       Option<String>,
+    //nesting depth inside the compile unit, inlined subroutines are deeper than their callers
+    pub depth:      usize,
+    //where an inlined subroutine was called from, file is an index into DebugInfo::files
+    pub callFile:   Option<usize>,
+    pub callLine:   u32,
+    pub callColumn: u32,
+}
+
+//one source location of an address, innermost inlined function first
+#[derive(Debug, Clone, PartialEq)]
+pub struct Frame {
+    pub function: Option<String>,
+    pub file:     Option<String>,
+    pub line:     u32,
+    pub column:   u32,
+}
+
+//a compile unit and the code it covers
+#[derive(Debug, Clone)]
+pub struct Unit {
+    //DW_AT_name, the main source file as the compiler was given it
+    pub name:   String,
+    pub ranges: Vec<(u64, u64)>,
+}
+
+//line tables and function ranges of a file, read once
+pub struct DebugInfo {
+    //full paths of every file named by the line tables, shared by all units
+    pub files:     Vec<String>,
+    //rows of every sequence sorted by address, end of sequence rows mark the gaps
+    pub rows:      Vec<LineRow>,
+    pub functions: Vec<Function>,
+    pub units:     Vec<Unit>,
+    index:         IntervalIndex<usize>,
+}
+
+impl DebugInfo {
+
+    //reads the DWARF sections of a file, None when there is no usable .debug_info or .debug_line
+    pub fn new(loader: &loader::Loader) -> Option<Self> {
+        let sections = Sections::new(loader);
+        if sections.info.is_empty() && sections.line.is_empty() {return None;}
+        let big = loader.header.e_ident.Data == 2;
+        let mut info = Self{files: vec![], rows: vec![], functions: vec![], units: vec![], index: IntervalIndex::new(vec![])};
+        let mut intervals = vec![];
+        let mut lineTables: HashMap<usize, Vec<usize>> = HashMap::new();
+
+        let mut r = Reader::new(sections.info, 0, big);
+        while !r.isEmpty() {
+            let start = r.pos;
+            let (length, offsetSize) = match r.initialLength() {
+                Some(l) => l,
+                None    => break,
+            };
+            let end = match r.pos.checked_add(length) {
+                Some(e) if e <= sections.info.len() => e,
+                _                                   => break,
+            };
+            let version = match r.read(2) {
+                Some(v) => v as u16,
+                None    => break,
+            };
+            let (unitType, abbrevOffset, addressSize) = if version >= 5 {
+                let unitType = r.u8().unwrap_or(0);
+                let addressSize = r.u8().unwrap_or(8) as usize;
+                let abbrev = r.read(offsetSize).unwrap_or(0) as usize;
+                (unitType, abbrev, addressSize)
+            } else {
+                let abbrev = r.read(offsetSize).unwrap_or(0) as usize;
+                (DW_UT_compile, abbrev, r.u8().unwrap_or(8) as usize)
+            };
+            match unitType {
+                DW_UT_compile | DW_UT_partial => {},
+                DW_UT_skeleton | DW_UT_split_compile => {r.skip(8);},
+                _ => {
+                    r.pos = end;
+                    continue;
+                },
+            }
+            let unit = UnitHeader{offset: start, version: version, offsetSize: offsetSize, addressSize: addressSize};
+            let abbreviations = readAbbreviations(sections.abbrev, abbrevOffset);
+            info.readUnit(&mut r, end, &unit, &abbreviations, &sections, big, &mut intervals, &mut lineTables);
+            r.pos = end;
+        }
+
+        //units without debug info entries still have line tables
+        let mut offset = 0;
+        while offset < sections.line.len() {
+            let next = match Reader::new(sections.line, offset, big).initialLength() {
+                Some((length, size)) => match offset.checked_add(length).and_then(|o| o.checked_add(if size == 8 {12} else {4})) {
+                    Some(n) => n,
+                    None    => break,
+                },
+                None                 => break,
+            };
+            if !lineTables.contains_key(&offset) {
+                info.readLineProgram(&sections, offset, "", big);
+            }
+            offset = next;
+        }
+
+        info.rows.sort_by_key(|r| (r.address, !r.endSequence));
+        info.index = IntervalIndex::new(intervals);
+        Some(info)
+    }
+
+    //reads the entries of one compile unit, recording function ranges and the unit's line table
+    fn readUnit(&mut self, r: &mut Reader, end: usize, unit: &UnitHeader, abbreviations: &HashMap<u64, Abbreviation>,
+                sections: &Sections, big: bool, intervals: &mut Vec<(u64, u64, usize)>, lineTables: &mut HashMap<usize, Vec<usize>>) {
+        //names of every entry that has one, so abstract origins can be followed after the unit is read
+        let mut names: HashMap<usize, String> = HashMap::new();
+        let mut origins: HashMap<usize, usize> = HashMap::new();
+        let mut pending: Vec<(usize, usize)> = vec![];
+        let mut fileMap: Vec<usize> = vec![];
+        let mut strOffsetsBase = 8u64;
+        let mut addrBase = 8u64;
+        let mut rnglistsBase = 0u64;
+        let mut unitBase = 0u64;
+        let mut depth = 0usize;
+
+        while r.pos < end {
+            let entryOffset = r.pos;
+            let code = match r.uleb() {
+                Some(c) => c,
+                None    => return,
+            };
+            if code == 0 {
+                depth = depth.saturating_sub(1);
+                continue;
+            }
+            let abbreviation = match abbreviations.get(&code) {
+                Some(a) => a,
+                None    => return,
+            };
+            let mut values: Vec<(u64, AttributeValue)> = vec![];
+            for (name, form, implicit) in abbreviation.attributes.iter() {
+                match readAttribute(r, *form, *implicit, unit, sections) {
+                    Some(v) => values.push((*name, v)),
+                    None    => return,
+                }
+            }
+            let get = |name: u64| values.iter().find(|(n, _)| *n == name).map(|(_, v)| v.clone());
+
+            if abbreviation.tag == DW_TAG_compile_unit || abbreviation.tag == DW_TAG_partial_unit {
+                if let Some(v) = get(DW_AT_str_offsets_base).and_then(|v| v.unsigned()) {strOffsetsBase = v;}
+                if let Some(v) = get(DW_AT_addr_base).and_then(|v| v.unsigned()) {addrBase = v;}
+                if let Some(v) = get(DW_AT_rnglists_base).and_then(|v| v.unsigned()) {rnglistsBase = v;}
+            }
+            let resolveString = |v: AttributeValue| -> Option<String> {
+                match v {
+                    AttributeValue::String(s)       => Some(s),
+                    AttributeValue::StringOffset(o) => stringAt(sections.str, o as usize),
+                    AttributeValue::StringIndex(i)  => {
+                        let at = (strOffsetsBase as usize).wrapping_add((i as usize).wrapping_mul(unit.offsetSize));
+                        let offset = Reader::new(sections.strOffsets, at, big).read(unit.offsetSize)?;
+                        stringAt(sections.str, offset as usize)
+                    },
+                    _ => None,
+                }
+            };
+            let resolveAddress = |v: AttributeValue| -> Option<u64> {
+                match v {
+                    AttributeValue::Address(a)      => Some(a),
+                    AttributeValue::AddressIndex(i) => {
+                        let at = (addrBase as usize).wrapping_add((i as usize).wrapping_mul(unit.addressSize));
+                        Reader::new(sections.addr, at, big).read(unit.addressSize)
+                    },
+                    _ => None,
+                }
+            };
+
+            //DW_AT_ranges wins when an entry has both, DW_AT_low_pc is then only the base
+            let rangesOf = |base: u64| -> Vec<(u64, u64)> {
+                if let Some(v) = get(DW_AT_ranges) {
+                    return self.readRanges(sections, unit, v, base, addrBase, rnglistsBase, big);
+                }
+                let low = match get(DW_AT_low_pc).and_then(|v| resolveAddress(v)) {
+                    Some(l) => l,
+                    None    => return vec![],
+                };
+                let high = match get(DW_AT_high_pc) {
+                    Some(AttributeValue::Address(a))        => Some(a),
+                    Some(v @ AttributeValue::AddressIndex(_)) => resolveAddress(v),
+                    Some(v)                                 => v.unsigned().map(|o| low.wrapping_add(o)),
+                    None                                    => low.checked_add(1),
+                };
+                high.map(|h| vec![(low, h)]).unwrap_or_default()
+            };
+
+            match abbreviation.tag {
+                DW_TAG_compile_unit | DW_TAG_partial_unit => {
+                    unitBase = get(DW_AT_low_pc).and_then(|v| resolveAddress(v)).unwrap_or(0);
+                    //a unit with only a low_pc has no code
+                    let ranges = if get(DW_AT_high_pc).is_some() || get(DW_AT_ranges).is_some() {rangesOf(unitBase)} else {vec![]};
+                    let name = get(DW_AT_name).and_then(|v| resolveString(v)).unwrap_or_default();
+                    self.units.push(Unit{name: name, ranges: ranges.into_iter().filter(|(l, h)| l < h).collect()});
+                    let compDir = get(DW_AT_comp_dir).and_then(|v| resolveString(v)).unwrap_or_default();
+                    if let Some(offset) = get(DW_AT_stmt_list).and_then(|v| v.unsigned()) {
+                        fileMap = self.readLineProgram(sections, offset as usize, &compDir, big);
+                        lineTables.insert(offset as usize, fileMap.clone());
+                    }
+                },
+                DW_TAG_subprogram | DW_TAG_inlined_subroutine => {
+                    let name = get(DW_AT_linkage_name).or_else(|| get(DW_AT_MIPS_linkage_name)).or_else(|| get(DW_AT_name))
+                        .and_then(|v| resolveString(v));
+                    if let Some(n) = name.as_ref() {names.insert(entryOffset, n.clone());}
+                    if let Some(AttributeValue::Reference(o)) = get(DW_AT_abstract_origin).or_else(|| get(DW_AT_specification)) {
+                        origins.insert(entryOffset, o);
+                    }
+
+                    let ranges = rangesOf(unitBase);
+                    if !ranges.is_empty() {
+                        let index = self.functions.len();
+                        self.functions.push(Function {
+                            name:       name,
+                            depth:      depth,
+                            callFile:   get(DW_AT_call_file).and_then(|v| v.unsigned())
+                                .and_then(|f| fileMap.get(f as usize).cloned()),
+                            callLine:   get(DW_AT_call_line).and_then(|v| v.unsigned()).unwrap_or(0) as u32,
+                            callColumn: get(DW_AT_call_column).and_then(|v| v.unsigned()).unwrap_or(0) as u32,
+                        });
+                        pending.push((index, entryOffset));
+                        for (low, high) in ranges {
+                            if low < high {intervals.push((low, high, index));}
+                        }
+                    }
+                },
+                _ => {},
+            }
+            if abbreviation.hasChildren {depth += 1;}
+        }
+
+        //inlined copies only name their abstract origin, which can itself point at a declaration
+        for (index, offset) in pending {
+            let mut current = offset;
+            for _ in 0..8 {
+                if let Some(n) = names.get(&current) {
+                    self.functions[index].name = Some(n.clone());
+                    break;
+                }
+                match origins.get(&current) {
+                    Some(o) => current = *o,
+                    None    => break,
+                }
+            }
+        }
+    }
+
+    //reads a DW_AT_ranges list from .debug_ranges before DWARF 5 and .debug_rnglists after
+    fn readRanges(&self, sections: &Sections, unit: &UnitHeader, value: AttributeValue, base: u64,
+                  addrBase: u64, rnglistsBase: u64, big: bool) -> Vec<(u64, u64)> {
+        let mut ranges = vec![];
+        let size = unit.addressSize;
+        let offset = match value {
+            AttributeValue::RangeIndex(i) => {
+                let at = (rnglistsBase as usize).wrapping_add((i as usize).wrapping_mul(unit.offsetSize));
+                match Reader::new(sections.rnglists, at, big).read(unit.offsetSize) {
+                    Some(o) => rnglistsBase.wrapping_add(o),
+                    None    => return ranges,
+                }
+            },
+            v => match v.unsigned() {
+                Some(o) => o,
+                None    => return ranges,
+            },
+        };
+
+        if unit.version < 5 {
+            let mut r = Reader::new(sections.ranges, offset as usize, big);
+            let mut base = base;
+            let max = if size == 4 {0xffffffff} else {u64::MAX};
+            while let (Some(start), Some(end)) = (r.read(size), r.read(size)) {
+                if start == 0 && end == 0 {break;}
+                if start == max {
+                    base = end;
+                    continue;
+                }
+                ranges.push((base.wrapping_add(start), base.wrapping_add(end)));
+            }
+            return ranges;
+        }
+
+        let address = |i: u64| Reader::new(sections.addr, (addrBase as usize).wrapping_add((i as usize).wrapping_mul(size)), big).read(size);
+        let mut r = Reader::new(sections.rnglists, offset as usize, big);
+        let mut base = base;
+        loop {
+            let kind = match r.u8() {
+                Some(k) => k,
+                None    => break,
+            };
+            let range = match kind {
+                DW_RLE_end_of_list   => break,
+                DW_RLE_base_addressx => {
+                    base = r.uleb().and_then(|i| address(i)).unwrap_or(0);
+                    None
+                },
+                DW_RLE_startx_endx   => match (r.uleb().and_then(|i| address(i)), r.uleb().and_then(|i| address(i))) {
+                    (Some(s), Some(e)) => Some((s, e)),
+                    _ => break,
+                },
+                DW_RLE_startx_length => match (r.uleb().and_then(|i| address(i)), r.uleb()) {
+                    (Some(s), Some(l)) => Some((s, s.wrapping_add(l))),
+                    _ => break,
+                },
+                DW_RLE_offset_pair   => match (r.uleb(), r.uleb()) {
+                    (Some(s), Some(e)) => Some((base.wrapping_add(s), base.wrapping_add(e))),
+                    _ => break,
+                },
+                DW_RLE_base_address  => {
+                    base = r.read(size).unwrap_or(0);
+                    None
+                },
+                DW_RLE_start_end     => match (r.read(size), r.read(size)) {
+                    (Some(s), Some(e)) => Some((s, e)),
+                    _ => break,
+                },
+                DW_RLE_start_length  => match (r.read(size), r.uleb()) {
+                    (Some(s), Some(l)) => Some((s, s.wrapping_add(l))),
+                    _ => break,
+                },
+                _ => break,
+            };
+            if let Some(range) = range {ranges.push(range);}
+        }
+        ranges
+    }
+
+    //runs the line number program at offset, appending its rows
+    //returns the unit's file table as indexes into files, index 0 unused before DWARF 5
+    fn readLineProgram(&mut self, sections: &Sections, offset: usize, compDir: &str, big: bool) -> Vec<usize> {
+        let mut fileMap = vec![];
+        let mut r = Reader::new(sections.line, offset, big);
+        let (length, offsetSize) = match r.initialLength() {
+            Some(l) => l,
+            None    => return fileMap,
+        };
+        let end = match r.pos.checked_add(length) {
+            Some(e) => e.min(sections.line.len()),
+            None    => return fileMap,
+        };
+        let header = (|| {
+            let version = r.read(2)? as u16;
+            let mut addressSize = 8;
+            if version >= 5 {
+                addressSize = r.u8()? as usize;
+                r.u8()?;
+            }
+            let headerLength = r.read(offsetSize)? as usize;
+            let programStart = r.pos.checked_add(headerLength)?;
+            let minLength = r.u8()? as u64;
+            if version >= 4 {r.u8()?;}
+            let defaultIsStmt = r.u8()?;
+            let lineBase = r.u8()? as i8 as i64;
+            let lineRange = r.u8()? as u64;
+            let opcodeBase = r.u8()?;
+            let mut standardLengths = vec![];
+            for _ in 1..opcodeBase {
+                standardLengths.push(r.u8()?);
+            }
+            let _ = defaultIsStmt;
+            Some((version, addressSize, programStart, minLength, lineBase, lineRange, opcodeBase, standardLengths))
+        })();
+        let (version, _addressSize, programStart, minLength, lineBase, lineRange, opcodeBase, standardLengths) = match header {
+            Some(h) if h.5 != 0 => h,
+            _ => return fileMap,
+        };
+
+        let mut directories: Vec<String> = vec![];
+        let mut names: Vec<(String, usize)> = vec![];
+        if version >= 5 {
+            let unit = UnitHeader{offset: 0, version: version, offsetSize: offsetSize, addressSize: 8};
+            let readEntries = |r: &mut Reader| -> Option<Vec<(String, usize)>> {
+                let formatCount = r.u8()?;
+                let mut format = vec![];
+                for _ in 0..formatCount {
+                    format.push((r.uleb()?, r.uleb()?));
+                }
+                let count = r.uleb()?;
+                let mut entries = vec![];
+                for _ in 0..count {
+                    let mut path = String::new();
+                    let mut directory = 0;
+                    for (kind, form) in format.iter() {
+                        let value = readAttribute(r, *form, 0, &unit, sections)?;
+                        match *kind {
+                            DW_LNCT_path => path = match value {
+                                AttributeValue::String(s)       => s,
+                                AttributeValue::StringOffset(o) => stringAt(sections.str, o as usize).unwrap_or_default(),
+                                _                               => String::new(),
+                            },
+                            DW_LNCT_directory_index => directory = value.unsigned().unwrap_or(0) as usize,
+                            _ => {},
+                        }
+                    }
+                    entries.push((path, directory));
+                }
+                Some(entries)
+            };
+            directories = readEntries(&mut r).unwrap_or_default().into_iter().map(|(p, _)| p).collect();
+            names = readEntries(&mut r).unwrap_or_default();
+        } else {
+            directories.push(compDir.to_string());
+            while let Some(d) = r.string() {
+                if d.is_empty() {break;}
+                directories.push(d);
+            }
+            //file numbers start at 1, keep a placeholder at 0
+            names.push((String::new(), 0));
+            while let Some(n) = r.string() {
+                if n.is_empty() {break;}
+                let directory = r.uleb().unwrap_or(0) as usize;
+                r.uleb();
+                r.uleb();
+                names.push((n, directory));
+            }
+        }
+        for (name, directory) in names.iter() {
+            fileMap.push(self.addFile(name, directories.get(*directory).map(|d| d.as_str()).unwrap_or(""), compDir));
+        }
+
+        r.pos = programStart;
+        let reset = LineRow{address: 0, file: 1, line: 1, column: 0, endSequence: false};
+        let fileOf = |fileMap: &Vec<usize>, f: usize| fileMap.get(f).cloned().unwrap_or(usize::MAX);
+        //a unit whose addresses or lengths overflow is malformed, none of its rows are kept
+        let first = self.rows.len();
+        let ran = (|| {
+            let mut row = reset;
+            while r.pos < end {
+                let opcode = match r.u8() {
+                    Some(o) => o,
+                    None    => break,
+                };
+                if opcode >= opcodeBase {
+                    let adjusted = (opcode - opcodeBase) as u64;
+                    row.address = row.address.checked_add((adjusted / lineRange).checked_mul(minLength)?)?;
+                    row.line = (row.line as i64 + lineBase + (adjusted % lineRange) as i64) as u32;
+                    self.rows.push(LineRow{file: fileOf(&fileMap, row.file), ..row});
+                    continue;
+                }
+                match opcode {
+                    0 => {
+                        let length = r.uleb().unwrap_or(0) as usize;
+                        let next = r.pos.checked_add(length)?;
+                        match r.u8().unwrap_or(0) {
+                            DW_LNE_end_sequence => {
+                                row.endSequence = true;
+                                self.rows.push(LineRow{file: fileOf(&fileMap, row.file), ..row});
+                                row = reset;
+                            },
+                            DW_LNE_set_address => {
+                                //length counts the sub-opcode, the address is the rest
+                                let size = length.checked_sub(1).filter(|s| *s <= 8)?;
+                                row.address = r.read(size).unwrap_or(0);
+                            },
+                            DW_LNE_define_file => {
+                                let name = r.string().unwrap_or_default();
+                                let directory = r.uleb().unwrap_or(0) as usize;
+                                let d = directories.get(directory).cloned().unwrap_or_default();
+                                let index = self.addFile(&name, &d, compDir);
+                                fileMap.push(index);
+                            },
+                            _ => {},
+                        }
+                        r.pos = next;
+                    },
+                    DW_LNS_copy => self.rows.push(LineRow{file: fileOf(&fileMap, row.file), ..row}),
+                    DW_LNS_advance_pc => row.address = row.address.checked_add(r.uleb().unwrap_or(0).checked_mul(minLength)?)?,
+                    DW_LNS_advance_line => row.line = (row.line as i64).wrapping_add(r.sleb().unwrap_or(0)) as u32,
+                    DW_LNS_set_file => row.file = r.uleb().unwrap_or(0) as usize,
+                    DW_LNS_set_column => row.column = r.uleb().unwrap_or(0) as u32,
+                    DW_LNS_negate_stmt | DW_LNS_set_basic_block => {},
+                    DW_LNS_const_add_pc => row.address = row.address.checked_add(((255 - opcodeBase as u64) / lineRange).checked_mul(minLength)?)?,
+                    DW_LNS_fixed_advance_pc => row.address = row.address.checked_add(r.read(2).unwrap_or(0))?,
+                    _ => {
+                        //unknown standard opcodes say how many operands to skip
+                        for _ in 0..standardLengths.get(opcode as usize - 1).cloned().unwrap_or(0) {
+                            r.uleb();
+                        }
+                    },
+                }
+            }
+            Some(())
+        })();
+        if ran.is_none() {self.rows.truncate(first);}
+        fileMap
+    }
+
+    //returns the index of a file in files, adding it if it is new
+    fn addFile(&mut self, name: &str, directory: &str, compDir: &str) -> usize {
+        let mut path = if name.starts_with('/') || directory.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}/{}", directory.trim_end_matches('/'), name)
+        };
+        if !path.starts_with('/') && !compDir.is_empty() {
+            path = format!("{}/{}", compDir.trim_end_matches('/'), path);
+        }
+        match self.files.iter().position(|f| *f == path) {
+            Some(i) => i,
+            None    => {
+                self.files.push(path);
+                self.files.len() - 1
+            },
+        }
+    }
+
+    //returns the line table row covering an address
+    pub fn lineFor(&self, address: u64) -> Option<LineRow> {
+        let i = self.rows.partition_point(|r| r.address <= address);
+        let row = self.rows.get(i.checked_sub(1)?)?;
+        if row.endSequence {return None;}
+        Some(*row)
+    }
+
+    //returns the source locations of an address, the innermost inlined function first
+    pub fn frames(&self, address: u64) -> Vec<Frame> {
+        let mut functions: Vec<&Function> = self.index.containing(address).into_iter().map(|(_, _, i)| &self.functions[*i]).collect();
+        functions.sort_by_key(|f| std::cmp::Reverse(f.depth));
+        let line = self.lineFor(address);
+        let fileName = |i: usize| self.files.get(i).cloned();
+
+        let mut frames = vec![];
+        let mut location = (line.and_then(|l| fileName(l.file)), line.map(|l| l.line).unwrap_or(0), line.map(|l| l.column).unwrap_or(0));
+        for f in functions.iter() {
+            frames.push(Frame{function: f.name.clone(), file: location.0.clone(), line: location.1, column: location.2});
+            location = (f.callFile.and_then(|i| fileName(i)), f.callLine, f.callColumn);
+        }
+        if frames.is_empty() && line.is_some() {
+            frames.push(Frame{function: None, file: location.0, line: location.1, column: location.2});
+        }
+        frames
+    }
+}
@@ -0,0 +1,83 @@
+//dynamic section tags found in d_tag
+pub const DT_NULL:         usize = 0;
+pub const DT_NEEDED:       usize = 1;
+pub const DT_PLTRELSZ:     usize = 2;
+pub const DT_PLTGOT:       usize = 3;
+pub const DT_HASH:         usize = 4;
+pub const DT_STRTAB:       usize = 5;
+pub const DT_SYMTAB:       usize = 6;
+pub const DT_RELA:         usize = 7;
+pub const DT_RELASZ:       usize = 8;
+pub const DT_RELAENT:      usize = 9;
+pub const DT_STRSZ:        usize = 10;
+pub const DT_SYMENT:       usize = 11;
+pub const DT_INIT:         usize = 12;
+pub const DT_FINI:         usize = 13;
+pub const DT_SONAME:       usize = 14;
+pub const DT_RPATH:        usize = 15;
+pub const DT_SYMBOLIC:     usize = 16;
+pub const DT_REL:          usize = 17;
+pub const DT_RELSZ:        usize = 18;
+pub const DT_RELENT:       usize = 19;
+pub const DT_PLTREL:       usize = 20;
+pub const DT_DEBUG:        usize = 21;
+pub const DT_TEXTREL:      usize = 22;
+pub const DT_JMPREL:       usize = 23;
+pub const DT_BIND_NOW:     usize = 24;
+pub const DT_INIT_ARRAY:   usize = 25;
+pub const DT_FINI_ARRAY:   usize = 26;
+pub const DT_INIT_ARRAYSZ: usize = 27;
+pub const DT_FINI_ARRAYSZ: usize = 28;
+pub const DT_RUNPATH:      usize = 29;
+pub const DT_FLAGS:        usize = 30;
+pub const DT_GNU_HASH:     usize = 0x6ffffef5;
+pub const DT_VERSYM:       usize = 0x6ffffff0;
+pub const DT_RELACOUNT:    usize = 0x6ffffff9;
+pub const DT_RELCOUNT:     usize = 0x6ffffffa;
+pub const DT_FLAGS_1:      usize = 0x6ffffffb;
+pub const DT_VERDEF:       usize = 0x6ffffffc;
+pub const DT_VERDEFNUM:    usize = 0x6ffffffd;
+pub const DT_VERNEED:      usize = 0x6ffffffe;
+pub const DT_VERNEEDNUM:   usize = 0x6fffffff;
+
+//bits found in the DT_FLAGS entry
+pub const DF_ORIGIN:     usize = 0x1;
+pub const DF_SYMBOLIC:   usize = 0x2;
+pub const DF_TEXTREL:    usize = 0x4;
+pub const DF_BIND_NOW:   usize = 0x8;
+pub const DF_STATIC_TLS: usize = 0x10;
+
+//bits found in the DT_FLAGS_1 entry
+pub const DF_1_NOW:       usize = 0x1;
+pub const DF_1_ORIGIN:    usize = 0x80;
+pub const DF_1_NODEFLIB:  usize = 0x800;
+pub const DF_1_PIE:       usize = 0x08000000;
+
+#[derive(Debug, Clone)]
+pub struct DynamicEntry {
+
+    //type of the entry
+    pub d_tag: usize,
+
+    //integer value or virtual address depending on the tag
+    pub d_val: usize,
+}
+
+impl DynamicEntry {
+
+    //returns an uninitialized dynamic entry
+    pub fn new() -> Self {
+        Self {
+            d_tag: 0,
+            d_val: 0,
+        }
+    }
+
+    //true for the tags whose value is an offset into the dynamic string table
+    pub fn isString(&self) -> bool {
+        match self.d_tag {
+            DT_NEEDED | DT_SONAME | DT_RPATH | DT_RUNPATH => true,
+            _ => false,
+        }
+    }
+}
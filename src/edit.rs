@@ -0,0 +1,387 @@
+use crate::dynamic;
+use crate::programheader;
+use crate::sectionheader;
+use crate::writer;
+
+//most zero bytes write pads a file with to reach the offset of a new segment
+pub const MAX_PADDING: usize = 1 << 30;
+
+//changes the interpreter and the dynamic section of a file like patchelf
+//edits are collected first and applied by write, which keeps .interp, .dynstr and .dynamic in
+//place when they still fit and otherwise moves them, together with the program header table,
+//into a new PT_LOAD segment after the end of the file
+pub struct Editor {
+    pub file:        writer::ElfFile,
+
+    //PT_INTERP path, None for static files
+    pub interpreter: Option<String>,
+
+    //tags and values of the dynamic section without the terminating DT_NULL
+    pub entries:     Vec<(u64, u64)>,
+
+    //contents of the dynamic string table, only ever appended to since the symbol and version
+    //tables refer to it by offset
+    pub strings:     Vec<u8>,
+
+    //library names whose version requirements must follow a DT_NEEDED change
+    replaced:        Vec<(String, String)>,
+    removed:         Vec<String>,
+
+    //indexes of the sections being edited
+    dynamicIndex:    usize,
+    stringsIndex:    usize,
+}
+
+impl Editor {
+
+    //reads the dynamic section of a file, fails for files without one
+    pub fn new(file: writer::ElfFile) -> Result<Self, String> {
+        let dynamicIndex = file.sections.iter().position(|s| s.header.sh_type == sectionheader::SHT_DYNAMIC)
+            .ok_or("no dynamic section")?;
+        let stringsIndex = file.sections[dynamicIndex].header.sh_link as usize;
+        if file.sections.get(stringsIndex).map(|s| s.header.sh_type) != Some(sectionheader::SHT_STRTAB) {
+            return Err("the dynamic section is not linked to a string table".to_string());
+        }
+
+        let e = file.encoder();
+        let size = if e.wide {8} else {4};
+        let mut entries = vec![];
+        for entry in file.sections[dynamicIndex].data.chunks_exact(size * 2) {
+            let tag = decode(&e, &entry[..size]);
+            if tag == dynamic::DT_NULL as u64 {break;}
+            entries.push((tag, decode(&e, &entry[size..])));
+        }
+
+        let interpreter = file.segments.iter().find(|p| p.getTYPE() == programheader::PT_INTERP).and_then(|p| {
+            let bytes = file.image.get(p.getOFFSET()..p.getOFFSET().saturating_add(p.getFILESZ()))?;
+            let end = bytes.iter().position(|b| *b == 0).unwrap_or(bytes.len());
+            Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+        });
+
+        Ok(Self {
+            strings:      file.sections[stringsIndex].data.clone(),
+            file:         file,
+            interpreter:  interpreter,
+            entries:      entries,
+            replaced:     vec![],
+            removed:      vec![],
+            dynamicIndex: dynamicIndex,
+            stringsIndex: stringsIndex,
+        })
+    }
+
+    //returns the string at an offset into the dynamic string table
+    pub fn string(&self, offset: u64) -> Option<String> {
+        let bytes = self.strings.get(offset as usize..)?;
+        let end = bytes.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    //returns the offset of a string in the dynamic string table, adding it if it is not there,
+    //a tail of a longer string is reused like the linker does
+    pub fn addString(&mut self, text: &str) -> u64 {
+        let mut wanted = text.as_bytes().to_vec();
+        wanted.push(0);
+        if let Some(i) = self.strings.windows(wanted.len()).position(|w| w == &wanted[..]) {
+            return i as u64;
+        }
+        let offset = self.strings.len();
+        self.strings.extend_from_slice(&wanted);
+        offset as u64
+    }
+
+    //returns the strings of every entry with a tag, for example the DT_NEEDED names
+    pub fn strings(&self, tag: usize) -> Vec<String> {
+        self.entries.iter().filter(|(t, _)| *t == tag as u64).filter_map(|(_, v)| self.string(*v)).collect()
+    }
+
+    pub fn setInterpreter(&mut self, path: &str) -> Result<(), String> {
+        if self.interpreter.is_none() {return Err("the file has no PT_INTERP segment".to_string());}
+        self.interpreter = Some(path.to_string());
+        Ok(())
+    }
+
+    //sets DT_RUNPATH, or DT_RPATH when forced, turning an existing entry of the other kind into it
+    pub fn setRunpath(&mut self, path: &str, forceRpath: bool) {
+        let tag = if forceRpath {dynamic::DT_RPATH} else {dynamic::DT_RUNPATH} as u64;
+        let value = self.addString(path);
+        self.entries.retain(|(t, _)| *t != dynamic::DT_RPATH as u64 && *t != dynamic::DT_RUNPATH as u64 || *t == tag);
+        self.setEntry(tag, value);
+    }
+
+    //removes both DT_RPATH and DT_RUNPATH
+    pub fn removeRunpath(&mut self) {
+        self.entries.retain(|(t, _)| *t != dynamic::DT_RPATH as u64 && *t != dynamic::DT_RUNPATH as u64);
+    }
+
+    pub fn setSoname(&mut self, name: &str) {
+        let value = self.addString(name);
+        self.setEntry(dynamic::DT_SONAME as u64, value);
+    }
+
+    //adds a DT_NEEDED entry after the existing ones unless the library is already needed
+    pub fn addNeeded(&mut self, name: &str) {
+        if self.strings(dynamic::DT_NEEDED).iter().any(|n| n == name) {return;}
+        let value = self.addString(name);
+        let position = self.entries.iter().rposition(|(t, _)| *t == dynamic::DT_NEEDED as u64).map(|i| i + 1).unwrap_or(0);
+        self.entries.insert(position, (dynamic::DT_NEEDED as u64, value));
+    }
+
+    //removes the DT_NEEDED entries of a library, returns false if it was not needed
+    pub fn removeNeeded(&mut self, name: &str) -> bool {
+        let before = self.entries.len();
+        let strings = self.strings.clone();
+        self.entries.retain(|(t, v)| *t != dynamic::DT_NEEDED as u64 || stringAt(&strings, *v) != Some(name.as_bytes()));
+        if self.entries.len() == before {return false;}
+        self.removed.push(name.to_string());
+        true
+    }
+
+    //renames a needed library, returns false if it was not needed
+    pub fn replaceNeeded(&mut self, old: &str, new: &str) -> bool {
+        let positions: Vec<usize> = (0..self.entries.len())
+            .filter(|i| self.entries[*i].0 == dynamic::DT_NEEDED as u64 && self.string(self.entries[*i].1).as_deref() == Some(old))
+            .collect();
+        if positions.is_empty() {return false;}
+        let value = self.addString(new);
+        for i in positions {
+            self.entries[i].1 = value;
+        }
+        self.replaced.push((old.to_string(), new.to_string()));
+        true
+    }
+
+    //applies the edits and returns the bytes of the new file
+    pub fn write(&mut self) -> Result<Vec<u8>, String> {
+        self.updateVersionNeeds()?;
+        let strsz = self.strings.len() as u64;
+        self.setEntry(dynamic::DT_STRSZ as u64, strsz);
+
+        let e = self.file.encoder();
+        let entrySize = if e.wide {16} else {8};
+        let interpBytes = self.interpreter.as_ref().map(|p| {
+            let mut bytes = p.as_bytes().to_vec();
+            bytes.push(0);
+            bytes
+        });
+        //the room in place is what the file really holds, not what a header claims
+        let imageLen = self.file.image.len();
+        let interpSize = self.file.segments.iter().find(|p| p.getTYPE() == programheader::PT_INTERP)
+            .map(|p| p.getFILESZ().min(imageLen.saturating_sub(p.getOFFSET()))).unwrap_or(0);
+        let moveInterp = interpBytes.as_ref().map(|b| b.len() > interpSize).unwrap_or(false);
+        let stringsRoom = self.file.sections[self.stringsIndex].data.len();
+        let dynamicRoom = self.file.sections[self.dynamicIndex].data.len();
+        let moveStrings = self.strings.len() > stringsRoom;
+        let moveDynamic = (self.entries.len() + 1) * entrySize > dynamicRoom;
+
+        //file offset of the interpreter when it moves into the new segment
+        let mut interpOffset = None;
+        let loads: Vec<&programheader::ProgramHeader> = self.file.segments.iter().filter(|p| p.getTYPE() == programheader::PT_LOAD).collect();
+        let delta = loads.first().map(|p| p.getVADDR().wrapping_sub(p.getOFFSET())).unwrap_or(0);
+        if moveInterp || moveStrings || moveDynamic {
+            //the new segment keeps the distance between addresses and file offsets of the first
+            //PT_LOAD, so kernels computing AT_PHDR from e_phoff still find the table
+            if loads.is_empty() {return Err("no PT_LOAD segment to extend".to_string());}
+            let page = loads.iter().map(|p| p.getALIGN()).filter(|a| a.is_power_of_two()).max().unwrap_or(0).max(0x1000);
+            let memoryEnd = loads.iter().map(|p| p.getVADDR().saturating_add(p.getMEMSZ())).max().unwrap_or(0);
+            let fileEnd = self.file.image.len().max(self.file.header.e_shoff.saturating_add(self.file.sections.len() * self.file.header.e_shentsize as usize));
+            let vaddr = alignUp(memoryEnd.max(fileEnd.wrapping_add(delta)), page).ok_or("no room for a new PT_LOAD segment at the end of the address space")?;
+            let offset = vaddr.wrapping_sub(delta);
+            //headers claiming huge sizes would otherwise have the file padded out to match
+            if offset < self.file.image.len() || offset - self.file.image.len() > MAX_PADDING {
+                return Err(format!("the new PT_LOAD segment would start at offset {:#x}, too far past the end of the file", offset));
+            }
+
+            //the program header table grows by the new segment and moves to its start
+            let lastLoad = self.file.segments.iter().rposition(|p| p.getTYPE() == programheader::PT_LOAD).unwrap();
+            let mut segment = programheader::ProgramHeader::new();
+            segment.setTYPE(programheader::PT_LOAD);
+            segment.setFLAGS(programheader::PF_R | programheader::PF_W);
+            segment.setOFFSET(offset);
+            segment.setVADDR(vaddr);
+            segment.setPADDR(vaddr);
+            segment.setALIGN(page);
+            self.file.segments.insert(lastLoad + 1, segment);
+            self.file.header.e_phoff = offset;
+            for p in self.file.segments.iter_mut().filter(|p| p.getTYPE() == programheader::PT_PHDR) {
+                p.setOFFSET(offset);
+                p.setVADDR(vaddr);
+                p.setPADDR(vaddr);
+            }
+
+            let mut end = offset + self.file.segments.len() * if e.wide {56} else {32};
+            let mut place = |size: usize, align: usize| -> usize {
+                //offset was bounded above, so this can not overflow
+                let start = alignUp(end, align).unwrap();
+                end = start + size;
+                start
+            };
+            if moveInterp {interpOffset = Some(place(interpBytes.as_ref().unwrap().len(), 1));}
+            for (moved, index, align) in [(moveStrings, self.stringsIndex, 1), (moveDynamic, self.dynamicIndex, if e.wide {8} else {4})].iter() {
+                if !moved {continue;}
+                let size = if *index == self.stringsIndex {self.strings.len()} else {(self.entries.len() + 1) * entrySize};
+                let start = place(size, *align);
+                let s = &mut self.file.sections[*index].header;
+                s.sh_offset = start;
+                s.sh_addr = start.wrapping_add(delta);
+            }
+            let segment = &mut self.file.segments[lastLoad + 1];
+            segment.setFILESZ(end - offset);
+            segment.setMEMSZ(end - offset);
+        }
+
+        //the interpreter, padded with zeros in place or in the new segment
+        if let Some(mut bytes) = interpBytes {
+            let segment = self.file.segments.iter_mut().find(|p| p.getTYPE() == programheader::PT_INTERP).unwrap();
+            match interpOffset {
+                Some(start) => {
+                    segment.setOFFSET(start);
+                    segment.setVADDR(start.wrapping_add(delta));
+                    segment.setPADDR(start.wrapping_add(delta));
+                    segment.setFILESZ(bytes.len());
+                    segment.setMEMSZ(bytes.len());
+                },
+                None => bytes.resize(interpSize, 0),
+            }
+            let (offset, vaddr) = (segment.getOFFSET(), segment.getVADDR());
+            match self.file.findSection(".interp") {
+                Some(i) => {
+                    let s = &mut self.file.sections[i];
+                    s.header.sh_offset = offset;
+                    s.header.sh_addr = vaddr;
+                    s.data = bytes;
+                },
+                //without a section the bytes go straight into the image
+                None => {
+                    if self.file.image.len() < offset + bytes.len() {self.file.image.resize(offset + bytes.len(), 0);}
+                    self.file.image[offset..offset + bytes.len()].copy_from_slice(&bytes);
+                },
+            }
+        }
+
+        let strtab = self.file.sections[self.stringsIndex].header.sh_addr as u64;
+        self.setEntry(dynamic::DT_STRTAB as u64, strtab);
+        let mut strings = self.strings.clone();
+        if !moveStrings {strings.resize(stringsRoom, 0);}
+        self.file.sections[self.stringsIndex].data = strings;
+
+        //unused room in the dynamic section stays filled with DT_NULL entries
+        let mut data = vec![];
+        for (tag, value) in self.entries.iter().chain(std::iter::once(&(dynamic::DT_NULL as u64, 0))) {
+            e.word(&mut data, *tag);
+            e.word(&mut data, *value);
+        }
+        if !moveDynamic {data.resize(dynamicRoom, 0);}
+        let (offset, vaddr, size) = {
+            let s = &self.file.sections[self.dynamicIndex].header;
+            (s.sh_offset, s.sh_addr, data.len())
+        };
+        self.file.sections[self.dynamicIndex].data = data;
+        for p in self.file.segments.iter_mut().filter(|p| p.getTYPE() == programheader::PT_DYNAMIC) {
+            p.setOFFSET(offset);
+            p.setVADDR(vaddr);
+            p.setPADDR(vaddr);
+            p.setFILESZ(size);
+            p.setMEMSZ(size);
+        }
+        self.file.write()
+    }
+
+    //replaces the value of the first entry with a tag or adds one before DT_NULL
+    fn setEntry(&mut self, tag: u64, value: u64) {
+        match self.entries.iter_mut().find(|(t, _)| *t == tag) {
+            Some(entry) => entry.1 = value,
+            None        => self.entries.push((tag, value)),
+        }
+    }
+
+    //points the version requirements of renamed libraries at their new names and drops the
+    //ones of removed libraries, which the runtime linker would otherwise look for
+    fn updateVersionNeeds(&mut self) -> Result<(), String> {
+        if self.replaced.is_empty() && self.removed.is_empty() {return Ok(());}
+        let index = match self.file.sections.iter().position(|s| s.header.sh_type == sectionheader::SHT_GNU_VERNEED) {
+            Some(i) => i,
+            None    => return Ok(()),
+        };
+        let e = self.file.encoder();
+        let old = self.file.sections[index].data.clone();
+        let field = |offset: usize| -> Result<u32, String> {
+            old.get(offset..offset + 4).map(|b| decode(&e, b) as u32).ok_or_else(|| "truncated version requirements".to_string())
+        };
+
+        //each kept Elf_Verneed is written back followed by its Elf_Vernaux entries
+        let mut kept: Vec<(u16, u32, Vec<(u32, u16, u16, u32)>)> = vec![];
+        let mut offset = 0;
+        for _ in 0..self.file.sections[index].header.sh_info {
+            let version = (field(offset)? >> if e.bigEndian {16} else {0}) as u16;
+            let count = (field(offset)? >> if e.bigEndian {0} else {16}) as u16;
+            let mut file = field(offset + 4)?;
+            let mut aux = offset + field(offset + 8)? as usize;
+            let next = field(offset + 12)? as usize;
+            let name = self.string(file as u64).unwrap_or_default();
+            if !self.removed.contains(&name) {
+                if let Some((_, new)) = self.replaced.iter().find(|(old, _)| *old == name).cloned() {
+                    file = self.addString(&new) as u32;
+                }
+                let mut auxiliaries = vec![];
+                for _ in 0..count {
+                    let flagsOther = field(aux + 4)?;
+                    let (flags, other) = if e.bigEndian {((flagsOther >> 16) as u16, flagsOther as u16)} else {(flagsOther as u16, (flagsOther >> 16) as u16)};
+                    auxiliaries.push((field(aux)?, flags, other, field(aux + 8)?));
+                    let next = field(aux + 12)? as usize;
+                    if next == 0 {break;}
+                    aux += next;
+                }
+                kept.push((version, file, auxiliaries));
+            }
+            if next == 0 {break;}
+            offset += next;
+        }
+
+        let mut data = vec![];
+        for (i, (version, file, auxiliaries)) in kept.iter().enumerate() {
+            let size = 16 + 16 * auxiliaries.len();
+            e.u16(&mut data, *version);
+            e.u16(&mut data, auxiliaries.len() as u16);
+            e.u32(&mut data, *file);
+            e.u32(&mut data, 16);
+            e.u32(&mut data, if i + 1 < kept.len() {size as u32} else {0});
+            for (j, (hash, flags, other, name)) in auxiliaries.iter().enumerate() {
+                e.u32(&mut data, *hash);
+                e.u16(&mut data, *flags);
+                e.u16(&mut data, *other);
+                e.u32(&mut data, *name);
+                e.u32(&mut data, if j + 1 < auxiliaries.len() {16} else {0});
+            }
+        }
+        data.resize(old.len().max(data.len()), 0);
+
+        if kept.is_empty() {
+            self.entries.retain(|(t, _)| *t != dynamic::DT_VERNEED as u64 && *t != dynamic::DT_VERNEEDNUM as u64);
+        } else {
+            self.setEntry(dynamic::DT_VERNEEDNUM as u64, kept.len() as u64);
+        }
+        self.file.sections[index].header.sh_info = kept.len() as u32;
+        self.file.sections[index].data = data;
+        Ok(())
+    }
+}
+
+//reads an unsigned value in the byte order of a file
+fn decode(e: &writer::Encoder, bytes: &[u8]) -> u64 {
+    let mut value = 0;
+    for i in 0..bytes.len() {
+        let b = if e.bigEndian {bytes[i]} else {bytes[bytes.len() - 1 - i]};
+        value = (value << 8) | b as u64;
+    }
+    value
+}
+
+fn stringAt(strings: &[u8], offset: u64) -> Option<&[u8]> {
+    let bytes = strings.get(offset as usize..)?;
+    Some(&bytes[..bytes.iter().position(|b| *b == 0)?])
+}
+
+fn alignUp(value: usize, align: usize) -> Option<usize> {
+    Some(value.checked_add(align - 1)? / align * align)
+}
@@ -0,0 +1,444 @@
+use std::collections::HashSet;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net::TcpListener;
+use std::net::TcpStream;
+use std::os::unix::net::UnixListener;
+use std::os::unix::net::UnixStream;
+use crate::addressspace;
+use crate::riscv;
+use crate::syscall;
+
+//largest packet gdb may send or receive, memory replies take two characters per byte
+pub const PACKET_SIZE: usize = 0x4000;
+
+//signal numbers reported in stop replies
+pub const SIGINT:  u8 = 2;
+pub const SIGILL:  u8 = 4;
+pub const SIGTRAP: u8 = 5;
+pub const SIGSEGV: u8 = 11;
+
+//why a target stopped running
+#[derive(Debug, PartialEq)]
+pub enum StopReason {
+    //a single step finished
+    Stepped,
+
+    //execution stopped with a signal
+    Signal(u8),
+
+    //the program exited with a status
+    Exited(i32),
+}
+
+//cpu backends implement this so the stub can serve their registers and drive execution
+//register bytes are in target byte order and laid out the way gdb's g packet expects
+pub trait DebugTarget {
+    //returns every register in g packet order
+    fn readRegisters(&self) -> Vec<u8>;
+
+    //sets every register from a G packet
+    fn writeRegisters(&mut self, bytes: &[u8]);
+
+    //returns a single register for the p packet
+    fn readRegister(&self, n: usize) -> Option<Vec<u8>>;
+
+    //sets a single register for the P packet
+    fn writeRegister(&mut self, n: usize, bytes: &[u8]) -> Option<()>;
+
+    //returns the current program counter
+    fn pc(&self) -> u64;
+
+    //executes one instruction, servicing syscalls through os
+    fn singleStep(&mut self, space: &mut addressspace::AddressSpace, os: &mut dyn syscall::SyscallHandler) -> StopReason;
+
+    //bfd architecture name sent in target.xml so gdb picks the right register layout
+    fn architecture(&self) -> &'static str;
+}
+
+impl DebugTarget for riscv::Cpu {
+    fn readRegisters(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        for r in self.x.iter() {
+            bytes.extend_from_slice(&r.to_le_bytes());
+        }
+        bytes.extend_from_slice(&self.pc.to_le_bytes());
+        bytes
+    }
+    fn writeRegisters(&mut self, bytes: &[u8]) {
+        for (i, chunk) in bytes.chunks(8).enumerate().take(33) {
+            self.writeRegister(i, chunk);
+        }
+    }
+    fn readRegister(&self, n: usize) -> Option<Vec<u8>> {
+        match n {
+            0..=31 => Some(self.x[n].to_le_bytes().to_vec()),
+            32     => Some(self.pc.to_le_bytes().to_vec()),
+            _      => None,
+        }
+    }
+    fn writeRegister(&mut self, n: usize, bytes: &[u8]) -> Option<()> {
+        if bytes.len() != 8 {return None;}
+        let mut value = [0u8; 8];
+        value.copy_from_slice(bytes);
+        match n {
+            0      => {},
+            1..=31 => self.x[n] = u64::from_le_bytes(value),
+            32     => self.pc = u64::from_le_bytes(value),
+            _      => return None,
+        }
+        Some(())
+    }
+    fn pc(&self) -> u64 {
+        self.pc
+    }
+    fn singleStep(&mut self, space: &mut addressspace::AddressSpace, os: &mut dyn syscall::SyscallHandler) -> StopReason {
+        match self.step(space) {
+            Ok(()) => StopReason::Stepped,
+            Err(riscv::Trap::Ecall) => match os.syscall(self, space) {
+                Some(code) => StopReason::Exited(code),
+                None       => StopReason::Stepped,
+            },
+            Err(riscv::Trap::Exit(code))               => StopReason::Exited(code),
+            Err(riscv::Trap::Breakpoint(_))            => StopReason::Signal(SIGTRAP),
+            Err(riscv::Trap::IllegalInstruction{..})   => StopReason::Signal(SIGILL),
+            Err(_)                                     => StopReason::Signal(SIGSEGV),
+        }
+    }
+    fn architecture(&self) -> &'static str {
+        "riscv:rv64"
+    }
+}
+
+//byte stream the stub talks over, lets the stub poll for ctrl-c while the target runs
+pub trait Connection: Read + Write {
+    fn setNonblocking(&self, nonblocking: bool) -> io::Result<()>;
+}
+
+impl Connection for TcpStream {
+    fn setNonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.set_nonblocking(nonblocking)
+    }
+}
+
+impl Connection for UnixStream {
+    fn setNonblocking(&self, nonblocking: bool) -> io::Result<()> {
+        self.set_nonblocking(nonblocking)
+    }
+}
+
+//waits for gdb to connect to localhost:port
+pub fn acceptTcp(port: u16) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind(("127.0.0.1", port))?;
+    let (stream, _) = listener.accept()?;
+    stream.set_nodelay(true)?;
+    Ok(stream)
+}
+
+//waits for gdb to connect to a unix socket created at path
+pub fn acceptUnix(path: &str) -> io::Result<UnixStream> {
+    let listener = UnixListener::bind(path)?;
+    let (stream, _) = listener.accept()?;
+    Ok(stream)
+}
+
+//gdb remote serial protocol server for a program running in the emulator
+pub struct GdbStub<'a> {
+    pub target:      &'a mut dyn DebugTarget,
+    pub space:       &'a mut addressspace::AddressSpace,
+    pub os:          &'a mut dyn syscall::SyscallHandler,
+
+    //host path of the main executable returned by qXfer:exec-file
+    pub execFile:    String,
+
+    //addresses of software breakpoints set with Z0
+    pub breakpoints: HashSet<u64>,
+
+    //false once no-ack mode has been negotiated
+    ack:             bool,
+
+    //exit status once the program has finished
+    exited:          Option<i32>,
+}
+
+impl<'a> GdbStub<'a> {
+
+    //creates a stub serving the given target, memory image and syscall layer
+    pub fn new(target: &'a mut dyn DebugTarget, space: &'a mut addressspace::AddressSpace, os: &'a mut dyn syscall::SyscallHandler, execFile: &str) -> Self {
+        Self {
+            target:      target,
+            space:       space,
+            os:          os,
+            execFile:    execFile.to_string(),
+            breakpoints: HashSet::new(),
+            ack:         true,
+            exited:      None,
+        }
+    }
+
+    //serves packets until gdb detaches, kills the program or closes the connection
+    pub fn serve<C: Connection>(&mut self, conn: &mut C) -> io::Result<()> {
+        loop {
+            let packet = match self.readPacket(conn)? {
+                Some(p) => p,
+                None    => return Ok(()),
+            };
+            let reply = match self.handle(conn, &packet)? {
+                Some(r) => r,
+                None    => {
+                    self.writePacket(conn, b"OK")?;
+                    return Ok(());
+                },
+            };
+            self.writePacket(conn, &reply)?;
+        }
+    }
+
+    //reads the next packet, acknowledging it when acks are on
+    //returns None when the connection closes, packets with a bad checksum are refused until gdb resends them
+    fn readPacket<C: Connection>(&mut self, conn: &mut C) -> io::Result<Option<Vec<u8>>> {
+        let mut byte = [0u8; 1];
+        loop {
+            loop {
+                if conn.read(&mut byte)? == 0 {return Ok(None);}
+                match byte[0] {
+                    b'$' => break,
+                    //an interrupt outside of a continue is answered with a stop reply
+                    0x03 => return Ok(Some(b"?".to_vec())),
+                    _    => continue,
+                }
+            }
+            let mut data = vec![];
+            loop {
+                if conn.read(&mut byte)? == 0 {return Ok(None);}
+                if byte[0] == b'#' {break;}
+                data.push(byte[0]);
+            }
+            let mut checksum = [0u8; 2];
+            conn.read_exact(&mut checksum)?;
+            if !self.ack {return Ok(Some(data));}
+            let expected = data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+            let valid = std::str::from_utf8(&checksum).ok().and_then(|s| u8::from_str_radix(s, 16).ok()) == Some(expected);
+            conn.write_all(if valid {b"+"} else {b"-"})?;
+            if valid {return Ok(Some(data));}
+        }
+    }
+
+    //frames and sends a reply, waiting for the ack when acks are on
+    fn writePacket<C: Connection>(&mut self, conn: &mut C, data: &[u8]) -> io::Result<()> {
+        let checksum = data.iter().fold(0u8, |sum, b| sum.wrapping_add(*b));
+        let mut framed = vec![b'$'];
+        framed.extend_from_slice(data);
+        framed.extend_from_slice(format!("#{:02x}", checksum).as_bytes());
+        loop {
+            conn.write_all(&framed)?;
+            conn.flush()?;
+            if !self.ack {return Ok(());}
+            let mut byte = [0u8; 1];
+            if conn.read(&mut byte)? == 0 || byte[0] == b'+' {return Ok(());}
+        }
+    }
+
+    //builds the stop reply for the current state, swbreak tells gdb the stop was one of its
+    //breakpoints and must only be sent when the pc is on one
+    fn stopReply(&self, signal: u8, atBreakpoint: bool) -> Vec<u8> {
+        match self.exited {
+            Some(code) => format!("W{:02x}", code as u8).into_bytes(),
+            None if signal == SIGTRAP && atBreakpoint => format!("T{:02x}swbreak:;", signal).into_bytes(),
+            None if signal == SIGTRAP => format!("T{:02x}", signal).into_bytes(),
+            None => format!("S{:02x}", signal).into_bytes(),
+        }
+    }
+
+    //runs until a breakpoint, a signal, an exit or a ctrl-c from gdb
+    fn resume<C: Connection>(&mut self, conn: &mut C, step: bool) -> io::Result<Vec<u8>> {
+        if self.exited.is_some() {return Ok(self.stopReply(0, false));}
+        let mut steps: u64 = 0;
+        loop {
+            match self.target.singleStep(self.space, self.os) {
+                StopReason::Stepped      => {},
+                StopReason::Signal(s)    => return Ok(self.stopReply(s, false)),
+                StopReason::Exited(code) => {
+                    self.exited = Some(code);
+                    return Ok(self.stopReply(0, false));
+                },
+            }
+            let atBreakpoint = self.breakpoints.contains(&self.target.pc());
+            if step || atBreakpoint {return Ok(self.stopReply(SIGTRAP, atBreakpoint));}
+
+            //check for a ctrl-c every so often without slowing down execution
+            steps += 1;
+            if steps % 0x10000 == 0 {
+                conn.setNonblocking(true)?;
+                let mut byte = [0u8; 1];
+                let interrupted = matches!(conn.read(&mut byte), Ok(1) if byte[0] == 0x03);
+                conn.setNonblocking(false)?;
+                if interrupted {return Ok(self.stopReply(SIGINT, false));}
+            }
+        }
+    }
+
+    //handles one packet and returns the reply, None means gdb detached or killed the program
+    fn handle<C: Connection>(&mut self, conn: &mut C, packet: &[u8]) -> io::Result<Option<Vec<u8>>> {
+        let text = String::from_utf8_lossy(packet).to_string();
+        let reply: Vec<u8> = match packet.first() {
+            Some(b'?') => self.stopReply(SIGTRAP, false),
+            Some(b'g') => toHex(&self.target.readRegisters()).into_bytes(),
+            Some(b'G') => {
+                match fromHex(&text[1..]) {
+                    Some(bytes) => {
+                        self.target.writeRegisters(&bytes);
+                        b"OK".to_vec()
+                    },
+                    None => b"E01".to_vec(),
+                }
+            },
+            Some(b'p') => {
+                let register = usize::from_str_radix(&text[1..], 16).ok().and_then(|n| self.target.readRegister(n));
+                match register {
+                    Some(bytes) => toHex(&bytes).into_bytes(),
+                    None        => b"E01".to_vec(),
+                }
+            },
+            Some(b'P') => {
+                let mut parts = text[1..].splitn(2, '=');
+                let n = parts.next().and_then(|n| usize::from_str_radix(n, 16).ok());
+                let bytes = parts.next().and_then(fromHex);
+                match (n, bytes) {
+                    (Some(n), Some(bytes)) if self.target.writeRegister(n, &bytes).is_some() => b"OK".to_vec(),
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(b'm') => {
+                //gdb splits larger reads itself, so a reply never has to exceed the packet size
+                let memory = parseAddrLen(&text[1..]).and_then(|(addr, len)| self.space.read(addr as usize, (len as usize).min(PACKET_SIZE / 2)));
+                match memory {
+                    Some(bytes) => toHex(&bytes).into_bytes(),
+                    None        => b"E01".to_vec(),
+                }
+            },
+            Some(b'M') => {
+                let mut parts = text[1..].splitn(2, ':');
+                let target = parts.next().and_then(parseAddrLen);
+                let bytes = parts.next().and_then(fromHex);
+                match (target, bytes) {
+                    (Some((addr, len)), Some(bytes)) if bytes.len() as u64 == len => {
+                        match self.space.write(addr as usize, &bytes) {
+                            Some(_) => b"OK".to_vec(),
+                            None    => b"E14".to_vec(),
+                        }
+                    },
+                    _ => b"E01".to_vec(),
+                }
+            },
+            Some(b'c') => self.resume(conn, false)?,
+            Some(b's') => self.resume(conn, true)?,
+            Some(b'Z') | Some(b'z') => {
+                let mut parts = text[1..].split(',');
+                let kind = parts.next();
+                let addr = parts.next().and_then(|a| u64::from_str_radix(a, 16).ok());
+                match (kind, addr) {
+                    (Some("0"), Some(addr)) => {
+                        if packet[0] == b'Z' {
+                            self.breakpoints.insert(addr);
+                        } else {
+                            self.breakpoints.remove(&addr);
+                        }
+                        b"OK".to_vec()
+                    },
+                    //hardware breakpoints and watchpoints are not supported
+                    _ => vec![],
+                }
+            },
+            Some(b'H') => b"OK".to_vec(),
+            Some(b'T') => b"OK".to_vec(),
+            Some(b'k') => return Ok(None),
+            Some(b'D') => return Ok(None),
+            Some(b'v') => {
+                if text == "vCont?" {
+                    b"vCont;c;C;s;S".to_vec()
+                } else if text.starts_with("vCont;c") || text.starts_with("vCont;C") {
+                    self.resume(conn, false)?
+                } else if text.starts_with("vCont;s") || text.starts_with("vCont;S") {
+                    self.resume(conn, true)?
+                } else if text.starts_with("vKill") {
+                    return Ok(None);
+                } else {
+                    vec![]
+                }
+            },
+            Some(b'q') | Some(b'Q') => self.query(&text),
+            _ => vec![],
+        };
+        Ok(Some(reply))
+    }
+
+    //handles the q and Q general query packets
+    fn query(&mut self, text: &str) -> Vec<u8> {
+        if text.starts_with("qSupported") {
+            //only static programs run, so there is no library list to offer
+            return format!("PacketSize={:x};QStartNoAckMode+;swbreak+;qXfer:features:read+;qXfer:exec-file:read+", PACKET_SIZE).into_bytes();
+        }
+        if text == "QStartNoAckMode" {
+            self.ack = false;
+            return b"OK".to_vec();
+        }
+        if text == "qAttached" {return b"1".to_vec();}
+        if text == "qC" {return b"QC1".to_vec();}
+        if text == "qfThreadInfo" {return b"m1".to_vec();}
+        if text == "qsThreadInfo" {return b"l".to_vec();}
+        if let Some(rest) = text.strip_prefix("qXfer:") {
+            //qXfer:object:read:annex:offset,length
+            let parts: Vec<&str> = rest.splitn(4, ':').collect();
+            if parts.len() != 4 || parts[1] != "read" {return vec![];}
+            let document = match (parts[0], parts[2]) {
+                ("features", "target.xml") => format!("<?xml version=\"1.0\"?><!DOCTYPE target SYSTEM \"gdb-target.dtd\"><target><architecture>{}</architecture></target>", self.target.architecture()),
+                ("exec-file", _)           => self.execFile.clone(),
+                _                          => return b"E00".to_vec(),
+            };
+            return match parseAddrLen(parts[3]) {
+                Some((offset, len)) => xferChunk(document.as_bytes(), offset as usize, len as usize),
+                None                => b"E01".to_vec(),
+            };
+        }
+        vec![]
+    }
+}
+
+//returns the requested window of a qXfer document, prefixed with m when more data follows
+//and l on the last chunk, escaping the bytes the protocol reserves
+fn xferChunk(document: &[u8], offset: usize, len: usize) -> Vec<u8> {
+    let start = offset.min(document.len());
+    let end = offset.saturating_add(len).min(document.len());
+    let mut reply = vec![if end < document.len() {b'm'} else {b'l'}];
+    for b in document[start..end].iter() {
+        match b {
+            b'#' | b'$' | b'}' | b'*' => {
+                reply.push(b'}');
+                reply.push(b ^ 0x20);
+            },
+            _ => reply.push(*b),
+        }
+    }
+    reply
+}
+
+//parses the addr,length pair used by the m, M and qXfer packets
+fn parseAddrLen(text: &str) -> Option<(u64, u64)> {
+    let mut parts = text.splitn(2, ',');
+    let addr = u64::from_str_radix(parts.next()?, 16).ok()?;
+    let len = u64::from_str_radix(parts.next()?, 16).ok()?;
+    Some((addr, len))
+}
+
+//encodes bytes as lowercase hex
+pub fn toHex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+//decodes a hex string into bytes
+pub fn fromHex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {return None;}
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
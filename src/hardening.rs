@@ -0,0 +1,226 @@
+use std::fmt;
+use crate::dynamic;
+use crate::header;
+use crate::loader;
+use crate::note;
+use crate::programheader;
+
+//position independence of the file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Pie {
+    //ET_EXEC, loaded at a fixed address
+    No,
+    //ET_DYN executable, either flagged DF_1_PIE or asking for an interpreter
+    Yes,
+    //ET_DYN without any sign of being an executable
+    SharedObject,
+}
+
+//how much of the relocated data is made read only after relocation
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Relro {
+    None,
+    //PT_GNU_RELRO without immediate binding, the GOT used by the PLT stays writable
+    Partial,
+    //PT_GNU_RELRO with BIND_NOW
+    Full,
+}
+
+//hardening properties of one ELF file
+pub struct HardeningReport {
+    pub pie:      Pie,
+    pub relro:    Relro,
+
+    //PT_GNU_STACK present and not executable
+    pub nx:       bool,
+
+    //__stack_chk_fail or __stack_chk_guard is referenced
+    pub canary:   bool,
+
+    //imported *_chk functions from FORTIFY_SOURCE
+    pub fortified: Vec<String>,
+
+    //text relocations are present
+    pub textrel:  bool,
+
+    pub rpath:    Option<String>,
+    pub runpath:  Option<String>,
+
+    //x86 control flow enforcement: indirect branch tracking and shadow stack
+    pub ibt:      bool,
+    pub shstk:    bool,
+
+    //aarch64 branch target identification and pointer authentication
+    pub bti:      bool,
+    pub pac:      bool,
+}
+
+impl HardeningReport {
+
+    //builds the report from the program headers, dynamic section, symbols and notes
+    pub fn new(loader: &loader::Loader) -> Self {
+        let flags = loader.getDynamic(dynamic::DT_FLAGS).unwrap_or(0);
+        let flags1 = loader.getDynamic(dynamic::DT_FLAGS_1).unwrap_or(0);
+        let hasSegment = |t: u32| loader.programHeaders.iter().any(|p| p.getTYPE() == t);
+
+        let pie = match loader.header.e_type {
+            header::ET_DYN if flags1 & dynamic::DF_1_PIE != 0 || hasSegment(programheader::PT_INTERP) => Pie::Yes,
+            header::ET_DYN => Pie::SharedObject,
+            _ => Pie::No,
+        };
+
+        let bindNow = loader.getDynamic(dynamic::DT_BIND_NOW).is_some()
+            || flags & dynamic::DF_BIND_NOW != 0
+            || flags1 & dynamic::DF_1_NOW != 0;
+        let relro = match (hasSegment(programheader::PT_GNU_RELRO), bindNow) {
+            (true, true)  => Relro::Full,
+            (true, false) => Relro::Partial,
+            _             => Relro::None,
+        };
+
+        let nx = match loader.programHeaders.iter().find(|p| p.getTYPE() == programheader::PT_GNU_STACK) {
+            Some(p) => p.getFLAGS() & programheader::PF_X == 0,
+            None    => false,
+        };
+
+        let names: Vec<&str> = loader.dynamicSymbols.iter().chain(loader.symbols.iter())
+            .map(|s| s.name.as_str())
+            .collect();
+        let canary = names.iter().any(|n| *n == "__stack_chk_fail" || *n == "__stack_chk_guard");
+        let mut fortified: Vec<String> = loader.dynamicSymbols.iter().chain(loader.symbols.iter())
+            .filter(|s| s.isUndefined())
+            .map(|s| s.name.split('@').next().unwrap_or("").to_string())
+            .filter(|n| n.starts_with("__") && n.ends_with("_chk") && n != "__stack_chk_fail")
+            .collect();
+        fortified.sort();
+        fortified.dedup();
+
+        let textrel = loader.getDynamic(dynamic::DT_TEXTREL).is_some() || flags & dynamic::DF_TEXTREL != 0;
+        let rpath = loader.getDynamic(dynamic::DT_RPATH).and_then(|o| loader.dynamicString(o));
+        let runpath = loader.getDynamic(dynamic::DT_RUNPATH).and_then(|o| loader.dynamicString(o));
+
+        let class = loader.header.e_ident.Class;
+        let data = loader.header.e_ident.Data;
+        let mut x86 = 0;
+        let mut aarch64 = 0;
+        for n in loader.notes.iter() {
+            x86 |= n.featureBits(class, data, note::GNU_PROPERTY_X86_FEATURE_1_AND).unwrap_or(0);
+            aarch64 |= n.featureBits(class, data, note::GNU_PROPERTY_AARCH64_FEATURE_1_AND).unwrap_or(0);
+        }
+        //the property numbers overlap between architectures so only trust the matching one
+        if loader.header.e_machine != header::EM_X86_64 && loader.header.e_machine != header::EM_386 {x86 = 0;}
+        if loader.header.e_machine != header::EM_AARCH64 {aarch64 = 0;}
+
+        Self {
+            pie:       pie,
+            relro:     relro,
+            nx:        nx,
+            canary:    canary,
+            fortified: fortified,
+            textrel:   textrel,
+            rpath:     rpath,
+            runpath:   runpath,
+            ibt:       x86 & note::GNU_PROPERTY_X86_FEATURE_1_IBT != 0,
+            shstk:     x86 & note::GNU_PROPERTY_X86_FEATURE_1_SHSTK != 0,
+            bti:       aarch64 & note::GNU_PROPERTY_AARCH64_FEATURE_1_BTI != 0,
+            pac:       aarch64 & note::GNU_PROPERTY_AARCH64_FEATURE_1_PAC != 0,
+        }
+    }
+}
+
+impl fmt::Display for HardeningReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let yesNo = |b: bool| if b {"yes"} else {"no"};
+        writeln!(f, "  PIE:      {}", match self.pie {
+            Pie::Yes          => "yes",
+            Pie::No           => "no",
+            Pie::SharedObject => "shared object",
+        })?;
+        writeln!(f, "  RELRO:    {}", match self.relro {
+            Relro::Full    => "full",
+            Relro::Partial => "partial",
+            Relro::None    => "none",
+        })?;
+        writeln!(f, "  NX:       {}", yesNo(self.nx))?;
+        writeln!(f, "  Canary:   {}", yesNo(self.canary))?;
+        writeln!(f, "  FORTIFY:  {} ({} functions)", yesNo(!self.fortified.is_empty()), self.fortified.len())?;
+        writeln!(f, "  TEXTREL:  {}", yesNo(self.textrel))?;
+        writeln!(f, "  RPATH:    {}", self.rpath.as_deref().unwrap_or("none"))?;
+        writeln!(f, "  RUNPATH:  {}", self.runpath.as_deref().unwrap_or("none"))?;
+        writeln!(f, "  IBT:      {}", yesNo(self.ibt))?;
+        writeln!(f, "  SHSTK:    {}", yesNo(self.shstk))?;
+        writeln!(f, "  BTI:      {}", yesNo(self.bti))?;
+        write!(f, "  PAC:      {}", yesNo(self.pac))
+    }
+}
+
+//requirements a release binary has to meet, read from a policy file
+//each line is "check = value", blank lines and text after # are ignored
+//
+//  pie     = required | optional
+//  relro   = none | partial | full      (minimum level)
+//  nx      = required | optional
+//  canary  = required | optional
+//  fortify = required | optional
+//  textrel = forbidden | allowed
+//  rpath   = forbidden | allowed
+//  runpath = forbidden | allowed
+//  ibt     = required | optional
+//  shstk   = required | optional
+//  bti     = required | optional
+//  pac     = required | optional
+pub struct Policy {
+    pub rules: Vec<(String, String)>,
+}
+
+impl Policy {
+
+    //parses a policy file, returning a message naming the bad line on error
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut rules = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {continue;}
+            let mut parts = line.splitn(2, '=');
+            let key = parts.next().unwrap_or("").trim().to_lowercase();
+            let value = match parts.next() {
+                Some(v) => v.trim().to_lowercase(),
+                None    => return Err(format!("line {}: expected check = value", number + 1)),
+            };
+            let valid = match key.as_str() {
+                "pie" | "nx" | "canary" | "fortify" | "ibt" | "shstk" | "bti" | "pac" => value == "required" || value == "optional",
+                "textrel" | "rpath" | "runpath" => value == "forbidden" || value == "allowed",
+                "relro" => value == "none" || value == "partial" || value == "full",
+                _ => return Err(format!("line {}: unknown check {}", number + 1, key)),
+            };
+            if !valid {return Err(format!("line {}: bad value {} for {}", number + 1, value, key));}
+            rules.push((key, value));
+        }
+        Ok(Self{rules: rules})
+    }
+
+    //returns a description of every rule the report breaks, empty when it passes
+    pub fn check(&self, report: &HardeningReport) -> Vec<String> {
+        let mut failures = vec![];
+        for (key, value) in self.rules.iter() {
+            let failed = match (key.as_str(), value.as_str()) {
+                ("pie", "required")       => report.pie == Pie::No,
+                ("nx", "required")        => !report.nx,
+                ("canary", "required")    => !report.canary,
+                ("fortify", "required")   => report.fortified.is_empty(),
+                ("ibt", "required")       => !report.ibt,
+                ("shstk", "required")     => !report.shstk,
+                ("bti", "required")       => !report.bti,
+                ("pac", "required")       => !report.pac,
+                ("textrel", "forbidden")  => report.textrel,
+                ("rpath", "forbidden")    => report.rpath.is_some(),
+                ("runpath", "forbidden")  => report.runpath.is_some(),
+                ("relro", "full")         => report.relro < Relro::Full,
+                ("relro", "partial")      => report.relro < Relro::Partial,
+                _                         => false,
+            };
+            if failed {failures.push(format!("{} must be {}", key, value));}
+        }
+        failures
+    }
+}
@@ -1,5 +1,19 @@
 use crate::loader;
 
+//object file types found in e_type
+pub const ET_NONE: u16 = 0;
+pub const ET_REL:  u16 = 1;
+pub const ET_EXEC: u16 = 2;
+pub const ET_DYN:  u16 = 3;
+pub const ET_CORE: u16 = 4;
+
+//machine types found in e_machine
+pub const EM_386:     u16 = 3;
+pub const EM_ARM:     u16 = 40;
+pub const EM_X86_64:  u16 = 62;
+pub const EM_AARCH64: u16 = 183;
+pub const EM_RISCV:   u16 = 243;
+
 pub struct Header {
 
     //16 bytes long containing E_IDENT struct
@@ -0,0 +1,400 @@
+use std::fmt;
+use crate::header;
+use crate::loader;
+use crate::programheader;
+use crate::relocation;
+use crate::sectionheader;
+
+//one suspicious property of a file together with the file offset that shows it
+#[derive(Debug)]
+pub struct Finding {
+    //short name of the technique, stable so reports can be filtered
+    pub technique:   &'static str,
+
+    //human readable explanation
+    pub description: String,
+
+    //file offset of the evidence, usually the header or the bytes that were changed
+    pub offset:      usize,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "[{}] {:#x}: {}", self.technique, self.offset, self.description)
+    }
+}
+
+//runs every check against a loaded file and returns what was found, empty for a clean file
+pub fn scan(loader: &loader::Loader) -> Vec<Finding> {
+    let mut findings = vec![];
+    checkHeader(loader, &mut findings);
+    if isLinked(loader) {
+        checkEntryPoint(loader, &mut findings);
+        checkSegments(loader, &mut findings);
+        checkNoteToLoad(loader, &mut findings);
+        checkPadding(loader, &mut findings);
+    }
+    checkSectionMapping(loader, &mut findings);
+    if isLinked(loader) {checkPointerTables(loader, &mut findings);}
+    findings
+}
+
+//relocatable objects and files without program headers have no segments to check against,
+//their sections only get addresses once they are linked
+fn isLinked(loader: &loader::Loader) -> bool {
+    loader.header.e_type != header::ET_REL && !loader.programHeaders.is_empty()
+}
+
+//file offset of the program header at index, using the real entry size like the loader does
+fn phdrOffset(loader: &loader::Loader, index: usize) -> usize {
+    loader.header.e_phoff.saturating_add(index.saturating_mul(if loader.header.e_ident.Class == 1 {32} else {56}))
+}
+
+//file offset of the section header at index
+fn shdrOffset(loader: &loader::Loader, index: usize) -> usize {
+    loader.header.e_shoff.saturating_add(index.saturating_mul(if loader.header.e_ident.Class == 1 {40} else {64}))
+}
+
+//end of a range, every field read from the file may be hostile so this never overflows
+fn end(start: usize, size: usize) -> usize {
+    start.saturating_add(size)
+}
+
+//returns the name of a section or its index when it has none
+fn sectionLabel(loader: &loader::Loader, index: usize) -> String {
+    match loader.sectionName(index) {
+        Some(n) if !n.is_empty() => n,
+        _ => format!("section {}", index),
+    }
+}
+
+//returns the index of the allocated section containing a virtual address
+fn sectionAt(loader: &loader::Loader, vaddr: usize) -> Option<usize> {
+    (0..loader.sectionHeaders.len()).find(|i| {
+        let s = &loader.sectionHeaders[*i];
+        s.sh_flags & sectionheader::SHF_ALLOC != 0 && s.sh_addr <= vaddr && vaddr < end(s.sh_addr, s.sh_size)
+    })
+}
+
+//header fields that do not match the structures the loader and kernel expect
+fn checkHeader(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    let (phent, shent) = if loader.header.e_ident.Class == 1 {(32, 40)} else {(56, 64)};
+    if loader.header.e_phnum != 0 && loader.header.e_phentsize != phent {
+        findings.push(Finding {
+            technique:   "header-mismatch",
+            description: format!("e_phentsize is {} but program headers are {} bytes", loader.header.e_phentsize, phent),
+            offset:      if loader.header.e_ident.Class == 1 {0x2a} else {0x36},
+        });
+    }
+    if loader.header.e_shnum != 0 && loader.header.e_shentsize != shent {
+        findings.push(Finding {
+            technique:   "header-mismatch",
+            description: format!("e_shentsize is {} but section headers are {} bytes", loader.header.e_shentsize, shent),
+            offset:      if loader.header.e_ident.Class == 1 {0x2e} else {0x3a},
+        });
+    }
+    let shEnd = shdrOffset(loader, loader.header.e_shnum as usize);
+    if loader.header.e_shnum != 0 && shEnd > loader.fileVec.len() {
+        findings.push(Finding {
+            technique:   "out-of-file",
+            description: format!("section header table claims {} entries and ends at {:#x} past the end of the file at {:#x}",
+                loader.header.e_shnum, shEnd, loader.fileVec.len()),
+            offset:      loader.header.e_shoff,
+        });
+    }
+    let phEnd = phdrOffset(loader, loader.header.e_phnum as usize);
+    if phEnd > loader.fileVec.len() {
+        findings.push(Finding {
+            technique:   "out-of-file",
+            description: format!("program header table ends at {:#x} past the end of the file", phEnd),
+            offset:      loader.header.e_phoff,
+        });
+    }
+}
+
+//the entry point should land in .text, and at the very least in executable code
+fn checkEntryPoint(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    let entry = loader.header.e_entry;
+    if entry == 0 {return;}
+    let entryOffset = loader.vaddrToOffset(entry).unwrap_or(0);
+
+    let inExecSegment = loader.programHeaders.iter().any(|p| {
+        p.getTYPE() == programheader::PT_LOAD && p.getFLAGS() & programheader::PF_X != 0
+            && p.getVADDR() <= entry && entry < end(p.getVADDR(), p.getMEMSZ())
+    });
+    if !inExecSegment {
+        findings.push(Finding {
+            technique:   "entry-point",
+            description: format!("entry point {:#x} is not inside an executable PT_LOAD segment", entry),
+            offset:      entryOffset,
+        });
+        return;
+    }
+    if loader.sectionHeaders.is_empty() {return;}
+
+    match sectionAt(loader, entry) {
+        Some(i) if loader.sectionHeaders[i].sh_flags & sectionheader::SHF_EXECINSTR == 0 => {
+            findings.push(Finding {
+                technique:   "entry-point",
+                description: format!("entry point {:#x} is in non executable section {}", entry, sectionLabel(loader, i)),
+                offset:      entryOffset,
+            });
+        },
+        Some(i) if loader.sectionName(i).as_deref() != Some(".text") => {
+            findings.push(Finding {
+                technique:   "entry-point",
+                description: format!("entry point {:#x} is in {} instead of .text", entry, sectionLabel(loader, i)),
+                offset:      entryOffset,
+            });
+        },
+        Some(_) => {},
+        None => {
+            findings.push(Finding {
+                technique:   "entry-point",
+                description: format!("entry point {:#x} is not inside any section", entry),
+                offset:      entryOffset,
+            });
+        },
+    }
+}
+
+//extra executable segments, writable code and segments that overlap or leave the file
+fn checkSegments(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    let loads: Vec<usize> = (0..loader.programHeaders.len())
+        .filter(|i| loader.programHeaders[*i].getTYPE() == programheader::PT_LOAD)
+        .collect();
+
+    let executable: Vec<usize> = loads.iter().cloned()
+        .filter(|i| loader.programHeaders[*i].getFLAGS() & programheader::PF_X != 0)
+        .collect();
+    if executable.len() > 1 {
+        findings.push(Finding {
+            technique:   "extra-exec-segment",
+            description: format!("{} executable PT_LOAD segments, linkers emit one", executable.len()),
+            offset:      phdrOffset(loader, executable[1]),
+        });
+    }
+
+    for i in loads.iter() {
+        let p = &loader.programHeaders[*i];
+        if p.getFLAGS() & programheader::PF_X != 0 && p.getFLAGS() & programheader::PF_W != 0 {
+            findings.push(Finding {
+                technique:   "extra-exec-segment",
+                description: format!("PT_LOAD {} at {:#x} is writable and executable", i, p.getVADDR()),
+                offset:      phdrOffset(loader, *i),
+            });
+        }
+        if end(p.getOFFSET(), p.getFILESZ()) > loader.fileVec.len() {
+            findings.push(Finding {
+                technique:   "out-of-file",
+                description: format!("PT_LOAD {} ends at file offset {:#x} past the end of the file", i, end(p.getOFFSET(), p.getFILESZ())),
+                offset:      phdrOffset(loader, *i),
+            });
+        }
+        if p.getFILESZ() > p.getMEMSZ() {
+            findings.push(Finding {
+                technique:   "out-of-file",
+                description: format!("PT_LOAD {} has p_filesz {:#x} larger than p_memsz {:#x}", i, p.getFILESZ(), p.getMEMSZ()),
+                offset:      phdrOffset(loader, *i),
+            });
+        }
+    }
+
+    for (n, a) in loads.iter().enumerate() {
+        for b in loads[n + 1..].iter() {
+            let pa = &loader.programHeaders[*a];
+            let pb = &loader.programHeaders[*b];
+            if pa.getVADDR() < end(pb.getVADDR(), pb.getMEMSZ()) && pb.getVADDR() < end(pa.getVADDR(), pa.getMEMSZ()) {
+                findings.push(Finding {
+                    technique:   "overlapping-segments",
+                    description: format!("PT_LOAD {} and {} overlap in memory", a, b),
+                    offset:      phdrOffset(loader, *b),
+                });
+            }
+            if pb.getVADDR() < pa.getVADDR() {
+                findings.push(Finding {
+                    technique:   "overlapping-segments",
+                    description: format!("PT_LOAD {} comes after PT_LOAD {} but has a lower address", b, a),
+                    offset:      phdrOffset(loader, *b),
+                });
+            }
+        }
+    }
+}
+
+//the PT_NOTE infection rewrites the note segment into a loadable one pointing at appended code
+//which leaves the note sections without a PT_NOTE and a PT_LOAD that no section explains
+fn checkNoteToLoad(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    if loader.sectionHeaders.is_empty() {return;}
+    let hasNoteSections = loader.sectionHeaders.iter()
+        .any(|s| s.sh_type == sectionheader::SHT_NOTE && s.sh_flags & sectionheader::SHF_ALLOC != 0);
+    let hasNoteSegment = loader.programHeaders.iter().any(|p| p.getTYPE() == programheader::PT_NOTE);
+
+    for (i, p) in loader.programHeaders.iter().enumerate() {
+        if p.getTYPE() != programheader::PT_LOAD || p.getMEMSZ() == 0 {continue;}
+        let coversSection = loader.sectionHeaders.iter().any(|s| {
+            s.sh_flags & sectionheader::SHF_ALLOC != 0 && s.sh_size != 0
+                && s.sh_addr < end(p.getVADDR(), p.getMEMSZ()) && p.getVADDR() < end(s.sh_addr, s.sh_size)
+        });
+        if coversSection {continue;}
+        let description = if hasNoteSections && !hasNoteSegment {
+            format!("PT_LOAD {} at {:#x} covers no section and the note sections have no PT_NOTE, a converted PT_NOTE", i, p.getVADDR())
+        } else {
+            format!("PT_LOAD {} at {:#x} covers no section", i, p.getVADDR())
+        };
+        findings.push(Finding {
+            technique:   "note-to-load",
+            description: description,
+            offset:      phdrOffset(loader, i),
+        });
+    }
+}
+
+//code hidden in the padding of the executable segment, past the last section it holds
+fn checkPadding(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    if loader.sectionHeaders.is_empty() {return;}
+    for (i, p) in loader.programHeaders.iter().enumerate() {
+        if p.getTYPE() != programheader::PT_LOAD || p.getFLAGS() & programheader::PF_X == 0 {continue;}
+        let start = p.getOFFSET();
+        let last = end(p.getOFFSET(), p.getFILESZ()).min(loader.fileVec.len());
+
+        //every byte in the segment that some section or header accounts for
+        let mut covered: Vec<(usize, usize)> = loader.sectionHeaders.iter()
+            .filter(|s| s.sh_type != sectionheader::SHT_NOBITS && s.sh_size != 0)
+            .map(|s| (s.sh_offset, end(s.sh_offset, s.sh_size)))
+            .collect();
+        covered.push((0, loader.header.e_ehsize as usize));
+        covered.push((loader.header.e_phoff, phdrOffset(loader, loader.header.e_phnum as usize)));
+        covered.sort();
+
+        let mut cur = start;
+        for (lo, hi) in covered.iter().chain([(last, last)].iter()) {
+            if *lo > cur && cur < last {
+                let gapEnd = (*lo).min(last);
+                if let Some(first) = loader.fileVec[cur..gapEnd].iter().position(|b| *b != 0) {
+                    findings.push(Finding {
+                        technique:   "segment-padding",
+                        description: format!("{} non zero bytes outside of any section in executable PT_LOAD {}",
+                            loader.fileVec[cur..gapEnd].iter().filter(|b| **b != 0).count(), i),
+                        offset:      cur + first,
+                    });
+                }
+            }
+            cur = cur.max(*hi);
+        }
+    }
+}
+
+//allocated sections must sit in a PT_LOAD at the same offset from the segment start in the
+//file and in memory, and must not extend past the end of the file
+fn checkSectionMapping(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    for (i, s) in loader.sectionHeaders.iter().enumerate() {
+        if s.sh_type != sectionheader::SHT_NOBITS && end(s.sh_offset, s.sh_size) > loader.fileVec.len() {
+            findings.push(Finding {
+                technique:   "section-mapping",
+                description: format!("{} ends at {:#x} past the end of the file", sectionLabel(loader, i), end(s.sh_offset, s.sh_size)),
+                offset:      shdrOffset(loader, i),
+            });
+        }
+        if !isLinked(loader) || s.sh_flags & sectionheader::SHF_ALLOC == 0 || s.sh_size == 0 {continue;}
+        //.tbss only takes space in the TLS template, not in any PT_LOAD
+        if s.sh_type == sectionheader::SHT_NOBITS && s.sh_flags & sectionheader::SHF_TLS != 0 {continue;}
+
+        let segment = loader.programHeaders.iter().find(|p| {
+            p.getTYPE() == programheader::PT_LOAD && p.getVADDR() <= s.sh_addr && s.sh_addr < end(p.getVADDR(), p.getMEMSZ())
+        });
+        match segment {
+            None => findings.push(Finding {
+                technique:   "section-mapping",
+                description: format!("allocated {} at {:#x} is not inside any PT_LOAD", sectionLabel(loader, i), s.sh_addr),
+                offset:      shdrOffset(loader, i),
+            }),
+            Some(p) => {
+                if end(s.sh_addr, s.sh_size) > end(p.getVADDR(), p.getMEMSZ()) {
+                    findings.push(Finding {
+                        technique:   "section-mapping",
+                        description: format!("{} runs past the end of its PT_LOAD", sectionLabel(loader, i)),
+                        offset:      shdrOffset(loader, i),
+                    });
+                }
+                if s.sh_type != sectionheader::SHT_NOBITS && s.sh_offset.wrapping_sub(p.getOFFSET()) != s.sh_addr - p.getVADDR() {
+                    findings.push(Finding {
+                        technique:   "section-mapping",
+                        description: format!("{} is at file offset {:#x} but its PT_LOAD maps address {:#x} from {:#x}",
+                            sectionLabel(loader, i), s.sh_offset, s.sh_addr, end(p.getOFFSET(), s.sh_addr - p.getVADDR())),
+                        offset:      shdrOffset(loader, i),
+                    });
+                }
+            },
+        }
+    }
+}
+
+//constructor tables and GOT slots must point at code or data inside the image
+//a slot's link time value is the word in the file or the addend of a relative relocation
+fn checkPointerTables(loader: &loader::Loader, findings: &mut Vec<Finding>) {
+    let word = loader.wordSize();
+    for (i, s) in loader.sectionHeaders.iter().enumerate() {
+        let name = loader.sectionName(i).unwrap_or_default();
+        let isArray = s.sh_type == sectionheader::SHT_INIT_ARRAY || s.sh_type == sectionheader::SHT_FINI_ARRAY
+            || s.sh_type == sectionheader::SHT_PREINIT_ARRAY;
+        let isGot = name == ".got" || name == ".got.plt";
+        if !isArray && !isGot {continue;}
+
+        //only the slots that are in the file, a hostile sh_size would otherwise take forever
+        let present = if s.sh_type == sectionheader::SHT_NOBITS {0} else {s.sh_size.min(loader.fileVec.len().saturating_sub(s.sh_offset))};
+        for n in 0..present / word {
+            let slot = s.sh_addr.wrapping_add(n * word);
+            let fileOffset = s.sh_offset + n * word;
+            let inFile = loader.readAt(fileOffset, word).unwrap_or(0) as usize;
+            let relocations: Vec<&relocation::Relocation> = loader.relocations.iter().filter(|r| r.r_offset == slot).collect();
+            //slots filled in by symbol lookups at run time say nothing about the file
+            if relocations.iter().any(|r| !r.isRelative(loader.header.e_machine)) {continue;}
+            let target = match relocations.first().and_then(|r| r.r_addend) {
+                Some(a) => a as usize,
+                None    => inFile,
+            };
+            //-1 and 0 are the terminators some toolchains put in these tables
+            if target == 0 || target as u64 == u64::MAX || (word == 4 && target as u32 == u32::MAX) {continue;}
+
+            //GOT slots can also hold TLS offsets and other constants, so only require them to
+            //point somewhere inside the loaded image
+            if isGot {
+                let inImage = loader.programHeaders.iter().any(|p| {
+                    p.getTYPE() == programheader::PT_LOAD && p.getVADDR() <= target && target < end(p.getVADDR(), p.getMEMSZ())
+                });
+                let lowest = loader.programHeaders.iter()
+                    .filter(|p| p.getTYPE() == programheader::PT_LOAD)
+                    .map(|p| p.getVADDR())
+                    .min()
+                    .unwrap_or(0);
+                if !inImage && target >= lowest {
+                    findings.push(Finding {
+                        technique:   "pointer-table",
+                        description: format!("{}[{}] points to {:#x} outside of the loaded image", name, n, target),
+                        offset:      fileOffset,
+                    });
+                }
+                continue;
+            }
+
+            match sectionAt(loader, target) {
+                Some(t) if loader.sectionHeaders[t].sh_flags & sectionheader::SHF_EXECINSTR == 0 => {
+                    findings.push(Finding {
+                        technique:   "pointer-table",
+                        description: format!("{}[{}] points to {:#x} in non executable {}", name, n, target, sectionLabel(loader, t)),
+                        offset:      fileOffset,
+                    });
+                },
+                Some(_) => {},
+                None => {
+                    findings.push(Finding {
+                        technique:   "pointer-table",
+                        description: format!("{}[{}] points to {:#x} outside of every section", name, n, target),
+                        offset:      fileOffset,
+                    });
+                },
+            }
+        }
+    }
+}
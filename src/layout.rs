@@ -0,0 +1,375 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use crate::loader;
+use crate::programheader;
+use crate::sectionheader;
+
+//pixels of the SVG map, a row grows with the number of bits of its size
+const ROW_HEIGHT:    u64 = 12;
+const ADDRESS_WIDTH: u64 = 100;
+const LANE_WIDTH:    u64 = 10;
+const CONTENT_WIDTH: u64 = 260;
+const COLUMN_GAP:    u64 = 40;
+const TOP:           u64 = 40;
+
+//which addresses a layout is drawn over
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Space {
+    //offsets into the file
+    File,
+    //virtual addresses once loaded
+    Memory,
+}
+
+impl fmt::Display for Space {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Space::File   => write!(f, "file"),
+            Space::Memory => write!(f, "memory"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    //the ELF header or the program or section header table
+    Header,
+    //a section and its sh_flags
+    Section(usize),
+    //a program header with its p_type and p_flags
+    Segment(u32, u32),
+}
+
+//a named range of offsets or addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct Block {
+    pub name:  String,
+    pub kind:  Kind,
+    pub start: u64,
+    pub end:   u64,
+}
+
+//a range between two consecutive block boundaries
+#[derive(Debug, Clone, PartialEq)]
+pub struct Row {
+    pub start:  u64,
+    pub end:    u64,
+
+    //indices of the headers and sections covering the row, more than one is an overlap
+    pub blocks: Vec<usize>,
+
+    //true when a LOAD segment covers the row, an uncovered row in a segment is padding and
+    //outside of them a gap
+    pub loaded: bool,
+}
+
+//the headers, sections and segments of a file laid out over file offsets or virtual addresses
+pub struct Layout {
+    pub space:    Space,
+
+    //headers and sections, the contents of the map
+    pub blocks:   Vec<Block>,
+
+    //program headers, drawn as one lane each beside the contents
+    pub segments: Vec<Block>,
+    pub rows:     Vec<Row>,
+    pub problems: Vec<String>,
+}
+
+impl Layout {
+
+    //the file from offset 0 to its end, NOBITS sections take no room in it
+    pub fn file(loader: &loader::Loader) -> Self {
+        let mut blocks = headers(loader);
+        for (i, s) in loader.sectionHeaders.iter().enumerate().skip(1) {
+            if s.sh_type == sectionheader::SHT_NOBITS || s.sh_size == 0 {continue;}
+            let name = loader.sectionName(i).unwrap_or_default();
+            blocks.push(Block{name: name, kind: Kind::Section(s.sh_flags), start: s.sh_offset as u64, end: s.sh_offset.saturating_add(s.sh_size) as u64});
+        }
+        let mut segments = vec![];
+        for (i, p) in loader.programHeaders.iter().enumerate().filter(|(_, p)| p.getFILESZ() != 0) {
+            let (start, end) = (p.getOFFSET() as u64, p.getOFFSET().saturating_add(p.getFILESZ()) as u64);
+            segments.push(Block{name: segmentName(i, p.getTYPE()), kind: Kind::Segment(p.getTYPE(), p.getFLAGS()), start: start, end: end});
+        }
+        Self::new(Space::File, blocks, segments, Some(loader.fileVec.len() as u64))
+    }
+
+    //the address space as the loader maps it, empty for object files which have no segments
+    pub fn memory(loader: &loader::Loader) -> Self {
+        let mut blocks = vec![];
+        let mut segments = vec![];
+        if !loader.programHeaders.is_empty() {
+            //the headers are in memory when a LOAD segment maps their offsets
+            for h in headers(loader) {
+                if let Some((start, end)) = mapped(loader, h.start, h.end) {
+                    blocks.push(Block{start: start, end: end, ..h});
+                }
+            }
+            for (i, s) in loader.sectionHeaders.iter().enumerate().skip(1) {
+                //.tbss takes no addresses of its own, its image is made per thread
+                if s.sh_flags & sectionheader::SHF_ALLOC == 0 || s.sh_size == 0 {continue;}
+                if s.sh_flags & sectionheader::SHF_TLS != 0 && s.sh_type == sectionheader::SHT_NOBITS {continue;}
+                let name = loader.sectionName(i).unwrap_or_default();
+                blocks.push(Block{name: name, kind: Kind::Section(s.sh_flags), start: s.sh_addr as u64, end: s.sh_addr.saturating_add(s.sh_size) as u64});
+            }
+            for (i, p) in loader.programHeaders.iter().enumerate().filter(|(_, p)| p.getMEMSZ() != 0) {
+                let (start, end) = (p.getVADDR() as u64, p.getVADDR().saturating_add(p.getMEMSZ()) as u64);
+                segments.push(Block{name: segmentName(i, p.getTYPE()), kind: Kind::Segment(p.getTYPE(), p.getFLAGS()), start: start, end: end});
+            }
+        }
+        Self::new(Space::Memory, blocks, segments, None)
+    }
+
+    //cuts the range at every block boundary and looks for overlaps and sections split by segments,
+    //the file layout starts at 0 and ends at the file size, the memory layout spans its blocks
+    fn new(space: Space, blocks: Vec<Block>, segments: Vec<Block>, size: Option<u64>) -> Self {
+        let mut boundaries = BTreeSet::new();
+        for b in blocks.iter().chain(segments.iter()) {
+            boundaries.insert(b.start);
+            boundaries.insert(b.end);
+        }
+        if let Some(size) = size {
+            boundaries.insert(0);
+            boundaries.insert(size);
+        }
+        let boundaries: Vec<u64> = boundaries.into_iter().collect();
+        let mut rows = vec![];
+        for pair in boundaries.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            let covering = blocks.iter().enumerate().filter(|(_, b)| b.start <= start && start < b.end).map(|(i, _)| i).collect();
+            let loaded = segments.iter().any(|s| s.isLoad() && s.start <= start && start < s.end);
+            rows.push(Row{start: start, end: end, blocks: covering, loaded: loaded});
+        }
+
+        let mut problems = vec![];
+        for (i, a) in blocks.iter().enumerate() {
+            for b in blocks[i + 1..].iter().filter(|b| b.start < a.end && a.start < b.end) {
+                problems.push(format!("{} and {} overlap at {:#x}..{:#x}", a.name, b.name, a.start.max(b.start), a.end.min(b.end)));
+            }
+        }
+        let loads: Vec<&Block> = segments.iter().filter(|s| s.isLoad()).collect();
+        for (i, a) in loads.iter().enumerate() {
+            for b in loads[i + 1..].iter().filter(|b| b.start < a.end && a.start < b.end) {
+                problems.push(format!("{} and {} overlap at {:#x}..{:#x}", a.name, b.name, a.start.max(b.start), a.end.min(b.end)));
+            }
+        }
+        //a section split over two segments or left out of them is not loaded the way it was linked
+        for b in blocks.iter().filter(|b| !loads.is_empty() && (b.kind == Kind::Header || b.flags() & sectionheader::SHF_ALLOC as u32 != 0)) {
+            let touching: Vec<&&Block> = loads.iter().filter(|s| s.start < b.end && b.start < s.end).collect();
+            if touching.is_empty() {
+                if b.kind != Kind::Header && space == Space::Memory {problems.push(format!("{} at {:#x}..{:#x} is in no LOAD segment", b.name, b.start, b.end));}
+            } else if !touching.iter().any(|s| s.start <= b.start && b.end <= s.end) {
+                problems.push(format!("{} at {:#x}..{:#x} is only partly inside {}", b.name, b.start, b.end, touching[0].name));
+            }
+        }
+        Self{space: space, blocks: blocks, segments: segments, rows: rows, problems: problems}
+    }
+
+    //what a row holds: its blocks joined by +, padding inside a LOAD segment or a gap
+    pub fn rowName(&self, row: &Row) -> String {
+        if row.blocks.is_empty() {
+            return if row.loaded {"<padding>".to_string()} else {"<gap>".to_string()};
+        }
+        let names: Vec<String> = row.blocks.iter().map(|i| {
+            let b = &self.blocks[*i];
+            if b.start < row.start {format!("{} (continued)", b.name)} else {b.name.clone()}
+        }).collect();
+        let name = names.join(" + ");
+        if row.blocks.len() > 1 {format!("{}  <- overlap", name)} else {name}
+    }
+
+    //bytes in rows no block covers, inside LOAD segments and outside of them
+    pub fn unused(&self) -> (u64, u64) {
+        let empty = self.rows.iter().filter(|r| r.blocks.is_empty());
+        empty.fold((0, 0), |(padding, gaps), r| if r.loaded {(padding.saturating_add(r.end - r.start), gaps)} else {(padding, gaps.saturating_add(r.end - r.start))})
+    }
+
+    //hex digits for the addresses of the map, 8 unless an address needs more
+    fn digits(&self) -> usize {
+        if self.rows.last().map_or(0, |r| r.end) > 0xffffffff {16} else {8}
+    }
+
+    //the SVG elements of this map with its left edge at x, and the width and height they take
+    fn svg(&self, x: u64) -> (String, u64, u64) {
+        let digits = self.digits();
+        let contentX = x + ADDRESS_WIDTH + self.segments.len() as u64 * LANE_WIDTH + LANE_WIDTH;
+        let mut text = format!("<text x=\"{}\" y=\"{}\" font-size=\"14\" font-weight=\"bold\">{} layout</text>\n", x, TOP - 16, self.space);
+        let mut top = vec![];
+        let mut y = TOP;
+        for row in self.rows.iter() {
+            let height = rowHeight(row);
+            let (fill, dashed) = match row.blocks.len() {
+                0 if row.loaded => ("#fff7bc", false),
+                0               => ("#ffffff", true),
+                1               => (blockColor(&self.blocks[row.blocks[0]]), false),
+                _               => ("#e34a33", false),
+            };
+            let name = escape(&self.rowName(row));
+            text += &format!("<text x=\"{}\" y=\"{}\">{:#0w$x}</text>\n", x, y + 10, row.start, w = digits + 2);
+            text += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\" stroke=\"#555555\"{}><title>{} {:#x}..{:#x}, {} bytes</title></rect>\n",
+                             contentX, y, CONTENT_WIDTH, height, fill, if dashed {" stroke-dasharray=\"4 2\""} else {""}, name, row.start, row.end, row.end - row.start);
+            text += &format!("<text x=\"{}\" y=\"{}\">{}</text>\n", contentX + 4, y + height / 2 + 4, name);
+            text += &format!("<text x=\"{}\" y=\"{}\" text-anchor=\"end\">{}</text>\n", contentX + CONTENT_WIDTH - 4, y + height / 2 + 4, row.end - row.start);
+            top.push(y);
+            y += height;
+        }
+        if let Some(last) = self.rows.last() {
+            text += &format!("<text x=\"{}\" y=\"{}\">{:#0w$x}</text>\n", x, y + 10, last.end, w = digits + 2);
+        }
+        //a segment spans the rows from the one it starts in to the one it ends after
+        for (lane, s) in self.segments.iter().enumerate() {
+            let first = self.rows.iter().position(|r| r.start == s.start);
+            let last = self.rows.iter().rposition(|r| r.end == s.end);
+            if let (Some(first), Some(last)) = (first, last) {
+                let bottom = top[last] + rowHeight(&self.rows[last]);
+                let fill = if s.isLoad() {"#4393c3"} else {"#999999"};
+                text += &format!("<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"{}\"><title>{} {:#x}..{:#x}</title></rect>\n",
+                                 x + ADDRESS_WIDTH + lane as u64 * LANE_WIDTH, top[first], LANE_WIDTH - 2, bottom - top[first], fill, escape(&s.name), s.start, s.end);
+            }
+        }
+        (text, contentX + CONTENT_WIDTH - x, y + 20)
+    }
+}
+
+impl Block {
+
+    pub fn isLoad(&self) -> bool {
+        match self.kind {
+            Kind::Segment(kind, _) => kind == programheader::PT_LOAD,
+            _                      => false,
+        }
+    }
+
+    //sh_flags of a section and p_flags of a segment, 0 for headers
+    pub fn flags(&self) -> u32 {
+        match self.kind {
+            Kind::Header            => 0,
+            Kind::Section(flags)    => flags as u32,
+            Kind::Segment(_, flags) => flags,
+        }
+    }
+}
+
+//the map as text, one line per row with a lane for every segment which shows + where the segment
+//starts and | while it continues
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self.digits() + 2;
+        writeln!(f, "{} layout:", self.space)?;
+        if self.rows.is_empty() {return writeln!(f, "  nothing is loaded");}
+        for (lane, s) in self.segments.iter().enumerate() {
+            writeln!(f, "  {}  {:<16} {:<4} {:#x}..{:#x}", laneName(lane), s.name, permissions(s.flags()), s.start, s.end)?;
+        }
+        let lanes: String = (0..self.segments.len()).map(|l| format!("{} ", laneName(l))).collect();
+        writeln!(f, "  {:>w$} {:>w$} {:>9}  {}  CONTENTS", "START", "END", "SIZE", lanes, w = width)?;
+        for row in self.rows.iter() {
+            let marks: String = self.segments.iter().map(|s| {
+                if s.start == row.start {"+ "} else if s.start < row.start && row.start < s.end {"| "} else {"  "}
+            }).collect();
+            writeln!(f, "  {:#0w$x} {:#0w$x} {:>9}  {}  {}", row.start, row.end, row.end - row.start, marks, self.rowName(row), w = width)?;
+        }
+        let (padding, gaps) = self.unused();
+        writeln!(f, "  {} bytes of padding, {} bytes in gaps", padding, gaps)
+    }
+}
+
+//the maps side by side as a standalone SVG image
+pub fn svg(layouts: &[Layout], title: &str) -> String {
+    let mut body = String::new();
+    let (mut x, mut height) = (10, 0);
+    for layout in layouts.iter().filter(|l| !l.rows.is_empty()) {
+        let (text, w, h) = layout.svg(x);
+        body += &text;
+        x += w + COLUMN_GAP;
+        height = height.max(h);
+    }
+    format!("<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" font-family=\"monospace\" font-size=\"11\">\n<title>{}</title>\n{}</svg>\n",
+            x, height, escape(title), body)
+}
+
+//a standalone HTML page with the SVG maps and the problems found
+pub fn html(layouts: &[Layout], title: &str) -> String {
+    let mut page = format!("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{0}</title>\n\
+                            <style>body {{font-family: sans-serif}} li {{font-family: monospace}}</style>\n</head>\n<body>\n<h1>{0}</h1>\n",
+                           escape(title));
+    page += &svg(layouts, title);
+    let problems: Vec<&String> = layouts.iter().flat_map(|l| l.problems.iter()).collect();
+    if !problems.is_empty() {
+        page += "<h2>problems</h2>\n<ul>\n";
+        for p in problems {
+            page += &format!("<li>{}</li>\n", escape(p));
+        }
+        page += "</ul>\n";
+    }
+    page + "</body>\n</html>\n"
+}
+
+//the ELF header and the header tables in the file
+fn headers(loader: &loader::Loader) -> Vec<Block> {
+    let h = &loader.header;
+    let tables = [
+        ("[ELF header]",      0,                 h.e_ehsize as u64),
+        ("[program headers]", h.e_phoff as u64, (h.e_phoff as u64).saturating_add(h.e_phnum as u64 * h.e_phentsize as u64)),
+        ("[section headers]", h.e_shoff as u64, (h.e_shoff as u64).saturating_add(h.e_shnum as u64 * h.e_shentsize as u64)),
+    ];
+    tables.iter().filter(|(_, s, e)| s < e).map(|(n, s, e)| Block{name: n.to_string(), kind: Kind::Header, start: *s, end: *e}).collect()
+}
+
+//the addresses of a file range that lies in the file part of a LOAD segment
+fn mapped(loader: &loader::Loader, start: u64, end: u64) -> Option<(u64, u64)> {
+    let p = loader.programHeaders.iter().find(|p| {
+        p.getTYPE() == programheader::PT_LOAD && p.getOFFSET() as u64 <= start && end <= p.getOFFSET().saturating_add(p.getFILESZ()) as u64
+    })?;
+    let address = (p.getVADDR() as u64).saturating_add(start - p.getOFFSET() as u64);
+    Some((address, address.saturating_add(end - start)))
+}
+
+fn segmentName(index: usize, kind: u32) -> String {
+    let name = match kind {
+        programheader::PT_NULL         => "NULL".to_string(),
+        programheader::PT_LOAD         => "LOAD".to_string(),
+        programheader::PT_DYNAMIC      => "DYNAMIC".to_string(),
+        programheader::PT_INTERP       => "INTERP".to_string(),
+        programheader::PT_NOTE         => "NOTE".to_string(),
+        programheader::PT_SHLIB        => "SHLIB".to_string(),
+        programheader::PT_PHDR         => "PHDR".to_string(),
+        programheader::PT_TLS          => "TLS".to_string(),
+        programheader::PT_GNU_EH_FRAME => "GNU_EH_FRAME".to_string(),
+        programheader::PT_GNU_STACK    => "GNU_STACK".to_string(),
+        programheader::PT_GNU_RELRO    => "GNU_RELRO".to_string(),
+        programheader::PT_GNU_PROPERTY => "GNU_PROPERTY".to_string(),
+        other                          => format!("{:#x}", other),
+    };
+    format!("{} #{}", name, index)
+}
+
+fn permissions(flags: u32) -> String {
+    [(programheader::PF_R, 'R'), (programheader::PF_W, 'W'), (programheader::PF_X, 'X')].iter()
+        .filter(|(f, _)| flags & f != 0).map(|(_, c)| *c).collect()
+}
+
+//lanes are numbered 0 to 9, then a to z
+fn laneName(lane: usize) -> char {
+    std::char::from_digit((lane % 36) as u32, 36).unwrap()
+}
+
+fn rowHeight(row: &Row) -> u64 {
+    ROW_HEIGHT + (64 - (row.end - row.start).leading_zeros()) as u64
+}
+
+//headers grey, code red, writable data blue, read-only data green and sections that are not
+//loaded light grey
+fn blockColor(block: &Block) -> &'static str {
+    let flags = block.flags() as usize;
+    match block.kind {
+        Kind::Header                                      => "#c8c8c8",
+        _ if flags & sectionheader::SHF_ALLOC == 0        => "#e0e0e0",
+        _ if flags & sectionheader::SHF_EXECINSTR != 0    => "#f4a582",
+        _ if flags & sectionheader::SHF_WRITE != 0        => "#92c5de",
+        _                                                 => "#b8e186",
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
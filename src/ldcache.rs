@@ -0,0 +1,287 @@
+use std::fmt;
+use crate::header;
+use crate::loader;
+
+//magic of the libc5 era cache format, ld.so-1.7.0
+pub const CACHE_MAGIC_OLD: &[u8] = b"ld.so-1.7.0";
+
+//magic of the cache format written by glibc 2.2 and later, followed by the version "1.1"
+pub const CACHE_MAGIC_NEW: &[u8] = b"glibc-ld.so.cache1.1";
+
+//magic of the extension directory pointed to by extension_offset
+pub const CACHE_EXTENSION_MAGIC: u32 = 0xeaa42174;
+
+//extension section tags
+pub const CACHE_EXTENSION_TAG_GENERATOR:    u32 = 0;
+pub const CACHE_EXTENSION_TAG_GLIBC_HWCAPS: u32 = 1;
+
+//upper half of the hwcap field of an entry that lives in a glibc-hwcaps subdirectory
+//the lower half is then an index into the glibc-hwcaps extension section
+pub const DL_CACHE_HWCAP_EXTENSION: u64 = 1 << 62;
+
+//low byte of the entry flags, the kind of library
+pub const FLAG_TYPE_MASK: i32 = 0x00ff;
+pub const FLAG_LIBC4:     i32 = 0x0000;
+pub const FLAG_ELF:       i32 = 0x0001;
+pub const FLAG_ELF_LIBC5: i32 = 0x0002;
+pub const FLAG_ELF_LIBC6: i32 = 0x0003;
+
+//high byte of the entry flags, the ABI the library needs
+pub const FLAG_REQUIRED_MASK:          i32 = 0xff00;
+pub const FLAG_SPARC_LIB64:            i32 = 0x0100;
+pub const FLAG_IA64_LIB64:             i32 = 0x0200;
+pub const FLAG_X8664_LIB64:            i32 = 0x0300;
+pub const FLAG_S390_LIB64:             i32 = 0x0400;
+pub const FLAG_POWERPC_LIB64:          i32 = 0x0500;
+pub const FLAG_MIPS64_LIBN32:          i32 = 0x0600;
+pub const FLAG_MIPS64_LIBN64:          i32 = 0x0700;
+pub const FLAG_X8664_LIBX32:           i32 = 0x0800;
+pub const FLAG_ARM_LIBHF:              i32 = 0x0900;
+pub const FLAG_AARCH64_LIB64:          i32 = 0x0a00;
+pub const FLAG_ARM_LIBSF:              i32 = 0x0b00;
+pub const FLAG_MIPS_LIB32_NAN2008:     i32 = 0x0c00;
+pub const FLAG_MIPS64_LIBN32_NAN2008:  i32 = 0x0d00;
+pub const FLAG_MIPS64_LIBN64_NAN2008:  i32 = 0x0e00;
+pub const FLAG_RISCV_FLOAT_ABI_SOFT:   i32 = 0x0f00;
+pub const FLAG_RISCV_FLOAT_ABI_DOUBLE: i32 = 0x1000;
+
+//layout of the cache file
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum CacheFormat {
+    //only ld.so-1.7.0 entries
+    Old,
+    //only glibc-ld.so.cache1.1 entries
+    New,
+    //ld.so-1.7.0 entries followed by a glibc-ld.so.cache1.1 table, the new table is the one used
+    Compat,
+}
+
+//one library in the cache
+#[derive(Debug, Clone)]
+pub struct CacheEntry {
+    pub flags:     i32,
+
+    //file name the library is looked up by, normally its SONAME
+    pub key:       String,
+
+    //full path of the library
+    pub value:     String,
+
+    //lowest kernel version the library supports, 0 if any, always 0 in old format caches
+    pub osversion: u32,
+
+    //hardware capabilities the library needs, 0 if none, always 0 in old format caches
+    pub hwcap:     u64,
+
+    //name of the glibc-hwcaps subdirectory the library is in, for example x86-64-v3
+    pub hwcapSubdirectory: Option<String>,
+}
+
+impl CacheEntry {
+
+    //true if the library can be used on any CPU of its architecture
+    pub fn isGeneric(&self) -> bool {
+        self.hwcap == 0
+    }
+
+    //the flags as ldconfig -p prints them, for example libc6,x86-64
+    pub fn flagsDescription(&self) -> String {
+        let kind = match self.flags & FLAG_TYPE_MASK {
+            FLAG_LIBC4     => "libc4",
+            FLAG_ELF       => "ELF",
+            FLAG_ELF_LIBC5 => "libc5",
+            FLAG_ELF_LIBC6 => "libc6",
+            _              => "unknown",
+        };
+        let abi = match self.flags & FLAG_REQUIRED_MASK {
+            0                           => "",
+            FLAG_SPARC_LIB64            => ",64bit",
+            FLAG_IA64_LIB64             => ",IA-64",
+            FLAG_X8664_LIB64            => ",x86-64",
+            FLAG_S390_LIB64             => ",64bit",
+            FLAG_POWERPC_LIB64          => ",64bit",
+            FLAG_MIPS64_LIBN32          => ",N32",
+            FLAG_MIPS64_LIBN64          => ",64bit",
+            FLAG_X8664_LIBX32           => ",x32",
+            FLAG_ARM_LIBHF              => ",hard-float",
+            FLAG_AARCH64_LIB64          => ",AArch64",
+            FLAG_ARM_LIBSF              => ",soft-float",
+            FLAG_MIPS_LIB32_NAN2008     => ",nan2008",
+            FLAG_MIPS64_LIBN32_NAN2008  => ",N32,nan2008",
+            FLAG_MIPS64_LIBN64_NAN2008  => ",64bit,nan2008",
+            FLAG_RISCV_FLOAT_ABI_SOFT   => ",soft-float",
+            FLAG_RISCV_FLOAT_ABI_DOUBLE => ",double-float",
+            _                           => ",unknown",
+        };
+        let mut out = format!("{}{}", kind, abi);
+        match (&self.hwcapSubdirectory, self.hwcap) {
+            (Some(s), _) => out.push_str(&format!(", hwcap: \"{}\"", s)),
+            (None, 0)    => {},
+            (None, h)    => out.push_str(&format!(", hwcap: {:#018x}", h)),
+        }
+        if self.osversion != 0 {
+            out.push_str(&format!(", OS ABI: Linux {}.{}.{}", (self.osversion >> 16) & 0xff, (self.osversion >> 8) & 0xff, self.osversion & 0xff));
+        }
+        out
+    }
+}
+
+impl fmt::Display for CacheEntry {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} ({}) => {}", self.key, self.flagsDescription(), self.value)
+    }
+}
+
+//the contents of /etc/ld.so.cache
+pub struct LdCache {
+    pub format:  CacheFormat,
+
+    pub entries: Vec<CacheEntry>,
+
+    //ldconfig version that wrote the cache, from the generator extension section
+    pub generator: Option<String>,
+
+    //names of the glibc-hwcaps subdirectories, indexed by the low half of an extension hwcap
+    pub hwcapSubdirectories: Vec<String>,
+}
+
+impl LdCache {
+
+    //parses a cache file in any of the formats, None if the format is not recognised
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        if bytes.starts_with(CACHE_MAGIC_OLD) {
+            //magic padded to 12 bytes, nlibs, then flags, key and value for every entry
+            let count = read32(bytes, 12, false)? as usize;
+            let stringBase = 16usize.checked_add(count.checked_mul(12)?)?;
+            //the new table follows aligned like struct cache_file_new, the runtime linker prefers it
+            let newBase = (stringBase + 7) & !7;
+            if bytes.get(newBase..).map(|b| b.starts_with(CACHE_MAGIC_NEW)).unwrap_or(false) {
+                let mut cache = Self::parseNew(bytes, newBase)?;
+                cache.format = CacheFormat::Compat;
+                return Some(cache);
+            }
+            let mut entries = vec![];
+            for i in 0..count {
+                let at = 16 + i * 12;
+                entries.push(CacheEntry {
+                    flags:     read32(bytes, at, false)? as i32,
+                    key:       readString(bytes, stringBase + read32(bytes, at + 4, false)? as usize)?,
+                    value:     readString(bytes, stringBase + read32(bytes, at + 8, false)? as usize)?,
+                    osversion: 0,
+                    hwcap:     0,
+                    hwcapSubdirectory: None,
+                });
+            }
+            return Some(Self{format: CacheFormat::Old, entries: entries, generator: None, hwcapSubdirectories: vec![]});
+        }
+        if bytes.starts_with(CACHE_MAGIC_NEW) {
+            return Self::parseNew(bytes, 0);
+        }
+        None
+    }
+
+    //parses a glibc-ld.so.cache1.1 table starting at base, string offsets are relative to base
+    fn parseNew(bytes: &[u8], base: usize) -> Option<Self> {
+        //header is magic and version, nlibs, len_strings, a flags byte holding the endianness,
+        //padding, extension_offset and three reserved words, 48 bytes in total
+        let big = *bytes.get(base + 28)? == 3;
+        let count = read32(bytes, base + 20, big)? as usize;
+        let extensionOffset = read32(bytes, base + 32, big)? as usize;
+
+        let mut cache = Self{format: CacheFormat::New, entries: vec![], generator: None, hwcapSubdirectories: vec![]};
+
+        //extension offsets are relative to the start of the file, the strings they name to base
+        if extensionOffset != 0 && read32(bytes, extensionOffset, big) == Some(CACHE_EXTENSION_MAGIC) {
+            let sections = read32(bytes, extensionOffset + 4, big)? as usize;
+            for i in 0..sections {
+                let at = extensionOffset + 8 + i * 16;
+                let (tag, offset, size) = match (read32(bytes, at, big), read32(bytes, at + 8, big), read32(bytes, at + 12, big)) {
+                    (Some(t), Some(o), Some(s)) => (t, o as usize, s as usize),
+                    _ => break,
+                };
+                match tag {
+                    CACHE_EXTENSION_TAG_GENERATOR => {
+                        let text = bytes.get(offset..offset.checked_add(size)?)?;
+                        cache.generator = Some(String::from_utf8_lossy(text).trim_end_matches('\0').to_string());
+                    },
+                    CACHE_EXTENSION_TAG_GLIBC_HWCAPS => {
+                        //only the entries that are in the file, a hostile size would otherwise take forever
+                        let present = size.min(bytes.len().saturating_sub(offset));
+                        for j in 0..present / 4 {
+                            let name = read32(bytes, offset + j * 4, big).and_then(|s| readString(bytes, base + s as usize));
+                            cache.hwcapSubdirectories.push(name.unwrap_or_default());
+                        }
+                    },
+                    _ => {},
+                }
+            }
+        }
+
+        for i in 0..count {
+            //entries are flags, key, value, osversion and hwcap, 24 bytes each
+            let at = base + 48 + i * 24;
+            let hwcap = read64(bytes, at + 16, big)?;
+            let hwcapSubdirectory = if hwcap >> 32 == DL_CACHE_HWCAP_EXTENSION >> 32 {
+                cache.hwcapSubdirectories.get((hwcap & 0xffffffff) as usize).cloned()
+            } else {
+                None
+            };
+            cache.entries.push(CacheEntry {
+                flags:     read32(bytes, at, big)? as i32,
+                key:       readString(bytes, base + read32(bytes, at + 4, big)? as usize)?,
+                value:     readString(bytes, base + read32(bytes, at + 8, big)? as usize)?,
+                osversion: read32(bytes, at + 12, big)?,
+                hwcap:     hwcap,
+                hwcapSubdirectory: hwcapSubdirectory,
+            });
+        }
+        Some(cache)
+    }
+
+    //returns the paths recorded for a library name whose flags suit the program
+    //entries usable on any CPU come first, the ones tied to hwcaps after them, both in cache order
+    pub fn lookup(&self, name: &str, required: i32) -> Vec<String> {
+        let matching: Vec<&CacheEntry> = self.entries.iter()
+            .filter(|e| e.key == name && e.flags & FLAG_TYPE_MASK == FLAG_ELF_LIBC6)
+            .filter(|e| e.flags & FLAG_REQUIRED_MASK == required)
+            .collect();
+        matching.iter().filter(|e| e.isGeneric())
+            .chain(matching.iter().filter(|e| !e.isGeneric()))
+            .map(|e| e.value.clone())
+            .collect()
+    }
+}
+
+fn read32(bytes: &[u8], offset: usize, big: bool) -> Option<u32> {
+    let b = bytes.get(offset..offset.checked_add(4)?)?;
+    let b = [b[0], b[1], b[2], b[3]];
+    Some(if big {u32::from_be_bytes(b)} else {u32::from_le_bytes(b)})
+}
+
+fn read64(bytes: &[u8], offset: usize, big: bool) -> Option<u64> {
+    let low = read32(bytes, offset, big)? as u64;
+    let high = read32(bytes, offset + 4, big)? as u64;
+    Some(if big {(low << 32) | high} else {(high << 32) | low})
+}
+
+fn readString(bytes: &[u8], offset: usize) -> Option<String> {
+    let rest = bytes.get(offset..)?;
+    let end = rest.iter().position(|b| *b == 0)?;
+    Some(String::from_utf8_lossy(&rest[..end]).to_string())
+}
+
+//the FLAG_REQUIRED_MASK bits a library has to carry to be loaded into a program
+pub fn requiredFlags(root: &loader::Loader) -> i32 {
+    match (root.header.e_machine, root.header.e_ident.Class) {
+        (header::EM_X86_64, 2)  => FLAG_X8664_LIB64,
+        (header::EM_X86_64, _)  => FLAG_X8664_LIBX32,
+        (header::EM_AARCH64, _) => FLAG_AARCH64_LIB64,
+        //EF_ARM_ABI_FLOAT_HARD
+        (header::EM_ARM, _) if root.header.e_flags & 0x400 != 0 => FLAG_ARM_LIBHF,
+        (header::EM_ARM, _)     => FLAG_ARM_LIBSF,
+        //EF_RISCV_FLOAT_ABI_DOUBLE
+        (header::EM_RISCV, _) if root.header.e_flags & 0x6 == 0x4 => FLAG_RISCV_FLOAT_ABI_DOUBLE,
+        (header::EM_RISCV, _)   => FLAG_RISCV_FLOAT_ABI_SOFT,
+        _                       => 0,
+    }
+}
@@ -5,6 +5,11 @@ use std::fs::File;
 use crate::header;
 use crate::programheader;
 use crate::sectionheader;
+use crate::symbol;
+use crate::dynamic;
+use crate::note;
+use crate::relocation;
+use crate::version;
 
 
 //the loader loads the elf into the appropriate structs and ensures everything is correct
@@ -24,6 +29,30 @@ pub struct Loader {
 
     //vector of structs for each section header found in the ELF
     pub sectionHeaders: Vec<sectionheader::SectionHeader>,
+
+    //symbols from the .symtab section, empty for stripped files
+    pub symbols: Vec<symbol::Symbol>,
+
+    //symbols from the .dynsym section
+    pub dynamicSymbols: Vec<symbol::Symbol>,
+
+    //entries of the dynamic section up to and including DT_NULL
+    pub dynamic: Vec<dynamic::DynamicEntry>,
+
+    //notes from the note sections, or the PT_NOTE segments when there are no section headers
+    pub notes: Vec<note::Note>,
+
+    //entries of every SHT_REL and SHT_RELA section
+    pub relocations: Vec<relocation::Relocation>,
+
+    //.gnu.version entries, one per dynamic symbol
+    pub versionSymbols: Vec<u16>,
+
+    //versions defined by the file from .gnu.version_d
+    pub versionDefinitions: Vec<version::VersionDefinition>,
+
+    //versions required from dependencies from .gnu.version_r
+    pub versionNeeds: Vec<version::VersionNeed>,
 }
 
 impl Loader {
@@ -38,15 +67,389 @@ impl Loader {
         let mut buffer = Vec::new();
         //use bufreader to copy the file into a vector byte by byte 
         match reader.read_to_end(&mut buffer){
-            Ok(b)  => Self{fileVec: buffer, fileIndex: 0, header: header::Header::new(), programHeaders: vec![],sectionHeaders: vec![]},
+            Ok(b)  => Self::fromBytes(buffer),
             Err(e) => panic!("Error reading file into vector"),
         }
     }       
+
+    //returns why bytes can not be loaded, the identification has to name a known class and
+    //byte order and the whole ELF header of that class has to be there
+    pub fn validate(bytes: &[u8]) -> Result<(), String> {
+        if bytes.len() < 16 || &bytes[..4] != b"\x7fELF" {return Err("not an ELF file".to_string());}
+        if bytes[4] != 1 && bytes[4] != 2 {return Err(format!("unknown ELF class {}", bytes[4]));}
+        if bytes[5] != 1 && bytes[5] != 2 {return Err(format!("unknown ELF byte order {}", bytes[5]));}
+        let size = if bytes[4] == 1 {52} else {64};
+        if bytes.len() < size {return Err(format!("truncated ELF header, {} of {} bytes", bytes.len(), size));}
+        Ok(())
+    }
+
+    //initializes a new Loader struct from bytes already in memory
+    pub fn fromBytes(buffer: Vec<u8>) -> Self {
+        Self{fileVec: buffer, fileIndex: 0, header: header::Header::new(), programHeaders: vec![],sectionHeaders: vec![],
+             symbols: vec![], dynamicSymbols: vec![], dynamic: vec![], notes: vec![], relocations: vec![],
+             versionSymbols: vec![], versionDefinitions: vec![], versionNeeds: vec![]}
+    }
+
     //loads each part of the header
     pub fn load(&mut self) {
         self.loadHeader();
         self.loadProgramHeaders();
         self.loadSectionHeaders();
+        self.loadDynamic();
+        self.loadSymbols();
+        self.loadNotes();
+        self.loadRelocations();
+        self.loadVersions();
+    }
+
+    //loads the symbol versioning sections into versionSymbols, versionDefinitions and versionNeeds
+    pub fn loadVersions(&mut self) {
+        self.versionSymbols.clear();
+        self.versionDefinitions.clear();
+        self.versionNeeds.clear();
+        for index in 0..self.sectionHeaders.len() {
+            let (sType, offset, size, link, info) = {
+                let s = &self.sectionHeaders[index];
+                (s.sh_type, s.sh_offset, s.sh_size, s.sh_link as usize, s.sh_info as usize)
+            };
+            let strOffset = self.sectionHeaders.get(link).map(|s| s.sh_offset).unwrap_or(0);
+            let name = |l: &Self, offset: u64| l.readString(strOffset + offset as usize).unwrap_or_default();
+
+            match sType {
+                sectionheader::SHT_GNU_VERSYM => {
+                    self.versionSymbols = (0..size / 2).map_while(|i| self.readAt(offset + i * 2, 2).map(|v| v as u16)).collect();
+                },
+                sectionheader::SHT_GNU_VERDEF => {
+                    //sh_info holds the number of entries, vd_next and vda_next chain them
+                    let mut at = offset;
+                    for _ in 0..info {
+                        let mut d = version::VersionDefinition::new();
+                        let (count, aux, next) = match (|| Some((
+                            self.readAt(at + 2, 2)?, self.readAt(at + 4, 2)?, self.readAt(at + 6, 2)?,
+                            self.readAt(at + 8, 4)?, self.readAt(at + 12, 4)?, self.readAt(at + 16, 4)?)))() {
+                            Some((flags, ndx, count, hash, aux, next)) => {
+                                d.vd_flags = flags as u16;
+                                d.vd_ndx = ndx as u16;
+                                d.vd_hash = hash as u32;
+                                (count, aux as usize, next as usize)
+                            },
+                            None => break,
+                        };
+                        let mut auxAt = at + aux;
+                        for _ in 0..count {
+                            match (self.readAt(auxAt, 4), self.readAt(auxAt + 4, 4)) {
+                                (Some(n), Some(auxNext)) => {
+                                    d.names.push(name(self, n));
+                                    if auxNext == 0 {break;}
+                                    auxAt += auxNext as usize;
+                                },
+                                _ => break,
+                            }
+                        }
+                        self.versionDefinitions.push(d);
+                        if next == 0 {break;}
+                        at += next;
+                    }
+                },
+                sectionheader::SHT_GNU_VERNEED => {
+                    let mut at = offset;
+                    for _ in 0..info {
+                        let (count, file, aux, next) = match (|| Some((
+                            self.readAt(at + 2, 2)?, self.readAt(at + 4, 4)?, self.readAt(at + 8, 4)?, self.readAt(at + 12, 4)?)))() {
+                            Some((count, file, aux, next)) => (count, name(self, file), aux as usize, next as usize),
+                            None => break,
+                        };
+                        let mut auxAt = at + aux;
+                        for _ in 0..count {
+                            let mut n = version::VersionNeed::new();
+                            n.file = file.clone();
+                            let auxNext = match (|| Some((
+                                self.readAt(auxAt, 4)?, self.readAt(auxAt + 4, 2)?, self.readAt(auxAt + 6, 2)?,
+                                self.readAt(auxAt + 8, 4)?, self.readAt(auxAt + 12, 4)?)))() {
+                                Some((hash, flags, other, vname, auxNext)) => {
+                                    n.vna_hash = hash as u32;
+                                    n.vna_flags = flags as u16;
+                                    n.vna_other = other as u16;
+                                    n.name = name(self, vname);
+                                    auxNext as usize
+                                },
+                                None => break,
+                            };
+                            self.versionNeeds.push(n);
+                            if auxNext == 0 {break;}
+                            auxAt += auxNext;
+                        }
+                        if next == 0 {break;}
+                        at += next;
+                    }
+                },
+                _ => {},
+            }
+        }
+    }
+
+    //returns the version of the dynamic symbol at index, None for unversioned and local symbols
+    pub fn symbolVersion(&self, index: usize) -> Option<version::SymbolVersion> {
+        let entry = *self.versionSymbols.get(index)?;
+        let ndx = entry & !version::VERSYM_HIDDEN;
+        if ndx == version::VER_NDX_LOCAL || ndx == version::VER_NDX_GLOBAL {return None;}
+        let hidden = entry & version::VERSYM_HIDDEN != 0;
+        if let Some(d) = self.versionDefinitions.iter().find(|d| d.vd_ndx == ndx) {
+            return Some(version::SymbolVersion{name: d.name().to_string(), hidden: hidden, file: None});
+        }
+        let n = self.versionNeeds.iter().find(|n| n.vna_other == ndx)?;
+        Some(version::SymbolVersion{name: n.name.clone(), hidden: hidden, file: Some(n.file.clone())})
+    }
+
+    //loads the entries of every SHT_REL and SHT_RELA section into the relocations vector
+    pub fn loadRelocations(&mut self) {
+        self.relocations.clear();
+        for index in 0..self.sectionHeaders.len() {
+            let (sType, offset, size) = {
+                let s = &self.sectionHeaders[index];
+                (s.sh_type, s.sh_offset, s.sh_size)
+            };
+            if sType != sectionheader::SHT_RELA && sType != sectionheader::SHT_REL {continue;}
+            let word = if self.header.e_ident.Class == 1 {4} else {8};
+            let entSize = if sType == sectionheader::SHT_RELA {word * 3} else {word * 2};
+
+            for i in 0..size / entSize {
+                self.fileIndex = offset + i * entSize;
+                let mut r = relocation::Relocation::new();
+                r.section = index;
+                r.r_offset = match self.readUSize() {
+                    Some(v) => v,
+                    None    => break,
+                };
+                r.r_info = match self.readUSize() {
+                    Some(v) => v,
+                    None    => break,
+                };
+                if sType == sectionheader::SHT_RELA {
+                    r.r_addend = match self.readUSize() {
+                        Some(v) => Some(v as i64),
+                        None    => break,
+                    };
+                    if word == 4 {r.r_addend = r.r_addend.map(|a| a as i32 as i64);}
+                }
+                r.class = self.header.e_ident.Class;
+                self.relocations.push(r);
+            }
+        }
+    }
+
+    //loads the entries of the dynamic section into the dynamic vector
+    //the PT_DYNAMIC segment is used when there is one since that is what the runtime linker reads
+    pub fn loadDynamic(&mut self) {
+        let location = match self.programHeaders.iter().find(|p| p.getTYPE() == programheader::PT_DYNAMIC) {
+            Some(p) => Some((p.getOFFSET(), p.getFILESZ())),
+            None    => self.sectionHeaders.iter()
+                .find(|s| s.sh_type == sectionheader::SHT_DYNAMIC)
+                .map(|s| (s.sh_offset, s.sh_size)),
+        };
+        let (offset, size) = match location {
+            Some(l) => l,
+            None    => return,
+        };
+
+        self.dynamic.clear();
+        self.fileIndex = offset;
+        while self.fileIndex < offset.saturating_add(size) {
+            let mut entry = dynamic::DynamicEntry::new();
+            entry.d_tag = match self.readUSize() {
+                Some(v) => v,
+                None    => break,
+            };
+            entry.d_val = match self.readUSize() {
+                Some(v) => v,
+                None    => break,
+            };
+            let done = entry.d_tag == dynamic::DT_NULL;
+            self.dynamic.push(entry);
+            if done {break;}
+        }
+    }
+
+    //loads .symtab into symbols and .dynsym into dynamicSymbols, resolving their names
+    pub fn loadSymbols(&mut self) {
+        self.symbols.clear();
+        self.dynamicSymbols.clear();
+        for index in 0..self.sectionHeaders.len() {
+            let sType = self.sectionHeaders[index].sh_type;
+            if sType != sectionheader::SHT_SYMTAB && sType != sectionheader::SHT_DYNSYM {continue;}
+            let symbols = self.readSymbolTable(index);
+            if sType == sectionheader::SHT_SYMTAB {
+                self.symbols = symbols;
+            } else {
+                self.dynamicSymbols = symbols;
+            }
+        }
+    }
+
+    //reads every symbol of the symbol table section at index
+    pub fn readSymbolTable(&mut self, index: usize) -> Vec<symbol::Symbol> {
+        let mut symbols = vec![];
+        let (offset, size, link) = {
+            let s = &self.sectionHeaders[index];
+            (s.sh_offset, s.sh_size, s.sh_link as usize)
+        };
+        let entSize = if self.header.e_ident.Class == 1 {16} else {24};
+        let strOffset = self.sectionHeaders.get(link).map(|s| s.sh_offset);
+
+        for i in 0..size / entSize {
+            self.fileIndex = offset + i * entSize;
+            let mut sym = symbol::Symbol::new();
+            let complete = if self.header.e_ident.Class == 1 {
+                (|| {
+                    sym.st_name  = self.readUInt()?;
+                    sym.st_value = self.readUInt()? as usize;
+                    sym.st_size  = self.readUInt()? as usize;
+                    sym.st_info  = self.readUByte()?;
+                    sym.st_other = self.readUByte()?;
+                    sym.st_shndx = self.readUShort()?;
+                    Some(())
+                })()
+            } else {
+                (|| {
+                    sym.st_name  = self.readUInt()?;
+                    sym.st_info  = self.readUByte()?;
+                    sym.st_other = self.readUByte()?;
+                    sym.st_shndx = self.readUShort()?;
+                    sym.st_value = self.readULong()? as usize;
+                    sym.st_size  = self.readULong()? as usize;
+                    Some(())
+                })()
+            };
+            if complete.is_none() {break;}
+            if let Some(strOffset) = strOffset {
+                sym.name = self.readString(strOffset + sym.st_name as usize).unwrap_or_default();
+            }
+            symbols.push(sym);
+        }
+        symbols
+    }
+
+    //loads notes from the SHT_NOTE sections, or from the PT_NOTE segments if there are no sections
+    pub fn loadNotes(&mut self) {
+        let mut areas: Vec<(usize, usize, usize)> = self.sectionHeaders.iter()
+            .filter(|s| s.sh_type == sectionheader::SHT_NOTE)
+            .map(|s| (s.sh_offset, s.sh_size, s.sh_addralign))
+            .collect();
+        if self.sectionHeaders.is_empty() {
+            areas = self.programHeaders.iter()
+                .filter(|p| p.getTYPE() == programheader::PT_NOTE)
+                .map(|p| (p.getOFFSET(), p.getFILESZ(), p.getALIGN()))
+                .collect();
+        }
+
+        self.notes.clear();
+        for (offset, size, align) in areas {
+            //notes are 4 byte aligned unless the container asks for 8
+            let align = if align == 8 {8} else {4};
+            self.fileIndex = offset;
+            let end = offset.saturating_add(size);
+            while self.fileIndex.saturating_add(12) <= end {
+                let mut n = note::Note::new();
+                n.offset = self.fileIndex;
+                let (nameSize, descSize) = match (self.readUInt(), self.readUInt(), self.readUInt()) {
+                    (Some(a), Some(b), Some(t)) => {
+                        n.n_type = t;
+                        (a as usize, b as usize)
+                    },
+                    _ => break,
+                };
+                let nameStart = self.fileIndex;
+                let descStart = nameStart + ((nameSize + align - 1) & !(align - 1));
+                let descEnd = descStart + descSize;
+                if descEnd > end || descEnd > self.fileVec.len() {break;}
+                n.name = String::from_utf8_lossy(&self.fileVec[nameStart..nameStart + nameSize])
+                    .trim_end_matches('\0').to_string();
+                n.desc = self.fileVec[descStart..descEnd].to_vec();
+                self.notes.push(n);
+                self.fileIndex = (descEnd + align - 1) & !(align - 1);
+            }
+        }
+    }
+
+    //reads an unsigned value of size bytes at a file offset without moving fileIndex
+    pub fn readAt(&self, offset: usize, size: usize) -> Option<u64> {
+        let bytes = self.fileVec.get(offset..offset.checked_add(size)?)?;
+        let mut value = 0u64;
+        for i in 0..size {
+            let b = match self.header.e_ident.Data {
+                2 => bytes[i],
+                _ => bytes[size - 1 - i],
+            };
+            value = (value << 8) | b as u64;
+        }
+        Some(value)
+    }
+
+    //returns the size of an address in the file, 4 for 32 bit and 8 for 64 bit ELFs
+    pub fn wordSize(&self) -> usize {
+        match self.header.e_ident.Class {
+            1 => 4,
+            _ => 8,
+        }
+    }
+
+    //returns the nul terminated string starting at a file offset
+    pub fn readString(&self, offset: usize) -> Option<String> {
+        let bytes = self.fileVec.get(offset..)?;
+        let end = bytes.iter().position(|b| *b == 0)?;
+        Some(String::from_utf8_lossy(&bytes[..end]).to_string())
+    }
+
+    //returns the name of the section at index from the section header string table
+    pub fn sectionName(&self, index: usize) -> Option<String> {
+        let section = self.sectionHeaders.get(index)?;
+        let strtab = self.sectionHeaders.get(self.header.e_shstrndx as usize)?;
+        self.readString(strtab.sh_offset + section.sh_name as usize)
+    }
+
+    //returns the index of the first section called name
+    pub fn findSection(&self, name: &str) -> Option<usize> {
+        (0..self.sectionHeaders.len()).find(|i| self.sectionName(*i).as_deref() == Some(name))
+    }
+
+    //returns the bytes of a section, empty for SHT_NOBITS sections
+    pub fn sectionData(&self, index: usize) -> Option<&[u8]> {
+        let section = self.sectionHeaders.get(index)?;
+        if section.sh_type == sectionheader::SHT_NOBITS {return Some(&[]);}
+        self.fileVec.get(section.sh_offset..section.sh_offset.checked_add(section.sh_size)?)
+    }
+
+    //translates a virtual address to a file offset using the PT_LOAD segments
+    pub fn vaddrToOffset(&self, vaddr: usize) -> Option<usize> {
+        let p = self.programHeaders.iter().find(|p| {
+            p.getTYPE() == programheader::PT_LOAD && p.getVADDR() <= vaddr && vaddr < p.getVADDR().saturating_add(p.getFILESZ())
+        })?;
+        p.getOFFSET().checked_add(vaddr - p.getVADDR())
+    }
+
+    //returns the value of the first dynamic entry with the tag
+    pub fn getDynamic(&self, tag: usize) -> Option<usize> {
+        self.dynamic.iter().find(|d| d.d_tag == tag).map(|d| d.d_val)
+    }
+
+    //returns the string at an offset into the dynamic string table
+    pub fn dynamicString(&self, offset: usize) -> Option<String> {
+        let strtab = match self.getDynamic(dynamic::DT_STRTAB).and_then(|a| self.vaddrToOffset(a)) {
+            Some(o) => o,
+            None    => {
+                let section = self.sectionHeaders.iter().find(|s| s.sh_type == sectionheader::SHT_DYNAMIC)?;
+                self.sectionHeaders.get(section.sh_link as usize)?.sh_offset
+            },
+        };
+        self.readString(strtab + offset)
+    }
+
+    //returns the strings of every dynamic entry with the tag, for example all DT_NEEDED names
+    pub fn dynamicStrings(&self, tag: usize) -> Vec<String> {
+        self.dynamic.iter()
+            .filter(|d| d.d_tag == tag)
+            .filter_map(|d| self.dynamicString(d.d_val))
+            .collect()
     }
     
     //loads each section header into the sectionHeaders vector
@@ -60,6 +463,11 @@ impl Loader {
             //break if greater than e_shnum
             if self.sectionHeaders.len() >= self.header.e_shnum as usize {break;}
 
+            //stop at a truncated table instead of panicking, tampered files can claim more
+            //entries than they contain
+            let entSize = if self.header.e_ident.Class == 1 {40} else {64};
+            if self.fileIndex.saturating_add(entSize) > self.fileVec.len() {break;}
+
             //create new empty sectionHeader struct and fill each
             //it with the appropriate amount of bytes
             let mut sHeader = sectionheader::SectionHeader::new();
@@ -91,20 +499,24 @@ impl Loader {
             //break if greater than e_phnum
             if self.programHeaders.len() >= self.header.e_phnum as usize {break;}
 
+            //stop at a truncated table instead of panicking
+            let entSize = if self.header.e_ident.Class == 1 {32} else {56};
+            if self.fileIndex.saturating_add(entSize) > self.fileVec.len() {break;}
+
             //create new empty sectionHeader struct and fill each
             //it with the appropriate amount of bytes
             let mut pHeader = programheader::ProgramHeader::new();
             
             pHeader.setTYPE(self.readUInt().unwrap()); 
-            //on 64-bit machines the setFLAGS value is the second value in the struct while in 32
-            //bit machines it is later on
-            if usize::BITS == 64 {pHeader.setFLAGS(self.readUInt().unwrap());}
+            //in 64-bit ELFs the setFLAGS value is the second value in the struct while in 32
+            //bit ELFs it is later on
+            if self.header.e_ident.Class == 2 {pHeader.setFLAGS(self.readUInt().unwrap());}
             pHeader.setOFFSET(self.readUSize().unwrap()); 
             pHeader.setVADDR(self.readUSize().unwrap()); 
             pHeader.setPADDR(self.readUSize().unwrap()); 
             pHeader.setFILESZ(self.readUSize().unwrap()); 
             pHeader.setMEMSZ(self.readUSize().unwrap()); 
-            if usize::BITS == 32 {pHeader.setFLAGS(self.readUInt().unwrap());}
+            if self.header.e_ident.Class == 1 {pHeader.setFLAGS(self.readUInt().unwrap());}
             pHeader.setALIGN(self.readUSize().unwrap()); 
 
             //push the program vector to the array
@@ -225,12 +637,13 @@ impl Loader {
         }
 
     }
-    //returns a UInt on 32 bit architecture
-    //and a ULong on 64 bit architecture
+    //returns a UInt for 32 bit ELFs
+    //and a ULong for 64 bit ELFs
+    //the class comes from e_ident so 32 bit files can be parsed on a 64 bit host
     pub fn readUSize(&mut self) -> Option<usize> {
-        match usize::BITS {
-            32 => return Some(self.readUInt().unwrap() as usize),
-            _  => return Some(self.readULong().unwrap() as usize),
+        match self.header.e_ident.Class {
+            1 => return Some(self.readUInt()? as usize),
+            _ => return Some(self.readULong()? as usize),
         } 
 
     }
@@ -5,9 +5,1122 @@ mod loader;
 mod header;
 mod programheader;
 mod sectionheader;
+mod addressspace;
+mod stack;
+mod riscv;
+mod syscall;
+mod gdbstub;
+mod symbol;
+mod dynamic;
+mod note;
+mod hardening;
+mod relocation;
+mod infection;
+mod diff;
+mod version;
+mod abi;
+mod deps;
+mod ldcache;
+mod dwarf;
+mod symbolize;
+mod process;
+mod memscan;
+mod writer;
+mod edit;
+mod patch;
+mod strip;
+mod debugfile;
+mod xz;
+mod convert;
+mod memory;
+mod size;
+mod layout;
 
 fn main() {
-    
+    let args: Vec<String> = std::env::args().collect();
+    let code = match args.get(1).map(|s| s.as_str()) {
+        Some("checksec") => checksecCommand(&args[2..]),
+        Some("scan")     => scanCommand(&args[2..]),
+        Some("diff")     => diffCommand(&args[2..]),
+        Some("abi")      => abiCommand(&args[2..]),
+        Some("closure")  => closureCommand(&args[2..]),
+        Some("ldd")      => lddCommand(&args[2..]),
+        Some("cache")    => cacheCommand(&args[2..]),
+        Some("symbolize") => symbolizeCommand(&args[2..]),
+        Some("memscan")  => memscanCommand(&args[2..]),
+        Some("edit")     => editCommand(&args[2..]),
+        Some("patch")    => patchCommand(&args[2..]),
+        Some("strip")    => stripCommand(&args[2..]),
+        Some("convert")  => convertCommand(&args[2..]),
+        Some("memory")   => memoryCommand(&args[2..]),
+        Some("size")     => sizeCommand(&args[2..]),
+        Some("layout")   => layoutCommand(&args[2..]),
+        Some("run")      => runCommand(&args[2..]),
+        Some("gdbserver") => gdbserverCommand(&args[2..]),
+        _ => {
+            eprintln!("usage: elfLoader <command> [args]");
+            eprintln!("commands:");
+            eprintln!("  checksec [--policy FILE] FILE...");
+            eprintln!("  scan FILE...");
+            eprintln!("  diff [--json] OLD NEW");
+            eprintln!("  abi [--json] OLD NEW");
+            eprintln!("  closure [--sysroot DIR] FILE");
+            eprintln!("  ldd [--sysroot DIR] [--library-path PATHS] [--tree|--flat|--dot] FILE");
+            eprintln!("  cache [FILE]");
+            eprintln!("  symbolize [--bias N] [--sysroot DIR] [--debug-dir DIR]... FILE ADDRESS...");
+            eprintln!("  symbolize --pid N ADDRESS...");
+            eprintln!("  memscan --pid N [--module NAME] [--section NAME|--segment N] --i32 V|--f64 V|--string S|PATTERN");
+            eprintln!("  edit [--set-interpreter P] [--set-rpath P] [--force-rpath] [--remove-rpath] [--set-soname N]");
+            eprintln!("       [--add-needed N] [--remove-needed N] [--replace-needed OLD NEW] [--output OUT] FILE");
+            eprintln!("  patch [--write ADDRESS|SYMBOL[+OFFSET] HEX]... [--import SET] [--export SET] [--output OUT] FILE");
+            eprintln!("  strip [--only-keep-debug] [--split-debug DEBUG] [--add-debuglink DEBUG] [--output OUT] FILE");
+            eprintln!("  convert [--format binary|ihex|srec] [--virtual] [--gap-fill BYTE] [--only-section NAME]...");
+            eprintln!("          [--remove-section NAME]... FILE OUT");
+            eprintln!("  memory LAYOUT FILE...");
+            eprintln!("  size [--by segments|sections|symbols|files] [--sort both|file|vm|name] [--top N] [--diff OLD] FILE");
+            eprintln!("  layout [--file|--memory] [--svg OUT] [--html OUT] FILE");
+            eprintln!("  run [--sandbox DIR] [--env NAME=VALUE]... FILE [ARGS...]");
+            eprintln!("  gdbserver [--port N|--socket PATH] [--sandbox DIR] [--env NAME=VALUE]... FILE [ARGS...]");
+            2
+        },
+    };
+    std::process::exit(code);
+}
+
+//reads and parses an ELF file, printing an error instead of panicking when it is not one
+fn openElf(path: &str) -> Option<loader::Loader> {
+    let bytes = match std::fs::read(path) {
+        Ok(b)  => b,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return None;
+        },
+    };
+    if let Err(e) = loader::Loader::validate(&bytes) {
+        eprintln!("{}: {}", path, e);
+        return None;
+    }
+    let mut parser = loader::Loader::fromBytes(bytes);
+    parser.load();
+    Some(parser)
+}
+
+//prints the hardening report of every file and checks them against the policy if one is given
+//exits with 1 when a file breaks the policy and 2 on usage or read errors
+fn checksecCommand(args: &[String]) -> i32 {
+    let mut policy = None;
+    let mut files = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--policy" {
+            let path = match args.get(i + 1) {
+                Some(p) => p,
+                None    => {
+                    eprintln!("--policy needs a file");
+                    return 2;
+                },
+            };
+            let text = match std::fs::read_to_string(path) {
+                Ok(t)  => t,
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return 2;
+                },
+            };
+            policy = match hardening::Policy::parse(&text) {
+                Ok(p)  => Some(p),
+                Err(e) => {
+                    eprintln!("{}: {}", path, e);
+                    return 2;
+                },
+            };
+            i += 2;
+        } else {
+            files.push(args[i].clone());
+            i += 1;
+        }
+    }
+    if files.is_empty() {
+        eprintln!("usage: elfLoader checksec [--policy FILE] FILE...");
+        return 2;
+    }
+
+    let mut code = 0;
+    for path in files.iter() {
+        let parser = match openElf(path) {
+            Some(p) => p,
+            None    => {
+                code = 2;
+                continue;
+            },
+        };
+        let report = hardening::HardeningReport::new(&parser);
+        println!("{}:", path);
+        println!("{}", report);
+        if let Some(policy) = policy.as_ref() {
+            let failures = policy.check(&report);
+            if failures.is_empty() {
+                println!("  policy:   pass");
+            } else {
+                for f in failures.iter() {
+                    println!("  policy:   FAIL {}", f);
+                }
+                if code == 0 {code = 1;}
+            }
+        }
+    }
+    code
+}
+
+//looks for signs of ELF infection in every file, exits with 1 if anything was found
+fn scanCommand(args: &[String]) -> i32 {
+    if args.is_empty() {
+        eprintln!("usage: elfLoader scan FILE...");
+        return 2;
+    }
+    let mut code = 0;
+    for path in args.iter() {
+        let parser = match openElf(path) {
+            Some(p) => p,
+            None    => {
+                code = 2;
+                continue;
+            },
+        };
+        let findings = infection::scan(&parser);
+        if findings.is_empty() {
+            println!("{}: clean", path);
+            continue;
+        }
+        println!("{}:", path);
+        for f in findings.iter() {
+            println!("  {}", f);
+        }
+        if code == 0 {code = 1;}
+    }
+    code
+}
+
+//prints the structural differences between two files, exits with 1 if they differ like diff(1)
+fn diffCommand(args: &[String]) -> i32 {
+    let json = args.iter().any(|a| a == "--json");
+    let files: Vec<&String> = args.iter().filter(|a| *a != "--json").collect();
+    if files.len() != 2 {
+        eprintln!("usage: elfLoader diff [--json] OLD NEW");
+        return 2;
+    }
+    let (old, new) = match (openElf(files[0]), openElf(files[1])) {
+        (Some(a), Some(b)) => (a, b),
+        _                  => return 2,
+    };
+    let changes = diff::ElfDiff::new(&old, &new);
+    if json {
+        println!("{}", changes.toJson());
+    } else {
+        print!("{}", changes);
+    }
+    if changes.isEmpty() {0} else {1}
+}
+
+//reports interface changes between two versions of a shared object, exits with 1 if any of them break consumers
+fn abiCommand(args: &[String]) -> i32 {
+    let json = args.iter().any(|a| a == "--json");
+    let files: Vec<&String> = args.iter().filter(|a| *a != "--json").collect();
+    if files.len() != 2 {
+        eprintln!("usage: elfLoader abi [--json] OLD NEW");
+        return 2;
+    }
+    let (old, new) = match (openElf(files[0]), openElf(files[1])) {
+        (Some(a), Some(b)) => (a, b),
+        _                  => return 2,
+    };
+    let report = abi::AbiReport::new(&old, &new);
+    if json {
+        println!("{}", report.toJson());
+    } else {
+        println!("{}", report);
+    }
+    if report.isCompatible() {0} else {1}
+}
+
+//resolves the libraries of a program inside a sysroot and checks that every symbol it imports is provided
+//exits with 1 when the runtime linker would refuse to start it
+fn closureCommand(args: &[String]) -> i32 {
+    let mut sysroot = "/".to_string();
+    let mut files = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--sysroot" {
+            match args.get(i + 1) {
+                Some(d) => sysroot = d.clone(),
+                None    => {
+                    eprintln!("--sysroot needs a directory");
+                    return 2;
+                },
+            }
+            i += 2;
+        } else {
+            files.push(args[i].clone());
+            i += 1;
+        }
+    }
+    if files.len() != 1 {
+        eprintln!("usage: elfLoader closure [--sysroot DIR] FILE");
+        return 2;
+    }
+
+    let resolver = deps::Resolver::new(std::path::Path::new(&sysroot));
+    let objects = match resolver.closure(&files[0]) {
+        Some(o) => o,
+        None    => {
+            eprintln!("{}: cannot open ELF file in {}", files[0], sysroot);
+            return 2;
+        },
+    };
+    for o in objects.iter().skip(1) {
+        println!("{} => {}", o.name, o.path);
+    }
+    let problems = deps::checkClosure(&objects);
+    for p in problems.iter() {
+        println!("{}", p);
+    }
+    if problems.is_empty() {0} else {1}
+}
+
+//prints the libraries a program would load without running it, exits with 1 if any are missing
+fn lddCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader ldd [--sysroot DIR] [--library-path PATHS] [--tree|--flat|--dot] FILE";
+    let mut sysroot = "/".to_string();
+    let mut libraryPath = vec![];
+    let mut format = deps::Format::Tree;
+    let mut files = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--sysroot" | "--library-path" => {
+                let value = match args.get(i + 1) {
+                    Some(v) => v.clone(),
+                    None    => {
+                        eprintln!("{} needs a value", args[i]);
+                        return 2;
+                    },
+                };
+                if args[i] == "--sysroot" {sysroot = value;} else {libraryPath.push(value);}
+                i += 2;
+                continue;
+            },
+            "--tree" => format = deps::Format::Tree,
+            "--flat" => format = deps::Format::Flat,
+            "--dot"  => format = deps::Format::Dot,
+            _        => files.push(args[i].clone()),
+        }
+        i += 1;
+    }
+    if files.len() != 1 {
+        eprintln!("{}", usage);
+        return 2;
+    }
+
+    let mut resolver = deps::Resolver::new(std::path::Path::new(&sysroot));
+    resolver.libraryPath = libraryPath;
+    let objects = match resolver.closure(&files[0]) {
+        Some(o) => o,
+        None    => {
+            eprintln!("{}: cannot open ELF file in {}", files[0], sysroot);
+            return 2;
+        },
+    };
+    print!("{}", deps::formatDependencies(&objects, format));
+    if objects.iter().all(|o| o.dependencies.iter().all(|(_, i)| i.is_some())) {0} else {1}
+}
+
+//lists the entries of a runtime linker cache like ldconfig -p, /etc/ld.so.cache by default
+fn cacheCommand(args: &[String]) -> i32 {
+    if args.len() > 1 {
+        eprintln!("usage: elfLoader cache [FILE]");
+        return 2;
+    }
+    let path = args.get(0).map(|s| s.as_str()).unwrap_or("/etc/ld.so.cache");
+    let bytes = match std::fs::read(path) {
+        Ok(b)  => b,
+        Err(e) => {
+            eprintln!("{}: {}", path, e);
+            return 2;
+        },
+    };
+    let cache = match ldcache::LdCache::parse(&bytes) {
+        Some(c) => c,
+        None    => {
+            eprintln!("{}: not a ld.so cache", path);
+            return 2;
+        },
+    };
+    println!("{} libs found in cache `{}'", cache.entries.len(), path);
+    for e in cache.entries.iter() {
+        println!("\t{}", e);
+    }
+    if let Some(g) = cache.generator.as_ref() {
+        println!("Cache generated by: {}", g);
+    }
+    0
+}
+
+//parses a number written in hex with a 0x prefix or in decimal
+fn parseNumber(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None      => text.parse().ok(),
+    }
+}
+
+//prints the segment, section, symbol and source lines of addresses in a file loaded at an optional bias,
+//or of addresses in a running process
+fn symbolizeCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader symbolize [--bias N] [--sysroot DIR] [--debug-dir DIR]... FILE ADDRESS...\n       elfLoader symbolize --pid N ADDRESS...";
+    let mut bias = 0;
+    let mut pid = None;
+    let mut sysroot = "/".to_string();
+    let mut debugDirectories = vec![];
+    let mut rest = vec![];
+    let mut i = 0;
+    while i < args.len() {
+        if args[i] == "--bias" || args[i] == "--pid" {
+            let value = match args.get(i + 1).and_then(|b| parseNumber(b)) {
+                Some(b) => b,
+                None    => {
+                    eprintln!("{}", usage);
+                    return 2;
+                },
+            };
+            if args[i] == "--bias" {bias = value;} else {pid = Some(value as u32);}
+            i += 2;
+        } else if args[i] == "--sysroot" || args[i] == "--debug-dir" {
+            let value = match args.get(i + 1) {
+                Some(v) => v.clone(),
+                None    => {
+                    eprintln!("{}", usage);
+                    return 2;
+                },
+            };
+            if args[i] == "--sysroot" {sysroot = value;} else {debugDirectories.push(value);}
+            i += 2;
+        } else {
+            rest.push(args[i].clone());
+            i += 1;
+        }
+    }
+
+    if let Some(pid) = pid {
+        if rest.is_empty() {
+            eprintln!("{}", usage);
+            return 2;
+        }
+        let symbolizer = match process::ProcessSymbolizer::new(pid) {
+            Ok(s)  => s,
+            Err(e) => {
+                eprintln!("process {}: {}", pid, e);
+                return 2;
+            },
+        };
+        let mut code = 0;
+        for text in rest.iter() {
+            match parseNumber(text) {
+                Some(address) => match symbolizer.symbolize(address) {
+                    Some(l) => println!("{}", l),
+                    None    => println!("{:#x} not in any mapped ELF file of process {}", address, pid),
+                },
+                None => {
+                    eprintln!("{}: not an address", text);
+                    code = 2;
+                },
+            }
+        }
+        return code;
+    }
+
+    if rest.len() < 2 {
+        eprintln!("{}", usage);
+        return 2;
+    }
+    let parser = match openElf(&rest[0]) {
+        Some(p) => p,
+        None    => return 2,
+    };
+    let mut locator = debugfile::DebugLocator::new(std::path::Path::new(&sysroot));
+    if !debugDirectories.is_empty() {locator.debugDirectories = debugDirectories;}
+    //the file is named by its path on this machine, the debug file lookup wants the path inside the sysroot
+    let inside = std::fs::canonicalize(&rest[0]).ok()
+        .and_then(|p| p.strip_prefix(std::fs::canonicalize(&sysroot).ok()?).ok().map(|p| format!("/{}", p.display())))
+        .unwrap_or_else(|| rest[0].clone());
+    let debug = if debugfile::hasDebugInfo(&parser) {None} else {locator.locate(&parser, &inside)};
+    if let Some(d) = debug.as_ref() {
+        eprintln!("using {} found by {}", d.path.display(), d.method);
+    }
+    let symbolizer = symbolize::Symbolizer::withDebugFile(parser, debug.map(|d| d.loader));
+    let mut code = 0;
+    for text in rest[1..].iter() {
+        let address = match parseNumber(text) {
+            Some(a) => a,
+            None    => {
+                eprintln!("{}: not an address", text);
+                code = 2;
+                continue;
+            },
+        };
+        match symbolizer.symbolize(address, bias) {
+            Some(l) => println!("{}", l),
+            None    => println!("{:#x} not in {}", address, rest[0]),
+        }
+    }
+    code
+}
+
+//searches the memory of a process for a value or byte pattern, exits with 1 if nothing was found
+fn memscanCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader memscan --pid N [--module NAME] [--section NAME|--segment N] --i32 V|--f64 V|--string S|PATTERN";
+    let mut pid = None;
+    let mut module = None;
+    let mut target = memscan::Target::Module;
+    let mut value = None;
+    let mut pattern = None;
+    let mut i = 0;
+    while i < args.len() {
+        let next = args.get(i + 1);
+        let mut step = 2;
+        let ok = match (args[i].as_str(), next) {
+            ("--pid", Some(n))     => parseNumber(n).map(|n| pid = Some(n as u32)).is_some(),
+            ("--module", Some(n))  => {module = Some(n.clone()); true},
+            ("--section", Some(n)) => {target = memscan::Target::Section(n.clone()); true},
+            ("--segment", Some(n)) => parseNumber(n).map(|n| target = memscan::Target::Segment(n as usize)).is_some(),
+            ("--i32", Some(n))     => n.parse::<i32>().map(|n| value = Some(memscan::Value::I32(n))).is_ok(),
+            ("--f64", Some(n))     => n.parse::<f64>().map(|n| value = Some(memscan::Value::F64(n))).is_ok(),
+            ("--string", Some(n))  => {value = Some(memscan::Value::String(n.clone())); true},
+            (text, _) if !text.starts_with("--") => {
+                pattern = match memscan::Pattern::parse(text) {
+                    Ok(p)  => Some(p),
+                    Err(e) => {
+                        eprintln!("{}", e);
+                        return 2;
+                    },
+                };
+                step = 1;
+                true
+            },
+            _ => false,
+        };
+        if !ok {
+            eprintln!("{}", usage);
+            return 2;
+        }
+        i += step;
+    }
+    let pid = match (pid, value.is_some() != pattern.is_some()) {
+        (Some(p), true) => p,
+        _               => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+
+    let scanner = match memscan::ProcessScanner::new(pid) {
+        Ok(s)  => s,
+        Err(e) => {
+            eprintln!("process {}: {}", pid, e);
+            return 2;
+        },
+    };
+    let regions = scanner.regions(module.as_deref(), &target);
+    let mut hits = vec![];
+    match (value, pattern) {
+        (Some(v), _) => {
+            //typed values are encoded per module since a process could mix byte orders
+            for region in regions.iter() {
+                let p = v.pattern(scanner.isLittleEndian(region.module));
+                hits.extend(scanner.scan(std::slice::from_ref(region), &p));
+            }
+        },
+        (None, Some(p)) => hits = scanner.scan(&regions, &p),
+        _ => {},
+    }
+    for hit in hits.iter() {
+        println!("{}", hit);
+    }
+    if hits.is_empty() {1} else {0}
+}
+
+//rewrites the interpreter, search path, soname and needed libraries of a file like patchelf,
+//in place unless --output is given
+fn editCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader edit [--set-interpreter P] [--set-rpath P] [--force-rpath] [--remove-rpath] [--set-soname N]\n       \
+                 \x20              [--add-needed N] [--remove-needed N] [--replace-needed OLD NEW] [--output OUT] FILE";
+    let mut edits: Vec<(&str, Vec<&String>)> = vec![];
+    let mut forceRpath = false;
+    let mut output = None;
+    let mut input = None;
+    let mut i = 0;
+    while i < args.len() {
+        let arity = match args[i].as_str() {
+            "--set-interpreter" | "--set-rpath" | "--set-soname" | "--add-needed" | "--remove-needed" | "--output" => 1,
+            "--replace-needed" => 2,
+            "--force-rpath" | "--remove-rpath" => 0,
+            a if !a.starts_with("--") && input.is_none() => {
+                input = Some(args[i].clone());
+                i += 1;
+                continue;
+            },
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        };
+        if i + arity >= args.len() {
+            eprintln!("{}", usage);
+            return 2;
+        }
+        match args[i].as_str() {
+            "--force-rpath" => forceRpath = true,
+            "--output"      => output = Some(args[i + 1].clone()),
+            option          => edits.push((option, args[i + 1..i + 1 + arity].iter().collect())),
+        }
+        i += 1 + arity;
+    }
+    let input = match input {
+        Some(i) => i,
+        None    => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+
+    let bytes = match std::fs::read(&input) {
+        Ok(b)  => b,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        },
+    };
+    let mut editor = match writer::ElfFile::parse(bytes).ok_or_else(|| "not an ELF file".to_string()).and_then(edit::Editor::new) {
+        Ok(e)  => e,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        },
+    };
+    for (option, values) in edits.iter() {
+        let result = match *option {
+            "--set-interpreter" => editor.setInterpreter(values[0]),
+            "--set-rpath"       => Ok(editor.setRunpath(values[0], forceRpath)),
+            "--remove-rpath"    => Ok(editor.removeRunpath()),
+            "--set-soname"      => Ok(editor.setSoname(values[0])),
+            "--add-needed"      => Ok(editor.addNeeded(values[0])),
+            "--remove-needed"   => if editor.removeNeeded(values[0]) {Ok(())} else {Err(format!("{} is not needed", values[0]))},
+            _                   => if editor.replaceNeeded(values[0], values[1]) {Ok(())} else {Err(format!("{} is not needed", values[0]))},
+        };
+        if let Err(e) = result {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        }
+    }
+    let bytes = match editor.write() {
+        Ok(b)  => b,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        },
+    };
+    //keep the permissions of the input, the file is usually executable
+    let permissions = std::fs::metadata(&input).map(|m| m.permissions()).ok();
+    let output = output.unwrap_or(input);
+    if let Err(e) = std::fs::write(&output, bytes) {
+        eprintln!("{}: {}", output, e);
+        return 2;
+    }
+    if let Some(p) = permissions {
+        let _ = std::fs::set_permissions(&output, p);
+    }
+    0
+}
+
+//writes bytes into a file by address or symbol and imports or exports patch sets,
+//the file is patched in place unless --output is given
+fn patchCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader patch [--write ADDRESS|SYMBOL[+OFFSET] HEX]... [--import SET] [--export SET] [--output OUT] FILE";
+    let mut writes = vec![];
+    let (mut import, mut export, mut output, mut input) = (None, None, None, None);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1), args.get(i + 2)) {
+            ("--write", Some(target), Some(bytes)) => {
+                writes.push((target.clone(), bytes.clone()));
+                i += 3;
+            },
+            ("--import", Some(path), _) => {import = Some(path.clone()); i += 2;},
+            ("--export", Some(path), _) => {export = Some(path.clone()); i += 2;},
+            ("--output", Some(path), _) => {output = Some(path.clone()); i += 2;},
+            (path, _, _) if !path.starts_with("--") && input.is_none() => {input = Some(path.to_string()); i += 1;},
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    let input = match input {
+        Some(i) => i,
+        None    => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+    let mut patcher = match openElf(&input) {
+        Some(p) => patch::Patcher::new(p),
+        None    => return 2,
+    };
+
+    if let Some(path) = import.as_ref() {
+        let result = std::fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| patcher.import(&text));
+        match result {
+            Ok(n)  => println!("{}: applied {} patches", path, n),
+            Err(e) => {
+                eprintln!("{}: {}", path, e);
+                return 2;
+            },
+        }
+    }
+    for (target, text) in writes.iter() {
+        let bytes: Option<Vec<u8>> = if text.len() % 2 == 0 {
+            (0..text.len()).step_by(2).map(|i| text.get(i..i + 2).and_then(|b| u8::from_str_radix(b, 16).ok())).collect()
+        } else {None};
+        let bytes = match bytes {
+            Some(b) if !b.is_empty() => b,
+            _ => {
+                eprintln!("{}: not hex bytes", text);
+                return 2;
+            },
+        };
+        let (name, offset) = match target.rsplit_once('+') {
+            Some((n, o)) => (n, parseNumber(o)),
+            None         => (target.as_str(), Some(0)),
+        };
+        let result = match (parseNumber(target), offset) {
+            (Some(address), _) => patcher.write(address, &bytes),
+            (None, Some(o))    => patcher.writeSymbol(name, o, &bytes),
+            (None, None)       => Err(format!("{}: bad offset", target)),
+        };
+        match result {
+            Ok(p)  => println!("{}", p),
+            Err(e) => {
+                eprintln!("{}", e);
+                return 2;
+            },
+        }
+    }
+
+    if let Some(path) = export.as_ref() {
+        if let Err(e) = std::fs::write(path, patcher.export()) {
+            eprintln!("{}: {}", path, e);
+            return 2;
+        }
+    }
+    if patcher.patches.is_empty() {return 0;}
+    let permissions = std::fs::metadata(&input).map(|m| m.permissions()).ok();
+    let output = output.unwrap_or(input);
+    if let Err(e) = std::fs::write(&output, patcher.bytes()) {
+        eprintln!("{}: {}", output, e);
+        return 2;
+    }
+    if let Some(p) = permissions {
+        let _ = std::fs::set_permissions(&output, p);
+    }
+    0
+}
+
+//strips symbols and debug information, or keeps only them, in place unless --output is given
+//--split-debug writes the debug information to DEBUG and links the stripped file to it
+fn stripCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader strip [--only-keep-debug] [--split-debug DEBUG] [--add-debuglink DEBUG] [--output OUT] FILE";
+    let mut keepDebug = false;
+    let (mut split, mut link, mut output, mut input) = (None, None, None, None);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--only-keep-debug", _)  => {keepDebug = true; i += 1;},
+            ("--split-debug", Some(p))   => {split = Some(p.clone()); i += 2;},
+            ("--add-debuglink", Some(p)) => {link = Some(p.clone()); i += 2;},
+            ("--output", Some(p))        => {output = Some(p.clone()); i += 2;},
+            (p, _) if !p.starts_with("--") && input.is_none() => {input = Some(p.to_string()); i += 1;},
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    let input = match input {
+        Some(i) if !(keepDebug && split.is_some()) => i,
+        _ => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+    let bytes = match std::fs::read(&input) {
+        Ok(b)  => b,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return 2;
+        },
+    };
+    let parse = || writer::ElfFile::parse(bytes.clone()).ok_or_else(|| format!("{}: not an ELF file", input));
+    let result = (|| -> Result<(), String> {
+        let mut file = parse()?;
+        let mut debugFile = None;
+        if let Some(path) = split.as_ref() {
+            let mut debug = parse()?;
+            strip::onlyKeepDebug(&mut debug);
+            let debugBytes = debug.write()?;
+            std::fs::write(path, &debugBytes).map_err(|e| format!("{}: {}", path, e))?;
+            debugFile = Some((path.clone(), debugBytes));
+        }
+        if keepDebug {
+            strip::onlyKeepDebug(&mut file);
+        } else {
+            let removed = strip::strip(&mut file);
+            if !removed.is_empty() {println!("removed {}", removed.join(" "));}
+        }
+        if let Some(path) = link.as_ref() {
+            let debugBytes = std::fs::read(path).map_err(|e| format!("{}: {}", path, e))?;
+            debugFile = Some((path.clone(), debugBytes));
+        }
+        if let Some((path, debugBytes)) = debugFile {
+            let name = std::path::Path::new(&path).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or(path.clone());
+            strip::addDebuglink(&mut file, &name, &debugBytes);
+        }
+        let out = file.write()?;
+        let permissions = std::fs::metadata(&input).map(|m| m.permissions()).ok();
+        let output = output.clone().unwrap_or(input.clone());
+        std::fs::write(&output, out).map_err(|e| format!("{}: {}", output, e))?;
+        if let Some(p) = permissions {
+            let _ = std::fs::set_permissions(&output, p);
+        }
+        Ok(())
+    })();
+    match result {
+        Ok(_)  => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            2
+        },
+    }
+}
+
+//writes the loadable contents as a flat binary, Intel HEX or S-records for flash programmers,
+//the format follows the extension of OUT unless --format is given
+fn convertCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader convert [--format binary|ihex|srec] [--virtual] [--gap-fill BYTE] [--only-section NAME]...\n       \
+                 \x20                 [--remove-section NAME]... FILE OUT";
+    let (mut format, mut useVirtual, mut gapFill) = (None, false, None);
+    let (mut only, mut remove, mut files) = (vec![], vec![], vec![]);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--format", Some(f)) if convert::Format::parse(f).is_some() => {format = convert::Format::parse(f); i += 2;},
+            ("--virtual", _) => {useVirtual = true; i += 1;},
+            ("--gap-fill", Some(b)) if parseNumber(b).map_or(false, |b| b <= 0xff) => {gapFill = parseNumber(b).map(|b| b as u8); i += 2;},
+            ("--only-section", Some(n))   => {only.push(n.clone()); i += 2;},
+            ("--remove-section", Some(n)) => {remove.push(n.clone()); i += 2;},
+            (p, _) if !p.starts_with("--") => {files.push(p.to_string()); i += 1;},
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    if files.len() != 2 {
+        eprintln!("{}", usage);
+        return 2;
+    }
+    let mut converter = match openElf(&files[0]) {
+        Some(p) => convert::Converter::new(p),
+        None    => return 2,
+    };
+    converter.useVirtual = useVirtual;
+    converter.gapFill = gapFill;
+    converter.only = only;
+    converter.remove = remove;
+    let format = format.unwrap_or_else(|| convert::Format::fromPath(&files[1]));
+    //objcopy names the output file in the S0 record
+    let header = std::path::Path::new(&files[1]).file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let result = converter.convert(format, &header).and_then(|bytes| std::fs::write(&files[1], bytes).map_err(|e| format!("{}: {}", files[1], e)));
+    match result {
+        Ok(_)  => 0,
+        Err(e) => {
+            eprintln!("{}: {}", files[0], e);
+            2
+        },
+    }
+}
+
+//checks that sections and segments fit the memory regions of a device and prints their usage
+//like ld --print-memory-usage, exits with 1 when anything does not fit
+fn memoryCommand(args: &[String]) -> i32 {
+    if args.len() < 2 {
+        eprintln!("usage: elfLoader memory LAYOUT FILE...");
+        return 2;
+    }
+    let layout = match std::fs::read_to_string(&args[0]).map_err(|e| e.to_string()).and_then(|t| memory::MemoryLayout::parse(&t)) {
+        Ok(l)  => l,
+        Err(e) => {
+            eprintln!("{}: {}", args[0], e);
+            return 2;
+        },
+    };
+    let mut code = 0;
+    for path in args[1..].iter() {
+        let parser = match openElf(path) {
+            Some(p) => p,
+            None    => {
+                code = 2;
+                continue;
+            },
+        };
+        let report = layout.check(&parser);
+        if args.len() > 2 {println!("{}:", path);}
+        print!("{}", report);
+        for problem in report.problems.iter() {
+            println!("{}: {}", path, problem);
+        }
+        if !report.problems.is_empty() && code == 0 {code = 1;}
+    }
+    code
+}
+
+//attributes the file and VM size of a file to segments, sections, symbols or source files like
+//bloaty, with --diff OLD the growth since OLD is shown instead
+fn sizeCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader size [--by segments|sections|symbols|files] [--sort both|file|vm|name] [--top N] [--diff OLD] FILE";
+    let (mut source, mut key, mut top) = (size::Source::Sections, size::SortKey::Both, 20);
+    let (mut old, mut input) = (None, None);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--by", Some(s)) if size::Source::parse(s).is_some()    => {source = size::Source::parse(s).unwrap(); i += 2;},
+            ("--sort", Some(k)) if size::SortKey::parse(k).is_some() => {key = size::SortKey::parse(k).unwrap(); i += 2;},
+            ("--top", Some(n)) if parseNumber(n).is_some()           => {top = parseNumber(n).unwrap() as usize; i += 2;},
+            ("--diff", Some(p)) => {old = Some(p.clone()); i += 2;},
+            (p, _) if !p.starts_with("--") && input.is_none() => {input = Some(p.to_string()); i += 1;},
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    let input = match input {
+        Some(i) => i,
+        None    => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+    let parser = match openElf(&input) {
+        Some(p) => p,
+        None    => return 2,
+    };
+    let mut items = size::attribute(&parser, source);
+    let old = match old {
+        Some(path) => match openElf(&path) {
+            Some(p) => Some(size::attribute(&p, source)),
+            None    => return 2,
+        },
+        None => None,
+    };
+    match old {
+        Some(oldItems) => {
+            let totals = |items: &[size::Item]| (items.iter().map(|i| i.fileSize).sum(), items.iter().map(|i| i.vmSize).sum());
+            let diff = size::SizeDiff{changes: size::compare(&oldItems, &items, key), top: top, old: totals(&oldItems), new: totals(&items)};
+            print!("{}", diff);
+        },
+        None => {
+            size::sortItems(&mut items, key);
+            print!("{}", size::SizeReport{items: items, top: top});
+        },
+    }
+    0
+}
+
+//draws the file offsets and virtual addresses of the headers, sections and segments as a map with
+//the padding, gaps and overlaps between them, as text or as an SVG image or HTML page, exits with 1
+//when blocks overlap or sections are split by their segments
+fn layoutCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader layout [--file|--memory] [--svg OUT] [--html OUT] FILE";
+    let (mut spaces, mut svg, mut html, mut input) = (vec![], None, None, None);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--file", _)        => {spaces.push(layout::Space::File); i += 1;},
+            ("--memory", _)      => {spaces.push(layout::Space::Memory); i += 1;},
+            ("--svg", Some(p))   => {svg = Some(p.clone()); i += 2;},
+            ("--html", Some(p))  => {html = Some(p.clone()); i += 2;},
+            (p, _) if !p.starts_with("--") && input.is_none() => {input = Some(p.to_string()); i += 1;},
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    let input = match input {
+        Some(i) => i,
+        None    => {
+            eprintln!("{}", usage);
+            return 2;
+        },
+    };
+    let parser = match openElf(&input) {
+        Some(p) => p,
+        None    => return 2,
+    };
+    if spaces.is_empty() {spaces = vec![layout::Space::File, layout::Space::Memory];}
+    let layouts: Vec<layout::Layout> = spaces.iter().map(|s| match s {
+        layout::Space::File   => layout::Layout::file(&parser),
+        layout::Space::Memory => layout::Layout::memory(&parser),
+    }).collect();
+
+    let title = format!("layout of {}", input);
+    for (path, text) in svg.iter().map(|p| (p, layout::svg(&layouts, &title))).chain(html.iter().map(|p| (p, layout::html(&layouts, &title)))) {
+        if let Err(e) = std::fs::write(path, text) {
+            eprintln!("{}: {}", path, e);
+            return 2;
+        }
+    }
+    let mut code = 0;
+    for l in layouts.iter() {
+        if svg.is_none() && html.is_none() {print!("{}", l);}
+        for problem in l.problems.iter() {
+            println!("{}: {} layout: {}", input, l.space, problem);
+            code = 1;
+        }
+    }
+    code
+}
+
+//runs a static RV64 executable in the interpreter with the linux syscall layer, FILE is passed as
+//argv[0] followed by ARGS. exits with the exit code of the program and 2 when it traps or can not
+//be loaded
+fn runCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader run [--sandbox DIR] [--env NAME=VALUE]... FILE [ARGS...]";
+    let (mut sandbox, mut envp) = (None, vec![]);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--sandbox", Some(d)) => {sandbox = Some(std::path::PathBuf::from(d)); i += 2;},
+            ("--env", Some(e))     => {envp.push(e.as_str()); i += 2;},
+            (p, _) if !p.starts_with("--") => break,
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    if i == args.len() {
+        eprintln!("{}", usage);
+        return 2;
+    }
+    let argv: Vec<&str> = args[i..].iter().map(|a| a.as_str()).collect();
+    let parser = match openElf(argv[0]) {
+        Some(p) => p,
+        None    => return 2,
+    };
+    let mut os = match syscall::Linux::new(&parser, 0) {
+        Some(os) => os,
+        None     => {
+            eprintln!("{}: not a static RV64 executable", argv[0]);
+            return 2;
+        },
+    };
+    os.sandbox = sandbox;
+    match riscv::runProgram(&parser, &argv, &envp, &mut os) {
+        Ok(code)                   => code,
+        Err(riscv::Trap::BadImage) => {
+            eprintln!("{}: not a static RV64 executable", argv[0]);
+            2
+        },
+        Err(trap)                  => {
+            eprintln!("{}: stopped by {:?}", argv[0], trap);
+            2
+        },
+    }
+}
+
+//loads a static RV64 executable like run but stops it at its entry point and serves it to gdb,
+//over localhost:N (1234 by default) or a unix socket created at PATH. exits once gdb detaches,
+//kills the program or disconnects, with 2 on load or connection errors
+fn gdbserverCommand(args: &[String]) -> i32 {
+    let usage = "usage: elfLoader gdbserver [--port N|--socket PATH] [--sandbox DIR] [--env NAME=VALUE]... FILE [ARGS...]";
+    let (mut port, mut socket, mut sandbox, mut envp) = (1234, None, None, vec![]);
+    let mut i = 0;
+    while i < args.len() {
+        match (args[i].as_str(), args.get(i + 1)) {
+            ("--port", Some(n))    => match n.parse::<u16>() {
+                Ok(n)  => {port = n; i += 2;},
+                Err(_) => {
+                    eprintln!("bad port {}", n);
+                    return 2;
+                },
+            },
+            ("--socket", Some(p))  => {socket = Some(p.clone()); i += 2;},
+            ("--sandbox", Some(d)) => {sandbox = Some(std::path::PathBuf::from(d)); i += 2;},
+            ("--env", Some(e))     => {envp.push(e.as_str()); i += 2;},
+            (p, _) if !p.starts_with("--") => break,
+            _ => {
+                eprintln!("{}", usage);
+                return 2;
+            },
+        }
+    }
+    if i == args.len() {
+        eprintln!("{}", usage);
+        return 2;
+    }
+    let argv: Vec<&str> = args[i..].iter().map(|a| a.as_str()).collect();
+    let parser = match openElf(argv[0]) {
+        Some(p) => p,
+        None    => return 2,
+    };
+    let (mut cpu, mut space) = match riscv::boot(&parser, &argv, &envp) {
+        Ok(booted) => booted,
+        Err(_)     => {
+            eprintln!("{}: not a static RV64 executable", argv[0]);
+            return 2;
+        },
+    };
+    let mut os = match syscall::Linux::new(&parser, 0) {
+        Some(os) => os,
+        None     => {
+            eprintln!("{}: not a static RV64 executable", argv[0]);
+            return 2;
+        },
+    };
+    os.sandbox = sandbox;
+    //gdb opens the exec file itself, so hand it an absolute path
+    let execFile = std::fs::canonicalize(argv[0]).map(|p| p.display().to_string()).unwrap_or(argv[0].to_string());
+    let mut stub = gdbstub::GdbStub::new(&mut cpu, &mut space, &mut os, &execFile);
+
+    let served = match &socket {
+        Some(path) => {
+            eprintln!("waiting for gdb on {}", path);
+            let served = gdbstub::acceptUnix(path).and_then(|mut conn| stub.serve(&mut conn));
+            let _ = std::fs::remove_file(path);
+            served
+        },
+        None => {
+            eprintln!("waiting for gdb on localhost:{}", port);
+            gdbstub::acceptTcp(port).and_then(|mut conn| stub.serve(&mut conn))
+        },
+    };
+    match served {
+        Ok(())  => 0,
+        Err(e)  => {
+            eprintln!("gdbserver: {}", e);
+            2
+        },
+    }
 }
 
 #[cfg(test)]
@@ -83,4 +1196,1437 @@ mod tests {
         println!("{:#x?}", parser.sectionHeaders[26]);
         assert_eq!(0x222b4, parser.sectionHeaders[26].sh_offset);
     }
+
+    #[test]
+    fn testInitialStack() {
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        let mut space = addressspace::AddressSpace::fromLoader(&parser, 0x555555554000).unwrap();
+        space.map(0x7ffff000_0000, 0x10000, programheader::PF_R | programheader::PF_W).unwrap();
+
+        let mut init = stack::InitialStack::new(&["/bin/ls", "-l"], &["HOME=/root"], 0x7ffff001_0000);
+        init.loadBias = 0x555555554000;
+        init.platform = stack::InitialStack::platformFor(parser.header.e_machine).to_string();
+        let sp = init.build(&parser, &mut space).unwrap();
+
+        assert_eq!(0, sp % 16);
+        assert_eq!(2, space.readUValue(sp, 8).unwrap());
+        let argv0 = space.readUValue(sp + 8, 8).unwrap() as usize;
+        assert_eq!(b"/bin/ls".to_vec(), space.readCString(argv0).unwrap());
+        assert_eq!(0, space.readUValue(sp + 24, 8).unwrap());
+
+        //walk past envp to the auxv and look up AT_ENTRY and AT_PHDR
+        let mut cur = sp + 48;
+        let mut entry = 0;
+        let mut phdr = 0;
+        loop {
+            let key = space.readUValue(cur, 8).unwrap();
+            let value = space.readUValue(cur + 8, 8).unwrap();
+            if key == stack::AT_NULL {break;}
+            if key == stack::AT_ENTRY {entry = value;}
+            if key == stack::AT_PHDR {phdr = value;}
+            cur += 16;
+        }
+        assert_eq!(0x555555554000 + 0x5b20, entry);
+        assert_eq!(0x555555554000 + 0x40, phdr);
+        assert_eq!(0x7f, space.readUValue(phdr as usize - 0x40, 1).unwrap());
+
+        //a bias that pushes the entry point past the end of the address space fails the build
+        init.loadBias = usize::MAX - 0x10;
+        assert_eq!(None, init.phdrAddress(&parser));
+        assert_eq!(None, init.build(&parser, &mut space));
+    }
+
+    #[test]
+    fn testInitialStack32() {
+        //tiny32 has no PT_PHDR, AT_PHDR comes from the PT_LOAD covering e_phoff
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny32"));
+        parser.load();
+        let mut space = addressspace::AddressSpace::fromLoader(&parser, 0).unwrap();
+        space.map(0xbfff0000, 0x10000, programheader::PF_R | programheader::PF_W).unwrap();
+
+        let init = stack::InitialStack::new(&["/tiny32"], &["A=1", "B=2"], 0xc0000000);
+        let sp = init.build(&parser, &mut space).unwrap();
+        assert_eq!(0, sp % 16);
+        assert_eq!(1, space.readUValue(sp, 4).unwrap());
+        let argv0 = space.readUValue(sp + 4, 4).unwrap() as usize;
+        assert_eq!(b"/tiny32".to_vec(), space.readCString(argv0).unwrap());
+        assert_eq!(0, space.readUValue(sp + 8, 4).unwrap());
+        let envp1 = space.readUValue(sp + 16, 4).unwrap() as usize;
+        assert_eq!(b"B=2".to_vec(), space.readCString(envp1).unwrap());
+        assert_eq!(0, space.readUValue(sp + 20, 4).unwrap());
+
+        //auxv pairs are two 4 byte words
+        let mut cur = sp + 24;
+        let mut values = std::collections::HashMap::new();
+        loop {
+            let key = space.readUValue(cur, 4).unwrap();
+            if key == stack::AT_NULL {break;}
+            values.insert(key, space.readUValue(cur + 4, 4).unwrap());
+            cur += 8;
+        }
+        assert_eq!(Some(&0x80480d8), values.get(&stack::AT_ENTRY));
+        assert_eq!(Some(&0x8048034), values.get(&stack::AT_PHDR));
+        assert_eq!(Some(&32), values.get(&stack::AT_PHENT));
+        assert_eq!(Some(&(parser.header.e_phnum as u64)), values.get(&stack::AT_PHNUM));
+        assert_eq!(parser.fileVec[52..84].to_vec(), space.read(0x8048034, 32).unwrap());
+    }
+
+    //wraps code in a minimal static RV64 executable with a single RWX PT_LOAD at 0x10000
+    fn riscvElf(code: &[u8]) -> Vec<u8> {
+        let mut elf = vec![0x7f, b'E', b'L', b'F', 2, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0];
+        let size = (64 + 56 + code.len()) as u64;
+        elf.extend_from_slice(&2u16.to_le_bytes());
+        elf.extend_from_slice(&243u16.to_le_bytes());
+        elf.extend_from_slice(&1u32.to_le_bytes());
+        elf.extend_from_slice(&(0x10000u64 + 64 + 56).to_le_bytes());
+        elf.extend_from_slice(&64u64.to_le_bytes());
+        elf.extend_from_slice(&0u64.to_le_bytes());
+        elf.extend_from_slice(&0u32.to_le_bytes());
+        for v in [64u16, 56, 1, 64, 0, 0].iter() {
+            elf.extend_from_slice(&v.to_le_bytes());
+        }
+        elf.extend_from_slice(&programheader::PT_LOAD.to_le_bytes());
+        elf.extend_from_slice(&(programheader::PF_R | programheader::PF_W | programheader::PF_X).to_le_bytes());
+        for v in [0u64, 0x10000, 0x10000, size, size, 0x1000].iter() {
+            elf.extend_from_slice(&v.to_le_bytes());
+        }
+        elf.extend_from_slice(code);
+        elf
+    }
+
+    //write(1, "hi\n", 3) then exit(6 * 7), the li t0 is compressed
+    fn riscvHello() -> Vec<u8> {
+        let words: [u32; 11] = [
+            0x00000597, 0x02c58593, 0x00100513, 0x00300613, 0x04000893, 0x00000073,
+            0x00014299, 0x00700313, 0x02628533, 0x05d00893, 0x00000073,
+        ];
+        let mut code = vec![];
+        for w in words.iter() {
+            code.extend_from_slice(&w.to_le_bytes());
+        }
+        code.extend_from_slice(b"hi\n");
+        riscvElf(&code)
+    }
+
+    #[test]
+    fn testRiscvRunProgram() {
+        let mut parser = loader::Loader::fromBytes(riscvHello());
+        parser.load();
+        let mut os = syscall::Linux::new(&parser, 0).unwrap();
+        os.output = Some(vec![]);
+        assert_eq!(Ok(42), riscv::runProgram(&parser, &["prog"], &[], &mut os));
+        assert_eq!(Some(b"hi\n".to_vec()), os.output);
+    }
+
+    #[test]
+    fn testRiscvRunStatic() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/rv64-echo");
+        let mut parser = loader::Loader::new(path);
+        parser.load();
+        let mut os = syscall::Linux::new(&parser, 0).unwrap();
+        os.output = Some(vec![]);
+        assert_eq!(Ok(0), riscv::runProgram(&parser, &["rv64-echo", "hello world"], &["HOME=/"], &mut os));
+        assert_eq!(Some(b"hello world\n".to_vec()), os.output);
+
+        //without an argument it prints its usage to stderr and exits with 1
+        os.output = Some(vec![]);
+        os.errorOutput = Some(vec![]);
+        assert_eq!(Ok(1), riscv::runProgram(&parser, &["rv64-echo"], &[], &mut os));
+        assert_eq!(Some(vec![]), os.output);
+        assert_eq!(Some(b"usage: echo X\n".to_vec()), os.errorOutput);
+
+        assert_eq!(2, runCommand(&["--sandbox".to_string()]));
+        assert_eq!(2, runCommand(&[concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls").to_string()]));
+        let (cpu, _) = riscv::boot(&parser, &["rv64-echo"], &[]).unwrap();
+        assert_eq!(parser.header.e_entry as u64, cpu.pc);
+    }
+
+    #[test]
+    fn testRiscvTraps() {
+        let run = |code: &[u32]| {
+            let bytes: Vec<u8> = code.iter().flat_map(|i| i.to_le_bytes().to_vec()).collect();
+            let mut parser = loader::Loader::fromBytes(riscvElf(&bytes));
+            parser.load();
+            let mut os = syscall::Linux::new(&parser, 0).unwrap();
+            riscv::runProgram(&parser, &["prog"], &[], &mut os)
+        };
+        let exit = [0x05d00893, 0x00000073];
+        //csrr a0, cycle reads the counter, csrrw x0, cycle, ra writes a read only csr
+        assert_eq!(Ok(0), run(&[0xc0002573, 0x00000513, exit[0], exit[1]]));
+        assert_eq!(Err(riscv::Trap::IllegalInstruction{pc: 0x10078, insn: 0xc0009073}), run(&[0xc0009073]));
+        //csrrs with a nonzero rs1 writes too, fflags accepts writes
+        assert!(matches!(run(&[0xc000a073]), Err(riscv::Trap::IllegalInstruction{..})));
+        assert_eq!(Ok(0), run(&[0x00109073, 0x00000513, exit[0], exit[1]]));
+
+        //a misaligned LR faults as a load and a misaligned SC as a store
+        assert_eq!(Err(riscv::Trap::LoadFault(0x10001)), run(&[0x00010537, 0x00150513, 0x100525af]));
+        assert_eq!(Err(riscv::Trap::StoreFault(0x10001)), run(&[0x00010537, 0x00150513, 0x18b525af]));
+    }
+
+    #[test]
+    fn testSyscallBrkAndSandbox() {
+        let mut parser = loader::Loader::fromBytes(riscvElf(&[0; 16]));
+        parser.load();
+        let mut space = addressspace::AddressSpace::fromLoader(&parser, 0).unwrap();
+        let mut os = syscall::Linux::new(&parser, 0).unwrap();
+        let mut cpu = riscv::Cpu::new(0, 0);
+
+        //brk(0) reports the start of the heap and growing it maps writable memory
+        cpu.x[17] = 214;
+        cpu.x[10] = 0;
+        assert_eq!(None, syscall::SyscallHandler::syscall(&mut os, &mut cpu, &mut space));
+        let heap = cpu.x[10] as usize;
+        assert_eq!(0x11000, heap);
+        cpu.x[17] = 214;
+        cpu.x[10] = (heap + 0x2000) as u64;
+        syscall::SyscallHandler::syscall(&mut os, &mut cpu, &mut space);
+        assert_eq!((heap + 0x2000) as u64, cpu.x[10]);
+        assert!(space.write(heap + 0x1ff8, &[1; 8]).is_some());
+
+        //lengths come from the guest and must fail cleanly instead of exhausting the host
+        os.output = Some(vec![]);
+        let mut call = |number: u64, args: &[u64]| {
+            cpu.x[17] = number;
+            cpu.x[10..10 + args.len()].copy_from_slice(args);
+            syscall::SyscallHandler::syscall(&mut os, &mut cpu, &mut space);
+            cpu.x[10] as i64
+        };
+        assert_eq!(-syscall::EFAULT, call(64, &[1, 0x10000, u64::MAX]));
+        assert_eq!(-syscall::EFAULT, call(63, &[0, heap as u64, u64::MAX]));
+        assert_eq!(-syscall::EFAULT, call(278, &[heap as u64, 1 << 40, 0]));
+        assert_eq!(-syscall::EINVAL, call(66, &[1, heap as u64, 1 << 40]));
+        assert_eq!((heap + 0x2000) as i64, call(214, &[u64::MAX]));
+        assert_eq!(-syscall::ENOMEM, call(222, &[0, u64::MAX - 10, 3, 0x22, u64::MAX, 0]));
+        assert_eq!(-syscall::ENOMEM, call(222, &[0, 1 << 40, 3, 0x22, u64::MAX, 0]));
+        assert_eq!(-syscall::EINVAL, call(215, &[0, u64::MAX]));
+        //large writes and getrandom go through in pieces
+        let buffer = call(222, &[0, 0x30000, 3, 0x22, u64::MAX, 0]);
+        assert!(buffer > 0);
+        assert_eq!(0x30000, call(278, &[buffer as u64, 0x30000, 0]));
+        assert_eq!(0x30000, call(64, &[1, buffer as u64, 0x30000]));
+        //an unaligned hint is rounded down, an unaligned offset or fixed address is refused
+        assert_eq!(0x7000_0000, call(222, &[0x7000_0123, 0x1000, 3, 0x22, u64::MAX, 0]));
+        assert_eq!(-syscall::EINVAL, call(222, &[0, 0x1000, 3, 0x22, u64::MAX, 0x10]));
+        assert_eq!(-syscall::EINVAL, call(222, &[0x7000_1123, 0x1000, 3, 0x32, u64::MAX, 0]));
+        //getrandom fills at most MAX_RANDOM bytes per call
+        let big = call(222, &[0, 0x200_0000, 3, 0x22, u64::MAX, 0]);
+        assert_eq!(syscall::MAX_RANDOM as i64, call(278, &[big as u64, 1 << 40, 0]));
+        assert_eq!(Some(0x30000), os.output.as_ref().map(|o| o.len()));
+        assert_eq!(space.read(buffer as usize, 0x30000), os.output);
+        //segments ending past the address space are refused
+        assert!(syscall::Linux::new(&parser, usize::MAX - 0x10).is_none());
+
+        //paths may not climb out of the sandbox
+        os.sandbox = Some(std::path::PathBuf::from("/srv/root"));
+        assert_eq!(Some(std::path::PathBuf::from("/srv/root/etc/passwd")), os.sandboxPath(b"/etc/passwd"));
+        assert_eq!(Some(std::path::PathBuf::from("/srv/root/passwd")), os.sandboxPath(b"a/../passwd"));
+        assert_eq!(None, os.sandboxPath(b"../../etc/passwd"));
+
+        //symlinks are resolved inside the sandbox, absolute ones from its root
+        let root = std::env::temp_dir().join(format!("elfLoader-sandbox-{}", std::process::id()));
+        std::fs::create_dir_all(root.join("etc")).unwrap();
+        std::fs::create_dir_all(root.join("home/user")).unwrap();
+        std::os::unix::fs::symlink("/etc", root.join("home/user/escape")).unwrap();
+        std::os::unix::fs::symlink("../../etc/hosts", root.join("home/user/hosts")).unwrap();
+        std::os::unix::fs::symlink("../../../..", root.join("home/user/up")).unwrap();
+        std::os::unix::fs::symlink("loop", root.join("loop")).unwrap();
+        os.sandbox = Some(root.clone());
+        assert_eq!(Some(root.join("etc/passwd")), os.sandboxPath(b"/home/user/escape/passwd"));
+        assert_eq!(Some(root.join("etc/hosts")), os.sandboxPath(b"home/user/hosts"));
+        assert_eq!(None, os.sandboxPath(b"/home/user/up/etc/passwd"));
+        assert_eq!(None, os.sandboxPath(b"/loop"));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    //sends one gdb packet and returns the reply payload
+    fn gdbRequest<S: std::io::Read + std::io::Write>(stream: &mut S, packet: &str) -> String {
+        use std::io::{Read, Write};
+        let checksum = packet.bytes().fold(0u8, |sum, b| sum.wrapping_add(b));
+        write!(stream, "${}#{:02x}", packet, checksum).unwrap();
+        let mut byte = [0u8; 1];
+        stream.read_exact(&mut byte).unwrap();
+        assert_eq!(b'+', byte[0]);
+        stream.read_exact(&mut byte).unwrap();
+        assert_eq!(b'$', byte[0]);
+        let mut reply = vec![];
+        loop {
+            stream.read_exact(&mut byte).unwrap();
+            if byte[0] == b'#' {break;}
+            reply.push(byte[0]);
+        }
+        let mut checksum = [0u8; 2];
+        stream.read_exact(&mut checksum).unwrap();
+        stream.write_all(b"+").unwrap();
+        String::from_utf8(reply).unwrap()
+    }
+
+    #[test]
+    fn testGdbStub() {
+        let mut parser = loader::Loader::fromBytes(riscvHello());
+        parser.load();
+        let mut space = addressspace::AddressSpace::fromLoader(&parser, 0).unwrap();
+        let mut os = syscall::Linux::new(&parser, 0).unwrap();
+        os.output = Some(vec![]);
+        let mut cpu = riscv::Cpu::new(parser.header.e_entry as u64, 0);
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                let (mut conn, _) = listener.accept().unwrap();
+                conn.set_nodelay(true).unwrap();
+                let mut stub = gdbstub::GdbStub::new(&mut cpu, &mut space, &mut os, "/tmp/hello");
+                stub.serve(&mut conn).unwrap();
+            });
+
+            let mut client = std::net::TcpStream::connect(("127.0.0.1", port)).unwrap();
+            client.set_nodelay(true).unwrap();
+            let features = gdbRequest(&mut client, "qSupported:swbreak+");
+            assert!(features.contains("qXfer:exec-file:read+") && !features.contains("libraries-svr4"));
+            assert_eq!("l/tmp/hello", gdbRequest(&mut client, "qXfer:exec-file:read::0,100"));
+            assert_eq!("E00", gdbRequest(&mut client, "qXfer:libraries-svr4:read::0,100"));
+            //packets with a bad checksum are refused until they arrive intact
+            for _ in 0..3 {
+                use std::io::{Read, Write};
+                client.write_all(b"$?#00").unwrap();
+                let mut byte = [0u8; 1];
+                client.read_exact(&mut byte).unwrap();
+                assert_eq!(b'-', byte[0]);
+            }
+            assert_eq!("97050000", gdbRequest(&mut client, "m10078,4"));
+            assert_eq!("E01", gdbRequest(&mut client, "m0,ffffffffffffffff"));
+            assert_eq!("E01", gdbRequest(&mut client, "m10078,1000"));
+            assert_eq!("T05", gdbRequest(&mut client, "?"));
+
+            //stop on the first ecall, step over it and run to the exit
+            assert_eq!("OK", gdbRequest(&mut client, "Z0,1008c,4"));
+            assert_eq!("T05swbreak:;", gdbRequest(&mut client, "c"));
+            assert_eq!("8c00010000000000", gdbRequest(&mut client, "p20"));
+            assert_eq!("T05", gdbRequest(&mut client, "?"));
+            assert_eq!("T05", gdbRequest(&mut client, "s"));
+            assert_eq!("9000010000000000", gdbRequest(&mut client, "p20"));
+            assert_eq!("W2a", gdbRequest(&mut client, "c"));
+            assert_eq!("OK", gdbRequest(&mut client, "k"));
+        });
+        assert_eq!(Some(b"hi\n".to_vec()), os.output);
+    }
+
+    #[test]
+    fn testGdbServerCommand() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/rv64-echo");
+        let socket = std::env::temp_dir().join(format!("elfLoader-gdb-{}", std::process::id()));
+        let _ = std::fs::remove_file(&socket);
+        let args: Vec<String> = vec!["--socket".to_string(), socket.display().to_string(), path.to_string(), "x".to_string()];
+
+        std::thread::scope(|scope| {
+            let server = scope.spawn(|| gdbserverCommand(&args));
+            let mut client = loop {
+                match std::os::unix::net::UnixStream::connect(&socket) {
+                    Ok(c)  => break c,
+                    Err(_) => std::thread::sleep(std::time::Duration::from_millis(10)),
+                }
+            };
+            //stopped at e_entry 0x11168 before the first instruction
+            assert_eq!("6811010000000000", gdbRequest(&mut client, "p20"));
+            assert_eq!(format!("l{}", std::fs::canonicalize(path).unwrap().display()), gdbRequest(&mut client, "qXfer:exec-file:read::0,1000"));
+            assert_eq!("W00", gdbRequest(&mut client, "c"));
+            assert_eq!("OK", gdbRequest(&mut client, "k"));
+            assert_eq!(0, server.join().unwrap());
+        });
+        assert!(!socket.exists());
+        assert_eq!(2, gdbserverCommand(&["--port".to_string(), "x".to_string(), path.to_string()]));
+    }
+
+    #[test]
+    fn testSnapshotRestore() {
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        let mut space = addressspace::AddressSpace::fromLoader(&parser, 0).unwrap();
+        let original = space.read(0x23000, 0x268).unwrap();
+        space.snapshot();
+        assert!(space.dirtyPages().is_empty());
+
+        space.write(0x23004, &[0xaa; 8]).unwrap();
+        space.writeUValue(0x23ffc, 8, u64::MAX).unwrap();
+        assert_eq!(vec![0x23000, 0x24000], space.dirtyPages());
+        assert!(space.restore());
+        assert_eq!(original, space.read(0x23000, 0x268).unwrap());
+        assert_eq!(0, space.readUValue(0x23ffc, 8).unwrap());
+
+        //layout changes are undone as well, regions they did not touch keep their memory
+        let untouched = space.regions[0].data.as_ptr();
+        assert!(space.region(0x23000).unwrap().start != space.regions[0].start);
+        space.unmap(0x23000, 0x1000);
+        space.map(0x40000000, 0x1000, programheader::PF_R).unwrap();
+        assert_eq!(None, space.mapOrExtend(usize::MAX - 0x10, 0x1000, programheader::PF_R));
+        assert!(space.restore());
+        assert_eq!(original, space.read(0x23000, 0x268).unwrap());
+        assert!(space.region(0x40000000).is_none());
+        assert!(space.dirtyPages().is_empty());
+        assert_eq!(untouched, space.regions[0].data.as_ptr());
+
+        //huge ranges only cost as much as the memory mapped inside them
+        let layout: Vec<(usize, usize)> = space.regions.iter().map(|r| (r.start, r.data.len())).collect();
+        space.protect(0, usize::MAX, programheader::PF_R);
+        space.unmap(0, 1 << 47);
+        assert!(space.regions.is_empty());
+        assert!(space.restore());
+        assert_eq!(layout, space.regions.iter().map(|r| (r.start, r.data.len())).collect::<Vec<_>>());
+        assert_eq!(original, space.read(0x23000, 0x268).unwrap());
+    }
+
+    #[test]
+    fn testHardeningReport() {
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        let report = hardening::HardeningReport::new(&parser);
+        assert_eq!(hardening::Pie::Yes, report.pie);
+        assert_eq!(hardening::Relro::Full, report.relro);
+        assert!(report.nx);
+        assert!(report.canary);
+        assert!(!report.fortified.is_empty());
+        assert!(!report.textrel);
+        assert_eq!(None, report.runpath);
+
+        let policy = hardening::Policy::parse("pie = required\nrelro = full # strict\nrunpath = forbidden\n").unwrap();
+        assert!(policy.check(&report).is_empty());
+        let policy = hardening::Policy::parse("ibt = required").unwrap();
+        assert_eq!(vec!["ibt must be required".to_string()], policy.check(&report));
+        assert!(hardening::Policy::parse("aslr = yes").is_err());
+
+        //every command opens its input through openElf, which has to refuse headers the loader can not read
+        let mut bytes = parser.fileVec.clone();
+        assert_eq!(Ok(()), loader::Loader::validate(&bytes));
+        assert_eq!(Err("truncated ELF header, 18 of 64 bytes".to_string()), loader::Loader::validate(&bytes[..18]));
+        bytes[5] = 0;
+        assert_eq!(Err("unknown ELF byte order 0".to_string()), loader::Loader::validate(&bytes));
+        bytes[4] = 3;
+        assert_eq!(Err("unknown ELF class 3".to_string()), loader::Loader::validate(&bytes));
+        let path = std::env::temp_dir().join(format!("elfLoader-badheader-{}", std::process::id()));
+        std::fs::write(&path, &bytes[..18]).unwrap();
+        assert!(openElf(path.to_str().unwrap()).is_none());
+        std::fs::remove_file(&path).unwrap();
+
+        //dynamic and note areas ending past the address space are read up to the end of the file
+        for s in parser.sectionHeaders.iter_mut().filter(|s| s.sh_type == sectionheader::SHT_NOTE) {s.sh_size = usize::MAX;}
+        let dynamic = parser.programHeaders.iter().position(|p| p.getTYPE() == programheader::PT_DYNAMIC).unwrap();
+        parser.programHeaders[dynamic].setFILESZ(usize::MAX);
+        parser.loadDynamic();
+        parser.loadNotes();
+        assert!(parser.getDynamic(dynamic::DT_NEEDED).is_some());
+        assert!(!parser.notes.is_empty());
+    }
+
+    #[test]
+    fn testInfectionScan() {
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        assert!(infection::scan(&parser).is_empty());
+
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls-mod"));
+        parser.load();
+        let findings = infection::scan(&parser);
+        assert!(findings.iter().any(|f| f.technique == "header-mismatch" && f.offset == 0x36));
+        assert!(findings.iter().any(|f| f.technique == "out-of-file" && f.offset == 0x223b0));
+
+        //move the entry point into .rodata
+        let mut bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls")).unwrap();
+        bytes[24..32].copy_from_slice(&0x18010u64.to_le_bytes());
+        let mut parser = loader::Loader::fromBytes(bytes);
+        parser.load();
+        let findings = infection::scan(&parser);
+        assert_eq!(1, findings.len());
+        assert_eq!("entry-point", findings[0].technique);
+        assert_eq!(0x18010, findings[0].offset);
+
+        //hostile sizes and offsets are findings, not overflows or endless loops
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        parser.header.e_shoff = usize::MAX - 8;
+        let load = parser.programHeaders.iter().position(|p| p.getTYPE() == programheader::PT_LOAD && p.getFLAGS() & programheader::PF_X != 0).unwrap();
+        parser.programHeaders[load].setOFFSET(usize::MAX);
+        parser.programHeaders[load].setMEMSZ(usize::MAX);
+        let array = parser.sectionHeaders.iter().position(|s| s.sh_type == sectionheader::SHT_INIT_ARRAY).unwrap();
+        parser.sectionHeaders[array].sh_size = usize::MAX;
+        parser.sectionHeaders[array].sh_addr = usize::MAX - 4;
+        let findings = infection::scan(&parser);
+        assert!(findings.iter().any(|f| f.technique == "out-of-file" && f.description.starts_with("section header table")));
+        assert!(findings.iter().any(|f| f.technique == "out-of-file" && f.description == format!("PT_LOAD {} ends at file offset {:#x} past the end of the file", load, usize::MAX)));
+        assert!(findings.iter().any(|f| f.technique == "section-mapping" && f.description.starts_with(".init_array ends at")));
+
+        //relocatable objects have no segments, their sections are all at address 0
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny-ppc32.o"));
+        parser.load();
+        assert!(infection::scan(&parser).is_empty());
+    }
+
+    #[test]
+    fn testDiff() {
+        let mut old = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        old.load();
+        let mut new = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        new.load();
+        assert!(diff::ElfDiff::new(&old, &new).isEmpty());
+
+        let mut new = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls-mod"));
+        new.load();
+        let changes = diff::ElfDiff::new(&old, &new);
+        let fields: Vec<&str> = changes.changes.iter().map(|c| c.field.as_str()).collect();
+        assert_eq!(vec!["file size", "e_phentsize", "e_shnum"], fields);
+        assert_eq!(Some("56".to_string()), changes.changes[1].old);
+        assert_eq!(Some("88".to_string()), changes.changes[1].new);
+        assert!(changes.toJson().contains("\"field\": \"e_shnum\", \"kind\": \"changed\", \"old\": \"27\", \"new\": \"28\""));
+
+        //grow .text by patching its size, the content hash follows the size
+        let mut bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls")).unwrap();
+        let text = old.findSection(".text").unwrap();
+        let at = old.header.e_shoff + text * 64 + 32;
+        let size = old.sectionHeaders[text].sh_size + 16;
+        bytes[at..at + 8].copy_from_slice(&(size as u64).to_le_bytes());
+        let mut new = loader::Loader::fromBytes(bytes);
+        new.load();
+        let changes = diff::ElfDiff::new(&old, &new);
+        let fields: Vec<(&str, &str)> = changes.changes.iter().map(|c| (c.item.as_str(), c.field.as_str())).collect();
+        assert_eq!(vec![(".text", "size"), (".text", "content")], fields);
+    }
+
+    #[test]
+    fn testAbiReport() {
+        let mut old = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi1.so"));
+        old.load();
+        let mut new = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi2.so"));
+        new.load();
+
+        let labels: Vec<String> = abi::exports(&new).iter().map(|e| e.label()).collect();
+        assert_eq!(vec!["foo@@LIBABI_1", "table@@LIBABI_1", "baz@@LIBABI_2", "counter@@LIBABI_1"], labels);
+        let baz = new.dynamicSymbols.iter().position(|s| s.name == "baz").unwrap();
+        assert_eq!(Some(version::SymbolVersion{name: "LIBABI_2".to_string(), hidden: false, file: None}), new.symbolVersion(baz));
+
+        assert!(abi::AbiReport::new(&old, &old).changes.is_empty());
+        let report = abi::AbiReport::new(&old, &new);
+        let changes: Vec<String> = report.changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(vec![
+            "compatible: version LIBABI_2 added",
+            "BREAKING:   bar@@LIBABI_1 removed",
+            "BREAKING:   table@@LIBABI_1 changed size from 16 to 32",
+            "compatible: baz@@LIBABI_2 added",
+        ], changes);
+        assert!(!report.isCompatible());
+        assert!(report.toJson().contains("\"compatible\": false"));
+
+        //going back is not compatible either since baz disappears
+        assert!(!abi::AbiReport::new(&new, &old).isCompatible());
+
+        //adding a version script binds old consumers to the default versions, and turning a function
+        //into an IFUNC is invisible to its callers
+        let mut unversioned = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi2.so"));
+        unversioned.load();
+        unversioned.versionSymbols.clear();
+        unversioned.versionDefinitions.clear();
+        unversioned.dynamicSymbols.retain(|s| !s.name.starts_with("LIBABI_"));
+        let foo = new.dynamicSymbols.iter().position(|s| s.name == "foo").unwrap();
+        new.dynamicSymbols[foo].st_info = (new.dynamicSymbols[foo].st_info & 0xf0) | symbol::STT_GNU_IFUNC;
+        let report = abi::AbiReport::new(&unversioned, &new);
+        let changes: Vec<String> = report.changes.iter().map(|c| c.to_string()).collect();
+        assert!(report.isCompatible(), "{:?}", changes);
+        assert!(changes.contains(&"compatible: foo became foo@@LIBABI_1".to_string()));
+        assert!(changes.contains(&"compatible: foo changed type from func to ifunc".to_string()));
+        assert!(!changes.iter().any(|c| c.ends_with(" added") && !c.contains("version")));
+    }
+
+    #[test]
+    fn testClosureCheck() {
+        let sysroot = std::env::temp_dir().join(format!("elfLoader-closure-{}", std::process::id()));
+        let lib = sysroot.join("opt/abi/lib");
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi"), sysroot.join("useabi")).unwrap();
+        let resolver = deps::Resolver::new(&sysroot);
+
+        let objects = resolver.closure("/useabi").unwrap();
+        assert_eq!(vec![deps::Problem::MissingLibrary{object: "/useabi".to_string(), name: "libabi.so.1".to_string()}],
+            deps::checkClosure(&objects));
+
+        //found through DT_RUNPATH
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi1.so"), lib.join("libabi.so.1")).unwrap();
+        let objects = resolver.closure("/useabi").unwrap();
+        assert_eq!(2, objects.len());
+        assert_eq!("/opt/abi/lib/libabi.so.1", objects[1].path);
+        assert!(deps::checkClosure(&objects).is_empty());
+
+        //the newer library dropped bar
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi2.so"), lib.join("libabi.so.1")).unwrap();
+        let objects = resolver.closure("/useabi").unwrap();
+        let problems = deps::checkClosure(&objects);
+        assert_eq!(vec![deps::Problem::MissingSymbol{object: "/useabi".to_string(), symbol: "bar".to_string(),
+            version: Some("LIBABI_1".to_string()), expected: Some("libabi.so.1".to_string())}], problems);
+        assert_eq!("/useabi: undefined symbol bar@LIBABI_1 (expected in libabi.so.1)", problems[0].to_string());
+
+        //absolute symlinks are followed inside the sysroot, never to the same path on this machine
+        let host = std::env::temp_dir().join(format!("elfLoader-closure-host-{}", std::process::id()));
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi"), &host).unwrap();
+        std::os::unix::fs::symlink(&host, sysroot.join("escape")).unwrap();
+        assert!(resolver.open("/escape").is_none());
+        std::os::unix::fs::symlink("/opt/abi/../abi/lib/libabi.so.1", sysroot.join("libabi.so")).unwrap();
+        assert_eq!(Some(lib.join("libabi.so.1")), resolver.hostPath("/libabi.so"));
+        assert!(resolver.open("/libabi.so").is_some());
+        assert_eq!(Some(sysroot.join("useabi")), resolver.hostPath("/../../useabi"));
+        std::os::unix::fs::symlink("loop", sysroot.join("loop")).unwrap();
+        assert_eq!(None, resolver.hostPath("/loop"));
+        std::fs::remove_file(&host).unwrap();
+        std::fs::remove_dir_all(&sysroot).unwrap();
+    }
+
+    #[test]
+    fn testLddTree() {
+        let sysroot = std::env::temp_dir().join(format!("elfLoader-ldd-{}", std::process::id()));
+        std::fs::create_dir_all(sysroot.join("app/lib")).unwrap();
+        std::fs::create_dir_all(sysroot.join("opt/abi/lib")).unwrap();
+        std::fs::create_dir_all(sysroot.join("lib64")).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi"), sysroot.join("app/useabi")).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi1.so"), sysroot.join("app/lib/libabi.so.1")).unwrap();
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/libabi2.so"), sysroot.join("opt/abi/lib/libabi.so.1")).unwrap();
+        //an executable is never a usable library so the search carries on past it
+        std::fs::copy(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi"), sysroot.join("lib64/libabi.so.1")).unwrap();
+
+        let mut resolver = deps::Resolver::new(&sysroot);
+        let mut ls = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        ls.load();
+        assert_eq!(Some("/app/lib64/x86_64".to_string()), resolver.expand("${ORIGIN}/$LIB/$PLATFORM", "/app", &ls));
+        assert_eq!(None, resolver.expand("$ORIGIN/$UNKNOWN", "/app", &ls));
+
+        //DT_RUNPATH
+        let objects = resolver.closure("/app/useabi").unwrap();
+        assert_eq!("/opt/abi/lib/libabi.so.1", objects[1].path);
+
+        //LD_LIBRARY_PATH comes first and $ORIGIN is the directory of the program
+        resolver.libraryPath = vec!["/nonexistent:$ORIGIN/lib".to_string()];
+        let objects = resolver.closure("/app/useabi").unwrap();
+        assert_eq!("/app/lib/libabi.so.1", objects[1].path);
+        assert_eq!("/app/useabi\n    libabi.so.1 => /app/lib/libabi.so.1\n", deps::formatDependencies(&objects, deps::Format::Tree));
+        assert_eq!("\tlibabi.so.1 => /app/lib/libabi.so.1\n", deps::formatDependencies(&objects, deps::Format::Flat));
+        assert!(deps::formatDependencies(&objects, deps::Format::Dot).contains("n0 -> n1;"));
+
+        //the only copy left in the default directories is not a shared object
+        std::fs::remove_file(sysroot.join("app/lib/libabi.so.1")).unwrap();
+        std::fs::remove_file(sysroot.join("opt/abi/lib/libabi.so.1")).unwrap();
+        let objects = resolver.closure("/app/useabi").unwrap();
+        assert_eq!("\tlibabi.so.1 => not found\n", deps::formatDependencies(&objects, deps::Format::Flat));
+        std::fs::remove_dir_all(&sysroot).unwrap();
+    }
+
+    #[test]
+    fn testLdCache() {
+        let word = |v: u32| v.to_le_bytes().to_vec();
+        let strings = b"libfoo.so.1\0/usr/lib/libfoo.so.1\0/usr/lib/glibc-hwcaps/x86-64-v3/libfoo.so.1\0x86-64-v3\0".to_vec();
+
+        //old format only, strings follow the entries
+        let mut old = b"ld.so-1.7.0\0".to_vec();
+        old.extend(word(1));
+        old.extend(word(0x0303));
+        old.extend(word(0));
+        old.extend(word(12));
+        old.extend(&strings);
+        let cache = ldcache::LdCache::parse(&old).unwrap();
+        assert_eq!(ldcache::CacheFormat::Old, cache.format);
+        assert_eq!("libfoo.so.1 (libc6,x86-64) => /usr/lib/libfoo.so.1", cache.entries[0].to_string());
+
+        //the same old table followed by a new one at offset 32 whose strings start at 128
+        let mut compat = old[..28].to_vec();
+        compat.extend(word(0));
+        compat.extend(b"glibc-ld.so.cache1.1");
+        compat.extend(word(2));
+        compat.extend(word(strings.len() as u32));
+        compat.extend(&[2, 0, 0, 0]);
+        let extension = 128 + strings.len() as u32 + 1;
+        compat.extend(word(extension));
+        compat.extend(vec![0; 12]);
+        //an entry in a glibc-hwcaps subdirectory listed before the generic one
+        for (value, hwcap) in [(33u32, ldcache::DL_CACHE_HWCAP_EXTENSION), (12, 0)].iter() {
+            compat.extend(word(0x0303));
+            compat.extend(word(96));
+            compat.extend(word(96 + value));
+            compat.extend(word(0));
+            compat.extend(&hwcap.to_le_bytes());
+        }
+        compat.extend(&strings);
+        compat.push(0);
+        compat.extend(word(ldcache::CACHE_EXTENSION_MAGIC));
+        compat.extend(word(2));
+        for (tag, offset, size) in [(0, extension + 40, 8), (1, extension + 48, 4)].iter() {
+            compat.extend(word(*tag));
+            compat.extend(word(0));
+            compat.extend(word(*offset));
+            compat.extend(word(*size));
+        }
+        compat.extend(b"ldconfig");
+        compat.extend(word(96 + 77));
+
+        let cache = ldcache::LdCache::parse(&compat).unwrap();
+        assert_eq!(ldcache::CacheFormat::Compat, cache.format);
+        assert_eq!(Some("ldconfig".to_string()), cache.generator);
+        assert_eq!(vec!["x86-64-v3".to_string()], cache.hwcapSubdirectories);
+        assert_eq!("libfoo.so.1 (libc6,x86-64, hwcap: \"x86-64-v3\") => /usr/lib/glibc-hwcaps/x86-64-v3/libfoo.so.1", cache.entries[0].to_string());
+        assert_eq!(vec!["/usr/lib/libfoo.so.1", "/usr/lib/glibc-hwcaps/x86-64-v3/libfoo.so.1"], cache.lookup("libfoo.so.1", ldcache::FLAG_X8664_LIB64));
+        assert!(cache.lookup("libfoo.so.1", ldcache::FLAG_AARCH64_LIB64).is_empty());
+
+        //a hwcaps section claiming more entries than the file holds stops at its end
+        let at = extension as usize + 8 + 16 + 12;
+        compat[at..at + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+        let cache = ldcache::LdCache::parse(&compat).unwrap();
+        assert_eq!(vec!["x86-64-v3".to_string()], cache.hwcapSubdirectories);
+    }
+
+    #[test]
+    fn testSymbolize() {
+        //compute() in src/binaries/inline-dwarf* calls sumSquares() which calls square(), both always inlined
+        for file in ["inline-dwarf4", "inline-dwarf5"].iter() {
+            let mut parser = loader::Loader::new(&format!("{}/src/binaries/{}", env!("CARGO_MANIFEST_DIR"), file));
+            parser.load();
+            let symbolizer = symbolize::Symbolizer::new(parser);
+
+            let location = symbolizer.symbolize(0x1000000 + 0x4000f3, 0x1000000).unwrap();
+            assert_eq!(0x4000f3, location.address);
+            assert_eq!(Some(".text".to_string()), location.section);
+            assert_eq!(Some(("compute".to_string(), 3)), location.symbol);
+            let frames: Vec<(Option<&str>, u32)> = location.frames.iter().map(|f| (f.function.as_deref(), f.line)).collect();
+            assert_eq!(vec![(Some("square"), 5), (Some("sumSquares"), 10), (Some("compute"), 15)], frames);
+            assert_eq!(Some("/src/inline.c".to_string()), location.frames[0].file);
+
+            let location = symbolizer.symbolize(0x400110, 0).unwrap();
+            assert_eq!(Some(("_start".to_string(), 0)), location.symbol);
+            assert_eq!(vec![dwarf::Frame{function: Some("_start".to_string()), file: Some("/src/inline.c".to_string()), line: 20, column: 12}],
+                location.frames);
+
+            let location = symbolizer.symbolize(0x400138, 0).unwrap();
+            assert_eq!((Some(".bss".to_string()), Some(("sink".to_string(), 0))), (location.section, location.symbol));
+            assert!(location.frames.is_empty());
+            assert!(symbolizer.symbolize(0x500000, 0).is_none());
+        }
+    }
+
+    #[test]
+    fn testDwarfMalformedLineProgram() {
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/inline-dwarf4")).unwrap();
+        let parse = |bytes: Vec<u8>| {
+            let mut parser = loader::Loader::fromBytes(bytes);
+            parser.load();
+            dwarf::DebugInfo::new(&parser).unwrap()
+        };
+        assert!(!parse(bytes.clone()).rows.is_empty());
+
+        //.debug_line is at 0x42d with a 32 byte DWARF 4 header, so the program starts at 0x42d + 10 + 32
+        let program = 0x42d + 42;
+        let hostile: [&[u8]; 3] = [
+            //DW_LNE_set_address with an extended length of 0
+            &[0x00, 0x00, 0x02],
+            //an address of u64::MAX advanced by DW_LNS_advance_pc
+            &[0x00, 0x09, 0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x02, 0x01],
+            //the same through a special opcode
+            &[0x00, 0x09, 0x02, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01, 0x20],
+        ];
+        for opcodes in hostile.iter() {
+            let mut bytes = bytes.clone();
+            bytes[program..program + opcodes.len()].copy_from_slice(opcodes);
+            assert!(parse(bytes).rows.is_empty());
+        }
+
+        //a 64 bit unit length that runs past the end of the address space
+        let mut bytes = bytes.clone();
+        bytes[0x42d..0x42d + 12].copy_from_slice(&[0xff, 0xff, 0xff, 0xff, 0xf0, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]);
+        assert!(parse(bytes).rows.is_empty());
+
+        //the compile unit's DW_AT_name form at 0x314 made DW_FORM_indirect, its 4 byte value at 0x190
+        //then names the real form, which may not be DW_FORM_indirect again
+        let mut bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/inline-dwarf4")).unwrap();
+        bytes[0x314] = 0x16;
+        bytes[0x190..0x194].copy_from_slice(&[0x08, b'a', b'b', 0]);
+        assert_eq!(vec!["ab".to_string()], parse(bytes.clone()).units.iter().map(|u| u.name.clone()).collect::<Vec<_>>());
+        bytes[0x190..0x194].copy_from_slice(&[0x16, 0x08, b'a', 0]);
+        assert!(parse(bytes).units.is_empty());
+    }
+
+    #[test]
+    fn testProcessSymbolize() {
+        let maps = process::parseMaps("55d0c0a00000-55d0c0a04000 r--p 00000000 08:01 1234    /usr/bin/my program\n\
+                                       7ffd1e5f0000-7ffd1e611000 rw-p 00000000 00:00 0       [stack]\n");
+        assert_eq!(Some("/usr/bin/my program".to_string()), maps[0].path);
+        assert!(maps[0].isFile() && !maps[1].isFile());
+        //a segment whose file range runs past the end of the address space still matches
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls"));
+        parser.load();
+        let load = parser.programHeaders.iter().position(|p| p.getTYPE() == programheader::PT_LOAD).unwrap();
+        parser.programHeaders[load].setOFFSET(usize::MAX - 0xfff);
+        let mapping = process::Mapping{offset: u64::MAX - 0x10, ..maps[0].clone()};
+        assert!(process::loadBias(&parser, &mapping).is_some());
+
+        let mut child = std::process::Command::new("sleep").arg("10").spawn().unwrap();
+        //wait until the runtime linker has mapped libc
+        let mut symbolizer = process::ProcessSymbolizer::new(child.id()).unwrap();
+        for _ in 0..100 {
+            if symbolizer.modules.iter().any(|m| m.path.contains("libc.so")) {break;}
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            symbolizer = process::ProcessSymbolizer::new(child.id()).unwrap();
+        }
+
+        //AT_ENTRY in the auxiliary vector is the run time address of e_entry
+        let auxv = std::fs::read(format!("/proc/{}/auxv", child.id())).unwrap();
+        let entry = auxv.chunks(16).find(|c| u64::from_le_bytes([c[0], c[1], c[2], c[3], c[4], c[5], c[6], c[7]]) == stack::AT_ENTRY as u64)
+            .map(|c| u64::from_le_bytes([c[8], c[9], c[10], c[11], c[12], c[13], c[14], c[15]])).unwrap();
+        let location = symbolizer.symbolize(entry).unwrap();
+        assert_eq!(symbolizer.moduleAt(entry).unwrap().symbolizer.loader.header.e_entry as u64, location.location.address);
+
+        let libc = symbolizer.modules.iter().find(|m| m.path.contains("libc.so")).unwrap();
+        let nanosleep = libc.symbolizer.loader.dynamicSymbols.iter().find(|s| s.name == "clock_nanosleep" && s.st_size > 8).unwrap();
+        let address = libc.bias + nanosleep.st_value as u64 + 8;
+        let location = symbolizer.symbolize(address).unwrap();
+        assert_eq!(libc.path, location.module);
+        assert_eq!(Some(8), location.location.symbol.as_ref().map(|(_, o)| *o));
+        assert_eq!(Some(".text".to_string()), location.location.section);
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    static SCAN_MARKER: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0x5eed1234);
+    static SCAN_DOUBLE: f64 = 2718.281828459045;
+
+    #[test]
+    fn testMemoryScan() {
+        let pattern = memscan::Pattern::parse("48 ?? 05").unwrap();
+        assert_eq!(vec![1, 4], pattern.find(&[0x00, 0x48, 0x8b, 0x05, 0x48, 0x00, 0x05, 0x48]));
+        assert_eq!("48 ?? 05", pattern.to_string());
+        assert!(memscan::Pattern::parse("4g").is_err());
+
+        //scan this test process, whose executable holds the statics above
+        let scanner = memscan::ProcessScanner::new(std::process::id()).unwrap();
+        let exe = std::fs::read_link("/proc/self/exe").unwrap().to_string_lossy().into_owned();
+        let data = scanner.regions(Some(&exe), &memscan::Target::Section(".data".to_string()));
+        assert!(!data.is_empty());
+        let marker = &SCAN_MARKER as *const _ as u64;
+        let hits = scanner.scan(&data, &memscan::Value::I32(0x5eed1234).pattern(true));
+        let hit = hits.iter().find(|h| h.address == marker).unwrap();
+        assert_eq!(Some(".data".to_string()), hit.section);
+        assert_eq!(exe, hit.module);
+
+        //narrow the hits after the value changes
+        SCAN_MARKER.store(0x5eed4321, std::sync::atomic::Ordering::SeqCst);
+        let changed = scanner.rescan(&hits, &memscan::Condition::Changed);
+        assert_eq!(vec![marker], changed.iter().map(|h| h.address).collect::<Vec<_>>());
+        let narrowed = scanner.rescan(&hits, &memscan::Condition::Matches(memscan::Value::I32(0x5eed4321).pattern(true)));
+        assert_eq!(changed, narrowed);
+        assert!(scanner.rescan(&narrowed, &memscan::Condition::Unchanged).len() == 1);
+
+        //read only values live in a different segment than .data
+        let all = scanner.regions(Some(&exe), &memscan::Target::Module);
+        let double = &SCAN_DOUBLE as *const f64 as u64;
+        assert!(scanner.scan(&all, &memscan::Value::F64(SCAN_DOUBLE).pattern(true)).iter().any(|h| h.address == double));
+        assert!(scanner.scan(&data, &memscan::Value::F64(SCAN_DOUBLE).pattern(true)).iter().all(|h| h.address != double));
+        let text = "elfLoader memory scan marker";
+        let strings = scanner.scan(&all, &memscan::Value::String(text.to_string()).pattern(true));
+        assert!(strings.iter().any(|h| h.address == text.as_ptr() as u64 && h.section.as_deref() == Some(".rodata")));
+
+        //sizes running past the end of the address space are clipped to the mappings
+        let mut scanner = scanner;
+        let module = scanner.symbolizer.modules.iter().position(|m| m.path == exe).unwrap();
+        let loader = &mut scanner.symbolizer.modules[module].symbolizer.loader;
+        let index = loader.findSection(".data").unwrap();
+        loader.sectionHeaders[index].sh_size = usize::MAX;
+        for p in loader.programHeaders.iter_mut() {p.setMEMSZ(usize::MAX);}
+        assert!(!scanner.regions(Some(&exe), &memscan::Target::Section(".data".to_string())).is_empty());
+        assert!(!scanner.regions(Some(&exe), &memscan::Target::Module).is_empty());
+        assert!(scanner.scan(&data, &memscan::Value::I32(0x5eed4321).pattern(true)).iter().any(|h| h.address == marker));
+    }
+
+    #[test]
+    fn testElfWriter() {
+        //unmodified files come back byte for byte, in both classes and byte orders
+        for name in ["ls", "libabi1.so", "inline-dwarf5", "tiny32", "tiny-ppc32.o", "tiny-ppc64.o"].iter() {
+            let bytes = std::fs::read(format!("{}/src/binaries/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+            let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+            assert!(file.write().unwrap() == bytes, "{} did not round trip", name);
+        }
+        assert!(writer::ElfFile::parse(b"\x7fELF\x03".to_vec()).is_none());
+
+        //a new section in a big endian object whose .strtab also holds the section names
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny-ppc32.o")).unwrap();
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        file.addSection(writer::Section::new(".note.extra", sectionheader::SHT_NOTE, b"extra data".to_vec()));
+        let mut parser = loader::Loader::fromBytes(file.write().unwrap());
+        parser.load();
+        assert_eq!(8, parser.header.e_shnum);
+        assert_eq!(Some(&b"extra data"[..]), parser.findSection(".note.extra").and_then(|i| parser.sectionData(i)));
+        let original = writer::ElfFile::parse(bytes).unwrap();
+        for (i, s) in original.sections.iter().enumerate() {
+            assert_eq!(Some(s.name.clone()), parser.sectionName(i));
+            //the shared name table only grows
+            assert!(parser.sectionData(i).unwrap().starts_with(&s.data));
+        }
+        assert!(parser.sectionData(1).unwrap().ends_with(b"\0.note.extra\0"));
+        assert!(parser.symbols.iter().any(|s| s.name == "counter"));
+
+        //a grown non allocated section moves, allocated ones cannot
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny32")).unwrap();
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        let comment = file.findSection(".comment").unwrap();
+        file.sections[comment].data.extend_from_slice(b"patched\0");
+        let out = file.write().unwrap();
+        assert!(file.sections[comment].header.sh_offset >= bytes.len());
+        assert_eq!(&bytes[..0x200], &out[..0x200]);
+        let mut parser = loader::Loader::fromBytes(out);
+        parser.load();
+        assert!(parser.sectionData(comment).unwrap().ends_with(b"12.2.0\0patched\0"));
+        assert_eq!(symbolNames(&bytes), parser.symbols.iter().map(|s| s.name.clone()).collect::<Vec<_>>());
+        let text = file.findSection(".text").unwrap();
+        file.sections[text].data.extend_from_slice(&[0x90; 0x200]);
+        assert!(file.write().is_err());
+
+        //removing a section renumbers links and the name table index
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        file.removeSection(file.findSection(".comment").unwrap());
+        let mut parser = loader::Loader::fromBytes(file.write().unwrap());
+        parser.load();
+        assert_eq!(9, parser.sectionHeaders.len());
+        assert!(parser.findSection(".comment").is_none());
+        assert_eq!(symbolNames(&bytes), parser.symbols.iter().map(|s| s.name.clone()).collect::<Vec<_>>());
+        //an escaped name table index stays escaped and the one in section 0 is renumbered
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        let names = file.header.e_shstrndx as u32;
+        file.header.e_shstrndx = symbol::SHN_XINDEX;
+        file.sections[0].header.sh_link = names;
+        file.removeSection(file.findSection(".comment").unwrap());
+        assert_eq!((symbol::SHN_XINDEX, names - 1), (file.header.e_shstrndx, file.sections[0].header.sh_link));
+
+        //headers placed at the end of the address space are an error
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        file.header.e_shoff = usize::MAX - 8;
+        assert!(file.write().is_err());
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        file.header.e_phoff = usize::MAX - 8;
+        assert!(file.write().is_err());
+
+        //bogus alignments and offsets of non allocated sections do not pad the file by gigabytes
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        let comment = file.findSection(".comment").unwrap();
+        file.sections[comment].data.extend_from_slice(b"patched\0");
+        file.sections[comment].header.sh_addralign = 0xffffff01;
+        let strtab = file.findSection(".strtab").unwrap();
+        file.sections[strtab].header.sh_offset = 0xffff_0000;
+        let out = file.write().unwrap();
+        assert!(out.len() < bytes.len() + writer::MAX_MOVED_ALIGN * 2, "{}", out.len());
+        let mut parser = loader::Loader::fromBytes(out);
+        parser.load();
+        assert_eq!(symbolNames(&bytes), parser.symbols.iter().map(|s| s.name.clone()).collect::<Vec<_>>());
+    }
+
+    fn symbolNames(bytes: &[u8]) -> Vec<String> {
+        let mut parser = loader::Loader::fromBytes(bytes.to_vec());
+        parser.load();
+        parser.symbols.iter().map(|s| s.name.clone()).collect()
+    }
+
+    #[test]
+    fn testEditDynamic() {
+        let dir = std::env::temp_dir().join(format!("elfLoader-edit-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("lib")).unwrap();
+        let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+        let libc = process::parseMaps(&maps).into_iter().filter_map(|m| m.path).find(|p| p.contains("/libc.so")).unwrap();
+        std::fs::copy(&libc, dir.join("lib/libc-renamed.so.6")).unwrap();
+        let interpreter = dir.join("a-much-longer-interpreter-path-than-the-original.so");
+        std::os::unix::fs::symlink("/lib64/ld-linux-x86-64.so.2", &interpreter).unwrap();
+
+        //every edit needs more room than the original sections have, so they move to a new segment
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/ls")).unwrap();
+        let mut editor = edit::Editor::new(writer::ElfFile::parse(bytes).unwrap()).unwrap();
+        editor.setInterpreter(interpreter.to_str().unwrap()).unwrap();
+        editor.setRunpath("$ORIGIN/lib", false);
+        editor.addNeeded("libm.so.6");
+        editor.addNeeded("libdl.so.2");
+        assert!(editor.replaceNeeded("libc.so.6", "libc-renamed.so.6"));
+        assert!(!editor.removeNeeded("libnothere.so"));
+        let out = editor.write().unwrap();
+        std::fs::write(dir.join("ls"), &out).unwrap();
+        std::fs::set_permissions(dir.join("ls"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+
+        let mut parser = loader::Loader::fromBytes(out);
+        parser.load();
+        assert_eq!(vec!["libcap.so.2", "libc-renamed.so.6", "libm.so.6", "libdl.so.2"], parser.dynamicStrings(dynamic::DT_NEEDED));
+        assert_eq!(vec!["$ORIGIN/lib"], parser.dynamicStrings(dynamic::DT_RUNPATH));
+        assert_eq!(12, parser.programHeaders.len());
+        assert!(parser.versionNeeds.iter().all(|n| n.file == "libc-renamed.so.6"));
+
+        //the kernel and glibc accept the result and load the renamed library through the new run path
+        let run = std::process::Command::new(dir.join("ls")).arg("--version").output().unwrap();
+        assert!(run.status.success());
+        assert!(String::from_utf8_lossy(&run.stdout).starts_with("ls (GNU coreutils)"));
+        let trace = std::process::Command::new(dir.join("ls")).env("LD_TRACE_LOADED_OBJECTS", "1").output().unwrap();
+        let trace = String::from_utf8_lossy(&trace.stdout).to_string();
+        assert!(trace.contains(&format!("libc-renamed.so.6 => {}", dir.join("lib/libc-renamed.so.6").display())), "{}", trace);
+        assert!(trace.contains(interpreter.to_str().unwrap()));
+
+        //removing a library drops its version requirements, a soname that is already a string fits in place
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi")).unwrap();
+        let mut editor = edit::Editor::new(writer::ElfFile::parse(bytes).unwrap()).unwrap();
+        assert!(editor.removeNeeded("libabi.so.1"));
+        editor.setSoname("libabi.so.1");
+        let mut parser = loader::Loader::fromBytes(editor.write().unwrap());
+        parser.load();
+        assert!(parser.dynamicStrings(dynamic::DT_NEEDED).is_empty());
+        assert!(parser.versionNeeds.is_empty() && parser.getDynamic(dynamic::DT_VERNEED).is_none());
+        assert_eq!(vec!["libabi.so.1"], parser.dynamicStrings(dynamic::DT_SONAME));
+        let original = writer::ElfFile::parse(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi")).unwrap()).unwrap();
+        assert_eq!(original.segments.len(), parser.programHeaders.len());
+
+        //a PT_LOAD claiming a huge size would push the new segment past any sane file size
+        let mut file = writer::ElfFile::parse(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi")).unwrap()).unwrap();
+        let load = file.segments.iter().rposition(|p| p.getTYPE() == programheader::PT_LOAD).unwrap();
+        file.segments[load].setMEMSZ(1 << 40);
+        file.segments[load].setALIGN(0xffffffff);
+        let mut editor = edit::Editor::new(file).unwrap();
+        editor.addNeeded("a-library-name-too-long-for-the-string-table.so");
+        assert!(editor.write().unwrap_err().contains("too far past the end of the file"));
+
+        //so would a PT_INTERP claiming a huge size, the interpreter is only padded to the end of the file
+        let mut file = writer::ElfFile::parse(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi")).unwrap()).unwrap();
+        let interp = file.segments.iter().position(|p| p.getTYPE() == programheader::PT_INTERP).unwrap();
+        file.segments[interp].setFILESZ(usize::MAX);
+        let mut editor = edit::Editor::new(file).unwrap();
+        assert_eq!(None, editor.interpreter);
+        file = writer::ElfFile::parse(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/useabi")).unwrap()).unwrap();
+        file.segments[interp].setFILESZ(1 << 40);
+        editor = edit::Editor::new(file).unwrap();
+        editor.interpreter = Some("/lib/ld.so".to_string());
+        assert!(editor.write().unwrap_err().contains(".interp does not fit"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn testPatcher() {
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny32")).unwrap();
+        let mut parser = loader::Loader::fromBytes(bytes.clone());
+        parser.load();
+        let mut patcher = patch::Patcher::new(parser);
+
+        let patch = patcher.writeSymbol("counter", 0, &[9, 0, 0, 0]).unwrap().clone();
+        assert_eq!((0x08048200, 0x200), (patch.address, patch.offset));
+        assert_eq!((vec![5, 0, 0, 0], Some(("counter".to_string(), 0))), (patch.old, patch.symbol));
+        patcher.write(0x080480d9, &[0x90]).unwrap();
+        assert_eq!(0x90, patcher.bytes()[0xd9]);
+
+        //only bytes backed by the file can be written
+        assert!(patcher.write(0x08048204, &[1]).unwrap_err().contains("outside"));
+        assert!(patcher.write(0x08048202, &[1, 2, 3]).is_err());
+        let mut parser = loader::Loader::new(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/inline-dwarf4"));
+        parser.load();
+        assert!(patch::Patcher::new(parser).writeSymbol("sink", 0, &[1]).unwrap_err().contains(".bss"));
+
+        //undo restores the file, an exported set brings the patches back
+        let patched = patcher.bytes().to_vec();
+        let set = patcher.export();
+        assert_eq!("# elfLoader patch set\nbuild-id c2d4a95c82cfb41ddd2961eba0169d6d78c5d46e\n\
+                    0x8048200 counter+0x0 05000000 09000000\n0x80480d9 _start+0x1 fe 90\n", set);
+        patcher.undoAll();
+        assert!(patcher.bytes() == &bytes[..]);
+        assert_eq!(Ok(2), patcher.import(&set));
+        assert!(patcher.bytes() == &patched[..]);
+
+        //a set that does not match is not applied at all
+        patcher.undoAll();
+        assert!(patcher.import("0x8048200 - 05000000 07000000\n0x80480d9 - 00 90\n").unwrap_err().starts_with("line 2: expected 00"));
+        assert!(patcher.bytes() == &bytes[..] && patcher.patches.is_empty());
+
+        //in another build only patches with symbols find their place
+        assert_eq!(Ok(1), patcher.import("build-id 00\n0x1000 counter+0x1 00 01\n"));
+        assert_eq!(1, patcher.bytes()[0x201]);
+        assert!(patcher.import("build-id 00\n0x8048200 - 09000000 05000000\n").is_err());
+
+        //offsets, sizes and segments reaching the end of the address space are errors, not overflows
+        assert_eq!("address range overflows", patcher.writeSymbol("counter", u64::MAX, &[1]).unwrap_err());
+        assert_eq!(Err("line 1: address range overflows".to_string()), patcher.import("0x0 counter+0xffffffffffffffff 00 01\n"));
+        let mut parser = loader::Loader::fromBytes(bytes.clone());
+        parser.load();
+        for s in parser.symbols.iter_mut().filter(|s| s.name == "counter") {s.st_size = usize::MAX;}
+        let load = parser.programHeaders.iter().position(|p| p.getTYPE() == programheader::PT_LOAD).unwrap();
+        let mut patcher = patch::Patcher::new(parser);
+        assert_eq!(Some(("counter".to_string(), 2)), patcher.write(0x08048202, &[1]).unwrap().symbol);
+        patcher.loader.programHeaders[load].setVADDR(usize::MAX - 0x10);
+        patcher.loader.programHeaders[load].setOFFSET(usize::MAX);
+        patcher.loader.programHeaders[load].setMEMSZ(usize::MAX);
+        assert_eq!("address range overflows", patcher.write(u64::MAX - 8, &[1]).unwrap_err());
+    }
+
+    #[test]
+    fn testStripDebug() {
+        assert_eq!(0xcbf43926, strip::crc32(b"123456789"));
+        assert_eq!(strip::crc32(b"123456789"), strip::crc32Update(strip::crc32(b"1234"), b"56789"));
+
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/hello-debug");
+        let bytes = std::fs::read(path).unwrap();
+        let mut debug = writer::ElfFile::parse(bytes.clone()).unwrap();
+        strip::onlyKeepDebug(&mut debug);
+        let debugBytes = debug.write().unwrap();
+
+        //segments of the debug file only cover what is left of their contents
+        let mut parser = loader::Loader::fromBytes(debugBytes.clone());
+        parser.load();
+        assert!(parser.programHeaders.iter().all(|p| p.getOFFSET() + p.getFILESZ() <= debugBytes.len()));
+        for kind in [programheader::PT_DYNAMIC, programheader::PT_INTERP].iter() {
+            assert_eq!(Some(0), parser.programHeaders.iter().find(|p| p.getTYPE() == *kind).map(|p| p.getFILESZ()));
+        }
+        let phdr = parser.programHeaders.iter().find(|p| p.getTYPE() == programheader::PT_PHDR).unwrap();
+        assert_eq!(parser.programHeaders.len() * 56, phdr.getFILESZ());
+        let note = parser.programHeaders.iter().find(|p| p.getTYPE() == programheader::PT_NOTE).unwrap();
+        assert!(note.getFILESZ() > 0 && debugBytes[note.getOFFSET()..note.getOFFSET() + note.getFILESZ()] == bytes[note.getOFFSET()..note.getOFFSET() + note.getFILESZ()]);
+
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        let removed = strip::strip(&mut file);
+        assert!(removed.contains(&".symtab".to_string()) && removed.contains(&".strtab".to_string()) && removed.contains(&".debug_info".to_string()));
+        assert!(!removed.contains(&".dynstr".to_string()) && !removed.contains(&".shstrtab".to_string()));
+        strip::addDebuglink(&mut file, "hello-debug.debug", &debugBytes);
+        let stripped = file.write().unwrap();
+        assert!(stripped.len() < bytes.len() - 2000, "{}", stripped.len());
+
+        //the stripped file keeps every loaded byte, links to the debug file and still runs
+        let mut parser = loader::Loader::fromBytes(stripped.clone());
+        parser.load();
+        assert!(parser.symbols.is_empty() && parser.sectionHeaders.iter().enumerate().all(|(i, _)| !strip::isDebugSection(&parser.sectionName(i).unwrap())));
+        for p in parser.programHeaders.iter().filter(|p| p.getTYPE() == programheader::PT_LOAD) {
+            //past the ELF header, whose section header fields change
+            let range = p.getOFFSET().max(64)..p.getOFFSET() + p.getFILESZ();
+            assert!(stripped[range.clone()] == bytes[range]);
+        }
+        let link = parser.findSection(".gnu_debuglink").and_then(|i| strip::debuglink(parser.sectionData(i).unwrap(), false));
+        assert_eq!(Some(("hello-debug.debug".to_string(), strip::crc32(&debugBytes))), link);
+        let dynsym = parser.dynamicSymbols.iter().map(|s| (s.name.clone(), s.st_shndx)).collect::<Vec<_>>();
+        let mut original = loader::Loader::fromBytes(bytes.clone());
+        original.load();
+        assert_eq!(original.dynamicSymbols.iter().map(|s| (s.name.clone(), s.st_shndx)).collect::<Vec<_>>(), dynsym);
+
+        let dir = std::env::temp_dir().join(format!("elfLoader-strip-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("hello"), &stripped).unwrap();
+        std::fs::set_permissions(dir.join("hello"), std::os::unix::fs::PermissionsExt::from_mode(0o755)).unwrap();
+        let run = std::process::Command::new(dir.join("hello")).output().unwrap();
+        assert_eq!("hello 42\n", String::from_utf8_lossy(&run.stdout));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        //the debug file has the symbols and DWARF but no loaded contents
+        let mut parser = loader::Loader::fromBytes(debugBytes.clone());
+        parser.load();
+        assert_eq!(original.symbols.len(), parser.symbols.len());
+        assert_eq!(original.findSection(".debug_info").and_then(|i| original.sectionData(i)), parser.findSection(".debug_info").and_then(|i| parser.sectionData(i)));
+        assert_eq!(Some(sectionheader::SHT_NOBITS), parser.findSection(".text").map(|i| parser.sectionHeaders[i].sh_type));
+        assert!(parser.notes.iter().any(|n| n.buildId().is_some()));
+        assert!(debugBytes.len() < bytes.len());
+        let main = original.symbols.iter().find(|s| s.name == "main").unwrap().st_value as u64;
+        let frames = symbolize::Symbolizer::new(parser).symbolize(main, 0).unwrap().frames;
+        assert_eq!(Some("main".to_string()), frames.last().and_then(|f| f.function.clone()));
+
+        //objects keep .symtab for their relocations
+        let mut object = writer::ElfFile::parse(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny-ppc32.o")).unwrap()).unwrap();
+        assert!(strip::strip(&mut object).is_empty());
+    }
+
+    #[test]
+    fn testDebugFileLookup() {
+        let bytes = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/hello-debug")).unwrap();
+        let mut debug = writer::ElfFile::parse(bytes.clone()).unwrap();
+        strip::onlyKeepDebug(&mut debug);
+        let debugBytes = debug.write().unwrap();
+        let mut file = writer::ElfFile::parse(bytes.clone()).unwrap();
+        strip::strip(&mut file);
+        strip::addDebuglink(&mut file, "hello.debug", &debugBytes);
+        let mut stripped = loader::Loader::fromBytes(file.write().unwrap());
+        stripped.load();
+        assert!(!debugfile::hasDebugInfo(&stripped));
+        let id = debugfile::buildId(&stripped).unwrap();
+
+        //a sysroot with the binary at /usr/bin/hello
+        let root = std::env::temp_dir().join(format!("elfLoader-debugfile-{}", std::process::id()));
+        let place = |path: &str, data: &[u8]| {
+            let host = root.join(path);
+            std::fs::create_dir_all(host.parent().unwrap()).unwrap();
+            std::fs::write(host, data).unwrap();
+        };
+        place("usr/bin/hello", &stripped.fileVec);
+        let locator = debugfile::DebugLocator::new(&root);
+        let candidates = locator.candidates(&stripped, "/usr/bin/hello");
+        assert_eq!(vec![
+            (format!("/usr/lib/debug/.build-id/{}/{}.debug", &id[..2], &id[2..]), debugfile::Method::BuildId),
+            ("/usr/bin/hello.debug".to_string(), debugfile::Method::Debuglink),
+            ("/usr/bin/.debug/hello.debug".to_string(), debugfile::Method::Debuglink),
+            ("/usr/lib/debug/usr/bin/hello.debug".to_string(), debugfile::Method::Debuglink),
+        ], candidates);
+        assert!(locator.locate(&stripped, "/usr/bin/hello").is_none());
+
+        //an absolute debuglink symlink names a file inside the sysroot, not the matching one on this machine
+        let host = std::env::temp_dir().join(format!("elfLoader-debugfile-host-{}", std::process::id()));
+        std::fs::write(&host, &debugBytes).unwrap();
+        std::os::unix::fs::symlink(&host, root.join("usr/bin/hello.debug")).unwrap();
+        assert!(locator.locate(&stripped, "/usr/bin/hello").is_none());
+        std::fs::remove_file(root.join("usr/bin/hello.debug")).unwrap();
+        std::os::unix::fs::symlink("/srv/hello.debug", root.join("usr/bin/hello.debug")).unwrap();
+        place("srv/hello.debug", &debugBytes);
+        assert_eq!(root.join("srv/hello.debug"), locator.locate(&stripped, "/usr/bin/hello").unwrap().path);
+        std::fs::remove_file(root.join("usr/bin/hello.debug")).unwrap();
+        std::fs::remove_file(&host).unwrap();
+
+        //each place is found once the earlier ones are gone, files that do not match are passed over
+        let mut corrupt = debugBytes.clone();
+        let last = corrupt.len() - 1;
+        corrupt[last] ^= 0xff;
+        place("usr/bin/hello.debug", &corrupt);
+        assert!(locator.locate(&stripped, "/usr/bin/hello").is_none());
+        place("usr/lib/debug/usr/bin/hello.debug", &debugBytes);
+        let found = locator.locate(&stripped, "/usr/bin/hello").unwrap();
+        assert_eq!((root.join("usr/lib/debug/usr/bin/hello.debug"), debugfile::Method::Debuglink), (found.path, found.method));
+        place("usr/bin/.debug/hello.debug", &debugBytes);
+        assert_eq!(root.join("usr/bin/.debug/hello.debug"), locator.locate(&stripped, "/usr/bin/hello").unwrap().path);
+        let other = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/inline-dwarf5")).unwrap();
+        place(&format!("usr/lib/debug/.build-id/{}/{}.debug", &id[..2], &id[2..]), &other);
+        assert_eq!(debugfile::Method::Debuglink, locator.locate(&stripped, "/usr/bin/hello").unwrap().method);
+        place(&format!("usr/lib/debug/.build-id/{}/{}.debug", &id[..2], &id[2..]), &debugBytes);
+        let found = locator.locate(&stripped, "/usr/bin/hello").unwrap();
+        assert_eq!(debugfile::Method::BuildId, found.method);
+
+        //the symbolizer takes the symbols and DWARF of the debug file
+        let mut original = loader::Loader::fromBytes(bytes);
+        original.load();
+        let main = original.symbols.iter().find(|s| s.name == "main").unwrap().st_value as u64;
+        let frames = symbolize::Symbolizer::withDebugFile(stripped, Some(found.loader)).symbolize(main, 0).unwrap().frames;
+        assert_eq!(Some("main".to_string()), frames.last().and_then(|f| f.function.clone()));
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn testMiniDebugInfo() {
+        assert_eq!(0x995dc9bbdf1939fa, xz::crc64(b"123456789"));
+
+        //four CRC-32 blocks, stream padding, then a second stream without check of incompressible bytes
+        let mixed = std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/mixed.xz")).unwrap();
+        let data = xz::decompress(&mixed, xz::MAX_OUTPUT).unwrap();
+        assert_eq!((61840, 0x61eb00fb), (data.len(), strip::crc32(&data)));
+        let mut corrupt = mixed.clone();
+        corrupt[200] ^= 1;
+        assert!(xz::decompress(&corrupt, xz::MAX_OUTPUT).is_err());
+        assert!(xz::decompress(&mixed[..mixed.len() - 1], xz::MAX_OUTPUT).is_err());
+        assert_eq!(Err("decompressed data is larger than 61839 bytes".to_string()), xz::decompress(&mixed, 61839));
+
+        //the stripped binary has no .symtab, its functions come from the compressed ELF file inside
+        let mut parser = loader::Loader::fromBytes(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/hello-minidebug")).unwrap());
+        parser.load();
+        assert!(parser.symbols.is_empty());
+        let mini = debugfile::miniDebugInfo(&parser).unwrap().unwrap();
+        let main = mini.symbols.iter().find(|s| s.name == "main").unwrap().st_value as u64;
+        assert!(mini.findSection(".debug_info").is_none());
+        let symbolizer = symbolize::Symbolizer::new(parser);
+        let location = symbolizer.symbolize(main + 4, 0).unwrap();
+        assert_eq!(Some(("main".to_string(), 4)), location.symbol);
+        let mut plain = loader::Loader::fromBytes(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/tiny32")).unwrap());
+        plain.load();
+        assert!(debugfile::miniDebugInfo(&plain).unwrap().is_none());
+    }
+
+    #[test]
+    fn testConvertFirmware() {
+        let fixture = |name: &str| std::fs::read(format!("{}/src/binaries/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap();
+        let open = || {
+            let mut parser = loader::Loader::fromBytes(fixture("firmware"));
+            parser.load();
+            convert::Converter::new(parser)
+        };
+
+        //.data is linked for RAM but loaded into flash right after .rodata, the references are from objcopy
+        let mut converter = open();
+        let data = converter.loader.findSection(".data").unwrap();
+        assert_eq!((0x0800010d, 0x20000000), (converter.loadAddress(data), converter.loader.sectionHeaders[data].sh_addr as u64));
+        assert_eq!(fixture("firmware.bin"), converter.binary().unwrap());
+        converter.gapFill = Some(0xff);
+        assert_eq!(fixture("firmware.hex"), converter.convert(convert::Format::IntelHex, "").unwrap());
+        let binary = converter.binary().unwrap();
+        assert!(binary[0x81..0x100].iter().all(|b| *b == 0xff) && binary[0x100..0x10d] == b"firmware 1.0\0"[..]);
+        let mut converter = open();
+        converter.remove = vec![".isr*".to_string()];
+        assert_eq!(fixture("firmware.srec"), converter.convert(convert::Format::Srec, "firmware.srec").unwrap());
+
+        //at the virtual addresses the image would be hundreds of megabytes, one section still fits
+        converter.useVirtual = true;
+        assert!(converter.binary().is_err());
+        converter.only = vec![".data".to_string()];
+        let chunks = converter.chunks().unwrap();
+        assert_eq!(vec![(".data".to_string(), 0x20000000)], chunks.iter().map(|c| (c.name.clone(), c.address)).collect::<Vec<_>>());
+        let hex = converter.intelHex().unwrap();
+        assert!(hex.starts_with(":020000042000DA\r\n:1000000001000000") && hex.ends_with(":040000050800004AA5\r\n:00000001FF\r\n"));
+        let srec = converter.srec("").unwrap();
+        assert!(srec.starts_with("S0030000FC\r\nS315200000000100") && srec.ends_with("S7050800004AA8\r\n"));
+
+        assert_eq!(convert::Format::Srec, convert::Format::fromPath("out/app.S19"));
+        assert!(convert::wildcard("*", "") && convert::wildcard(".text.*", ".text.main") && convert::wildcard("?data", ".data"));
+        assert!(!convert::wildcard(".text.*", ".text") && !convert::wildcard("*.o", "main.c"));
+    }
+
+    #[test]
+    fn testMemoryRegions() {
+        let mut parser = loader::Loader::fromBytes(std::fs::read(concat!(env!("CARGO_MANIFEST_DIR"), "/src/binaries/firmware")).unwrap());
+        parser.load();
+
+        //the MEMORY command the fixture was linked with, the table is what ld --print-memory-usage printed
+        let layout = memory::MemoryLayout::parse("MEMORY\n{\n    FLASH (rx)  : ORIGIN = 0x08000000, LENGTH = 16K  /* internal */\n    RAM   (rwx) : ORIGIN = 0x20000000, LENGTH = 8K\n}\n").unwrap();
+        let report = layout.check(&parser);
+        assert!(report.problems.is_empty(), "{:?}", report.problems);
+        assert_eq!("Memory region         Used Size  Region Size  %age Used\n           FLASH:         305 B        16 KB      1.86%\n             RAM:          40 B         8 KB      0.49%\n", report.to_string());
+
+        //too little flash, and RAM marked read only
+        let layout = memory::MemoryLayout::parse("# name origin length\nFLASH 0x08000000 0x200-0x100 rx\nRAM 0x20000000 1M+1M r  # sram\n").unwrap();
+        assert_eq!((256, 2 << 20, Some(true)), (layout.regions[0].length, layout.regions[1].length, layout.regions[1].isWritable().map(|w| !w)));
+        let report = layout.check(&parser);
+        assert!(report.problems.contains(&"section .rodata runs at 0x8000100..0x800010d, outside every memory region".to_string()));
+        assert!(report.problems.contains(&"segment 0 is loaded at 0x8000000..0x800010d, past the end of FLASH by 13 bytes".to_string()));
+        assert!(report.problems.contains(&".data runs in RAM which is not writable".to_string()));
+        assert!(report.to_string().contains("           FLASH:         269 B        256 B    105.08%\n             RAM:          40 B         2 MB      0.00%"));
+
+        //a single region holds both copies of .data
+        let layout = memory::MemoryLayout::parse("ALL 0 4G rwx").unwrap();
+        let report = layout.check(&parser);
+        assert_eq!(vec![".data is loaded at 0x800010d and runs at 0x20000000, both in ALL".to_string(), ".data is loaded into ALL which is writable, not flash".to_string()], report.problems);
+        assert!(report.to_string().ends_with("ALL:   536870952 B         4 GB     12.50%\n"));
+
+        assert!(memory::MemoryLayout::parse("FLASH 0x08000000").is_err());
+        assert!(memory::MemoryLayout::parse("A 0 1K\nA 1K 1K").err().unwrap().starts_with("line 2"));
+        assert!(memory::MemoryLayout::parse("RAM (rw) : ORIGIN = 0x20000000, SIZE = 8K").is_err());
+        assert_eq!(Err("line 1: RAM runs past the end of the address space".to_string()),
+            memory::MemoryLayout::parse("RAM (rw) : ORIGIN = 0xffffffffffffffff, LENGTH = 1K").map(|_| ()));
+
+        //sections and segments at the end of the address space are placed, not overflowed
+        let mut parser = parser;
+        let data = parser.findSection(".data").unwrap();
+        parser.sectionHeaders[data].sh_addr = usize::MAX;
+        for p in parser.programHeaders.iter_mut() {p.setPADDR(usize::MAX);}
+        let report = layout.check(&parser);
+        assert!(report.problems.iter().any(|p| p.starts_with("section .data runs at 0xffffffffffffffff..0xffffffffffffffff, outside")), "{:?}", report.problems);
+    }
+
+    #[test]
+    fn testSizeAttribution() {
+        let open = |name: &str| {
+            let mut parser = loader::Loader::fromBytes(std::fs::read(format!("{}/src/binaries/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap());
+            parser.load();
+            parser
+        };
+        let find = |items: &[size::Item], name: &str| items.iter().find(|i| i.name == name).map(|i| (i.fileSize, i.vmSize));
+
+        //every byte of the file and of the loaded segments is counted exactly once whatever the source
+        let hello = open("hello-debug");
+        let loaded: u64 = hello.programHeaders.iter().filter(|p| p.getTYPE() == programheader::PT_LOAD).map(|p| p.getMEMSZ() as u64).sum();
+        for source in [size::Source::Segments, size::Source::Sections, size::Source::Symbols, size::Source::Files].iter() {
+            let items = size::attribute(&hello, *source);
+            assert_eq!(hello.fileVec.len() as u64, items.iter().map(|i| i.fileSize).sum::<u64>(), "{:?}", source);
+            assert_eq!(loaded, items.iter().map(|i| i.vmSize).sum::<u64>(), "{:?}", source);
+        }
+        let text = hello.findSection(".text").map(|i| hello.sectionHeaders[i].sh_size as u64).unwrap();
+        assert_eq!(Some((text, text)), find(&size::attribute(&hello, size::Source::Sections), ".text"));
+        let main = hello.symbols.iter().find(|s| s.name == "main").unwrap().st_size as u64;
+        assert_eq!(Some((main, main)), find(&size::attribute(&hello, size::Source::Symbols), "main"));
+
+        //the compile unit covers all of .text in the firmware, the alignment gap after it belongs to the segment
+        let firmware = open("firmware");
+        let items = size::attribute(&firmware, size::Source::Files);
+        assert_eq!(Some((0x71, 0x71)), find(&items, "fw.c"));
+        assert_eq!(Some((0x7f, 0x7f)), find(&items, "[LOAD #0 [RX]]"));
+        let items = size::attribute(&firmware, size::Source::Symbols);
+        assert_eq!((Some((32, 32)), Some((0, 4))), (find(&items, "table"), find(&items, "counter")));
+
+        //without DWARF only the local symbols after an STT_FILE have a file
+        let mut file = writer::ElfFile::parse(firmware.fileVec.clone()).unwrap();
+        let debug: Vec<usize> = (0..file.sections.len()).rev().filter(|i| strip::isDebugSection(&file.sections[*i].name)).collect();
+        for i in debug {
+            file.removeSection(i);
+        }
+        let mut stripped = loader::Loader::fromBytes(file.write().unwrap());
+        stripped.load();
+        let items = size::attribute(&stripped, size::Source::Files);
+        assert_eq!((Some((13, 13)), Some((0x71, 0x71))), (find(&items, "fw.c"), find(&items, "[section .text]")));
+
+        let mut items = size::attribute(&hello, size::Source::Sections);
+        size::sortItems(&mut items, size::SortKey::File);
+        assert!(items.windows(2).all(|w| w[0].fileSize >= w[1].fileSize));
+        let count = items.len();
+        let report = size::SizeReport{items: items, top: 5}.to_string();
+        assert_eq!(5 + 2 + 2, report.lines().count());
+        assert!(report.contains(&format!("    [{} Others]\n", count - 5)) && report.ends_with(&format!("100.0%  {:>6}    TOTAL\n", size::human(loaded))));
+        assert_eq!(("1023", "1.00Ki", "12.3Ki", "139Ki", "2.00Mi"), (&size::human(1023)[..], &size::human(1024)[..], &size::human(12595)[..], &size::human(141926)[..], &size::human(2 << 20)[..]));
+
+        //the MiniDebugInfo build trades the symbol table and DWARF for .gnu_debugdata
+        let old = size::attribute(&hello, size::Source::Sections);
+        let new = size::attribute(&open("hello-minidebug"), size::Source::Sections);
+        let changes = size::compare(&old, &new, size::SortKey::Both);
+        let debugdata = changes.iter().find(|c| c.name == ".gnu_debugdata").unwrap();
+        assert_eq!((0, 844, 0, 0), (debugdata.oldFile, debugdata.newFile, debugdata.oldVm, debugdata.newVm));
+        assert!(changes.iter().any(|c| c.name == ".symtab" && c.newFile == 0) && changes.iter().all(|c| c.name != ".text"));
+        let total: i64 = changes.iter().map(|c| c.fileDelta()).sum();
+        assert_eq!(total, new.iter().map(|i| i.fileSize as i64).sum::<i64>() - old.iter().map(|i| i.fileSize as i64).sum::<i64>());
+        let diff = size::SizeDiff{changes: changes, top: 20, old: (17176, loaded), new: (15288, loaded)}.to_string();
+        assert!(diff.contains("  [NEW]    +844               0    .gnu_debugdata\n") && diff.ends_with(" -11.0% -1.84Ki   +0.0%       0    TOTAL\n"));
+    }
+
+    #[test]
+    fn testLayoutMaps() {
+        let open = |name: &str| {
+            let mut parser = loader::Loader::fromBytes(std::fs::read(format!("{}/src/binaries/{}", env!("CARGO_MANIFEST_DIR"), name)).unwrap());
+            parser.load();
+            parser
+        };
+
+        //the rows of the file map cover the whole file without overlapping
+        let hello = open("hello-debug");
+        let file = layout::Layout::file(&hello);
+        assert!(file.problems.is_empty(), "{:?}", file.problems);
+        assert_eq!((0, hello.fileVec.len() as u64), (file.rows[0].start, file.rows.last().unwrap().end));
+        assert!(file.rows.windows(2).all(|w| w[0].end == w[1].start));
+        assert_eq!((41, 9580), file.unused());
+        let names: Vec<String> = file.rows.iter().map(|r| file.rowName(r)).collect();
+        assert_eq!(("[ELF header]", "<padding>", "[section headers]"), (&names[0][..], &names[3][..], &names[names.len() - 1][..]));
+        assert!(names.iter().any(|n| n == ".got.plt (continued)") && !names.iter().any(|n| n == ".bss"));
+        let memory = layout::Layout::memory(&hello);
+        assert!(memory.problems.is_empty(), "{:?}", memory.problems);
+        assert_eq!(".bss", memory.rowName(memory.rows.last().unwrap()));
+        assert_eq!(12, memory.segments.len());
+
+        let firmware = open("firmware");
+        assert_eq!(layout::Layout::memory(&firmware).to_string(), "memory layout:\n\
+            \x20 0  LOAD #0          RX   0x8000000..0x800010d\n\
+            \x20 1  LOAD #1          RW   0x20000000..0x20000028\n\
+            \x20      START        END      SIZE  0 1   CONTENTS\n\
+            \x20 0x08000000 0x08000010        16  +     .isr_vector\n\
+            \x20 0x08000010 0x08000081       113  |     .text\n\
+            \x20 0x08000081 0x08000100       127  |     <padding>\n\
+            \x20 0x08000100 0x0800010d        13  |     .rodata\n\
+            \x20 0x0800010d 0x20000000 402652915        <gap>\n\
+            \x20 0x20000000 0x20000024        36    +   .data\n\
+            \x20 0x20000024 0x20000028         4    |   .bss\n\
+            \x20 127 bytes of padding, 402652915 bytes in gaps\n");
+        assert_eq!("memory layout:\n  nothing is loaded\n", layout::Layout::memory(&open("tiny-ppc32.o")).to_string());
+
+        //a section moved onto another one and one grown past the end of its segment
+        let mut broken = open("hello-debug");
+        let (gotplt, data) = (broken.findSection(".got.plt").unwrap(), broken.findSection(".data").unwrap());
+        broken.sectionHeaders[data].sh_offset = broken.sectionHeaders[gotplt].sh_offset;
+        let file = layout::Layout::file(&broken);
+        assert_eq!(vec![".got.plt and .data overlap at 0x2fe8..0x2ff8".to_string()], file.problems);
+        assert!(file.rows.iter().any(|r| file.rowName(r) == ".got.plt + .data  <- overlap"));
+        let mut broken = open("firmware");
+        let rodata = broken.findSection(".rodata").unwrap();
+        broken.sectionHeaders[rodata].sh_size = 0x20;
+        let memory = layout::Layout::memory(&broken);
+        assert_eq!(vec![".rodata at 0x8000100..0x8000120 is only partly inside LOAD #0".to_string()], memory.problems);
+
+        //the pictures escape what they show
+        let layouts = [layout::Layout::file(&broken), memory];
+        let svg = layout::svg(&layouts, "a<b");
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\"") && svg.contains("<title>a&lt;b</title>") && svg.contains(">&lt;padding&gt;</text>"));
+        assert!(!svg.contains("<padding>") && svg.matches("<rect").count() == layouts.iter().map(|l| l.rows.len() + l.segments.len()).sum());
+        let html = layout::html(&layouts, "a<b");
+        assert!(html.starts_with("<!DOCTYPE html>") && html.contains(&svg) && html.contains("<h1>a&lt;b</h1>") && html.ends_with("</html>\n"));
+        assert!(html.contains("<li>.rodata at 0x8000100..0x8000120 is only partly inside LOAD #0</li>"));
+    }
 }
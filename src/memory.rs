@@ -0,0 +1,222 @@
+use std::fmt;
+use crate::convert;
+use crate::loader;
+use crate::programheader;
+use crate::sectionheader;
+
+//a named range of device memory like FLASH or RAM
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    pub name:       String,
+    pub origin:     u64,
+    pub length:     u64,
+
+    //ld attribute letters like rx or rwx, empty when the layout gives none
+    pub attributes: String,
+}
+
+impl Region {
+
+    pub fn end(&self) -> u64 {
+        self.origin + self.length
+    }
+
+    //whether the attributes allow writes, None without attributes, letters after ! are negated
+    pub fn isWritable(&self) -> Option<bool> {
+        if self.attributes.is_empty() {return None;}
+        let attributes = self.attributes.to_lowercase();
+        let (allowed, denied) = attributes.split_once('!').unwrap_or((&attributes, ""));
+        Some(allowed.contains('w') && !denied.contains('w'))
+    }
+}
+
+//the memory regions of a device, read from a layout file
+//each line is "NAME ORIGIN LENGTH [ATTRIBUTES]" or a line of a GNU ld MEMORY command, blank lines
+//and text after # are ignored, sizes may end in K, M or G and be added or subtracted
+//
+//  FLASH   0x08000000  64K    rx
+//  RAM (rwx) : ORIGIN = 0x20000000, LENGTH = 20K - 1K
+pub struct MemoryLayout {
+    pub regions: Vec<Region>,
+}
+
+impl MemoryLayout {
+
+    //parses a layout file, returning a message naming the bad line on error
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut regions: Vec<Region> = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let mut line = line.split('#').next().unwrap_or("").to_string();
+            //C comments and the braces of a pasted MEMORY command
+            while let Some(start) = line.find("/*") {
+                let end = line[start..].find("*/").map(|e| start + e + 2).unwrap_or(line.len());
+                line.replace_range(start..end, "");
+            }
+            let mut line = line.trim();
+            if line.starts_with("MEMORY") && !line[6..].starts_with(|c: char| c.is_alphanumeric() || c == '_') {line = &line[6..];}
+            let line = line.trim().trim_matches(|c| c == '{' || c == '}').trim();
+            if line.is_empty() {continue;}
+            let region = parseRegion(line).ok_or_else(|| format!("line {}: expected NAME ORIGIN LENGTH [ATTRIBUTES]", number + 1))?;
+            if regions.iter().any(|r| r.name == region.name) {return Err(format!("line {}: {} is declared twice", number + 1, region.name));}
+            if region.origin.checked_add(region.length).is_none() {
+                return Err(format!("line {}: {} runs past the end of the address space", number + 1, region.name));
+            }
+            regions.push(region);
+        }
+        Ok(Self{regions: regions})
+    }
+
+    //the region holding a whole range, or the one it starts in and the bytes it sticks out by
+    pub fn regionOf(&self, start: u64, end: u64) -> Option<(&Region, u64)> {
+        let r = self.regions.iter().find(|r| r.origin <= start && start < r.end())?;
+        Some((r, end.saturating_sub(r.end())))
+    }
+
+    //places every allocatable section and PT_LOAD segment in the regions and reports the usage and
+    //everything that does not fit
+    pub fn check(&self, loader: &loader::Loader) -> MemoryReport {
+        let mut placements = vec![];
+        for (i, s) in loader.sectionHeaders.iter().enumerate() {
+            if s.sh_flags & sectionheader::SHF_ALLOC == 0 || s.sh_size == 0 {continue;}
+            let name = loader.sectionName(i).unwrap_or_default();
+            let (vma, lma) = (s.sh_addr as u64, convert::loadAddress(loader, i));
+            placements.push((format!("section {}", name), "runs", vma, vma.saturating_add(s.sh_size as u64)));
+            if s.sh_type != sectionheader::SHT_NOBITS && lma != vma {
+                placements.push((format!("section {}", name), "is loaded", lma, lma.saturating_add(s.sh_size as u64)));
+            }
+        }
+        for (i, p) in loader.programHeaders.iter().enumerate().filter(|(_, p)| p.getTYPE() == programheader::PT_LOAD) {
+            let (vaddr, paddr) = (p.getVADDR() as u64, p.getPADDR() as u64);
+            if p.getMEMSZ() != 0 {placements.push((format!("segment {}", i), "runs", vaddr, vaddr.saturating_add(p.getMEMSZ() as u64)));}
+            if p.getFILESZ() != 0 {placements.push((format!("segment {}", i), "is loaded", paddr, paddr.saturating_add(p.getFILESZ() as u64)));}
+        }
+
+        let mut used = vec![0; self.regions.len()];
+        let mut problems = vec![];
+        for (what, how, start, end) in placements {
+            match self.regionOf(start, end) {
+                None => problems.push(format!("{} {} at {:#x}..{:#x}, outside every memory region", what, how, start, end)),
+                Some((r, over)) => {
+                    let index = self.regions.iter().position(|x| x == r).unwrap();
+                    used[index] = used[index].max(end - r.origin);
+                    if over > 0 {
+                        problems.push(format!("{} {} at {:#x}..{:#x}, past the end of {} by {} bytes", what, how, start, end, r.name, over));
+                    }
+                },
+            }
+        }
+
+        //initialized data has to be copied from flash into RAM by the startup code
+        if let Some(i) = loader.findSection(".data").filter(|i| loader.sectionHeaders[*i].sh_size != 0) {
+            let s = &loader.sectionHeaders[i];
+            let (vma, lma) = (s.sh_addr as u64, convert::loadAddress(loader, i));
+            let (load, run) = (self.regionOf(lma, lma.saturating_add(1)).map(|r| r.0), self.regionOf(vma, vma.saturating_add(1)).map(|r| r.0));
+            if lma == vma {
+                problems.push(format!(".data is loaded where it runs, at {:#x}, its initial values are not kept in flash", vma));
+            } else if let (Some(l), Some(r)) = (load, run) {
+                if l.name == r.name {problems.push(format!(".data is loaded at {:#x} and runs at {:#x}, both in {}", lma, vma, l.name));}
+            }
+            if let Some(load) = load.filter(|l| lma != vma && l.isWritable() == Some(true)) {
+                problems.push(format!(".data is loaded into {} which is writable, not flash", load.name));
+            }
+            if let Some(run) = run.filter(|r| r.isWritable() == Some(false)) {
+                problems.push(format!(".data runs in {} which is not writable", run.name));
+            }
+        }
+
+        MemoryReport{usage: self.regions.iter().cloned().zip(used).collect(), problems: problems}
+    }
+}
+
+//the outcome of MemoryLayout::check
+pub struct MemoryReport {
+    //every region and the bytes from its origin to the end of the last thing placed in it
+    pub usage:    Vec<(Region, u64)>,
+    pub problems: Vec<String>,
+}
+
+//the table GNU ld prints for --print-memory-usage
+impl fmt::Display for MemoryReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "Memory region         Used Size  Region Size  %age Used")?;
+        for (region, used) in self.usage.iter() {
+            write!(f, "{:>16}: {}{}", region.name, size(*used), size(region.length))?;
+            if region.length != 0 {write!(f, "    {:6.2}%", *used as f64 * 100.0 / region.length as f64)?;}
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+//a size in the largest unit that divides it, like ld
+fn size(bytes: u64) -> String {
+    if bytes & 0x3fffffff == 0 {
+        format!("{:>10} GB", bytes >> 30)
+    } else if bytes & 0xfffff == 0 {
+        format!("{:>10} MB", bytes >> 20)
+    } else if bytes & 0x3ff == 0 {
+        format!("{:>10} KB", bytes >> 10)
+    } else {
+        format!(" {:>10} B", bytes)
+    }
+}
+
+//reads one region in either syntax
+fn parseRegion(line: &str) -> Option<Region> {
+    if let Some((left, right)) = line.split_once(':') {
+        //NAME (ATTRIBUTES) : ORIGIN = EXPRESSION, LENGTH = EXPRESSION
+        let (name, attributes) = match left.split_once('(') {
+            Some((n, a)) => (n.trim(), a.trim().strip_suffix(')')?.trim()),
+            None         => (left.trim(), ""),
+        };
+        let (mut origin, mut length) = (None, None);
+        for part in right.split(',') {
+            let (key, value) = part.split_once('=')?;
+            match key.trim().to_lowercase().as_str() {
+                "origin" | "org" | "o" => origin = Some(expression(value)?),
+                "length" | "len" | "l" => length = Some(expression(value)?),
+                _ => return None,
+            }
+        }
+        if name.is_empty() || name.contains(char::is_whitespace) {return None;}
+        return Some(Region{name: name.to_string(), origin: origin?, length: length?, attributes: attributes.to_string()});
+    }
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    if fields.len() != 3 && fields.len() != 4 {return None;}
+    Some(Region {
+        name:       fields[0].to_string(),
+        origin:     expression(fields[1])?,
+        length:     expression(fields[2])?,
+        attributes: fields.get(3).unwrap_or(&"").to_string(),
+    })
+}
+
+//numbers with K, M or G suffixes joined by + and -
+fn expression(text: &str) -> Option<u64> {
+    let text = text.replace(' ', "");
+    let mut total: u64 = 0;
+    let mut negative = false;
+    let mut rest = &text[..];
+    loop {
+        let end = rest.find(|c| c == '+' || c == '-').unwrap_or(rest.len());
+        let term = number(&rest[..end])?;
+        total = if negative {total.checked_sub(term)?} else {total.checked_add(term)?};
+        if end == rest.len() {return Some(total);}
+        negative = &rest[end..end + 1] == "-";
+        rest = &rest[end + 1..];
+    }
+}
+
+fn number(text: &str) -> Option<u64> {
+    let (digits, scale) = match text.chars().last()? {
+        'k' | 'K' => (&text[..text.len() - 1], 1 << 10),
+        'm' | 'M' => (&text[..text.len() - 1], 1 << 20),
+        'g' | 'G' => (&text[..text.len() - 1], 1 << 30),
+        _         => (text, 1),
+    };
+    let value = match digits.strip_prefix("0x").or_else(|| digits.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None      => digits.parse().ok()?,
+    };
+    value.checked_mul(scale)
+}
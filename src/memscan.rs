@@ -0,0 +1,277 @@
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::os::unix::fs::FileExt;
+use crate::loader;
+use crate::process;
+use crate::programheader;
+use crate::sectionheader;
+
+//a byte pattern where None matches any byte, written like "48 8b ?? 05"
+#[derive(Debug, Clone, PartialEq)]
+pub struct Pattern {
+    pub bytes: Vec<Option<u8>>,
+}
+
+impl Pattern {
+
+    //parses space separated hex bytes, ?? or ? is a wildcard
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let mut bytes = vec![];
+        for token in text.split_whitespace() {
+            if token == "?" || token == "??" {
+                bytes.push(None);
+                continue;
+            }
+            match u8::from_str_radix(token, 16) {
+                Ok(b) if token.len() <= 2 => bytes.push(Some(b)),
+                _ => return Err(format!("{}: not a hex byte", token)),
+            }
+        }
+        if bytes.is_empty() {return Err("empty pattern".to_string());}
+        Ok(Self{bytes: bytes})
+    }
+
+    pub fn fromBytes(bytes: &[u8]) -> Self {
+        Self{bytes: bytes.iter().map(|b| Some(*b)).collect()}
+    }
+
+    pub fn len(&self) -> usize {
+        self.bytes.len()
+    }
+
+    //true if the pattern matches the start of data
+    pub fn matches(&self, data: &[u8]) -> bool {
+        data.len() >= self.bytes.len() && self.bytes.iter().zip(data.iter()).all(|(p, b)| p.map(|p| p == *b).unwrap_or(true))
+    }
+
+    //returns the offsets of every match in data, overlapping matches included
+    pub fn find(&self, data: &[u8]) -> Vec<usize> {
+        if data.len() < self.bytes.len() {return vec![];}
+        //jump between occurrences of the first fixed byte instead of trying every offset
+        let anchor = self.bytes.iter().position(|b| b.is_some());
+        let mut found = vec![];
+        let mut i = 0;
+        while i + self.bytes.len() <= data.len() {
+            if let Some(a) = anchor {
+                let wanted = self.bytes[a].unwrap();
+                match data[i + a..data.len() - self.bytes.len() + a + 1].iter().position(|b| *b == wanted) {
+                    Some(p) => i += p,
+                    None    => break,
+                }
+            }
+            if self.matches(&data[i..]) {found.push(i);}
+            i += 1;
+        }
+        found
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let text: Vec<String> = self.bytes.iter().map(|b| b.map(|b| format!("{:02x}", b)).unwrap_or_else(|| "??".to_string())).collect();
+        write!(f, "{}", text.join(" "))
+    }
+}
+
+//a typed value to search for, encoded in the byte order of the target
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    I32(i32),
+    F64(f64),
+    String(String),
+}
+
+impl Value {
+
+    pub fn pattern(&self, littleEndian: bool) -> Pattern {
+        match self {
+            Value::I32(v) => Pattern::fromBytes(&if littleEndian {v.to_le_bytes()} else {v.to_be_bytes()}),
+            Value::F64(v) => Pattern::fromBytes(&if littleEndian {v.to_le_bytes()} else {v.to_be_bytes()}),
+            Value::String(s) => Pattern::fromBytes(s.as_bytes()),
+        }
+    }
+}
+
+//which part of the matching modules to scan
+#[derive(Debug, Clone, PartialEq)]
+pub enum Target {
+    //every PT_LOAD segment
+    Module,
+    Section(String),
+
+    //index into programHeaders
+    Segment(usize),
+}
+
+//a readable address range of a process belonging to part of a module
+#[derive(Debug, Clone, PartialEq)]
+pub struct Region {
+    //index into ProcessScanner::symbolizer.modules
+    pub module: usize,
+    pub name:   String,
+    pub start:  u64,
+    pub end:    u64,
+}
+
+//a match, placed by module, section and offset from the section start
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub address: u64,
+    pub module:  String,
+
+    //None when the address is in a segment but outside every section, the offset is then from the load bias
+    pub section: Option<String>,
+    pub offset:  u64,
+
+    //the bytes at the address when it was last scanned
+    pub bytes:   Vec<u8>,
+}
+
+impl fmt::Display for Hit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} {}:{}+{:#x}", self.address, self.module, self.section.as_deref().unwrap_or("*"), self.offset)
+    }
+}
+
+//how a rescan narrows earlier hits
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Matches(Pattern),
+    Changed,
+    Unchanged,
+}
+
+//reads the memory of a process through /proc/PID/mem
+pub struct ProcessMemory {
+    file: File,
+}
+
+impl ProcessMemory {
+
+    pub fn open(pid: u32) -> io::Result<Self> {
+        Ok(Self{file: File::open(format!("/proc/{}/mem", pid))?})
+    }
+
+    pub fn read(&self, address: u64, length: usize) -> io::Result<Vec<u8>> {
+        let mut buffer = vec![0; length];
+        self.file.read_exact_at(&mut buffer, address)?;
+        Ok(buffer)
+    }
+}
+
+//scans the modules of a process, using their ELF headers to pick the ranges to read
+pub struct ProcessScanner {
+    pub symbolizer: process::ProcessSymbolizer,
+    pub memory:     ProcessMemory,
+}
+
+impl ProcessScanner {
+
+    pub fn new(pid: u32) -> io::Result<Self> {
+        Ok(Self {
+            symbolizer: process::ProcessSymbolizer::new(pid)?,
+            memory:     ProcessMemory::open(pid)?,
+        })
+    }
+
+    //returns the readable ranges of the target in every module whose path contains the module filter
+    pub fn regions(&self, module: Option<&str>, target: &Target) -> Vec<Region> {
+        let mut regions = vec![];
+        for (index, m) in self.symbolizer.modules.iter().enumerate() {
+            if let Some(filter) = module {
+                if !m.path.contains(filter) {continue;}
+            }
+            let loader = &m.symbolizer.loader;
+            let mut ranges = vec![];
+            match target {
+                Target::Module | Target::Segment(_) => {
+                    for (i, p) in loader.programHeaders.iter().enumerate() {
+                        if p.getTYPE() != programheader::PT_LOAD {continue;}
+                        if let Target::Segment(s) = target {
+                            if *s != i {continue;}
+                        }
+                        ranges.push((format!("segment {}", i), p.getVADDR() as u64, p.getVADDR().saturating_add(p.getMEMSZ()) as u64));
+                    }
+                },
+                Target::Section(name) => {
+                    for (i, s) in loader.sectionHeaders.iter().enumerate() {
+                        if s.sh_flags & sectionheader::SHF_ALLOC == 0 || s.sh_size == 0 {continue;}
+                        if loader.sectionName(i).as_deref() != Some(name.as_str()) {continue;}
+                        ranges.push((name.clone(), s.sh_addr as u64, s.sh_addr.saturating_add(s.sh_size) as u64));
+                    }
+                },
+            }
+            //clip to the readable mappings of the whole process, .bss may continue into anonymous memory
+            for (name, start, end) in ranges {
+                let (start, end) = (start.wrapping_add(m.bias), start.wrapping_add(m.bias).saturating_add(end - start));
+                for mapping in self.symbolizer.mappings.iter().filter(|mapping| mapping.isReadable()) {
+                    let (s, e) = (start.max(mapping.start), end.min(mapping.end));
+                    if s < e {regions.push(Region{module: index, name: name.clone(), start: s, end: e});}
+                }
+            }
+        }
+        regions
+    }
+
+    //searches the regions for a pattern, regions that cannot be read are skipped
+    pub fn scan(&self, regions: &[Region], pattern: &Pattern) -> Vec<Hit> {
+        let mut hits = vec![];
+        for region in regions.iter() {
+            let data = match self.memory.read(region.start, (region.end - region.start) as usize) {
+                Ok(d)  => d,
+                Err(_) => continue,
+            };
+            for offset in pattern.find(&data) {
+                let address = region.start + offset as u64;
+                hits.push(self.hit(region.module, address, data[offset..offset + pattern.len()].to_vec()));
+            }
+        }
+        hits
+    }
+
+    //rereads earlier hits and keeps the ones meeting the condition, hits that became unreadable are dropped
+    pub fn rescan(&self, hits: &[Hit], condition: &Condition) -> Vec<Hit> {
+        let mut kept = vec![];
+        for hit in hits.iter() {
+            let length = match condition {
+                Condition::Matches(p) => p.len(),
+                _                     => hit.bytes.len(),
+            };
+            let bytes = match self.memory.read(hit.address, length) {
+                Ok(b)  => b,
+                Err(_) => continue,
+            };
+            let keep = match condition {
+                Condition::Matches(p) => p.matches(&bytes),
+                Condition::Changed    => bytes != hit.bytes,
+                Condition::Unchanged  => bytes == hit.bytes,
+            };
+            if keep {kept.push(Hit{bytes: bytes, ..hit.clone()});}
+        }
+        kept
+    }
+
+    //the byte order used for typed values of a module
+    pub fn isLittleEndian(&self, module: usize) -> bool {
+        self.symbolizer.modules[module].symbolizer.loader.header.e_ident.Data == 1
+    }
+
+    fn hit(&self, module: usize, address: u64, bytes: Vec<u8>) -> Hit {
+        let m = &self.symbolizer.modules[module];
+        let linkAddress = address.wrapping_sub(m.bias);
+        let (section, offset) = match sectionAt(&m.symbolizer.loader, linkAddress) {
+            Some((name, start)) => (Some(name), linkAddress - start),
+            None                => (None, linkAddress),
+        };
+        Hit{address: address, module: m.path.clone(), section: section, offset: offset, bytes: bytes}
+    }
+}
+
+//returns the name and address of the allocated section containing a link time address
+fn sectionAt(loader: &loader::Loader, address: u64) -> Option<(String, u64)> {
+    let (i, s) = loader.sectionHeaders.iter().enumerate().find(|(_, s)| {
+        s.sh_flags & sectionheader::SHF_ALLOC != 0 && (s.sh_addr as u64) <= address && address < s.sh_addr.saturating_add(s.sh_size) as u64
+    })?;
+    Some((loader.sectionName(i)?, s.sh_addr as u64))
+}
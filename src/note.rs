@@ -0,0 +1,87 @@
+//note types used by the GNU toolchain, they are only meaningful with the "GNU" owner
+pub const NT_GNU_ABI_TAG:         u32 = 1;
+pub const NT_GNU_HWCAP:           u32 = 2;
+pub const NT_GNU_BUILD_ID:        u32 = 3;
+pub const NT_GNU_GOLD_VERSION:    u32 = 4;
+pub const NT_GNU_PROPERTY_TYPE_0: u32 = 5;
+
+//property types found inside of a NT_GNU_PROPERTY_TYPE_0 note
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_AND: u32 = 0xc0000000;
+pub const GNU_PROPERTY_X86_FEATURE_1_AND:     u32 = 0xc0000002;
+
+//bits of GNU_PROPERTY_X86_FEATURE_1_AND
+pub const GNU_PROPERTY_X86_FEATURE_1_IBT:   u32 = 0x1;
+pub const GNU_PROPERTY_X86_FEATURE_1_SHSTK: u32 = 0x2;
+
+//bits of GNU_PROPERTY_AARCH64_FEATURE_1_AND
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_BTI: u32 = 0x1;
+pub const GNU_PROPERTY_AARCH64_FEATURE_1_PAC: u32 = 0x2;
+
+#[derive(Debug, Clone)]
+pub struct Note {
+
+    //owner of the note without the nul terminator, for example "GNU"
+    pub name:   String,
+
+    //meaning depends on the owner
+    pub n_type: u32,
+
+    //descriptor bytes
+    pub desc:   Vec<u8>,
+
+    //file offset of the note header
+    pub offset: usize,
+}
+
+impl Note {
+
+    //returns an uninitialized note
+    pub fn new() -> Self {
+        Self {
+            name:   String::new(),
+            n_type: 0,
+            desc:   vec![],
+            offset: 0,
+        }
+    }
+
+    //returns the build id as lowercase hex if this is a GNU build id note
+    pub fn buildId(&self) -> Option<String> {
+        if self.name != "GNU" || self.n_type != NT_GNU_BUILD_ID {return None;}
+        Some(self.desc.iter().map(|b| format!("{:02x}", b)).collect())
+    }
+
+    //splits a NT_GNU_PROPERTY_TYPE_0 descriptor into (pr_type, data) pairs
+    //properties are padded to 8 bytes in 64 bit files and 4 in 32 bit files
+    //data is the endianness of the file, same values as e_ident.Data
+    pub fn gnuProperties(&self, class: u8, data: u8) -> Vec<(u32, Vec<u8>)> {
+        let mut properties = vec![];
+        if self.name != "GNU" || self.n_type != NT_GNU_PROPERTY_TYPE_0 {return properties;}
+        let align = if class == 1 {4} else {8};
+        let read = |b: &[u8]| -> u32 {
+            let bytes = [b[0], b[1], b[2], b[3]];
+            if data == 2 {u32::from_be_bytes(bytes)} else {u32::from_le_bytes(bytes)}
+        };
+
+        let mut i = 0;
+        while i + 8 <= self.desc.len() {
+            let prType = read(&self.desc[i..]);
+            let size = read(&self.desc[i + 4..]) as usize;
+            let start = i + 8;
+            let end = match start.checked_add(size) {
+                Some(e) if e <= self.desc.len() => e,
+                _ => break,
+            };
+            properties.push((prType, self.desc[start..end].to_vec()));
+            i = (end + align - 1) & !(align - 1);
+        }
+        properties
+    }
+
+    //returns the value of an and-combined feature property such as GNU_PROPERTY_X86_FEATURE_1_AND
+    pub fn featureBits(&self, class: u8, data: u8, prType: u32) -> Option<u32> {
+        let (_, bytes) = self.gnuProperties(class, data).into_iter().find(|(t, b)| *t == prType && b.len() >= 4)?;
+        let value = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        Some(if data == 2 {u32::from_be_bytes(value)} else {u32::from_le_bytes(value)})
+    }
+}
@@ -0,0 +1,193 @@
+use std::fmt;
+use crate::loader;
+use crate::note;
+use crate::programheader;
+use crate::symbol;
+
+//one write into the file, keeping the bytes it replaced so it can be undone
+#[derive(Debug, Clone, PartialEq)]
+pub struct Patch {
+    pub address: u64,
+    pub offset:  usize,
+
+    //symbol containing the address and the distance from it, lets a patch set follow the code
+    //into another build of the same program
+    pub symbol:  Option<(String, u64)>,
+    pub old:     Vec<u8>,
+    pub new:     Vec<u8>,
+}
+
+impl fmt::Display for Patch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self.symbol.as_ref() {
+            Some((name, offset)) => format!("{}+{:#x}", name, offset),
+            None                 => "-".to_string(),
+        };
+        write!(f, "{:#x} {} {} {}", self.address, symbol, hex(&self.old), hex(&self.new))
+    }
+}
+
+//writes bytes into a file by virtual address or symbol name
+pub struct Patcher {
+    //the file being patched, fileVec holds the patched bytes
+    pub loader:  loader::Loader,
+
+    //applied patches, oldest first
+    pub patches: Vec<Patch>,
+}
+
+impl Patcher {
+
+    pub fn new(loader: loader::Loader) -> Self {
+        Self{loader: loader, patches: vec![]}
+    }
+
+    //the file with every patch applied
+    pub fn bytes(&self) -> &[u8] {
+        &self.loader.fileVec
+    }
+
+    //maps a range of virtual addresses to a file offset through the PT_LOAD segments, refusing
+    //ranges that are only in memory, like .bss, or that are outside every segment
+    pub fn offsetOf(&self, address: u64, length: usize) -> Result<usize, String> {
+        let end = address.checked_add(length as u64).ok_or("address range overflows")?;
+        for p in self.loader.programHeaders.iter().filter(|p| p.getTYPE() == programheader::PT_LOAD) {
+            let (vaddr, filesz, memsz) = (p.getVADDR() as u64, p.getFILESZ() as u64, p.getMEMSZ() as u64);
+            if address < vaddr || address >= vaddr.saturating_add(memsz.max(filesz)) {continue;}
+            if end > vaddr.saturating_add(filesz) {
+                return Err(format!("{:#x}..{:#x} reaches memory that is not backed by the file (.bss)", address, end));
+            }
+            return p.getOFFSET().checked_add((address - vaddr) as usize).ok_or("address range overflows".to_string());
+        }
+        Err(format!("{:#x} is outside every loadable segment", address))
+    }
+
+    //returns the address of a defined symbol from .symtab or .dynsym
+    pub fn symbolAddress(&self, name: &str) -> Option<u64> {
+        self.loader.symbols.iter().chain(self.loader.dynamicSymbols.iter())
+            .find(|s| s.name == name && !s.isUndefined() && s.st_shndx != symbol::SHN_ABS)
+            .map(|s| s.st_value as u64)
+    }
+
+    //returns the function or object symbol containing an address and the distance from its start
+    pub fn symbolAt(&self, address: u64) -> Option<(String, u64)> {
+        self.loader.symbols.iter().chain(self.loader.dynamicSymbols.iter())
+            .filter(|s| s.getType() == symbol::STT_FUNC || s.getType() == symbol::STT_OBJECT)
+            .find(|s| !s.isUndefined() && (s.st_value as u64) <= address && address < s.st_value.saturating_add(s.st_size) as u64)
+            .map(|s| (s.name.clone(), address - s.st_value as u64))
+    }
+
+    //writes bytes at a virtual address and returns the recorded patch
+    pub fn write(&mut self, address: u64, bytes: &[u8]) -> Result<&Patch, String> {
+        if bytes.is_empty() {return Err("nothing to write".to_string());}
+        let offset = self.offsetOf(address, bytes.len())?;
+        let end = offset.checked_add(bytes.len()).ok_or("address range overflows")?;
+        let old = self.loader.fileVec.get(offset..end).ok_or("segment extends past the end of the file")?.to_vec();
+        self.loader.fileVec[offset..end].copy_from_slice(bytes);
+        self.patches.push(Patch{address: address, offset: offset, symbol: self.symbolAt(address), old: old, new: bytes.to_vec()});
+        Ok(self.patches.last().unwrap())
+    }
+
+    //writes bytes at an offset from a symbol
+    pub fn writeSymbol(&mut self, name: &str, offset: u64, bytes: &[u8]) -> Result<&Patch, String> {
+        let address = self.symbolAddress(name).ok_or_else(|| format!("{}: no such symbol", name))?;
+        self.write(address.checked_add(offset).ok_or("address range overflows")?, bytes)
+    }
+
+    //reverts the most recent patch
+    pub fn undo(&mut self) -> Option<Patch> {
+        let patch = self.patches.pop()?;
+        self.loader.fileVec[patch.offset..patch.offset + patch.old.len()].copy_from_slice(&patch.old);
+        Some(patch)
+    }
+
+    //reverts every patch, newest first so overlapping patches restore the original bytes
+    pub fn undoAll(&mut self) {
+        while self.undo().is_some() {}
+    }
+
+    //returns the patches as text, one "ADDRESS SYMBOL+OFFSET OLD NEW" line each, after the build id
+    pub fn export(&self) -> String {
+        let mut text = String::from("# elfLoader patch set\n");
+        if let Some(id) = self.buildId() {text.push_str(&format!("build-id {}\n", id));}
+        for p in self.patches.iter() {
+            text.push_str(&format!("{}\n", p));
+        }
+        text
+    }
+
+    //applies an exported patch set, placing each patch by its symbol when the file has it and
+    //by address otherwise, nothing is written unless every patch finds the bytes it expects
+    pub fn import(&mut self, text: &str) -> Result<usize, String> {
+        let mut buildId = None;
+        let mut wanted = vec![];
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {continue;}
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let bad = || format!("line {}: expected ADDRESS SYMBOL+OFFSET OLD NEW", number + 1);
+            if fields.len() == 2 && fields[0] == "build-id" {
+                buildId = Some(fields[1].to_string());
+                continue;
+            }
+            if fields.len() != 4 {return Err(bad());}
+            let address = parseAddress(fields[0]).ok_or_else(bad)?;
+            let symbol = match fields[1] {
+                "-"  => None,
+                text => {
+                    let (name, offset) = text.rsplit_once('+').ok_or_else(bad)?;
+                    Some((name.to_string(), parseAddress(offset).ok_or_else(bad)?))
+                },
+            };
+            let (old, new) = (unhex(fields[2]).ok_or_else(bad)?, unhex(fields[3]).ok_or_else(bad)?);
+            if old.len() != new.len() || new.is_empty() {return Err(bad());}
+            wanted.push((number + 1, address, symbol, old, new));
+        }
+
+        //addresses only carry over to the build the set was made from
+        let sameBuild = buildId.is_none() || buildId == self.buildId();
+        let before = self.patches.len();
+        for (line, address, symbol, old, new) in wanted {
+            if let Err(e) = self.importOne(address, symbol, &old, &new, sameBuild) {
+                while self.patches.len() > before {self.undo();}
+                return Err(format!("line {}: {}", line, e));
+            }
+        }
+        Ok(self.patches.len() - before)
+    }
+
+    //applies one patch of a set, patches later in the set see the bytes written by earlier ones
+    fn importOne(&mut self, address: u64, symbol: Option<(String, u64)>, old: &[u8], new: &[u8], sameBuild: bool) -> Result<(), String> {
+        let address = match symbol.as_ref().and_then(|(name, offset)| Some((self.symbolAddress(name)?, *offset))) {
+            Some((a, offset)) => a.checked_add(offset).ok_or("address range overflows")?,
+            None if sameBuild => address,
+            None              => return Err(format!("the patch set is for another build and {:#x} has no symbol here", address)),
+        };
+        let offset = self.offsetOf(address, new.len())?;
+        let current = self.loader.fileVec.get(offset..offset.saturating_add(old.len())).unwrap_or(&[]);
+        if current != old {
+            return Err(format!("expected {} at {:#x} but found {}", hex(old), address, hex(current)));
+        }
+        self.write(address, new).map(|_| ())
+    }
+
+    fn buildId(&self) -> Option<String> {
+        self.loader.notes.iter().find(|n| n.n_type == note::NT_GNU_BUILD_ID).and_then(|n| n.buildId())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 {return None;}
+    (0..text.len()).step_by(2).map(|i| u8::from_str_radix(text.get(i..i + 2)?, 16).ok()).collect()
+}
+
+fn parseAddress(text: &str) -> Option<u64> {
+    match text.strip_prefix("0x") {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None      => text.parse().ok(),
+    }
+}
@@ -0,0 +1,177 @@
+use std::fmt;
+use std::io;
+use crate::debugfile;
+use crate::loader;
+use crate::programheader;
+use crate::symbolize;
+
+//one line of /proc/PID/maps
+#[derive(Debug, Clone, PartialEq)]
+pub struct Mapping {
+    pub start:  u64,
+    pub end:    u64,
+
+    //permissions such as r-xp, the last letter is p for private or s for shared
+    pub perms:  String,
+
+    //offset into the mapped file
+    pub offset: u64,
+
+    pub inode:  u64,
+
+    //path of the mapped file or a pseudo name such as [stack], None for anonymous memory
+    pub path:   Option<String>,
+}
+
+impl Mapping {
+
+    pub fn contains(&self, address: u64) -> bool {
+        self.start <= address && address < self.end
+    }
+
+    pub fn isReadable(&self) -> bool {
+        self.perms.starts_with('r')
+    }
+
+    //true for mappings of a real file rather than [heap], [vdso] and the like
+    pub fn isFile(&self) -> bool {
+        self.inode != 0 && self.path.as_ref().map(|p| p.starts_with('/')).unwrap_or(false)
+    }
+}
+
+//parses the text of /proc/PID/maps, skipping lines that do not look like mappings
+pub fn parseMaps(text: &str) -> Vec<Mapping> {
+    let mut mappings = vec![];
+    for line in text.lines() {
+        let mut fields = line.split_whitespace();
+        let (range, perms, offset, _device, inode) = match (fields.next(), fields.next(), fields.next(), fields.next(), fields.next()) {
+            (Some(r), Some(p), Some(o), Some(d), Some(i)) => (r, p, o, d, i),
+            _ => continue,
+        };
+        //the path is the rest of the line and may contain spaces
+        let path = fields.collect::<Vec<_>>().join(" ");
+        let mut bounds = range.splitn(2, '-');
+        let parsed = (|| Some((
+            u64::from_str_radix(bounds.next()?, 16).ok()?,
+            u64::from_str_radix(bounds.next()?, 16).ok()?,
+            u64::from_str_radix(offset, 16).ok()?,
+            inode.parse::<u64>().ok()?,
+        )))();
+        let (start, end, offset, inode) = match parsed {
+            Some(p) => p,
+            None    => continue,
+        };
+        mappings.push(Mapping {
+            start:  start,
+            end:    end,
+            perms:  perms.to_string(),
+            offset: offset,
+            inode:  inode,
+            path:   if path.is_empty() {None} else {Some(path)},
+        });
+    }
+    mappings
+}
+
+//reads the memory map of a process
+pub fn readMaps(pid: u32) -> io::Result<Vec<Mapping>> {
+    Ok(parseMaps(&std::fs::read_to_string(format!("/proc/{}/maps", pid))?))
+}
+
+//computes the load bias of a file from one of its mappings
+//the mapping offset picks the PT_LOAD segment, and the segment keeps vaddr - offset when it is mapped
+pub fn loadBias(loader: &loader::Loader, mapping: &Mapping) -> Option<u64> {
+    let segment = loader.programHeaders.iter().find(|p| {
+        let offset = p.getOFFSET() as u64;
+        p.getTYPE() == programheader::PT_LOAD && offset & !0xfff <= mapping.offset && mapping.offset < offset.saturating_add((p.getFILESZ() as u64).max(1))
+    })?;
+    let delta = (segment.getVADDR() as u64).wrapping_sub(segment.getOFFSET() as u64);
+    Some(mapping.start.wrapping_sub(mapping.offset).wrapping_sub(delta))
+}
+
+//an ELF file mapped into a process
+pub struct Module {
+    pub path:       String,
+    pub bias:       u64,
+
+    //the mappings of the file, sorted by address
+    pub mappings:   Vec<Mapping>,
+
+    pub symbolizer: symbolize::Symbolizer,
+}
+
+//an address of a process resolved to a module
+#[derive(Debug, Clone)]
+pub struct ProcessLocation {
+    pub address:  u64,
+    pub module:   String,
+    pub location: symbolize::Location,
+}
+
+impl fmt::Display for ProcessLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x} {}: {}", self.address, self.module, self.location)
+    }
+}
+
+//translates addresses of a running process to module, symbol and offset
+pub struct ProcessSymbolizer {
+    pub pid:      u32,
+    pub mappings: Vec<Mapping>,
+    pub modules:  Vec<Module>,
+}
+
+impl ProcessSymbolizer {
+
+    //reads the maps of a process and opens every mapped ELF file once
+    pub fn new(pid: u32) -> io::Result<Self> {
+        let mappings = readMaps(pid)?;
+        let mut modules: Vec<Module> = vec![];
+        //debug files are looked up inside the process's root directory when it can be read
+        let root = format!("/proc/{}/root", pid);
+        let sysroot = if std::fs::read_dir(&root).is_ok() {root} else {"/".to_string()};
+        let locator = debugfile::DebugLocator::new(std::path::Path::new(&sysroot));
+        for m in mappings.iter().filter(|m| m.isFile()) {
+            let path = m.path.clone().unwrap_or_default();
+            if let Some(module) = modules.iter_mut().find(|module| module.path == path) {
+                module.mappings.push(m.clone());
+                continue;
+            }
+            //the file may have been replaced on disk, the mapped copy is still reachable through map_files
+            let bytes = std::fs::read(format!("/proc/{}/map_files/{:x}-{:x}", pid, m.start, m.end))
+                .or_else(|_| std::fs::read(format!("/proc/{}/root{}", pid, path)))
+                .or_else(|_| std::fs::read(&path));
+            let bytes = match bytes {
+                Ok(b) if loader::Loader::validate(&b).is_ok() => b,
+                _ => continue,
+            };
+            let mut parser = loader::Loader::fromBytes(bytes);
+            parser.load();
+            let bias = match loadBias(&parser, m) {
+                Some(b) => b,
+                None    => continue,
+            };
+            //stripped libraries may have their debug information installed separately
+            let debug = if debugfile::hasDebugInfo(&parser) {None} else {locator.locate(&parser, &path).map(|d| d.loader)};
+            modules.push(Module {
+                path:       path,
+                bias:       bias,
+                mappings:   vec![m.clone()],
+                symbolizer: symbolize::Symbolizer::withDebugFile(parser, debug),
+            });
+        }
+        Ok(Self{pid: pid, mappings: mappings, modules: modules})
+    }
+
+    //returns the module mapped at an address
+    pub fn moduleAt(&self, address: u64) -> Option<&Module> {
+        self.modules.iter().find(|m| m.mappings.iter().any(|mapping| mapping.contains(address)))
+    }
+
+    //resolves an address of the process, None when no ELF file is mapped there
+    pub fn symbolize(&self, address: u64) -> Option<ProcessLocation> {
+        let module = self.moduleAt(address)?;
+        let location = module.symbolizer.symbolize(address, module.bias)?;
+        Some(ProcessLocation{address: address, module: module.path.clone(), location: location})
+    }
+}
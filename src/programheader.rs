@@ -1,5 +1,24 @@
 
-#[derive(Debug)]
+//segment types found in p_type
+pub const PT_NULL:         u32 = 0;
+pub const PT_LOAD:         u32 = 1;
+pub const PT_DYNAMIC:      u32 = 2;
+pub const PT_INTERP:       u32 = 3;
+pub const PT_NOTE:         u32 = 4;
+pub const PT_SHLIB:        u32 = 5;
+pub const PT_PHDR:         u32 = 6;
+pub const PT_TLS:          u32 = 7;
+pub const PT_GNU_EH_FRAME: u32 = 0x6474e550;
+pub const PT_GNU_STACK:    u32 = 0x6474e551;
+pub const PT_GNU_RELRO:    u32 = 0x6474e552;
+pub const PT_GNU_PROPERTY: u32 = 0x6474e553;
+
+//segment permission bits found in p_flags
+pub const PF_X: u32 = 1<<0;
+pub const PF_W: u32 = 1<<1;
+pub const PF_R: u32 = 1<<2;
+
+#[derive(Debug, Clone)]
 pub struct ProgramHeader32 {
 
     //findicates what type of segment this array element describes
@@ -29,7 +48,7 @@ pub struct ProgramHeader32 {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct ProgramHeader64 {
     pub p_type:   u32,
     pub p_flags:  u32,
@@ -42,7 +61,7 @@ pub struct ProgramHeader64 {
 }
 
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub enum ProgramHeader {
     ProgramHeader32(ProgramHeader32),
     ProgramHeader64(ProgramHeader64),
@@ -132,49 +151,49 @@ impl ProgramHeader {
         }
     }
 
-    pub fn getTYPE(&mut self) -> u32 {
+    pub fn getTYPE(&self) -> u32 {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_type,
             ProgramHeader::ProgramHeader64(h) => h.p_type,
         }
     }
-    pub fn getFLAGS(&mut self) -> u32{
+    pub fn getFLAGS(&self) -> u32{
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_flags,
             ProgramHeader::ProgramHeader64(h) => h.p_flags,
         }
     }
-    pub fn getOFFSET(&mut self) -> usize {
+    pub fn getOFFSET(&self) -> usize {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_offset,
             ProgramHeader::ProgramHeader64(h) => h.p_offset,
         }
     }
-    pub fn getVADDR(&mut self) -> usize {
+    pub fn getVADDR(&self) -> usize {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_vaddr,
             ProgramHeader::ProgramHeader64(h) => h.p_vaddr,
         }
     }
-    pub fn getPADDR(&mut self) -> usize {
+    pub fn getPADDR(&self) -> usize {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_paddr,
             ProgramHeader::ProgramHeader64(h) => h.p_paddr,
         }
     }
-    pub fn getFILESZ(&mut self) -> usize {
+    pub fn getFILESZ(&self) -> usize {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_filesz,
             ProgramHeader::ProgramHeader64(h) => h.p_filesz,
         }
     }
-    pub fn getMEMSZ(&mut self) -> usize  {
+    pub fn getMEMSZ(&self) -> usize  {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_memsz,
             ProgramHeader::ProgramHeader64(h) => h.p_memsz,
         }
     }
-    pub fn getALIGN(&mut self) -> usize {
+    pub fn getALIGN(&self) -> usize {
         match self {
             ProgramHeader::ProgramHeader32(h) => h.p_align,
             ProgramHeader::ProgramHeader64(h) => h.p_align,
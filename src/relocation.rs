@@ -0,0 +1,68 @@
+use crate::header;
+
+//relocation types that add the load bias to the addend, one per machine
+pub const R_386_RELATIVE:     u32 = 8;
+pub const R_X86_64_RELATIVE:  u32 = 8;
+pub const R_ARM_RELATIVE:     u32 = 23;
+pub const R_AARCH64_RELATIVE: u32 = 1027;
+pub const R_RISCV_RELATIVE:   u32 = 3;
+
+#[derive(Debug, Clone)]
+pub struct Relocation {
+
+    //virtual address of the location to relocate, a section offset in relocatable files
+    pub r_offset: usize,
+
+    //symbol index and relocation type packed together, the split depends on the class
+    pub r_info:   usize,
+
+    //explicit addend for SHT_RELA entries, None for SHT_REL entries where it is stored in place
+    pub r_addend: Option<i64>,
+
+    //index of the relocation section the entry came from
+    pub section:  usize,
+
+    //class of the file, needed to unpack r_info
+    pub class:    u8,
+}
+
+impl Relocation {
+
+    //returns an uninitialized relocation
+    pub fn new() -> Self {
+        Self {
+            r_offset: 0,
+            r_info:   0,
+            r_addend: None,
+            section:  0,
+            class:    2,
+        }
+    }
+
+    pub fn getSymbol(&self) -> usize {
+        match self.class {
+            1 => self.r_info >> 8,
+            _ => self.r_info >> 32,
+        }
+    }
+
+    pub fn getType(&self) -> u32 {
+        match self.class {
+            1 => (self.r_info & 0xff) as u32,
+            _ => (self.r_info & 0xffffffff) as u32,
+        }
+    }
+
+    //true if this relocation only adds the load bias, so the addend is the link time target
+    pub fn isRelative(&self, e_machine: u16) -> bool {
+        let t = self.getType();
+        match e_machine {
+            header::EM_X86_64  => t == R_X86_64_RELATIVE,
+            header::EM_386     => t == R_386_RELATIVE,
+            header::EM_ARM     => t == R_ARM_RELATIVE,
+            header::EM_AARCH64 => t == R_AARCH64_RELATIVE,
+            header::EM_RISCV   => t == R_RISCV_RELATIVE,
+            _                  => false,
+        }
+    }
+}
@@ -0,0 +1,603 @@
+use crate::addressspace;
+use crate::header;
+use crate::loader;
+use crate::programheader;
+use crate::stack;
+use crate::syscall;
+
+//top of the user address space with sv39 paging, the stack is placed right below it
+pub const STACK_TOP:  usize = 0x3f_ffff_f000;
+pub const STACK_SIZE: usize = 8 << 20;
+
+//reasons the interpreter stops executing
+#[derive(Debug, PartialEq)]
+pub enum Trap {
+    //the instruction at pc could not be decoded
+    IllegalInstruction{pc: u64, insn: u32},
+
+    //an access touched unmapped memory or memory without the needed permission
+    FetchFault(u64),
+    LoadFault(u64),
+    StoreFault(u64),
+
+    //an ebreak was executed at pc
+    Breakpoint(u64),
+
+    //an ecall was executed, pc already points past it
+    Ecall,
+
+    //the program called exit or exit_group
+    Exit(i32),
+
+    //the ELF is not a RV64 executable or its segments could not be mapped
+    BadImage,
+}
+
+//state of a single RV64IMAC hart
+pub struct Cpu {
+    //integer registers, x[0] is forced to zero after every instruction
+    pub x:           [u64; 32],
+
+    //address of the next instruction
+    pub pc:          u64,
+
+    //address reserved by the last LR, cleared by SC
+    pub reservation: Option<u64>,
+
+    //number of instructions retired, also read back through the cycle/time/instret csrs
+    pub instret:     u64,
+}
+
+impl Cpu {
+
+    //creates a hart that starts at entry with the stack pointer set to sp
+    pub fn new(entry: u64, sp: u64) -> Self {
+        let mut cpu = Self {
+            x:           [0; 32],
+            pc:          entry,
+            reservation: None,
+            instret:     0,
+        };
+        cpu.x[2] = sp;
+        cpu
+    }
+
+    //runs until the program exits or a trap other than an ecall happens
+    //ecalls are passed to the syscall handler
+    pub fn run(&mut self, space: &mut addressspace::AddressSpace, os: &mut dyn syscall::SyscallHandler) -> Result<i32, Trap> {
+        loop {
+            match self.step(space) {
+                Ok(())           => {},
+                Err(Trap::Ecall) => {
+                    if let Some(code) = os.syscall(self, space) {
+                        return Ok(code);
+                    }
+                },
+                Err(trap)        => return Err(trap),
+            }
+        }
+    }
+
+    //fetches, decodes and executes a single instruction
+    pub fn step(&mut self, space: &mut addressspace::AddressSpace) -> Result<(), Trap> {
+        let pc = self.pc;
+        let low = self.fetch(space, pc, 2)? as u32;
+        let (insn, length) = if low & 0b11 == 0b11 {
+            (self.fetch(space, pc, 4)? as u32, 4)
+        } else {
+            match expandCompressed(low as u16) {
+                Some(insn) => (insn, 2),
+                None       => return Err(Trap::IllegalInstruction{pc: pc, insn: low}),
+            }
+        };
+        self.pc = pc.wrapping_add(length);
+        let result = self.execute(space, insn, pc, length);
+        self.x[0] = 0;
+        if result.is_ok() || result == Err(Trap::Ecall) {
+            self.instret += 1;
+        }
+        result
+    }
+
+    //executes an uncompressed instruction located at pc
+    fn execute(&mut self, space: &mut addressspace::AddressSpace, insn: u32, pc: u64, length: u64) -> Result<(), Trap> {
+        let opcode = insn & 0x7f;
+        let rd = ((insn >> 7) & 0x1f) as usize;
+        let funct3 = (insn >> 12) & 0x7;
+        let rs1 = ((insn >> 15) & 0x1f) as usize;
+        let rs2 = ((insn >> 20) & 0x1f) as usize;
+        let funct7 = insn >> 25;
+        let illegal = Trap::IllegalInstruction{pc: pc, insn: insn};
+
+        let immI = ((insn as i32) >> 20) as i64 as u64;
+        let immS = ((((insn as i32) >> 25) << 5) as u32 | ((insn >> 7) & 0x1f)) as i32 as i64 as u64;
+        let immB = ((((insn as i32) >> 31) << 12) as u32
+            | ((insn & 0x80) << 4)
+            | ((insn >> 20) & 0x7e0)
+            | ((insn >> 7) & 0x1e)) as i32 as i64 as u64;
+        let immU = (insn & 0xfffff000) as i32 as i64 as u64;
+        let immJ = ((((insn as i32) >> 31) << 20) as u32
+            | (insn & 0xff000)
+            | ((insn >> 9) & 0x800)
+            | ((insn >> 20) & 0x7fe)) as i32 as i64 as u64;
+
+        let a = self.x[rs1];
+        let b = self.x[rs2];
+
+        match opcode {
+            //LUI
+            0x37 => self.x[rd] = immU,
+            //AUIPC
+            0x17 => self.x[rd] = pc.wrapping_add(immU),
+            //JAL
+            0x6f => {
+                self.x[rd] = pc.wrapping_add(length);
+                self.pc = pc.wrapping_add(immJ);
+            },
+            //JALR
+            0x67 => {
+                if funct3 != 0 {return Err(illegal);}
+                let target = a.wrapping_add(immI) & !1;
+                self.x[rd] = pc.wrapping_add(length);
+                self.pc = target;
+            },
+            //BRANCH
+            0x63 => {
+                let taken = match funct3 {
+                    0 => a == b,
+                    1 => a != b,
+                    4 => (a as i64) < (b as i64),
+                    5 => (a as i64) >= (b as i64),
+                    6 => a < b,
+                    7 => a >= b,
+                    _ => return Err(illegal),
+                };
+                if taken {self.pc = pc.wrapping_add(immB);}
+            },
+            //LOAD
+            0x03 => {
+                let addr = a.wrapping_add(immI);
+                self.x[rd] = match funct3 {
+                    0 => self.load(space, addr, 1)? as i8 as i64 as u64,
+                    1 => self.load(space, addr, 2)? as i16 as i64 as u64,
+                    2 => self.load(space, addr, 4)? as i32 as i64 as u64,
+                    3 => self.load(space, addr, 8)?,
+                    4 => self.load(space, addr, 1)?,
+                    5 => self.load(space, addr, 2)?,
+                    6 => self.load(space, addr, 4)?,
+                    _ => return Err(illegal),
+                };
+            },
+            //STORE
+            0x23 => {
+                let addr = a.wrapping_add(immS);
+                match funct3 {
+                    0 => self.store(space, addr, 1, b)?,
+                    1 => self.store(space, addr, 2, b)?,
+                    2 => self.store(space, addr, 4, b)?,
+                    3 => self.store(space, addr, 8, b)?,
+                    _ => return Err(illegal),
+                }
+            },
+            //OP-IMM
+            0x13 => {
+                let shamt = (insn >> 20) & 0x3f;
+                self.x[rd] = match funct3 {
+                    0 => a.wrapping_add(immI),
+                    1 if funct7 >> 1 == 0 => a << shamt,
+                    2 => ((a as i64) < (immI as i64)) as u64,
+                    3 => (a < immI) as u64,
+                    4 => a ^ immI,
+                    5 if funct7 >> 1 == 0x00 => a >> shamt,
+                    5 if funct7 >> 1 == 0x10 => ((a as i64) >> shamt) as u64,
+                    6 => a | immI,
+                    7 => a & immI,
+                    _ => return Err(illegal),
+                };
+            },
+            //OP
+            0x33 => {
+                self.x[rd] = match (funct7, funct3) {
+                    (0x00, 0) => a.wrapping_add(b),
+                    (0x20, 0) => a.wrapping_sub(b),
+                    (0x00, 1) => a << (b & 0x3f),
+                    (0x00, 2) => ((a as i64) < (b as i64)) as u64,
+                    (0x00, 3) => (a < b) as u64,
+                    (0x00, 4) => a ^ b,
+                    (0x00, 5) => a >> (b & 0x3f),
+                    (0x20, 5) => ((a as i64) >> (b & 0x3f)) as u64,
+                    (0x00, 6) => a | b,
+                    (0x00, 7) => a & b,
+                    (0x01, f) => mulDiv(f, a, b),
+                    _ => return Err(illegal),
+                };
+            },
+            //OP-IMM-32
+            0x1b => {
+                let shamt = (insn >> 20) & 0x1f;
+                let value = match (funct3, funct7) {
+                    (0, _)    => (a as u32).wrapping_add(immI as u32),
+                    (1, 0x00) => (a as u32) << shamt,
+                    (5, 0x00) => (a as u32) >> shamt,
+                    (5, 0x20) => ((a as i32) >> shamt) as u32,
+                    _ => return Err(illegal),
+                };
+                self.x[rd] = value as i32 as i64 as u64;
+            },
+            //OP-32
+            0x3b => {
+                let (a32, b32) = (a as u32, b as u32);
+                let value = match (funct7, funct3) {
+                    (0x00, 0) => a32.wrapping_add(b32),
+                    (0x20, 0) => a32.wrapping_sub(b32),
+                    (0x00, 1) => a32 << (b32 & 0x1f),
+                    (0x00, 5) => a32 >> (b32 & 0x1f),
+                    (0x20, 5) => ((a32 as i32) >> (b32 & 0x1f)) as u32,
+                    (0x01, 0) => a32.wrapping_mul(b32),
+                    (0x01, 4) => {
+                        let (x, y) = (a32 as i32, b32 as i32);
+                        if y == 0 {u32::MAX} else {x.wrapping_div(y) as u32}
+                    },
+                    (0x01, 5) => if b32 == 0 {u32::MAX} else {a32 / b32},
+                    (0x01, 6) => {
+                        let (x, y) = (a32 as i32, b32 as i32);
+                        if y == 0 {a32} else {x.wrapping_rem(y) as u32}
+                    },
+                    (0x01, 7) => if b32 == 0 {a32} else {a32 % b32},
+                    _ => return Err(illegal),
+                };
+                self.x[rd] = value as i32 as i64 as u64;
+            },
+            //MISC-MEM, fences are no-ops with a single hart
+            0x0f => {},
+            //SYSTEM
+            0x73 => {
+                match funct3 {
+                    0 => {
+                        match insn >> 20 {
+                            0 if rs1 == 0 && rd == 0 => return Err(Trap::Ecall),
+                            1 if rs1 == 0 && rd == 0 => {
+                                self.pc = pc;
+                                return Err(Trap::Breakpoint(pc));
+                            },
+                            _ => return Err(illegal),
+                        }
+                    },
+                    1 | 2 | 3 | 5 | 6 | 7 => {
+                        //only the read only counters and the floating point status are
+                        //supported, writes to the status are ignored. CSRRW always writes,
+                        //CSRRS and CSRRC only with a nonzero rs1 or immediate
+                        let csr = insn >> 20;
+                        let writes = funct3 & 3 == 1 || rs1 != 0;
+                        let value = match csr {
+                            0x001 | 0x002 | 0x003 => 0,
+                            0xc00 | 0xc01 | 0xc02 => self.instret,
+                            _ => return Err(illegal),
+                        };
+                        //csrs numbered 0xc00 and up are read only
+                        if writes && csr >> 10 == 3 {return Err(illegal);}
+                        self.x[rd] = value;
+                    },
+                    _ => return Err(illegal),
+                }
+            },
+            //AMO
+            0x2f => self.atomic(space, insn, funct3, rd, a, b, illegal)?,
+            _ => return Err(illegal),
+        }
+        Ok(())
+    }
+
+    //executes LR, SC and the AMO instructions
+    fn atomic(&mut self, space: &mut addressspace::AddressSpace, insn: u32, funct3: u32, rd: usize, addr: u64, b: u64, illegal: Trap) -> Result<(), Trap> {
+        let size = match funct3 {
+            2 => 4,
+            3 => 8,
+            _ => return Err(illegal),
+        };
+        //a misaligned LR is a load, SC and the AMOs are stores
+        if addr % size != 0 {
+            return Err(if insn >> 27 == 0x02 {Trap::LoadFault(addr)} else {Trap::StoreFault(addr)});
+        }
+
+        //sign extends a loaded value to 64 bits
+        let extend = |v: u64| if size == 4 {v as i32 as i64 as u64} else {v};
+        let funct5 = insn >> 27;
+        match funct5 {
+            //LR
+            0x02 => {
+                self.x[rd] = extend(self.load(space, addr, size)?);
+                self.reservation = Some(addr);
+            },
+            //SC
+            0x03 => {
+                if self.reservation == Some(addr) {
+                    self.store(space, addr, size, b)?;
+                    self.x[rd] = 0;
+                } else {
+                    self.x[rd] = 1;
+                }
+                self.reservation = None;
+            },
+            _ => {
+                let old = extend(self.load(space, addr, size)?);
+                let new = match funct5 {
+                    0x01 => b,
+                    0x00 => old.wrapping_add(b),
+                    0x04 => old ^ b,
+                    0x0c => old & b,
+                    0x08 => old | b,
+                    0x10 => if (old as i64) < (extend(b) as i64) {old} else {b},
+                    0x14 => if (old as i64) > (extend(b) as i64) {old} else {b},
+                    0x18 => if size == 4 {if (old as u32) < (b as u32) {old} else {b}} else if old < b {old} else {b},
+                    0x1c => if size == 4 {if (old as u32) > (b as u32) {old} else {b}} else if old > b {old} else {b},
+                    _ => return Err(illegal),
+                };
+                self.store(space, addr, size, new)?;
+                self.x[rd] = old;
+            },
+        }
+        Ok(())
+    }
+
+    //reads size bytes for instruction fetch, the region must be executable
+    fn fetch(&self, space: &addressspace::AddressSpace, addr: u64, size: u64) -> Result<u64, Trap> {
+        match space.region(addr as usize) {
+            Some(r) if r.flags & programheader::PF_X != 0 => {},
+            _ => return Err(Trap::FetchFault(addr)),
+        }
+        space.readUValue(addr as usize, size as usize).ok_or(Trap::FetchFault(addr))
+    }
+
+    //reads size bytes for a load, the region must be readable
+    fn load(&self, space: &addressspace::AddressSpace, addr: u64, size: u64) -> Result<u64, Trap> {
+        match space.region(addr as usize) {
+            Some(r) if r.flags & programheader::PF_R != 0 => {},
+            _ => return Err(Trap::LoadFault(addr)),
+        }
+        space.readUValue(addr as usize, size as usize).ok_or(Trap::LoadFault(addr))
+    }
+
+    //writes the low size bytes of value, the region must be writable
+    fn store(&mut self, space: &mut addressspace::AddressSpace, addr: u64, size: u64, value: u64) -> Result<(), Trap> {
+        match space.region(addr as usize) {
+            Some(r) if r.flags & programheader::PF_W != 0 => {},
+            _ => return Err(Trap::StoreFault(addr)),
+        }
+        //any store to the reserved address breaks the reservation
+        if self.reservation == Some(addr) {self.reservation = None;}
+        space.writeUValue(addr as usize, size as usize, value).ok_or(Trap::StoreFault(addr))
+    }
+}
+
+//linux riscv calling convention, number in a7, arguments in a0-a5 and the result in a0
+impl syscall::SyscallCpu for Cpu {
+    fn syscallNumber(&self) -> u64 {
+        self.x[17]
+    }
+    fn syscallArg(&self, index: usize) -> u64 {
+        self.x[10 + index]
+    }
+    fn setSyscallResult(&mut self, value: i64) {
+        self.x[10] = value as u64;
+    }
+}
+
+//executes the M extension instruction selected by funct3 on 64 bit operands
+fn mulDiv(funct3: u32, a: u64, b: u64) -> u64 {
+    match funct3 {
+        0 => a.wrapping_mul(b),
+        1 => ((a as i64 as i128 * b as i64 as i128) >> 64) as u64,
+        2 => ((a as i64 as i128 * b as u128 as i128) >> 64) as u64,
+        3 => ((a as u128 * b as u128) >> 64) as u64,
+        4 => if b == 0 {u64::MAX} else {(a as i64).wrapping_div(b as i64) as u64},
+        5 => if b == 0 {u64::MAX} else {a / b},
+        6 => if b == 0 {a} else {(a as i64).wrapping_rem(b as i64) as u64},
+        _ => if b == 0 {a} else {a % b},
+    }
+}
+
+//helpers for building 32 bit instructions out of their fields
+fn encodeR(funct7: u32, rs2: u32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    (funct7 << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+fn encodeI(imm: i32, rs1: u32, funct3: u32, rd: u32, opcode: u32) -> u32 {
+    ((imm as u32 & 0xfff) << 20) | (rs1 << 15) | (funct3 << 12) | (rd << 7) | opcode
+}
+fn encodeS(imm: i32, rs2: u32, rs1: u32, funct3: u32, opcode: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 5) & 0x7f) << 25) | (rs2 << 20) | (rs1 << 15) | (funct3 << 12) | ((imm & 0x1f) << 7) | opcode
+}
+fn encodeB(imm: i32, rs2: u32, rs1: u32, funct3: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 12) & 1) << 31) | (((imm >> 5) & 0x3f) << 25) | (rs2 << 20) | (rs1 << 15)
+        | (funct3 << 12) | (((imm >> 1) & 0xf) << 8) | (((imm >> 11) & 1) << 7) | 0x63
+}
+fn encodeJ(imm: i32, rd: u32) -> u32 {
+    let imm = imm as u32;
+    (((imm >> 20) & 1) << 31) | (((imm >> 1) & 0x3ff) << 21) | (((imm >> 11) & 1) << 20)
+        | (((imm >> 12) & 0xff) << 12) | (rd << 7) | 0x6f
+}
+
+//returns bits hi..=lo of value shifted down to bit 0
+fn bits(value: u32, hi: u32, lo: u32) -> u32 {
+    (value >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+//sign extends the low width bits of value
+fn signExtend(value: u32, width: u32) -> i32 {
+    ((value << (32 - width)) as i32) >> (32 - width)
+}
+
+//expands a 16 bit RVC instruction into the equivalent 32 bit instruction
+//returns None for reserved encodings and the floating point loads and stores
+pub fn expandCompressed(c: u16) -> Option<u32> {
+    let c = c as u32;
+    let funct3 = bits(c, 15, 13);
+    //registers x8-x15 used by the 3 bit register fields
+    let rdP = bits(c, 4, 2) + 8;
+    let rs1P = bits(c, 9, 7) + 8;
+    let rd = bits(c, 11, 7);
+    let rs2 = bits(c, 6, 2);
+
+    match (bits(c, 1, 0), funct3) {
+        //C.ADDI4SPN
+        (0, 0) => {
+            let imm = (bits(c, 12, 11) << 4) | (bits(c, 10, 7) << 6) | (bits(c, 6, 6) << 2) | (bits(c, 5, 5) << 3);
+            if imm == 0 {return None;}
+            Some(encodeI(imm as i32, 2, 0, rdP, 0x13))
+        },
+        //C.LW
+        (0, 2) => {
+            let imm = (bits(c, 12, 10) << 3) | (bits(c, 6, 6) << 2) | (bits(c, 5, 5) << 6);
+            Some(encodeI(imm as i32, rs1P, 2, rdP, 0x03))
+        },
+        //C.LD
+        (0, 3) => {
+            let imm = (bits(c, 12, 10) << 3) | (bits(c, 6, 5) << 6);
+            Some(encodeI(imm as i32, rs1P, 3, rdP, 0x03))
+        },
+        //C.SW
+        (0, 6) => {
+            let imm = (bits(c, 12, 10) << 3) | (bits(c, 6, 6) << 2) | (bits(c, 5, 5) << 6);
+            Some(encodeS(imm as i32, rdP, rs1P, 2, 0x23))
+        },
+        //C.SD
+        (0, 7) => {
+            let imm = (bits(c, 12, 10) << 3) | (bits(c, 6, 5) << 6);
+            Some(encodeS(imm as i32, rdP, rs1P, 3, 0x23))
+        },
+        //C.ADDI and C.NOP
+        (1, 0) => {
+            let imm = signExtend((bits(c, 12, 12) << 5) | bits(c, 6, 2), 6);
+            Some(encodeI(imm, rd, 0, rd, 0x13))
+        },
+        //C.ADDIW
+        (1, 1) => {
+            if rd == 0 {return None;}
+            let imm = signExtend((bits(c, 12, 12) << 5) | bits(c, 6, 2), 6);
+            Some(encodeI(imm, rd, 0, rd, 0x1b))
+        },
+        //C.LI
+        (1, 2) => {
+            let imm = signExtend((bits(c, 12, 12) << 5) | bits(c, 6, 2), 6);
+            Some(encodeI(imm, 0, 0, rd, 0x13))
+        },
+        //C.ADDI16SP and C.LUI
+        (1, 3) => {
+            if rd == 2 {
+                let imm = (bits(c, 12, 12) << 9) | (bits(c, 6, 6) << 4) | (bits(c, 5, 5) << 6)
+                    | (bits(c, 4, 3) << 7) | (bits(c, 2, 2) << 5);
+                if imm == 0 {return None;}
+                Some(encodeI(signExtend(imm, 10), 2, 0, 2, 0x13))
+            } else {
+                let imm = signExtend((bits(c, 12, 12) << 17) | (bits(c, 6, 2) << 12), 18);
+                if imm == 0 {return None;}
+                Some(((imm as u32) & 0xfffff000) | (rd << 7) | 0x37)
+            }
+        },
+        //arithmetic on the compressed registers
+        (1, 4) => {
+            let shamt = (bits(c, 12, 12) << 5) | bits(c, 6, 2);
+            match bits(c, 11, 10) {
+                0 => Some(encodeI(shamt as i32, rs1P, 5, rs1P, 0x13)),
+                1 => Some(encodeI((0x400 | shamt) as i32, rs1P, 5, rs1P, 0x13)),
+                2 => {
+                    let imm = signExtend((bits(c, 12, 12) << 5) | bits(c, 6, 2), 6);
+                    Some(encodeI(imm, rs1P, 7, rs1P, 0x13))
+                },
+                _ => {
+                    match (bits(c, 12, 12), bits(c, 6, 5)) {
+                        (0, 0) => Some(encodeR(0x20, rdP, rs1P, 0, rs1P, 0x33)),
+                        (0, 1) => Some(encodeR(0x00, rdP, rs1P, 4, rs1P, 0x33)),
+                        (0, 2) => Some(encodeR(0x00, rdP, rs1P, 6, rs1P, 0x33)),
+                        (0, 3) => Some(encodeR(0x00, rdP, rs1P, 7, rs1P, 0x33)),
+                        (1, 0) => Some(encodeR(0x20, rdP, rs1P, 0, rs1P, 0x3b)),
+                        (1, 1) => Some(encodeR(0x00, rdP, rs1P, 0, rs1P, 0x3b)),
+                        _      => None,
+                    }
+                },
+            }
+        },
+        //C.J
+        (1, 5) => {
+            let imm = (bits(c, 12, 12) << 11) | (bits(c, 11, 11) << 4) | (bits(c, 10, 9) << 8)
+                | (bits(c, 8, 8) << 10) | (bits(c, 7, 7) << 6) | (bits(c, 6, 6) << 7)
+                | (bits(c, 5, 3) << 1) | (bits(c, 2, 2) << 5);
+            Some(encodeJ(signExtend(imm, 12), 0))
+        },
+        //C.BEQZ and C.BNEZ
+        (1, 6) | (1, 7) => {
+            let imm = (bits(c, 12, 12) << 8) | (bits(c, 11, 10) << 3) | (bits(c, 6, 5) << 6)
+                | (bits(c, 4, 3) << 1) | (bits(c, 2, 2) << 5);
+            Some(encodeB(signExtend(imm, 9), 0, rs1P, funct3 - 6))
+        },
+        //C.SLLI
+        (2, 0) => {
+            let shamt = (bits(c, 12, 12) << 5) | bits(c, 6, 2);
+            Some(encodeI(shamt as i32, rd, 1, rd, 0x13))
+        },
+        //C.LWSP
+        (2, 2) => {
+            if rd == 0 {return None;}
+            let imm = (bits(c, 12, 12) << 5) | (bits(c, 6, 4) << 2) | (bits(c, 3, 2) << 6);
+            Some(encodeI(imm as i32, 2, 2, rd, 0x03))
+        },
+        //C.LDSP
+        (2, 3) => {
+            if rd == 0 {return None;}
+            let imm = (bits(c, 12, 12) << 5) | (bits(c, 6, 5) << 3) | (bits(c, 4, 2) << 6);
+            Some(encodeI(imm as i32, 2, 3, rd, 0x03))
+        },
+        //C.JR, C.MV, C.EBREAK, C.JALR and C.ADD
+        (2, 4) => {
+            match (bits(c, 12, 12), rd, rs2) {
+                (0, 0, 0) => None,
+                (0, _, 0) => Some(encodeI(0, rd, 0, 0, 0x67)),
+                (0, _, _) => Some(encodeR(0, rs2, 0, 0, rd, 0x33)),
+                (1, 0, 0) => Some(0x00100073),
+                (1, _, 0) => Some(encodeI(0, rd, 0, 1, 0x67)),
+                _         => Some(encodeR(0, rs2, rd, 0, rd, 0x33)),
+            }
+        },
+        //C.SWSP
+        (2, 6) => {
+            let imm = (bits(c, 12, 9) << 2) | (bits(c, 8, 7) << 6);
+            Some(encodeS(imm as i32, rs2, 2, 2, 0x23))
+        },
+        //C.SDSP
+        (2, 7) => {
+            let imm = (bits(c, 12, 10) << 3) | (bits(c, 9, 7) << 6);
+            Some(encodeS(imm as i32, rs2, 2, 3, 0x23))
+        },
+        _ => None,
+    }
+}
+
+//loads a static RV64 executable into a fresh address space and builds its initial stack
+//returns a hart positioned at e_entry with sp at argc, ready to run or to hand to the gdb stub
+pub fn boot(loader: &loader::Loader, argv: &[&str], envp: &[&str]) -> Result<(Cpu, addressspace::AddressSpace), Trap> {
+    if loader.header.e_machine != header::EM_RISCV || loader.header.e_ident.Class != 2 {
+        return Err(Trap::BadImage);
+    }
+    let mut space = match addressspace::AddressSpace::fromLoader(loader, 0) {
+        Some(s) => s,
+        None    => return Err(Trap::BadImage),
+    };
+    if space.map(STACK_TOP - STACK_SIZE, STACK_SIZE, programheader::PF_R | programheader::PF_W).is_none() {
+        return Err(Trap::BadImage);
+    }
+    let init = stack::InitialStack::new(argv, envp, STACK_TOP);
+    let sp = match init.build(loader, &mut space) {
+        Some(sp) => sp,
+        None     => return Err(Trap::BadImage),
+    };
+    Ok((Cpu::new(loader.header.e_entry as u64, sp as u64), space))
+}
+
+//loads a static RV64 executable, builds its initial stack and runs it to completion
+//syscalls go to os, returns the exit code passed to exit or the trap that stopped it
+pub fn runProgram(loader: &loader::Loader, argv: &[&str], envp: &[&str], os: &mut dyn syscall::SyscallHandler) -> Result<i32, Trap> {
+    let (mut cpu, mut space) = boot(loader, argv, envp)?;
+    cpu.run(&mut space, os)
+}
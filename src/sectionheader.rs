@@ -2,7 +2,38 @@ use crate::header;
 use crate::programheader;
 use crate::loader;
 
-#[derive(Debug)]
+//section types found in sh_type
+pub const SHT_NULL:          u32 = 0;
+pub const SHT_PROGBITS:      u32 = 1;
+pub const SHT_SYMTAB:        u32 = 2;
+pub const SHT_STRTAB:        u32 = 3;
+pub const SHT_RELA:          u32 = 4;
+pub const SHT_HASH:          u32 = 5;
+pub const SHT_DYNAMIC:       u32 = 6;
+pub const SHT_NOTE:          u32 = 7;
+pub const SHT_NOBITS:        u32 = 8;
+pub const SHT_REL:           u32 = 9;
+pub const SHT_DYNSYM:        u32 = 11;
+pub const SHT_INIT_ARRAY:    u32 = 14;
+pub const SHT_FINI_ARRAY:    u32 = 15;
+pub const SHT_PREINIT_ARRAY: u32 = 16;
+pub const SHT_GROUP:         u32 = 17;
+pub const SHT_GNU_HASH:      u32 = 0x6ffffff6;
+pub const SHT_GNU_VERDEF:    u32 = 0x6ffffffd;
+pub const SHT_GNU_VERNEED:   u32 = 0x6ffffffe;
+pub const SHT_GNU_VERSYM:    u32 = 0x6fffffff;
+
+//section attribute bits found in sh_flags
+pub const SHF_WRITE:     usize = 0x1;
+pub const SHF_ALLOC:     usize = 0x2;
+pub const SHF_EXECINSTR: usize = 0x4;
+pub const SHF_MERGE:     usize = 0x10;
+pub const SHF_STRINGS:   usize = 0x20;
+pub const SHF_INFO_LINK: usize = 0x40;
+pub const SHF_TLS:       usize = 0x400;
+pub const SHF_COMPRESSED: usize = 0x800;
+
+#[derive(Debug, Clone)]
 
 pub struct SectionHeader {
 
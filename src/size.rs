@@ -0,0 +1,416 @@
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::fmt;
+use crate::dwarf;
+use crate::loader;
+use crate::programheader;
+use crate::sectionheader;
+use crate::symbol;
+
+//what the bytes of a file are attributed to
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Source {
+    Segments,
+    Sections,
+    Symbols,
+    //source files from DWARF compile units, or STT_FILE symbols without debug information
+    Files,
+}
+
+impl Source {
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "segments"               => Some(Source::Segments),
+            "sections"               => Some(Source::Sections),
+            "symbols"                => Some(Source::Symbols),
+            "files" | "compileunits" => Some(Source::Files),
+            _                        => None,
+        }
+    }
+}
+
+//how items are ordered, largest first except by name
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    //the larger of the file and VM size, like bloaty
+    Both,
+    File,
+    Vm,
+    Name,
+}
+
+impl SortKey {
+
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "both" => Some(SortKey::Both),
+            "file" => Some(SortKey::File),
+            "vm"   => Some(SortKey::Vm),
+            "name" => Some(SortKey::Name),
+            _      => None,
+        }
+    }
+}
+
+//bytes of the file and of the address space attributed to one name
+#[derive(Debug, Clone, PartialEq)]
+pub struct Item {
+    pub name:     String,
+    pub fileSize: u64,
+    pub vmSize:   u64,
+}
+
+//the parts of a range not claimed before, every byte counts once however many things cover it
+struct Coverage {
+    //start to end, disjoint
+    ranges: BTreeMap<u64, u64>,
+}
+
+impl Coverage {
+
+    fn new() -> Self {
+        Self{ranges: BTreeMap::new()}
+    }
+
+    //marks a range as taken and returns how many of its bytes were still free
+    fn claim(&mut self, start: u64, end: u64) -> u64 {
+        if start >= end {return 0;}
+        //the intervals are disjoint so walking back by start also walks back by end
+        let touching: Vec<(u64, u64)> = self.ranges.range(..=end).rev().take_while(|(_, e)| **e >= start).map(|(s, e)| (*s, *e)).collect();
+        let mut taken = 0;
+        let (mut low, mut high) = (start, end);
+        for (s, e) in touching {
+            taken += e.min(end).saturating_sub(s.max(start));
+            low = low.min(s);
+            high = high.max(e);
+            self.ranges.remove(&s);
+        }
+        self.ranges.insert(low, high);
+        end - start - taken
+    }
+}
+
+//claims ranges in priority order and adds the bytes each one won to its name
+struct Attribution<'a> {
+    loader: &'a loader::Loader,
+    file:   Coverage,
+    vm:     Coverage,
+    items:  Vec<Item>,
+    byName: HashMap<String, usize>,
+}
+
+impl<'a> Attribution<'a> {
+
+    fn new(loader: &'a loader::Loader) -> Self {
+        Self{loader: loader, file: Coverage::new(), vm: Coverage::new(), items: vec![], byName: HashMap::new()}
+    }
+
+    fn add(&mut self, name: &str, file: Option<(u64, u64)>, vm: Option<(u64, u64)>) {
+        let fileSize = file.map(|(s, e)| self.file.claim(s, e)).unwrap_or(0);
+        let vmSize = vm.map(|(s, e)| self.vm.claim(s, e)).unwrap_or(0);
+        let index = match self.byName.get(name) {
+            Some(i) => *i,
+            None    => {
+                self.items.push(Item{name: name.to_string(), fileSize: 0, vmSize: 0});
+                self.byName.insert(name.to_string(), self.items.len() - 1);
+                self.items.len() - 1
+            },
+        };
+        self.items[index].fileSize += fileSize;
+        self.items[index].vmSize += vmSize;
+    }
+
+    //the file bytes behind an address range, clipped to the part of its segment that is in the file
+    fn fileRange(&self, start: u64, end: u64) -> Option<(u64, u64)> {
+        let p = self.loader.programHeaders.iter().find(|p| {
+            p.getTYPE() == programheader::PT_LOAD && p.getVADDR() as u64 <= start && start < p.getVADDR().saturating_add(p.getFILESZ()) as u64
+        })?;
+        let offset = (p.getOFFSET() as u64).saturating_add(start - p.getVADDR() as u64);
+        Some((offset, offset.saturating_add(end.min(p.getVADDR().saturating_add(p.getFILESZ()) as u64) - start)))
+    }
+
+    //the addresses a file range is loaded at
+    fn vmRange(&self, start: u64, end: u64) -> Option<(u64, u64)> {
+        let p = self.loader.programHeaders.iter().find(|p| {
+            p.getTYPE() == programheader::PT_LOAD && p.getOFFSET() as u64 <= start && start < p.getOFFSET().saturating_add(p.getFILESZ()) as u64
+        })?;
+        let address = (p.getVADDR() as u64).saturating_add(start - p.getOFFSET() as u64);
+        Some((address, address.saturating_add(end.min(p.getOFFSET().saturating_add(p.getFILESZ()) as u64) - start)))
+    }
+
+    fn headers(&mut self) {
+        let h = &self.loader.header;
+        let tables = [
+            (0, h.e_ehsize as u64),
+            (h.e_phoff as u64, (h.e_phoff as u64).saturating_add(h.e_phnum as u64 * h.e_phentsize as u64)),
+            (h.e_shoff as u64, (h.e_shoff as u64).saturating_add(h.e_shnum as u64 * h.e_shentsize as u64)),
+        ];
+        for (start, end) in tables.iter().filter(|(s, e)| s < e) {
+            let vm = self.vmRange(*start, *end);
+            self.add("[ELF Headers]", Some((*start, *end)), vm);
+        }
+    }
+
+    fn sections(&mut self, label: fn(&str) -> String) {
+        for (i, s) in self.loader.sectionHeaders.iter().enumerate().skip(1) {
+            if s.sh_size == 0 {continue;}
+            let name = label(&self.loader.sectionName(i).unwrap_or_default());
+            let file = if s.sh_type == sectionheader::SHT_NOBITS {None} else {Some((s.sh_offset as u64, s.sh_offset.saturating_add(s.sh_size) as u64))};
+            //.tbss takes no addresses of its own, its image is made per thread
+            let vm = if s.sh_flags & sectionheader::SHF_ALLOC == 0 || (s.sh_flags & sectionheader::SHF_TLS != 0 && file.is_none()) {None}
+                     else {Some((s.sh_addr as u64, s.sh_addr.saturating_add(s.sh_size) as u64))};
+            self.add(&name, file, vm);
+        }
+    }
+
+    fn segments(&mut self, label: fn(&str) -> String) {
+        for (i, p) in self.loader.programHeaders.iter().enumerate().filter(|(_, p)| p.getTYPE() == programheader::PT_LOAD) {
+            let flags = p.getFLAGS();
+            let permissions: String = [(programheader::PF_R, 'R'), (programheader::PF_W, 'W'), (programheader::PF_X, 'X')].iter()
+                .filter(|(f, _)| flags & f != 0).map(|(_, c)| *c).collect();
+            let name = label(&format!("LOAD #{} [{}]", i, permissions));
+            let file = Some((p.getOFFSET() as u64, p.getOFFSET().saturating_add(p.getFILESZ()) as u64));
+            let vm = Some((p.getVADDR() as u64, p.getVADDR().saturating_add(p.getMEMSZ()) as u64));
+            self.add(&name, file, vm);
+        }
+    }
+
+    //defined functions and objects of .symtab, then .dynsym
+    fn symbols(&mut self) {
+        let mut symbols = vec![];
+        for s in self.loader.symbols.iter().chain(self.loader.dynamicSymbols.iter()) {
+            let t = s.getType();
+            if s.isUndefined() || s.st_size == 0 || s.st_shndx == symbol::SHN_ABS || s.name.is_empty() {continue;}
+            if t != symbol::STT_FUNC && t != symbol::STT_OBJECT && t != symbol::STT_GNU_IFUNC {continue;}
+            symbols.push((s.name.clone(), s.st_value as u64, s.st_value.saturating_add(s.st_size) as u64, s.st_shndx as usize));
+        }
+        let relocatable = self.loader.programHeaders.is_empty();
+        for (name, start, end, index) in symbols {
+            //values in object files are offsets into their section
+            if relocatable {
+                let file = self.loader.sectionHeaders.get(index).filter(|h| h.sh_type != sectionheader::SHT_NOBITS)
+                    .map(|h| ((h.sh_offset as u64).saturating_add(start), (h.sh_offset as u64).saturating_add(end)));
+                self.add(&name, file, None);
+                continue;
+            }
+            let file = self.fileRange(start, end);
+            self.add(&name, file, Some((start, end)));
+        }
+    }
+
+    //compile units from DWARF, otherwise the STT_FILE symbol before each local symbol
+    fn files(&mut self) {
+        if let Some(debug) = dwarf::DebugInfo::new(self.loader) {
+            if debug.units.iter().any(|u| !u.ranges.is_empty()) {
+                for unit in debug.units.iter() {
+                    for (start, end) in unit.ranges.iter() {
+                        let file = self.fileRange(*start, *end);
+                        self.add(&unit.name, file, Some((*start, *end)));
+                    }
+                }
+                return;
+            }
+        }
+        let mut current: Option<String> = None;
+        let mut owned = vec![];
+        for s in self.loader.symbols.iter() {
+            if s.getType() == symbol::STT_FILE {
+                current = Some(s.name.clone());
+                continue;
+            }
+            //global symbols come after every local one and belong to no STT_FILE
+            if s.getBind() != symbol::STB_LOCAL {break;}
+            let t = s.getType();
+            if s.isUndefined() || s.st_size == 0 || s.st_shndx == symbol::SHN_ABS || (t != symbol::STT_FUNC && t != symbol::STT_OBJECT) {continue;}
+            if let Some(name) = current.as_ref() {owned.push((name.clone(), s.st_value as u64, s.st_value.saturating_add(s.st_size) as u64));}
+        }
+        for (name, start, end) in owned {
+            let file = self.fileRange(start, end);
+            self.add(&name, file, Some((start, end)));
+        }
+    }
+
+    //whatever no item claimed
+    fn unmapped(&mut self) {
+        let length = self.loader.fileVec.len() as u64;
+        self.add("[Unmapped]", Some((0, length)), None);
+    }
+}
+
+//attributes every byte of the file, and every address of the loaded segments, to one item of the
+//source, the headers, whole sections or segments fill in what the source does not cover
+pub fn attribute(loader: &loader::Loader, source: Source) -> Vec<Item> {
+    let mut a = Attribution::new(loader);
+    a.headers();
+    match source {
+        Source::Segments => {
+            a.segments(|n| n.to_string());
+        },
+        Source::Sections => {
+            a.sections(|n| n.to_string());
+            a.segments(|n| format!("[{}]", n));
+        },
+        Source::Symbols | Source::Files => {
+            if source == Source::Symbols {a.symbols();} else {a.files();}
+            a.sections(|n| format!("[section {}]", n));
+            a.segments(|n| format!("[{}]", n));
+        },
+    }
+    a.unmapped();
+    a.items.retain(|i| i.fileSize != 0 || i.vmSize != 0);
+    a.items
+}
+
+pub fn sortItems(items: &mut Vec<Item>, key: SortKey) {
+    match key {
+        SortKey::Both => items.sort_by(|a, b| b.fileSize.max(b.vmSize).cmp(&a.fileSize.max(a.vmSize)).then(a.name.cmp(&b.name))),
+        SortKey::File => items.sort_by(|a, b| b.fileSize.cmp(&a.fileSize).then(a.name.cmp(&b.name))),
+        SortKey::Vm   => items.sort_by(|a, b| b.vmSize.cmp(&a.vmSize).then(a.name.cmp(&b.name))),
+        SortKey::Name => items.sort_by(|a, b| a.name.cmp(&b.name)),
+    }
+}
+
+//the sorted items of one file, everything past the first top is summed into one line
+pub struct SizeReport {
+    pub items: Vec<Item>,
+    pub top:   usize,
+}
+
+impl fmt::Display for SizeReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (file, vm) = (self.items.iter().map(|i| i.fileSize).sum::<u64>(), self.items.iter().map(|i| i.vmSize).sum::<u64>());
+        let percent = |part: u64, whole: u64| if whole == 0 {0.0} else {part as f64 * 100.0 / whole as f64};
+        writeln!(f, "    FILE SIZE        VM SIZE    ")?;
+        writeln!(f, " --------------  -------------- ")?;
+        let mut rows = self.items.iter().take(self.top).cloned().collect::<Vec<_>>();
+        if self.items.len() > self.top {
+            let rest = &self.items[self.top..];
+            rows.push(Item {
+                name:     others(self.items.len(), self.top),
+                fileSize: rest.iter().map(|i| i.fileSize).sum(),
+                vmSize:   rest.iter().map(|i| i.vmSize).sum(),
+            });
+        }
+        for i in rows.iter() {
+            writeln!(f, " {:>5.1}%  {:>6}  {:>5.1}%  {:>6}    {}", percent(i.fileSize, file), human(i.fileSize), percent(i.vmSize, vm), human(i.vmSize), i.name)?;
+        }
+        writeln!(f, " {:>5.1}%  {:>6}  {:>5.1}%  {:>6}    TOTAL", 100.0, human(file), 100.0, human(vm))
+    }
+}
+
+//how one item differs between two builds
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub name:    String,
+    pub oldFile: u64,
+    pub newFile: u64,
+    pub oldVm:   u64,
+    pub newVm:   u64,
+}
+
+impl Change {
+
+    pub fn fileDelta(&self) -> i64 {
+        self.newFile as i64 - self.oldFile as i64
+    }
+
+    pub fn vmDelta(&self) -> i64 {
+        self.newVm as i64 - self.oldVm as i64
+    }
+}
+
+//the items whose sizes changed, the largest change first by the sort key
+pub fn compare(old: &[Item], new: &[Item], key: SortKey) -> Vec<Change> {
+    let mut changes: Vec<Change> = vec![];
+    let oldByName: HashMap<&str, &Item> = old.iter().map(|i| (i.name.as_str(), i)).collect();
+    for n in new.iter() {
+        let o = oldByName.get(n.name.as_str());
+        changes.push(Change {
+            name:    n.name.clone(),
+            oldFile: o.map(|o| o.fileSize).unwrap_or(0),
+            newFile: n.fileSize,
+            oldVm:   o.map(|o| o.vmSize).unwrap_or(0),
+            newVm:   n.vmSize,
+        });
+    }
+    let newNames: std::collections::HashSet<&str> = new.iter().map(|i| i.name.as_str()).collect();
+    for o in old.iter().filter(|o| !newNames.contains(o.name.as_str())) {
+        changes.push(Change{name: o.name.clone(), oldFile: o.fileSize, newFile: 0, oldVm: o.vmSize, newVm: 0});
+    }
+    changes.retain(|c| c.fileDelta() != 0 || c.vmDelta() != 0);
+    let magnitude = |c: &Change| match key {
+        SortKey::File => c.fileDelta().abs(),
+        SortKey::Vm   => c.vmDelta().abs(),
+        _             => c.fileDelta().abs().max(c.vmDelta().abs()),
+    };
+    if key == SortKey::Name {
+        changes.sort_by(|a, b| a.name.cmp(&b.name));
+    } else {
+        changes.sort_by(|a, b| magnitude(b).cmp(&magnitude(a)).then(a.name.cmp(&b.name)));
+    }
+    changes
+}
+
+//the growth of every changed item between two builds
+pub struct SizeDiff {
+    pub changes: Vec<Change>,
+    pub top:     usize,
+
+    //totals of the old and new file, file size then VM size
+    pub old:     (u64, u64),
+    pub new:     (u64, u64),
+}
+
+impl fmt::Display for SizeDiff {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "    FILE SIZE        VM SIZE    ")?;
+        writeln!(f, " --------------  -------------- ")?;
+        let mut rows = self.changes.iter().take(self.top).cloned().collect::<Vec<_>>();
+        if self.changes.len() > self.top {
+            let rest = &self.changes[self.top..];
+            rows.push(Change {
+                name:    others(self.changes.len(), self.top),
+                oldFile: rest.iter().map(|c| c.oldFile).sum(),
+                newFile: rest.iter().map(|c| c.newFile).sum(),
+                oldVm:   rest.iter().map(|c| c.oldVm).sum(),
+                newVm:   rest.iter().map(|c| c.newVm).sum(),
+            });
+        }
+        for c in rows.iter() {
+            writeln!(f, " {}  {}    {}", growth(c.oldFile, c.newFile), growth(c.oldVm, c.newVm), c.name)?;
+        }
+        writeln!(f, " {}  {}    TOTAL", growth(self.old.0, self.new.0), growth(self.old.1, self.new.1))
+    }
+}
+
+//the name of the line adding up everything past the first top rows
+fn others(count: usize, top: usize) -> String {
+    format!("[{} Others]", count - top)
+}
+
+//a change as percent and bytes, [NEW] and [DEL] for items only in one build
+fn growth(old: u64, new: u64) -> String {
+    let delta = new as i64 - old as i64;
+    let sign = if delta > 0 {"+"} else if delta < 0 {"-"} else {""};
+    let size = format!("{}{}", sign, human(delta.unsigned_abs()));
+    let percent = match (old, new) {
+        (0, 0) => "".to_string(),
+        (0, _) => "[NEW]".to_string(),
+        (_, 0) => "[DEL]".to_string(),
+        _      => format!("{:+.1}%", delta as f64 * 100.0 / old as f64),
+    };
+    format!("{:>6} {:>7}", percent, size)
+}
+
+//a size in bytes or three significant digits of Ki, Mi or Gi like bloaty
+pub fn human(bytes: u64) -> String {
+    let (value, unit) = match bytes {
+        0..=1023             => return format!("{}", bytes),
+        1024..=1048575       => (bytes as f64 / 1024.0, "Ki"),
+        1048576..=1073741823 => (bytes as f64 / 1048576.0, "Mi"),
+        _                    => (bytes as f64 / 1073741824.0, "Gi"),
+    };
+    let decimals = if value < 10.0 {2} else if value < 100.0 {1} else {0};
+    format!("{:.*}{}", decimals, value, unit)
+}
@@ -0,0 +1,219 @@
+use crate::addressspace;
+use crate::header;
+use crate::loader;
+use crate::programheader;
+
+//auxiliary vector entry types
+pub const AT_NULL:     u64 = 0;
+pub const AT_PHDR:     u64 = 3;
+pub const AT_PHENT:    u64 = 4;
+pub const AT_PHNUM:    u64 = 5;
+pub const AT_PAGESZ:   u64 = 6;
+pub const AT_BASE:     u64 = 7;
+pub const AT_FLAGS:    u64 = 8;
+pub const AT_ENTRY:    u64 = 9;
+pub const AT_UID:      u64 = 11;
+pub const AT_EUID:     u64 = 12;
+pub const AT_GID:      u64 = 13;
+pub const AT_EGID:     u64 = 14;
+pub const AT_PLATFORM: u64 = 15;
+pub const AT_HWCAP:    u64 = 16;
+pub const AT_CLKTCK:   u64 = 17;
+pub const AT_SECURE:   u64 = 23;
+pub const AT_RANDOM:   u64 = 25;
+pub const AT_EXECFN:   u64 = 31;
+
+//describes the process a stack should be built for
+//the defaults match what linux gives a freshly exec'd static binary
+pub struct InitialStack {
+
+    //argument strings, argv[0] is normally the program name
+    pub argv:       Vec<String>,
+
+    //environment strings in KEY=VALUE form
+    pub envp:       Vec<String>,
+
+    //path the program was executed as, pointed to by AT_EXECFN
+    pub execfn:     String,
+
+    //highest address of the stack, the first string is placed right below it
+    pub stackTop:   usize,
+
+    //offset the executable was loaded at, 0 for ET_EXEC
+    pub loadBias:   usize,
+
+    //address the program interpreter was loaded at, 0 when there is none
+    pub interpBase: usize,
+
+    //value of AT_HWCAP
+    pub hwcap:      u64,
+
+    //value of AT_PAGESZ
+    pub pageSize:   usize,
+
+    //string pointed to by AT_PLATFORM, left out of the auxv when empty
+    pub platform:   String,
+
+    //16 bytes pointed to by AT_RANDOM, fixed by default so runs are reproducible
+    pub random:     [u8; 16],
+}
+
+impl InitialStack {
+
+    //creates a stack description for the given arguments and environment
+    pub fn new(argv: &[&str], envp: &[&str], stackTop: usize) -> Self {
+        Self {
+            argv:       argv.iter().map(|s| s.to_string()).collect(),
+            envp:       envp.iter().map(|s| s.to_string()).collect(),
+            execfn:     argv.first().map(|s| s.to_string()).unwrap_or_default(),
+            stackTop:   stackTop,
+            loadBias:   0,
+            interpBase: 0,
+            hwcap:      0,
+            pageSize:   4096,
+            platform:   String::new(),
+            random:     [0; 16],
+        }
+    }
+
+    //returns the AT_PLATFORM string the kernel uses for a machine type
+    pub fn platformFor(e_machine: u16) -> &'static str {
+        match e_machine {
+            header::EM_X86_64  => "x86_64",
+            header::EM_386     => "i686",
+            header::EM_AARCH64 => "aarch64",
+            header::EM_ARM     => "v7l",
+            _                  => "",
+        }
+    }
+
+    //computes the run time address of the program header table for AT_PHDR
+    //uses PT_PHDR when present, otherwise the PT_LOAD segment that covers e_phoff
+    //None when there is neither or the address does not fit the address space
+    pub fn phdrAddress(&self, loader: &loader::Loader) -> Option<usize> {
+        if let Some(p) = loader.programHeaders.iter().find(|p| p.getTYPE() == programheader::PT_PHDR) {
+            return self.loadBias.checked_add(p.getVADDR());
+        }
+        let phoff = loader.header.e_phoff;
+        let p = loader.programHeaders.iter().find(|p| {
+            p.getTYPE() == programheader::PT_LOAD && p.getOFFSET() <= phoff && phoff < p.getOFFSET().saturating_add(p.getFILESZ())
+        })?;
+        self.loadBias.checked_add(p.getVADDR())?.checked_add(phoff - p.getOFFSET())
+    }
+
+    //writes the strings, random bytes, auxv and pointer arrays into space below stackTop
+    //and returns the initial stack pointer. the layout follows create_elf_tables in the
+    //linux kernel:
+    //
+    //  stackTop -> execfn string
+    //              envp strings
+    //              argv strings
+    //              platform string
+    //              16 random bytes
+    //              auxv pairs ending with AT_NULL
+    //              envp pointers ending with NULL
+    //              argv pointers ending with NULL
+    //        sp -> argc
+    //
+    //the word size and byte order come from the ELF class and data encoding so the same call
+    //builds 32 and 64 bit stacks. returns None if the stack region is not mapped or too small
+    //or the entry point does not fit the address space once biased
+    pub fn build(&self, loader: &loader::Loader, space: &mut addressspace::AddressSpace) -> Option<usize> {
+        let wordSize = match loader.header.e_ident.Class {
+            1 => 4,
+            _ => 8,
+        };
+        let mut p = self.stackTop;
+
+        //execfn goes at the very top of the stack
+        p = p.checked_sub(self.execfn.len() + 1)?;
+        let execfnAddr = p;
+        space.write(execfnAddr, &Self::cString(&self.execfn))?;
+
+        //argv and envp strings are packed back to back in order
+        let stringsSize: usize = self.argv.iter().chain(self.envp.iter()).map(|s| s.len() + 1).sum();
+        p = p.checked_sub(stringsSize)?;
+        let mut cur = p;
+        let mut argvPtrs = vec![];
+        for s in self.argv.iter() {
+            argvPtrs.push(cur);
+            space.write(cur, &Self::cString(s))?;
+            cur += s.len() + 1;
+        }
+        let mut envpPtrs = vec![];
+        for s in self.envp.iter() {
+            envpPtrs.push(cur);
+            space.write(cur, &Self::cString(s))?;
+            cur += s.len() + 1;
+        }
+
+        p &= !0xf;
+        let mut platformAddr = 0;
+        if !self.platform.is_empty() {
+            p = p.checked_sub(self.platform.len() + 1)?;
+            platformAddr = p;
+            space.write(platformAddr, &Self::cString(&self.platform))?;
+        }
+
+        p = p.checked_sub(16)?;
+        let randomAddr = p;
+        space.write(randomAddr, &self.random)?;
+
+        //build the auxiliary vector
+        let mut auxv: Vec<(u64, u64)> = vec![];
+        auxv.push((AT_HWCAP, self.hwcap));
+        auxv.push((AT_PAGESZ, self.pageSize as u64));
+        auxv.push((AT_CLKTCK, 100));
+        auxv.push((AT_PHDR, self.phdrAddress(loader).unwrap_or(0) as u64));
+        auxv.push((AT_PHENT, loader.header.e_phentsize as u64));
+        auxv.push((AT_PHNUM, loader.header.e_phnum as u64));
+        auxv.push((AT_BASE, self.interpBase as u64));
+        auxv.push((AT_FLAGS, 0));
+        auxv.push((AT_ENTRY, self.loadBias.checked_add(loader.header.e_entry)? as u64));
+        auxv.push((AT_UID, 0));
+        auxv.push((AT_EUID, 0));
+        auxv.push((AT_GID, 0));
+        auxv.push((AT_EGID, 0));
+        auxv.push((AT_SECURE, 0));
+        auxv.push((AT_RANDOM, randomAddr as u64));
+        auxv.push((AT_EXECFN, execfnAddr as u64));
+        if platformAddr != 0 {
+            auxv.push((AT_PLATFORM, platformAddr as u64));
+        }
+        auxv.push((AT_NULL, 0));
+
+        //argc + argv + NULL + envp + NULL + auxv
+        let words = 1 + argvPtrs.len() + 1 + envpPtrs.len() + 1 + auxv.len() * 2;
+        let sp = p.checked_sub(words * wordSize)? & !0xf;
+
+        let mut cur = sp;
+        let mut values = vec![argvPtrs.len() as u64];
+        values.extend(argvPtrs.iter().map(|a| *a as u64));
+        values.push(0);
+        values.extend(envpPtrs.iter().map(|a| *a as u64));
+        values.push(0);
+        for (key, value) in auxv.iter() {
+            values.push(*key);
+            values.push(*value);
+        }
+        let previousData = space.data;
+        space.data = loader.header.e_ident.Data;
+        for v in values.iter() {
+            if space.writeUValue(cur, wordSize, *v).is_none() {
+                space.data = previousData;
+                return None;
+            }
+            cur += wordSize;
+        }
+        space.data = previousData;
+
+        Some(sp)
+    }
+
+    //returns the bytes of s followed by a nul terminator
+    fn cString(s: &str) -> Vec<u8> {
+        let mut bytes = s.as_bytes().to_vec();
+        bytes.push(0);
+        bytes
+    }
+}
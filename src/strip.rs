@@ -0,0 +1,172 @@
+use crate::programheader;
+use crate::sectionheader;
+use crate::writer;
+
+//section indexes at or above this are special values like SHN_ABS rather than sections
+const SHN_LORESERVE: u16 = 0xff00;
+
+//CRC-32 as used by .gnu_debuglink, the reflected IEEE polynomial
+pub fn crc32(bytes: &[u8]) -> u32 {
+    crc32Update(0, bytes)
+}
+
+//continues a CRC-32 over more bytes, starting from 0
+pub fn crc32Update(crc: u32, bytes: &[u8]) -> u32 {
+    let mut crc = !crc;
+    for b in bytes.iter() {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xedb88320} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+//true for DWARF sections, compressed or not
+pub fn isDebugSection(name: &str) -> bool {
+    name.starts_with(".debug_") || name.starts_with(".zdebug_") || name == ".gdb_index" || name == ".stab" || name == ".stabstr"
+}
+
+//removes the symbol table, debug information and the sections only they referred to, returns
+//the names of the removed sections
+//sections still needed by a kept one, like .symtab for the relocations of an object file, stay
+pub fn strip(file: &mut writer::ElfFile) -> Vec<String> {
+    let shstrndx = file.header.e_shstrndx as usize;
+    let mut removed: Vec<bool> = file.sections.iter()
+        .map(|s| isDebugSection(&s.name) || s.header.sh_type == sectionheader::SHT_SYMTAB)
+        .collect();
+
+    //repeat until nothing changes since every decision can enable another
+    loop {
+        let before = removed.clone();
+        for i in 1..file.sections.len() {
+            let h = &file.sections[i].header;
+            //relocations for a removed section go with it
+            if writer::infoIsSection(h) && removed.get(h.sh_info as usize) == Some(&true) && h.sh_info != 0 {removed[i] = true;}
+        }
+        for i in 1..file.sections.len() {
+            let referenced = (0..file.sections.len()).any(|j| !removed[j] && j != i && file.sections[j].header.sh_link as usize == i);
+            if removed[i] && referenced && !isDebugSection(&file.sections[i].name) {removed[i] = false;}
+            //string tables nobody links to any more
+            let s = &file.sections[i];
+            if !referenced && i != shstrndx && s.header.sh_type == sectionheader::SHT_STRTAB && !s.isAllocated() && before.iter().enumerate().any(|(j, r)| *r && file.sections[j].header.sh_link as usize == i) {
+                removed[i] = true;
+            }
+        }
+        if removed == before {break;}
+    }
+
+    let mapping = renumbering(&removed);
+    fixSymbolSections(file, &removed, &mapping);
+    let mut names = vec![];
+    for i in (1..file.sections.len()).rev() {
+        if removed[i] {names.push(file.removeSection(i).name);}
+    }
+    names.reverse();
+    let end = loadedEnd(file);
+    file.pack(end);
+    names
+}
+
+//turns a file into a separate debug file like objcopy --only-keep-debug, the loaded sections
+//keep their headers but lose their contents, notes stay so the build id can be matched
+pub fn onlyKeepDebug(file: &mut writer::ElfFile) {
+    for s in file.sections.iter_mut() {
+        if s.isAllocated() && s.header.sh_type != sectionheader::SHT_NOTE {
+            s.header.sh_type = sectionheader::SHT_NOBITS;
+            s.data.clear();
+        }
+    }
+    let wide = file.header.e_ident.Class == 2;
+    let end = (if wide {64} else {52} as usize).max(file.header.e_phoff.saturating_add(file.segments.len() * file.header.e_phentsize as usize));
+
+    //segments keep their addresses but only cover the file contents that are left, the headers
+    //and the notes, so none of them points past the end of the file. a segment with nothing left,
+    //like PT_DYNAMIC or PT_INTERP, gets a p_filesz of 0 and the lowest offset congruent to its
+    //address
+    let kept: Vec<(usize, usize)> = std::iter::once((0, end))
+        .chain(file.sections.iter().filter(|s| s.isAllocated() && s.hasFileData()).map(|s| (s.header.sh_offset, s.header.sh_offset.saturating_add(s.data.len()))))
+        .collect();
+    for p in file.segments.iter_mut() {
+        let (start, stop) = (p.getOFFSET(), p.getOFFSET().saturating_add(p.getFILESZ()));
+        let last = kept.iter().filter(|(s, e)| *s < stop && *e > start).map(|(_, e)| (*e).min(stop)).max();
+        match last {
+            Some(l) => p.setFILESZ(l - start),
+            None    => {
+                p.setFILESZ(0);
+                let offset = p.getVADDR() % p.getALIGN().max(1);
+                p.setOFFSET(offset);
+            },
+        }
+    }
+    file.pack(end);
+}
+
+//adds or replaces .gnu_debuglink, naming the debug file and holding its CRC-32
+pub fn addDebuglink(file: &mut writer::ElfFile, name: &str, debugFile: &[u8]) {
+    let mut data = name.as_bytes().to_vec();
+    data.push(0);
+    while data.len() % 4 != 0 {data.push(0);}
+    let e = file.encoder();
+    e.u32(&mut data, crc32(debugFile));
+    match file.findSection(".gnu_debuglink") {
+        Some(i) => file.sections[i].data = data,
+        None    => {
+            let mut section = writer::Section::new(".gnu_debuglink", sectionheader::SHT_PROGBITS, data);
+            section.header.sh_addralign = 4;
+            file.addSection(section);
+        },
+    }
+}
+
+//returns the file name and CRC-32 stored in .gnu_debuglink
+pub fn debuglink(data: &[u8], bigEndian: bool) -> Option<(String, u32)> {
+    let end = data.iter().position(|b| *b == 0)?;
+    let crcOffset = (end + 4) / 4 * 4;
+    let crc = data.get(crcOffset..crcOffset + 4)?;
+    let crc = [crc[0], crc[1], crc[2], crc[3]];
+    let crc = if bigEndian {u32::from_be_bytes(crc)} else {u32::from_le_bytes(crc)};
+    Some((String::from_utf8_lossy(&data[..end]).to_string(), crc))
+}
+
+//maps old section indexes to new ones once the removed sections are gone
+fn renumbering(removed: &[bool]) -> Vec<u16> {
+    let mut next = 0;
+    removed.iter().map(|r| {
+        let index = next;
+        if !r {next += 1;}
+        if *r {0} else {index}
+    }).collect()
+}
+
+//rewrites st_shndx in the kept symbol tables, symbols of removed sections become undefined
+fn fixSymbolSections(file: &mut writer::ElfFile, removed: &[bool], mapping: &[u16]) {
+    let e = file.encoder();
+    let (size, field) = if e.wide {(24, 6)} else {(16, 14)};
+    for (i, s) in file.sections.iter_mut().enumerate() {
+        if removed[i] || (s.header.sh_type != sectionheader::SHT_SYMTAB && s.header.sh_type != sectionheader::SHT_DYNSYM) {continue;}
+        for entry in s.data.chunks_exact_mut(size) {
+            let bytes = [entry[field], entry[field + 1]];
+            let index = if e.bigEndian {u16::from_be_bytes(bytes)} else {u16::from_le_bytes(bytes)};
+            if index == 0 || index >= SHN_LORESERVE {continue;}
+            let new = mapping.get(index as usize).copied().unwrap_or(0);
+            if new == index {continue;}
+            let bytes = if e.bigEndian {new.to_be_bytes()} else {new.to_le_bytes()};
+            entry[field..field + 2].copy_from_slice(&bytes);
+        }
+    }
+}
+
+//end of the file contents that are loaded or otherwise placed by the program headers
+fn loadedEnd(file: &writer::ElfFile) -> usize {
+    let wide = file.header.e_ident.Class == 2;
+    let mut end = if wide {64} else {52};
+    end = end.max(file.header.e_phoff.saturating_add(file.segments.len() * file.header.e_phentsize as usize));
+    for p in file.segments.iter().filter(|p| p.getTYPE() != programheader::PT_NULL) {
+        end = end.max(p.getOFFSET().saturating_add(p.getFILESZ()));
+    }
+    for s in file.sections.iter().filter(|s| s.isAllocated() && s.hasFileData()) {
+        end = end.max(s.header.sh_offset.saturating_add(s.data.len()));
+    }
+    end
+}
@@ -0,0 +1,86 @@
+//symbol binding found in the high nibble of st_info
+pub const STB_LOCAL:  u8 = 0;
+pub const STB_GLOBAL: u8 = 1;
+pub const STB_WEAK:   u8 = 2;
+pub const STB_GNU_UNIQUE: u8 = 10;
+
+//symbol type found in the low nibble of st_info
+pub const STT_NOTYPE:    u8 = 0;
+pub const STT_OBJECT:    u8 = 1;
+pub const STT_FUNC:      u8 = 2;
+pub const STT_SECTION:   u8 = 3;
+pub const STT_FILE:      u8 = 4;
+pub const STT_COMMON:    u8 = 5;
+pub const STT_TLS:       u8 = 6;
+pub const STT_GNU_IFUNC: u8 = 10;
+
+//symbol visibility found in the low bits of st_other
+pub const STV_DEFAULT:   u8 = 0;
+pub const STV_INTERNAL:  u8 = 1;
+pub const STV_HIDDEN:    u8 = 2;
+pub const STV_PROTECTED: u8 = 3;
+
+//special section indexes found in st_shndx
+pub const SHN_UNDEF:  u16 = 0;
+pub const SHN_ABS:    u16 = 0xfff1;
+pub const SHN_COMMON: u16 = 0xfff2;
+//the real index did not fit, for e_shstrndx it is in sh_link of section 0
+pub const SHN_XINDEX: u16 = 0xffff;
+
+#[derive(Debug, Clone)]
+pub struct Symbol {
+
+    //offset of the name in the linked string table
+    pub st_name:  u32,
+
+    //binding in the high nibble and type in the low nibble
+    pub st_info:  u8,
+
+    //visibility
+    pub st_other: u8,
+
+    //index of the section the symbol is defined in, SHN_UNDEF for imports
+    pub st_shndx: u16,
+
+    //value of the symbol, normally a virtual address
+    pub st_value: usize,
+
+    //size of the object or function in bytes
+    pub st_size:  usize,
+
+    //name read from the linked string table
+    pub name:     String,
+}
+
+impl Symbol {
+
+    //returns an uninitialized symbol struct
+    pub fn new() -> Self {
+        Self {
+            st_name:  0,
+            st_info:  0,
+            st_other: 0,
+            st_shndx: 0,
+            st_value: 0,
+            st_size:  0,
+            name:     String::new(),
+        }
+    }
+
+    pub fn getBind(&self) -> u8 {
+        self.st_info >> 4
+    }
+
+    pub fn getType(&self) -> u8 {
+        self.st_info & 0xf
+    }
+
+    pub fn getVisibility(&self) -> u8 {
+        self.st_other & 0x3
+    }
+
+    //true for symbols that are referenced but not defined in this file
+    pub fn isUndefined(&self) -> bool {
+        self.st_shndx == SHN_UNDEF && !self.name.is_empty()
+    }
+}
@@ -0,0 +1,170 @@
+use std::fmt;
+use crate::debugfile;
+use crate::dwarf;
+use crate::loader;
+use crate::programheader;
+use crate::symbol;
+
+//half open address intervals that may nest, sorted once so lookups are a binary search
+pub struct IntervalIndex<T> {
+    //start, end and value sorted by start
+    intervals: Vec<(u64, u64, T)>,
+
+    //largest end among intervals[0..=i], lets a lookup stop walking back early
+    maxEnd:    Vec<u64>,
+}
+
+impl<T> IntervalIndex<T> {
+
+    pub fn new(mut intervals: Vec<(u64, u64, T)>) -> Self {
+        intervals.sort_by_key(|(start, end, _)| (*start, std::cmp::Reverse(*end)));
+        let mut maxEnd = Vec::with_capacity(intervals.len());
+        let mut max = 0;
+        for (_, end, _) in intervals.iter() {
+            max = max.max(*end);
+            maxEnd.push(max);
+        }
+        Self{intervals: intervals, maxEnd: maxEnd}
+    }
+
+    //returns every interval containing address, outermost first
+    pub fn containing(&self, address: u64) -> Vec<(u64, u64, &T)> {
+        let mut found = vec![];
+        let mut i = self.intervals.partition_point(|(start, _, _)| *start <= address);
+        while i > 0 && self.maxEnd[i - 1] > address {
+            i -= 1;
+            let (start, end, value) = &self.intervals[i];
+            if *end > address {found.push((*start, *end, value));}
+        }
+        found.reverse();
+        found
+    }
+
+    //returns the interval with the greatest start not above address, whether it contains it or not
+    pub fn preceding(&self, address: u64) -> Option<(u64, u64, &T)> {
+        let i = self.intervals.partition_point(|(start, _, _)| *start <= address);
+        let (start, end, value) = self.intervals.get(i.checked_sub(1)?)?;
+        Some((*start, *end, value))
+    }
+}
+
+//everything known about one address
+#[derive(Debug, Clone)]
+pub struct Location {
+    //link time address, the run time address minus the load bias
+    pub address: u64,
+
+    //index of the PT_LOAD segment in programHeaders
+    pub segment: Option<usize>,
+
+    pub section: Option<String>,
+
+    //nearest symbol at or below the address and the distance from it
+    pub symbol:  Option<(String, u64)>,
+
+    //source locations from DWARF, the innermost inlined function first, empty without debug info
+    pub frames:  Vec<dwarf::Frame>,
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#x}", self.address)?;
+        match self.symbol.as_ref() {
+            Some((name, 0))      => write!(f, " {}", name)?,
+            Some((name, offset)) => write!(f, " {}+{:#x}", name, offset)?,
+            None                 => write!(f, " ??")?,
+        }
+        if let Some(s) = self.section.as_ref() {write!(f, " in {}", s)?;}
+        if let Some(s) = self.segment {write!(f, " (segment {})", s)?;}
+        for (i, frame) in self.frames.iter().enumerate() {
+            let inlined = if i + 1 < self.frames.len() {" (inlined)"} else {""};
+            write!(f, "\n    {} at {}:{}{}", frame.function.as_deref().unwrap_or("??"),
+                frame.file.as_deref().unwrap_or("??"), frame.line, inlined)?;
+        }
+        Ok(())
+    }
+}
+
+//answers address queries for one module, the indexes are built once in new
+pub struct Symbolizer {
+    pub loader: loader::Loader,
+
+    //defined function and object symbols from .symtab and .dynsym
+    symbols:    IntervalIndex<String>,
+
+    //allocated sections
+    sections:   IntervalIndex<usize>,
+
+    pub debug:  Option<dwarf::DebugInfo>,
+}
+
+impl Symbolizer {
+
+    //indexes the symbols, sections and debug information of a loaded file
+    pub fn new(loader: loader::Loader) -> Self {
+        Self::withDebugFile(loader, None)
+    }
+
+    //like new, also taking symbols from a separate debug file and its DWARF when the file has none
+    pub fn withDebugFile(loader: loader::Loader, debugFile: Option<loader::Loader>) -> Self {
+        let mut symbols = vec![];
+        let extra = debugFile.as_ref().map(|d| d.symbols.iter()).into_iter().flatten();
+        for s in loader.symbols.iter().chain(loader.dynamicSymbols.iter()).chain(extra) {
+            let t = s.getType();
+            if s.isUndefined() || s.name.is_empty() || s.st_value == 0 {continue;}
+            if t != symbol::STT_FUNC && t != symbol::STT_OBJECT && t != symbol::STT_GNU_IFUNC && t != symbol::STT_NOTYPE {continue;}
+            if s.st_shndx == symbol::SHN_ABS {continue;}
+            let start = s.st_value as u64;
+            symbols.push((start, start.saturating_add(s.st_size as u64), s.name.clone()));
+        }
+        //function symbols kept in .gnu_debugdata by distributions that strip .symtab
+        if let Ok(Some(mini)) = debugfile::miniDebugInfo(&loader) {
+            for s in mini.symbols.iter().filter(|s| s.getType() == symbol::STT_FUNC && !s.isUndefined() && s.st_value != 0) {
+                let start = s.st_value as u64;
+                symbols.push((start, start.saturating_add(s.st_size as u64), s.name.clone()));
+            }
+        }
+        symbols.sort();
+        symbols.dedup();
+
+        let sections = loader.sectionHeaders.iter().enumerate()
+            .filter(|(_, s)| s.sh_flags & crate::sectionheader::SHF_ALLOC != 0 && s.sh_size != 0)
+            .map(|(i, s)| (s.sh_addr as u64, s.sh_addr.saturating_add(s.sh_size) as u64, i))
+            .collect();
+
+        let debug = dwarf::DebugInfo::new(&loader).or_else(|| debugFile.as_ref().and_then(|d| dwarf::DebugInfo::new(d)));
+        Self {
+            loader:   loader,
+            symbols:  IntervalIndex::new(symbols),
+            sections: IntervalIndex::new(sections),
+            debug:    debug,
+        }
+    }
+
+    //describes a run time address of a module loaded with the given bias, None outside every segment and section
+    pub fn symbolize(&self, address: u64, bias: u64) -> Option<Location> {
+        let address = address.wrapping_sub(bias);
+        let segment = self.loader.programHeaders.iter().position(|p| {
+            p.getTYPE() == programheader::PT_LOAD && (p.getVADDR() as u64) <= address && address < p.getVADDR().saturating_add(p.getMEMSZ()) as u64
+        });
+        let section = self.sections.containing(address).last().map(|(_, _, i)| **i);
+        if segment.is_none() && section.is_none() {return None;}
+
+        //the innermost sized symbol containing the address, otherwise the closest unsized one below it in the same section
+        let symbol = match self.symbols.containing(address).last() {
+            Some((start, _, name)) => Some(((*name).clone(), address - start)),
+            None => self.symbols.preceding(address)
+                .filter(|(start, end, _)| start == end)
+                .filter(|(start, _, _)| self.sections.containing(*start).last().map(|(_, _, i)| **i) == section)
+                .map(|(start, _, name)| (name.clone(), address - start)),
+        };
+
+        Some(Location {
+            address: address,
+            segment: segment,
+            section: section.and_then(|i| self.loader.sectionName(i)),
+            symbol:  symbol,
+            frames:  self.debug.as_ref().map(|d| d.frames(address)).unwrap_or_default(),
+        })
+    }
+}
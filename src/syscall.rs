@@ -0,0 +1,617 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io::Read;
+use std::io::Seek;
+use std::io::SeekFrom;
+use std::io::Write;
+use std::ffi::OsString;
+use std::path::Component;
+use std::path::Path;
+use std::path::PathBuf;
+use std::time::Instant;
+use std::time::SystemTime;
+use std::time::UNIX_EPOCH;
+use crate::addressspace;
+use crate::header;
+use crate::loader;
+use crate::programheader;
+
+//errno values returned negated to the guest
+pub const EPERM:   i64 = 1;
+pub const ENOENT:  i64 = 2;
+pub const EIO:     i64 = 5;
+pub const EBADF:   i64 = 9;
+pub const ENOMEM:  i64 = 12;
+pub const EACCES:  i64 = 13;
+pub const EFAULT:  i64 = 14;
+pub const EINVAL:  i64 = 22;
+pub const ENOTTY:  i64 = 25;
+pub const ENOSYS:  i64 = 38;
+
+//value of dirfd meaning relative to the current directory
+pub const AT_FDCWD: i64 = -100;
+
+//open flags shared by every architecture we emulate
+pub const O_ACCMODE: u64 = 0x3;
+pub const O_WRONLY:  u64 = 0x1;
+pub const O_RDWR:    u64 = 0x2;
+pub const O_CREAT:   u64 = 0x40;
+pub const O_TRUNC:   u64 = 0x200;
+pub const O_APPEND:  u64 = 0x400;
+
+//mmap protection and flag bits
+pub const PROT_READ:     u64 = 0x1;
+pub const PROT_WRITE:    u64 = 0x2;
+pub const PROT_EXEC:     u64 = 0x4;
+pub const MAP_FIXED:     u64 = 0x10;
+pub const MAP_ANONYMOUS: u64 = 0x20;
+
+pub const PAGE_SIZE: usize = 4096;
+
+//most bytes copied between the guest and the host at once
+pub const IO_CHUNK: usize = 64 * 1024;
+
+//largest mapping or heap the guest may ask for, mappings are backed by host memory
+pub const MAX_MAPPING: usize = 1 << 30;
+
+//most bytes one getrandom call fills, like the kernel's limit
+pub const MAX_RANDOM: usize = 33554431;
+
+//symlinks followed while resolving one path, like the kernel's limit
+pub const MAX_SYMLINKS: usize = 40;
+
+//most iovec entries a single writev takes, like UIO_MAXIOV
+pub const IOV_MAX: usize = 1024;
+
+//architecture independent names for the syscalls the layer understands
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Sys {
+    Read,
+    Write,
+    Writev,
+    Openat,
+    Close,
+    Ioctl,
+    Exit,
+    ExitGroup,
+    Brk,
+    Mmap,
+    Mmap2,
+    Munmap,
+    Mprotect,
+    Uname,
+    ClockGettime,
+    ClockGettime64,
+    Getrandom,
+    SetTidAddress,
+    SetRobustList,
+    RtSigaction,
+    RtSigprocmask,
+    Getpid,
+    Gettid,
+}
+
+//syscall numbers of the generic table used by riscv64 and aarch64
+const GENERIC_TABLE: &[(u64, Sys)] = &[
+    (29, Sys::Ioctl), (56, Sys::Openat), (57, Sys::Close), (63, Sys::Read), (64, Sys::Write),
+    (66, Sys::Writev), (93, Sys::Exit), (94, Sys::ExitGroup), (96, Sys::SetTidAddress),
+    (99, Sys::SetRobustList), (113, Sys::ClockGettime), (134, Sys::RtSigaction),
+    (135, Sys::RtSigprocmask), (160, Sys::Uname), (172, Sys::Getpid), (178, Sys::Gettid),
+    (214, Sys::Brk), (215, Sys::Munmap), (222, Sys::Mmap), (226, Sys::Mprotect),
+    (278, Sys::Getrandom),
+];
+
+//syscall numbers for x86_64
+const X86_64_TABLE: &[(u64, Sys)] = &[
+    (0, Sys::Read), (1, Sys::Write), (3, Sys::Close), (9, Sys::Mmap), (10, Sys::Mprotect),
+    (11, Sys::Munmap), (12, Sys::Brk), (13, Sys::RtSigaction), (14, Sys::RtSigprocmask),
+    (16, Sys::Ioctl), (20, Sys::Writev), (39, Sys::Getpid), (60, Sys::Exit), (63, Sys::Uname),
+    (186, Sys::Gettid), (218, Sys::SetTidAddress), (228, Sys::ClockGettime),
+    (231, Sys::ExitGroup), (257, Sys::Openat), (273, Sys::SetRobustList), (318, Sys::Getrandom),
+];
+
+//syscall numbers for i386
+const I386_TABLE: &[(u64, Sys)] = &[
+    (1, Sys::Exit), (3, Sys::Read), (4, Sys::Write), (6, Sys::Close), (20, Sys::Getpid),
+    (45, Sys::Brk), (54, Sys::Ioctl), (91, Sys::Munmap), (122, Sys::Uname), (125, Sys::Mprotect),
+    (146, Sys::Writev), (174, Sys::RtSigaction), (175, Sys::RtSigprocmask), (192, Sys::Mmap2),
+    (224, Sys::Gettid), (252, Sys::ExitGroup), (258, Sys::SetTidAddress),
+    (265, Sys::ClockGettime), (295, Sys::Openat), (311, Sys::SetRobustList),
+    (355, Sys::Getrandom), (403, Sys::ClockGettime64),
+];
+
+//syscall numbers for 32 bit arm EABI
+const ARM_TABLE: &[(u64, Sys)] = &[
+    (1, Sys::Exit), (3, Sys::Read), (4, Sys::Write), (6, Sys::Close), (20, Sys::Getpid),
+    (45, Sys::Brk), (54, Sys::Ioctl), (91, Sys::Munmap), (122, Sys::Uname), (125, Sys::Mprotect),
+    (146, Sys::Writev), (174, Sys::RtSigaction), (175, Sys::RtSigprocmask), (192, Sys::Mmap2),
+    (224, Sys::Gettid), (248, Sys::ExitGroup), (256, Sys::SetTidAddress),
+    (263, Sys::ClockGettime), (322, Sys::Openat), (338, Sys::SetRobustList),
+    (384, Sys::Getrandom), (403, Sys::ClockGettime64),
+];
+
+//returns the syscall number table for a machine type
+pub fn syscallTable(e_machine: u16) -> &'static [(u64, Sys)] {
+    match e_machine {
+        header::EM_X86_64                   => X86_64_TABLE,
+        header::EM_386                      => I386_TABLE,
+        header::EM_ARM                      => ARM_TABLE,
+        header::EM_RISCV | header::EM_AARCH64 => GENERIC_TABLE,
+        _                                   => &[],
+    }
+}
+
+//looks up the syscall a number refers to on a machine type
+pub fn lookupSyscall(e_machine: u16, number: u64) -> Option<Sys> {
+    syscallTable(e_machine).iter().find(|(n, _)| *n == number).map(|(_, s)| *s)
+}
+
+//view of a cpu backend's registers at the moment it trapped into the kernel
+//each backend maps these onto its own calling convention
+pub trait SyscallCpu {
+    //returns the raw syscall number
+    fn syscallNumber(&self) -> u64;
+
+    //returns argument index, starting at 0
+    fn syscallArg(&self, index: usize) -> u64;
+
+    //stores the return value, negative values are -errno
+    fn setSyscallResult(&mut self, value: i64);
+}
+
+//something that services syscalls for a cpu backend
+pub trait SyscallHandler {
+    //handles the pending syscall, returns Some(exit code) once the program has exited
+    fn syscall(&mut self, cpu: &mut dyn SyscallCpu, space: &mut addressspace::AddressSpace) -> Option<i32>;
+}
+
+//an entry of the guest file descriptor table
+pub enum Fd {
+    Stdin,
+    Stdout,
+    Stderr,
+    File(File),
+}
+
+//linux user mode emulation for a single process
+pub struct Linux {
+    //machine type used to pick the syscall table and the uname machine string
+    pub machine:     u16,
+
+    //size of a pointer in the guest, 4 or 8
+    pub wordSize:    usize,
+
+    //host directory guest paths are resolved against, None disallows opening files
+    pub sandbox:     Option<PathBuf>,
+
+    //guest file descriptors
+    pub fds:         HashMap<i64, Fd>,
+
+    //when set, writes to stdout and stderr are collected here instead of printed
+    pub output:      Option<Vec<u8>>,
+
+    //when set, writes to stderr are collected here instead, apart from stdout
+    pub errorOutput: Option<Vec<u8>>,
+
+    //start of the heap and the current program break
+    pub brkStart:    usize,
+    pub brk:         usize,
+
+    //next address handed out by mmap calls without a usable hint
+    pub mmapBase:    usize,
+
+    //state of the generator used for getrandom, fixed so runs are reproducible
+    pub randomState: u64,
+
+    //value returned by getpid and gettid
+    pub pid:         i64,
+
+    started:         Instant,
+}
+
+impl Linux {
+
+    //creates the emulated kernel state for a loaded program
+    //the heap starts at the first page after the highest PT_LOAD segment
+    //returns None when a segment or the heap would end past the address space
+    pub fn new(loader: &loader::Loader, bias: usize) -> Option<Self> {
+        let mut end = 0;
+        for p in loader.programHeaders.iter().filter(|p| p.getTYPE() == programheader::PT_LOAD) {
+            end = end.max(bias.checked_add(p.getVADDR())?.checked_add(p.getMEMSZ())?);
+        }
+        let brkStart = pageAlign(end)?;
+        let wordSize = match loader.header.e_ident.Class {
+            1 => 4,
+            _ => 8,
+        };
+
+        let mut fds = HashMap::new();
+        fds.insert(0, Fd::Stdin);
+        fds.insert(1, Fd::Stdout);
+        fds.insert(2, Fd::Stderr);
+
+        Some(Self {
+            machine:     loader.header.e_machine,
+            wordSize:    wordSize,
+            sandbox:     None,
+            fds:         fds,
+            output:      None,
+            errorOutput: None,
+            brkStart:    brkStart,
+            brk:         brkStart,
+            mmapBase:    if wordSize == 4 {0x4000_0000} else {0x20_0000_0000},
+            randomState: 0x2545f4914f6cdd1d,
+            pid:         1000,
+            started:     Instant::now(),
+        })
+    }
+
+    //maps a guest path onto the sandbox directory
+    //absolute paths are taken relative to the sandbox root and .. may not leave it. symlinks are
+    //resolved here one component at a time, absolute targets from the sandbox root, so opening
+    //the result can not follow one out of the sandbox
+    pub fn sandboxPath(&self, guestPath: &[u8]) -> Option<PathBuf> {
+        let root = self.sandbox.as_ref()?;
+        let guestPath = Path::new(std::str::from_utf8(guestPath).ok()?);
+        //components still to walk, the next one last
+        let mut pending = pathComponents(guestPath);
+        let mut path = root.clone();
+        let mut depth = 0;
+        let mut links = 0;
+        while let Some(part) = pending.pop() {
+            if part == ".." {
+                if depth == 0 {return None;}
+                path.pop();
+                depth -= 1;
+                continue;
+            }
+            path.push(&part);
+            let isLink = std::fs::symlink_metadata(&path).map(|m| m.file_type().is_symlink()).unwrap_or(false);
+            if !isLink {
+                depth += 1;
+                continue;
+            }
+            links += 1;
+            if links > MAX_SYMLINKS {return None;}
+            let target = std::fs::read_link(&path).ok()?;
+            path.pop();
+            if target.is_absolute() {
+                path = root.clone();
+                depth = 0;
+            }
+            pending.extend(pathComponents(&target));
+        }
+        Some(path)
+    }
+
+    //returns the lowest unused file descriptor
+    fn nextFd(&self) -> i64 {
+        let mut fd = 0;
+        while self.fds.contains_key(&fd) {fd += 1;}
+        fd
+    }
+
+    //sends bytes written by the guest to the descriptor
+    fn writeFd(&mut self, fd: i64, bytes: &[u8]) -> i64 {
+        match self.fds.get_mut(&fd) {
+            Some(Fd::Stdout) | Some(Fd::Stderr) => {
+                let captured = match self.errorOutput.as_mut() {
+                    Some(errors) if fd == 2 => Some(errors),
+                    _                       => self.output.as_mut(),
+                };
+                if let Some(out) = captured {
+                    out.extend_from_slice(bytes);
+                    return bytes.len() as i64;
+                }
+                let result = if fd == 2 {
+                    std::io::stderr().write_all(bytes)
+                } else {
+                    std::io::stdout().write_all(bytes)
+                };
+                match result {
+                    Ok(_)  => bytes.len() as i64,
+                    Err(_) => -EIO,
+                }
+            },
+            Some(Fd::File(f)) => match f.write(bytes) {
+                Ok(n)  => n as i64,
+                Err(_) => -EIO,
+            },
+            _ => -EBADF,
+        }
+    }
+
+    //writes len guest bytes at addr to the descriptor in bounded pieces, -EFAULT when any of
+    //them is unmapped
+    fn writeFrom(&mut self, space: &addressspace::AddressSpace, fd: i64, addr: usize, len: usize) -> i64 {
+        if !space.isMapped(addr, len) {return -EFAULT;}
+        let mut total = 0;
+        let mut cur = addr;
+        while cur < addr + len {
+            let take = (addr + len - cur).min(IO_CHUNK);
+            let bytes = match space.read(cur, take) {
+                Some(b) => b,
+                None    => return -EFAULT,
+            };
+            let n = self.writeFd(fd, &bytes);
+            if n < 0 {return if total > 0 {total} else {n};}
+            total += n;
+            if (n as usize) < take {break;}
+            cur += take;
+        }
+        total
+    }
+
+    //returns the next value of the xorshift generator behind getrandom
+    fn nextRandom(&mut self) -> u64 {
+        let mut x = self.randomState;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.randomState = x;
+        x
+    }
+
+    //converts PROT_* bits to the PF_* bits used by the address space
+    fn protToFlags(prot: u64) -> u32 {
+        let mut flags = 0;
+        if prot & PROT_READ != 0 {flags |= programheader::PF_R;}
+        if prot & PROT_WRITE != 0 {flags |= programheader::PF_W;}
+        if prot & PROT_EXEC != 0 {flags |= programheader::PF_X;}
+        flags
+    }
+
+    //finds a free page aligned range of len bytes starting the search at hint
+    //returns None when the search runs off the end of the address space
+    fn findFree(&self, space: &addressspace::AddressSpace, hint: usize, len: usize) -> Option<usize> {
+        let mut start = hint;
+        loop {
+            let end = start.checked_add(len)?;
+            match space.regions.iter().find(|r| start < r.end() && r.start < end) {
+                Some(r) => start = pageAlign(r.end())?,
+                None    => return Some(start),
+            }
+        }
+    }
+
+    fn brk(&mut self, space: &mut addressspace::AddressSpace, addr: usize) -> i64 {
+        //a break that can not be set leaves the old one, which is how brk reports failure
+        if addr < self.brkStart || addr - self.brkStart > MAX_MAPPING {return self.brk as i64;}
+        let (oldTop, newTop) = match (pageAlign(self.brk), pageAlign(addr)) {
+            (Some(o), Some(n)) => (o, n),
+            _                  => return self.brk as i64,
+        };
+        if newTop > oldTop {
+            //refuse to grow into something that is already mapped
+            if space.regions.iter().any(|r| oldTop < r.end() && r.start < newTop) {
+                return self.brk as i64;
+            }
+            if space.mapOrExtend(oldTop, newTop - oldTop, programheader::PF_R | programheader::PF_W).is_none() {
+                return self.brk as i64;
+            }
+        } else if newTop < oldTop {
+            space.unmap(newTop, oldTop - newTop);
+        }
+        self.brk = addr;
+        addr as i64
+    }
+
+    fn mmap(&mut self, space: &mut addressspace::AddressSpace, addr: usize, len: usize, prot: u64, flags: u64, fd: i64, offset: u64) -> i64 {
+        if len == 0 || offset % PAGE_SIZE as u64 != 0 {return -EINVAL;}
+        if flags & MAP_FIXED != 0 && addr % PAGE_SIZE != 0 {return -EINVAL;}
+        //without MAP_FIXED the address is only a hint, so round it down like the kernel
+        let addr = addr & !(PAGE_SIZE - 1);
+        let len = match pageAlign(len) {
+            Some(l) if l <= MAX_MAPPING => l,
+            _                           => return -ENOMEM,
+        };
+        if flags & MAP_FIXED != 0 && addr.checked_add(len).is_none() {return -EINVAL;}
+
+        //read the file contents first so a bad descriptor leaves memory untouched
+        let mut contents = vec![];
+        if flags & MAP_ANONYMOUS == 0 {
+            match self.fds.get_mut(&fd) {
+                Some(Fd::File(f)) => {
+                    if f.seek(SeekFrom::Start(offset)).is_err() {return -EINVAL;}
+                    if f.take(len as u64).read_to_end(&mut contents).is_err() {return -EIO;}
+                },
+                _ => return -EBADF,
+            }
+        }
+
+        let start = if flags & MAP_FIXED != 0 {
+            space.unmap(addr, len);
+            addr
+        } else if addr != 0 && self.findFree(space, addr, len) == Some(addr) {
+            addr
+        } else {
+            let start = match self.findFree(space, self.mmapBase, len) {
+                Some(s) => s,
+                None    => return -ENOMEM,
+            };
+            self.mmapBase = start + len;
+            start
+        };
+
+        if space.map(start, len, Self::protToFlags(prot)).is_none() {return -ENOMEM;}
+        space.write(start, &contents);
+        start as i64
+    }
+
+    fn uname(&mut self, space: &mut addressspace::AddressSpace, buf: usize) -> i64 {
+        let machine = match self.machine {
+            header::EM_X86_64  => "x86_64",
+            header::EM_386     => "i686",
+            header::EM_ARM     => "armv7l",
+            header::EM_AARCH64 => "aarch64",
+            header::EM_RISCV   => "riscv64",
+            _                  => "unknown",
+        };
+        let fields = ["Linux", "elfloader", "6.1.0", "#1 SMP", machine, "(none)"];
+        let mut bytes = vec![0u8; 65 * fields.len()];
+        for (i, f) in fields.iter().enumerate() {
+            bytes[i * 65..i * 65 + f.len()].copy_from_slice(f.as_bytes());
+        }
+        match space.write(buf, &bytes) {
+            Some(_) => 0,
+            None    => -EFAULT,
+        }
+    }
+
+    fn clockGettime(&mut self, space: &mut addressspace::AddressSpace, clock: u64, tp: usize, fieldSize: usize) -> i64 {
+        let (sec, nsec) = match clock {
+            //CLOCK_REALTIME and its coarse variant
+            0 | 5 => {
+                let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                (now.as_secs(), now.subsec_nanos() as u64)
+            },
+            _ => {
+                let elapsed = self.started.elapsed();
+                (elapsed.as_secs(), elapsed.subsec_nanos() as u64)
+            },
+        };
+        if space.writeUValue(tp, fieldSize, sec).is_none() {return -EFAULT;}
+        if space.writeUValue(tp.wrapping_add(fieldSize), fieldSize, nsec).is_none() {return -EFAULT;}
+        0
+    }
+}
+
+impl SyscallHandler for Linux {
+    fn syscall(&mut self, cpu: &mut dyn SyscallCpu, space: &mut addressspace::AddressSpace) -> Option<i32> {
+        let arg = |i: usize| cpu.syscallArg(i);
+        let (a0, a1, a2, a3, a4, a5) = (arg(0), arg(1), arg(2), arg(3), arg(4), arg(5));
+        //descriptors are ints so sign extend them from the low 32 bits
+        let fd0 = a0 as i32 as i64;
+
+        let result: i64 = match lookupSyscall(self.machine, cpu.syscallNumber()) {
+            Some(Sys::Exit) | Some(Sys::ExitGroup) => return Some(a0 as i32),
+            Some(Sys::Write) => self.writeFrom(space, fd0, a1 as usize, a2 as usize),
+            Some(Sys::Writev) if a2 as usize > IOV_MAX => -EINVAL,
+            Some(Sys::Writev) => {
+                let mut total = 0;
+                for i in 0..a2 as usize {
+                    let entry = (a1 as usize).wrapping_add(i * 2 * self.wordSize);
+                    let base = space.readUValue(entry, self.wordSize);
+                    let len = space.readUValue(entry.wrapping_add(self.wordSize), self.wordSize);
+                    let n = match (base, len) {
+                        (Some(b), Some(l)) => self.writeFrom(space, fd0, b as usize, l as usize),
+                        _                  => -EFAULT,
+                    };
+                    if n < 0 {total = n; break;}
+                    total += n;
+                }
+                total
+            },
+            //a short read is allowed, so one bounded read is enough
+            Some(Sys::Read) if !space.isMapped(a1 as usize, a2 as usize) => -EFAULT,
+            Some(Sys::Read) => {
+                let mut buffer = vec![0u8; (a2 as usize).min(IO_CHUNK)];
+                let n = match self.fds.get_mut(&fd0) {
+                    Some(Fd::Stdin)   => std::io::stdin().read(&mut buffer).map_err(|_| EIO),
+                    Some(Fd::File(f)) => f.read(&mut buffer).map_err(|_| EIO),
+                    _                 => Err(EBADF),
+                };
+                match n {
+                    Ok(n) => match space.write(a1 as usize, &buffer[..n]) {
+                        Some(_) => n as i64,
+                        None    => -EFAULT,
+                    },
+                    Err(e) => -e,
+                }
+            },
+            Some(Sys::Openat) => {
+                let path = space.readCString(a1 as usize);
+                let relative = path.as_ref().map(|p| p.first() != Some(&b'/')).unwrap_or(false);
+                if relative && fd0 != AT_FDCWD {
+                    -EBADF
+                } else {
+                    match path.and_then(|p| self.sandboxPath(&p)) {
+                        Some(hostPath) => {
+                            let mut options = OpenOptions::new();
+                            match a2 & O_ACCMODE {
+                                O_WRONLY => options.write(true),
+                                O_RDWR   => options.read(true).write(true),
+                                _        => options.read(true),
+                            };
+                            options.create(a2 & O_CREAT != 0);
+                            options.truncate(a2 & O_TRUNC != 0);
+                            options.append(a2 & O_APPEND != 0);
+                            match options.open(hostPath) {
+                                Ok(f) => {
+                                    let fd = self.nextFd();
+                                    self.fds.insert(fd, Fd::File(f));
+                                    fd
+                                },
+                                Err(e) if e.kind() == std::io::ErrorKind::NotFound         => -ENOENT,
+                                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => -EACCES,
+                                Err(_) => -EIO,
+                            }
+                        },
+                        None => if self.sandbox.is_none() {-EACCES} else {-ENOENT},
+                    }
+                }
+            },
+            Some(Sys::Close) => match self.fds.remove(&fd0) {
+                Some(_) => 0,
+                None    => -EBADF,
+            },
+            Some(Sys::Ioctl) => if self.fds.contains_key(&fd0) {-ENOTTY} else {-EBADF},
+            Some(Sys::Brk) => self.brk(space, a0 as usize),
+            Some(Sys::Mmap) => self.mmap(space, a0 as usize, a1 as usize, a2, a3, a4 as i32 as i64, a5),
+            Some(Sys::Mmap2) => self.mmap(space, a0 as usize, a1 as usize, a2, a3, a4 as i32 as i64, a5 * PAGE_SIZE as u64),
+            Some(Sys::Munmap) => match pageAlign(a1 as usize) {
+                Some(len) if a0 as usize % PAGE_SIZE == 0 => {
+                    space.unmap(a0 as usize, len);
+                    0
+                },
+                _ => -EINVAL,
+            },
+            Some(Sys::Mprotect) => match pageAlign(a1 as usize) {
+                Some(len) if a0 as usize % PAGE_SIZE == 0 => {
+                    space.protect(a0 as usize, len, Self::protToFlags(a2));
+                    0
+                },
+                _ => -EINVAL,
+            },
+            Some(Sys::Uname) => self.uname(space, a0 as usize),
+            Some(Sys::ClockGettime) => self.clockGettime(space, a0, a1 as usize, self.wordSize),
+            Some(Sys::ClockGettime64) => self.clockGettime(space, a0, a1 as usize, 8),
+            Some(Sys::Getrandom) if !space.isMapped(a0 as usize, (a1 as usize).min(MAX_RANDOM)) => -EFAULT,
+            Some(Sys::Getrandom) => {
+                let len = (a1 as usize).min(MAX_RANDOM);
+                let mut cur = a0 as usize;
+                while cur < a0 as usize + len {
+                    let mut bytes = vec![0u8; (a0 as usize + len - cur).min(IO_CHUNK)];
+                    for chunk in bytes.chunks_mut(8) {
+                        let r = self.nextRandom().to_le_bytes();
+                        let n = chunk.len();
+                        chunk.copy_from_slice(&r[..n]);
+                    }
+                    space.write(cur, &bytes);
+                    cur += bytes.len();
+                }
+                len as i64
+            },
+            Some(Sys::SetTidAddress) | Some(Sys::Getpid) | Some(Sys::Gettid) => self.pid,
+            Some(Sys::SetRobustList) | Some(Sys::RtSigaction) | Some(Sys::RtSigprocmask) => 0,
+            None => -ENOSYS,
+        };
+        cpu.setSyscallResult(result);
+        None
+    }
+}
+
+//rounds up to a whole number of pages, None when that overflows
+fn pageAlign(value: usize) -> Option<usize> {
+    Some(value.checked_add(PAGE_SIZE - 1)? & !(PAGE_SIZE - 1))
+}
+
+//the names and .. of a path in reverse order, . and the root are dropped
+fn pathComponents(path: &Path) -> Vec<OsString> {
+    path.components().rev().filter_map(|c| match c {
+        Component::Normal(p) => Some(p.to_os_string()),
+        Component::ParentDir => Some(OsString::from("..")),
+        _                    => None,
+    }).collect()
+}
@@ -0,0 +1,102 @@
+//special values of a .gnu.version entry
+pub const VER_NDX_LOCAL:  u16 = 0;
+pub const VER_NDX_GLOBAL: u16 = 1;
+
+//set in a .gnu.version entry when the version is not the default one, name@VERSION instead of name@@VERSION
+pub const VERSYM_HIDDEN:  u16 = 0x8000;
+
+//flags of a version definition or requirement
+pub const VER_FLG_BASE:   u16 = 0x1;
+pub const VER_FLG_WEAK:   u16 = 0x2;
+
+//an entry of .gnu.version_d, a version this file provides
+#[derive(Debug, Clone)]
+pub struct VersionDefinition {
+
+    //VER_FLG_BASE marks the entry naming the file itself
+    pub vd_flags: u16,
+
+    //index used by .gnu.version entries to refer to this definition
+    pub vd_ndx:   u16,
+
+    //ELF hash of the version name
+    pub vd_hash:  u32,
+
+    //the version name followed by the names of the versions it inherits from
+    pub names:    Vec<String>,
+}
+
+impl VersionDefinition {
+
+    //returns an uninitialized version definition
+    pub fn new() -> Self {
+        Self {
+            vd_flags: 0,
+            vd_ndx:   0,
+            vd_hash:  0,
+            names:    vec![],
+        }
+    }
+
+    //returns the version name
+    pub fn name(&self) -> &str {
+        self.names.first().map(|s| s.as_str()).unwrap_or("")
+    }
+}
+
+//a version required from a dependency, one vernaux entry of .gnu.version_r
+#[derive(Debug, Clone)]
+pub struct VersionNeed {
+
+    //name of the library expected to provide the version, from the parent verneed entry
+    pub file:      String,
+
+    //ELF hash of the version name
+    pub vna_hash:  u32,
+
+    //VER_FLG_WEAK if a missing version is only a warning
+    pub vna_flags: u16,
+
+    //index used by .gnu.version entries to refer to this requirement
+    pub vna_other: u16,
+
+    pub name:      String,
+}
+
+impl VersionNeed {
+
+    //returns an uninitialized version requirement
+    pub fn new() -> Self {
+        Self {
+            file:      String::new(),
+            vna_hash:  0,
+            vna_flags: 0,
+            vna_other: 0,
+            name:      String::new(),
+        }
+    }
+}
+
+//the version attached to a dynamic symbol
+#[derive(Debug, Clone, PartialEq)]
+pub struct SymbolVersion {
+    pub name:   String,
+
+    //true for name@VERSION, false for the default name@@VERSION
+    pub hidden: bool,
+
+    //library the version is required from, None when the file defines the version itself
+    pub file:   Option<String>,
+}
+
+//the hash function used for vd_hash and vna_hash
+pub fn elfHash(name: &str) -> u32 {
+    let mut h: u32 = 0;
+    for b in name.bytes() {
+        h = (h << 4).wrapping_add(b as u32);
+        let g = h & 0xf0000000;
+        if g != 0 {h ^= g >> 24;}
+        h &= !g;
+    }
+    h
+}
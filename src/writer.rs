@@ -0,0 +1,352 @@
+use crate::header;
+use crate::loader;
+use crate::programheader;
+use crate::sectionheader;
+use crate::symbol;
+
+//largest alignment honoured when a non allocated section is given a new offset
+pub const MAX_MOVED_ALIGN: usize = 0x1000;
+
+//a section with its name and contents, data is empty for SHT_NOBITS sections
+#[derive(Debug, Clone)]
+pub struct Section {
+    pub name:   String,
+    pub header: sectionheader::SectionHeader,
+    pub data:   Vec<u8>,
+}
+
+impl Section {
+
+    //returns a non allocated section that the writer will place at the end of the file
+    pub fn new(name: &str, sType: u32, data: Vec<u8>) -> Self {
+        let mut header = sectionheader::SectionHeader::new();
+        header.sh_type = sType;
+        header.sh_size = data.len();
+        header.sh_addralign = 1;
+        Self{name: name.to_string(), header: header, data: data}
+    }
+
+    pub fn isAllocated(&self) -> bool {
+        self.header.sh_flags & sectionheader::SHF_ALLOC != 0
+    }
+
+    //true if the section occupies bytes in the file
+    pub fn hasFileData(&self) -> bool {
+        self.header.sh_type != sectionheader::SHT_NOBITS && self.header.sh_type != sectionheader::SHT_NULL && !self.data.is_empty()
+    }
+}
+
+//writes integers in the class and byte order of a file
+pub struct Encoder {
+    pub bigEndian: bool,
+    pub wide:      bool,
+}
+
+impl Encoder {
+
+    pub fn u16(&self, out: &mut Vec<u8>, value: u16) {
+        out.extend_from_slice(&if self.bigEndian {value.to_be_bytes()} else {value.to_le_bytes()});
+    }
+
+    pub fn u32(&self, out: &mut Vec<u8>, value: u32) {
+        out.extend_from_slice(&if self.bigEndian {value.to_be_bytes()} else {value.to_le_bytes()});
+    }
+
+    pub fn u64(&self, out: &mut Vec<u8>, value: u64) {
+        out.extend_from_slice(&if self.bigEndian {value.to_be_bytes()} else {value.to_le_bytes()});
+    }
+
+    //writes an address sized value, 4 bytes for 32 bit files and 8 for 64 bit files
+    pub fn word(&self, out: &mut Vec<u8>, value: u64) {
+        if self.wide {self.u64(out, value)} else {self.u32(out, value as u32)}
+    }
+}
+
+//an editable ELF file that can be serialized back to bytes
+//the writer copies the original image and only rewrites the structures it knows, so bytes
+//outside the headers and sections (padding, trailing data) survive a round trip
+pub struct ElfFile {
+    pub header:   header::Header,
+    pub segments: Vec<programheader::ProgramHeader>,
+    pub sections: Vec<Section>,
+
+    //the original file bytes
+    pub image:    Vec<u8>,
+}
+
+impl ElfFile {
+
+    //takes over the headers and bytes of a loader, load or at least the header loads must have run
+    pub fn new(loader: loader::Loader) -> Self {
+        let sections = loader.sectionHeaders.iter().enumerate().map(|(i, s)| Section {
+            name:   loader.sectionName(i).unwrap_or_default(),
+            header: s.clone(),
+            data:   loader.sectionData(i).map(|d| d.to_vec()).unwrap_or_default(),
+        }).collect();
+        Self {
+            header:   loader.header,
+            segments: loader.programHeaders,
+            sections: sections,
+            image:    loader.fileVec,
+        }
+    }
+
+    //parses the headers of an ELF file, None if it is not a well formed one
+    pub fn parse(bytes: Vec<u8>) -> Option<Self> {
+        loader::Loader::validate(&bytes).ok()?;
+        let mut parser = loader::Loader::fromBytes(bytes);
+        parser.loadHeader();
+        parser.loadProgramHeaders();
+        parser.loadSectionHeaders();
+        if parser.sectionHeaders.len() != parser.header.e_shnum as usize || parser.programHeaders.len() != parser.header.e_phnum as usize {return None;}
+        Some(Self::new(parser))
+    }
+
+    pub fn encoder(&self) -> Encoder {
+        Encoder{bigEndian: self.header.e_ident.Data == 2, wide: self.header.e_ident.Class == 2}
+    }
+
+    //returns the index of the first section called name
+    pub fn findSection(&self, name: &str) -> Option<usize> {
+        self.sections.iter().position(|s| s.name == name)
+    }
+
+    //appends a section and returns its index
+    pub fn addSection(&mut self, section: Section) -> usize {
+        self.sections.push(section);
+        self.sections.len() - 1
+    }
+
+    //removes a section and renumbers the section indexes held by other headers,
+    //links to the removed section become 0. an escaped e_shstrndx is renumbered in section 0
+    pub fn removeSection(&mut self, index: usize) -> Section {
+        let removed = self.sections.remove(index);
+        let renumber = |i: u32| -> u32 {
+            if i as usize == index {0} else if i as usize > index {i - 1} else {i}
+        };
+        for s in self.sections.iter_mut() {
+            s.header.sh_link = renumber(s.header.sh_link);
+            if infoIsSection(&s.header) {s.header.sh_info = renumber(s.header.sh_info);}
+        }
+        if self.header.e_shstrndx != symbol::SHN_XINDEX {
+            self.header.e_shstrndx = renumber(self.header.e_shstrndx as u32) as u16;
+        }
+        removed
+    }
+
+    //drops the image after end and lets layout place the non allocated sections and the section
+    //header table again right after it, which shrinks the file after sections were removed
+    pub fn pack(&mut self, end: usize) {
+        self.image.truncate(end);
+        for s in self.sections.iter_mut().filter(|s| !s.isAllocated()) {
+            s.header.sh_offset = 0;
+        }
+        self.header.e_shoff = 0;
+    }
+
+    //makes the headers consistent with the sections and segments, rebuilding the section name
+    //table when a name is missing from it and giving new offsets to sections that no longer fit
+    //in place, fails when an allocated section would have to move
+    pub fn layout(&mut self) -> Result<(), String> {
+        self.layoutNames();
+        for s in self.sections.iter_mut() {
+            if s.header.sh_type != sectionheader::SHT_NOBITS && s.header.sh_type != sectionheader::SHT_NULL {s.header.sh_size = s.data.len();}
+        }
+
+        let wide = self.header.e_ident.Class == 2;
+        self.header.e_ehsize = if wide {64} else {52};
+        self.header.e_phnum = self.segments.len() as u16;
+        self.header.e_shnum = self.sections.len() as u16;
+        if !self.segments.is_empty() {self.header.e_phentsize = if wide {56} else {32};}
+        if !self.sections.is_empty() {self.header.e_shentsize = if wide {64} else {40};}
+
+        //byte ranges that are taken, sections whose bytes are already in the image at their offset stay put
+        let mut used: Vec<(usize, usize)> = vec![(0, self.header.e_ehsize as usize)];
+        let phSize = self.segments.len() * self.header.e_phentsize as usize;
+        if phSize > 0 {
+            let range = (self.header.e_phoff, self.header.e_phoff.saturating_add(phSize));
+            if used.iter().any(|u| overlaps(*u, range)) {return Err("the program header table overlaps the ELF header".to_string());}
+            used.push(range);
+        }
+        let mut changed = vec![];
+        for (i, s) in self.sections.iter().enumerate() {
+            if !s.hasFileData() {continue;}
+            let (start, end) = (s.header.sh_offset, s.header.sh_offset.saturating_add(s.data.len()));
+            if self.image.get(start..end) == Some(&s.data[..]) {used.push((start, end));} else {changed.push(i);}
+        }
+
+        //allocated sections first since they cannot move, then the rest in file order
+        changed.sort_by_key(|i| (!self.sections[*i].isAllocated(), self.sections[*i].header.sh_offset));
+        let mut moved = vec![];
+        for i in changed {
+            let s = &self.sections[i];
+            let range = (s.header.sh_offset, s.header.sh_offset.saturating_add(s.data.len()));
+            //a non allocated section may grow past the end of the file but is not left far beyond it
+            let inFile = s.isAllocated() || s.header.sh_offset <= self.image.len();
+            if s.header.sh_offset != 0 && inFile && !used.iter().any(|u| overlaps(*u, range)) {
+                used.push(range);
+            } else if s.isAllocated() {
+                return Err(format!("{} does not fit at offset {:#x} and is loaded at a fixed address", s.name, s.header.sh_offset));
+            } else {
+                moved.push(i);
+            }
+        }
+
+        let mut end = used.iter().map(|u| u.1).max().unwrap_or(0).max(self.image.len());
+        for i in moved {
+            //only non allocated sections move, a bogus huge alignment must not pad the file by gigabytes
+            let align = self.sections[i].header.sh_addralign.clamp(1, MAX_MOVED_ALIGN);
+            let offset = (end + align - 1) / align * align;
+            self.sections[i].header.sh_offset = offset;
+            end = offset + self.sections[i].data.len();
+            used.push((offset, end));
+        }
+
+        //the section header table keeps its place unless something now covers it
+        let shSize = self.sections.len() * self.header.e_shentsize as usize;
+        if shSize > 0 {
+            let range = (self.header.e_shoff, self.header.e_shoff.saturating_add(shSize));
+            if self.header.e_shoff == 0 || used.iter().any(|u| overlaps(*u, range)) {
+                let align = if wide {8} else {4};
+                self.header.e_shoff = (end + align - 1) / align * align;
+            }
+        } else {
+            self.header.e_shoff = 0;
+        }
+
+        //PT_PHDR describes the table itself
+        for p in self.segments.iter_mut().filter(|p| p.getTYPE() == programheader::PT_PHDR) {
+            let delta = self.header.e_phoff.wrapping_sub(p.getOFFSET());
+            p.setOFFSET(self.header.e_phoff);
+            p.setVADDR(p.getVADDR().wrapping_add(delta));
+            p.setPADDR(p.getPADDR().wrapping_add(delta));
+            p.setFILESZ(phSize);
+            p.setMEMSZ(phSize);
+        }
+        Ok(())
+    }
+
+    //lays the file out and returns its bytes
+    pub fn write(&mut self) -> Result<Vec<u8>, String> {
+        self.layout()?;
+        let e = self.encoder();
+        let mut out = self.image.clone();
+
+        let mut bytes = vec![];
+        self.writeHeader(&e, &mut bytes);
+        put(&mut out, 0, &bytes)?;
+
+        bytes.clear();
+        for p in self.segments.iter() {
+            e.u32(&mut bytes, p.getTYPE());
+            if e.wide {e.u32(&mut bytes, p.getFLAGS());}
+            e.word(&mut bytes, p.getOFFSET() as u64);
+            e.word(&mut bytes, p.getVADDR() as u64);
+            e.word(&mut bytes, p.getPADDR() as u64);
+            e.word(&mut bytes, p.getFILESZ() as u64);
+            e.word(&mut bytes, p.getMEMSZ() as u64);
+            if !e.wide {e.u32(&mut bytes, p.getFLAGS());}
+            e.word(&mut bytes, p.getALIGN() as u64);
+        }
+        put(&mut out, self.header.e_phoff, &bytes)?;
+
+        for s in self.sections.iter().filter(|s| s.hasFileData()) {
+            put(&mut out, s.header.sh_offset, &s.data)?;
+        }
+
+        bytes.clear();
+        for s in self.sections.iter() {
+            let h = &s.header;
+            e.u32(&mut bytes, h.sh_name);
+            e.u32(&mut bytes, h.sh_type);
+            e.word(&mut bytes, h.sh_flags as u64);
+            e.word(&mut bytes, h.sh_addr as u64);
+            e.word(&mut bytes, h.sh_offset as u64);
+            e.word(&mut bytes, h.sh_size as u64);
+            e.u32(&mut bytes, h.sh_link);
+            e.u32(&mut bytes, h.sh_info);
+            e.word(&mut bytes, h.sh_addralign as u64);
+            e.word(&mut bytes, h.sh_entsize as u64);
+        }
+        put(&mut out, self.header.e_shoff, &bytes)?;
+        Ok(out)
+    }
+
+    //serializes the ELF header, the padding of e_ident comes from the image
+    fn writeHeader(&self, e: &Encoder, out: &mut Vec<u8>) {
+        let h = &self.header;
+        //the loader reads the magic before it knows the byte order, so it is always big endian
+        out.extend_from_slice(&h.e_ident.Magic.to_be_bytes());
+        out.extend_from_slice(&[h.e_ident.Class, h.e_ident.Data, h.e_ident.Version, h.e_ident.OS_ABI, h.e_ident.ABI_Version]);
+        out.extend_from_slice(self.image.get(9..16).unwrap_or(&[0; 7]));
+        e.u16(out, h.e_type);
+        e.u16(out, h.e_machine);
+        e.u32(out, h.e_version);
+        e.word(out, h.e_entry as u64);
+        e.word(out, h.e_phoff as u64);
+        e.word(out, h.e_shoff as u64);
+        e.u32(out, h.e_flags);
+        e.u16(out, h.e_ehsize);
+        e.u16(out, h.e_phentsize);
+        e.u16(out, h.e_phnum);
+        e.u16(out, h.e_shentsize);
+        e.u16(out, h.e_shnum);
+        e.u16(out, h.e_shstrndx);
+    }
+
+    //rebuilds the section name string table if a section name is not found at its sh_name,
+    //a table that is also the string table of another section only gets the missing names appended
+    fn layoutNames(&mut self) {
+        let index = self.header.e_shstrndx as usize;
+        if index == 0 || index >= self.sections.len() {return;}
+        let table = &self.sections[index].data;
+        let current = self.sections.iter().all(|s| {
+            let start = s.header.sh_name as usize;
+            let name = table.get(start..).and_then(|t| t.iter().position(|b| *b == 0).map(|end| &t[..end]));
+            name == Some(s.name.as_bytes())
+        });
+        if current {return;}
+
+        let shared = self.sections.iter().any(|s| s.header.sh_link as usize == index);
+        let (mut table, mut offsets): (Vec<u8>, Vec<(String, u32)>) = if shared {
+            let table = self.sections[index].data.clone();
+            let offsets = self.sections.iter().filter(|s| table.get(s.header.sh_name as usize..).map(|t| t.starts_with(s.name.as_bytes()) && t.get(s.name.len()) == Some(&0)).unwrap_or(false))
+                .map(|s| (s.name.clone(), s.header.sh_name)).collect();
+            (table, offsets)
+        } else {
+            (vec![0u8], vec![(String::new(), 0)])
+        };
+        for s in self.sections.iter_mut() {
+            let offset = match offsets.iter().find(|(n, _)| *n == s.name) {
+                Some((_, o)) => *o,
+                None         => {
+                    let o = table.len() as u32;
+                    table.extend_from_slice(s.name.as_bytes());
+                    table.push(0);
+                    offsets.push((s.name.clone(), o));
+                    o
+                },
+            };
+            s.header.sh_name = offset;
+        }
+        self.sections[index].data = table;
+    }
+}
+
+//true if sh_info of a section header holds a section index
+pub fn infoIsSection(h: &sectionheader::SectionHeader) -> bool {
+    h.sh_flags & sectionheader::SHF_INFO_LINK != 0 || h.sh_type == sectionheader::SHT_REL || h.sh_type == sectionheader::SHT_RELA
+}
+
+fn overlaps(a: (usize, usize), b: (usize, usize)) -> bool {
+    a.0 < b.1 && b.0 < a.1
+}
+
+//copies bytes into out at an offset, growing it with zeros if needed
+fn put(out: &mut Vec<u8>, offset: usize, bytes: &[u8]) -> Result<(), String> {
+    if bytes.is_empty() {return Ok(());}
+    let end = offset.checked_add(bytes.len()).ok_or(format!("{} bytes at offset {:#x} run past the end of the address space", bytes.len(), offset))?;
+    if out.len() < end {out.resize(end, 0);}
+    out[offset..end].copy_from_slice(bytes);
+    Ok(())
+}
@@ -0,0 +1,493 @@
+use crate::strip;
+
+//first bytes of every .xz stream and last bytes of its footer
+const HEADER_MAGIC: [u8; 6] = [0xfd, b'7', b'z', b'X', b'Z', 0];
+const FOOTER_MAGIC: [u8; 2] = [b'Y', b'Z'];
+
+//the only filter MiniDebugInfo and plain xz use
+const FILTER_LZMA2: u64 = 0x21;
+
+//integrity checks a stream can carry
+const CHECK_NONE:  u8 = 0;
+const CHECK_CRC32: u8 = 1;
+const CHECK_CRC64: u8 = 4;
+
+//shortest match and the number of LZMA states
+const MATCH_MIN_LEN: usize = 2;
+const STATES:        usize = 12;
+
+//distance slots below this code their low bits with a bit tree, above with direct bits
+const END_POS_MODEL_INDEX: usize = 14;
+const FULL_DISTANCES:      usize = 128;
+const ALIGN_BITS:          usize = 4;
+
+//probabilities are 11 bit fixed point numbers starting at one half
+const PROBABILITY_INIT: u16 = 1024;
+
+//largest output decompress is normally allowed, a few kilobytes of LZMA2 can claim gigabytes
+pub const MAX_OUTPUT: usize = 1 << 30;
+
+//decompresses every stream of an .xz file, concatenated streams and stream padding included,
+//failing once the output would grow past limit bytes
+pub fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut out = vec![];
+    let mut pos = 0;
+    loop {
+        pos = decompressStream(data, pos, &mut out, limit)?;
+        //stream padding is a multiple of four zero bytes
+        while pos + 4 <= data.len() && data[pos..pos + 4] == [0, 0, 0, 0] {pos += 4;}
+        if pos == data.len() {return Ok(out);}
+        if !data[pos..].starts_with(&HEADER_MAGIC) {return Err(format!("garbage after the stream at {:#x}", pos));}
+    }
+}
+
+//CRC-64 with the ECMA-182 polynomial as .xz uses it
+pub fn crc64(bytes: &[u8]) -> u64 {
+    let mut crc = !0u64;
+    for b in bytes.iter() {
+        crc ^= *b as u64;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {(crc >> 1) ^ 0xc96c5795d7870f42} else {crc >> 1};
+        }
+    }
+    !crc
+}
+
+//decodes the stream at pos into out and returns the offset after its footer
+fn decompressStream(data: &[u8], pos: usize, out: &mut Vec<u8>, limit: usize) -> Result<usize, String> {
+    let header = data.get(pos..pos + 12).ok_or("truncated stream header")?;
+    if header[..6] != HEADER_MAGIC {return Err("not an xz stream".to_string());}
+    if strip::crc32(&header[6..8]) != u32le(&header[8..12]) {return Err("stream header CRC mismatch".to_string());}
+    if header[6] != 0 || header[7] > 0x0f {return Err("unsupported stream flags".to_string());}
+    let check = header[7];
+    let checkSize = match check {
+        0 => 0,
+        c => 4 << ((c - 1) / 3),
+    };
+
+    let mut pos = pos + 12;
+    let mut blocks = vec![];
+    loop {
+        let size = *data.get(pos).ok_or("truncated stream")?;
+        //a zero where a block header would start begins the index
+        if size == 0 {break;}
+        let start = out.len();
+        let blockStart = pos;
+        pos = decompressBlock(data, pos, out, limit)?;
+        //the index records the size without the padding
+        let unpaddedSize = pos - blockStart + checkSize;
+        while (pos - blockStart) % 4 != 0 {
+            if data.get(pos) != Some(&0) {return Err("bad block padding".to_string());}
+            pos += 1;
+        }
+        let stored = data.get(pos..pos + checkSize).ok_or("truncated block check")?;
+        match check {
+            CHECK_NONE  => {},
+            CHECK_CRC32 => if strip::crc32(&out[start..]) != u32le(stored) {return Err("block CRC-32 mismatch".to_string());},
+            CHECK_CRC64 => if crc64(&out[start..]).to_le_bytes() != stored {return Err("block CRC-64 mismatch".to_string());},
+            //SHA-256 and the reserved checks are skipped, the data is still decoded
+            _           => {},
+        }
+        pos += checkSize;
+        blocks.push((unpaddedSize, out.len() - start));
+    }
+
+    //the index repeats the block sizes, a mismatch means the stream was cut or spliced
+    let indexStart = pos;
+    pos += 1;
+    let count = varint(data, &mut pos)?;
+    if count != blocks.len() as u64 {return Err("index does not match the blocks".to_string());}
+    for (unpadded, uncompressed) in blocks.iter() {
+        if varint(data, &mut pos)? != *unpadded as u64 || varint(data, &mut pos)? != *uncompressed as u64 {
+            return Err("index does not match the blocks".to_string());
+        }
+    }
+    while (pos - indexStart) % 4 != 0 {pos += 1;}
+    let crc = data.get(pos..pos + 4).ok_or("truncated index")?;
+    if strip::crc32(&data[indexStart..pos]) != u32le(crc) {return Err("index CRC mismatch".to_string());}
+    pos += 4;
+
+    let footer = data.get(pos..pos + 12).ok_or("truncated stream footer")?;
+    if footer[10..] != FOOTER_MAGIC || footer[8..10] != header[6..8] {return Err("bad stream footer".to_string());}
+    if strip::crc32(&footer[4..10]) != u32le(&footer[..4]) {return Err("stream footer CRC mismatch".to_string());}
+    if (u32le(&footer[4..8]) as usize + 1) * 4 != pos - indexStart {return Err("stream footer does not match the index".to_string());}
+    Ok(pos + 12)
+}
+
+//decodes the block at pos and returns the offset after its compressed data
+fn decompressBlock(data: &[u8], pos: usize, out: &mut Vec<u8>, limit: usize) -> Result<usize, String> {
+    let headerSize = (data[pos] as usize + 1) * 4;
+    let header = data.get(pos..pos + headerSize).ok_or("truncated block header")?;
+    if strip::crc32(&header[..headerSize - 4]) != u32le(&header[headerSize - 4..]) {return Err("block header CRC mismatch".to_string());}
+    let flags = header[1];
+    if flags & 0x3c != 0 {return Err("unsupported block flags".to_string());}
+    let mut p = 2;
+    let compressedSize = if flags & 0x40 != 0 {Some(varint(header, &mut p)?)} else {None};
+    let uncompressedSize = if flags & 0x80 != 0 {Some(varint(header, &mut p)?)} else {None};
+    let filters = (flags & 3) as usize + 1;
+    let mut dictionary = 0;
+    for i in 0..filters {
+        let id = varint(header, &mut p)?;
+        let size = varint(header, &mut p)? as usize;
+        let properties = p.checked_add(size).and_then(|end| header.get(p..end)).ok_or("truncated filter flags")?;
+        p += size;
+        if id != FILTER_LZMA2 || i + 1 != filters || size != 1 {
+            return Err(format!("unsupported filter {:#x}", id));
+        }
+        dictionary = properties[0];
+    }
+    if dictionary > 40 {return Err("bad LZMA2 dictionary size".to_string());}
+
+    let start = out.len();
+    let dataStart = pos + headerSize;
+    let end = decodeLzma2(data, dataStart, out, limit)?;
+    if compressedSize.map_or(false, |s| s != (end - dataStart) as u64) || uncompressedSize.map_or(false, |s| s != (out.len() - start) as u64) {
+        return Err("block sizes do not match its header".to_string());
+    }
+    Ok(end)
+}
+
+//decodes LZMA2 chunks until the end marker, returns the offset after it
+fn decodeLzma2(data: &[u8], mut pos: usize, out: &mut Vec<u8>, limit: usize) -> Result<usize, String> {
+    let mut decoder: Option<LzmaDecoder> = None;
+    //matches may not reach back past the last dictionary reset
+    let mut dictionaryStart = out.len();
+    let mut first = true;
+    loop {
+        let control = *data.get(pos).ok_or("truncated LZMA2 data")?;
+        pos += 1;
+        if control == 0 {return Ok(pos);}
+        let size16 = |at: usize| data.get(at..at + 2).map(|b| u16::from_be_bytes([b[0], b[1]]) as usize + 1).ok_or("truncated LZMA2 chunk");
+        let tooLarge = || format!("decompressed data is larger than {} bytes", limit);
+
+        //uncompressed chunk, 1 also resets the dictionary
+        if control == 1 || control == 2 {
+            if control == 1 {dictionaryStart = out.len();} else if first {return Err("LZMA2 stream does not start with a dictionary reset".to_string());}
+            let size = size16(pos)?;
+            if out.len() + size > limit {return Err(tooLarge());}
+            out.extend_from_slice(data.get(pos + 2..pos + 2 + size).ok_or("truncated LZMA2 chunk")?);
+            pos += 2 + size;
+            first = false;
+            continue;
+        }
+        if control < 0x80 {return Err(format!("bad LZMA2 control byte {:#x}", control));}
+
+        let unpacked = ((control as usize & 0x1f) << 16) + size16(pos)?;
+        let packed = size16(pos + 2)?;
+        if out.len() + unpacked > limit {return Err(tooLarge());}
+        pos += 4;
+        let reset = (control >> 5) & 3;
+        if reset == 3 {dictionaryStart = out.len();} else if first {return Err("LZMA2 stream does not start with a dictionary reset".to_string());}
+        if reset >= 2 {
+            let properties = *data.get(pos).ok_or("truncated LZMA2 chunk")?;
+            pos += 1;
+            decoder = Some(LzmaDecoder::new(properties)?);
+        }
+        let decoder = decoder.as_mut().ok_or("LZMA chunk without properties")?;
+        if reset >= 1 {decoder.reset();}
+        let chunk = data.get(pos..pos + packed).ok_or("truncated LZMA2 chunk")?;
+        decoder.decode(chunk, out, dictionaryStart, unpacked)?;
+        pos += packed;
+        first = false;
+    }
+}
+
+//reads the binary fractions the LZMA coder writes
+struct RangeDecoder<'a> {
+    data:  &'a [u8],
+    pos:   usize,
+    range: u32,
+    code:  u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+
+    fn new(data: &'a [u8]) -> Result<Self, String> {
+        if data.len() < 5 || data[0] != 0 {return Err("bad range coder start".to_string());}
+        let code = u32::from_be_bytes([data[1], data[2], data[3], data[4]]);
+        Ok(Self{data: data, pos: 5, range: 0xffffffff, code: code})
+    }
+
+    fn normalize(&mut self) {
+        if self.range < 1 << 24 {
+            self.range <<= 8;
+            //reading past the chunk is caught afterwards by comparing positions
+            self.code = (self.code << 8) | *self.data.get(self.pos).unwrap_or(&0) as u32;
+            self.pos += 1;
+        }
+    }
+
+    fn bit(&mut self, probability: &mut u16) -> usize {
+        let bound = (self.range >> 11) * *probability as u32;
+        let bit = if self.code < bound {
+            self.range = bound;
+            *probability += (2048 - *probability) >> 5;
+            0
+        } else {
+            self.range -= bound;
+            self.code -= bound;
+            *probability -= *probability >> 5;
+            1
+        };
+        self.normalize();
+        bit
+    }
+
+    //bits with a fixed probability of one half
+    fn direct(&mut self, count: usize) -> usize {
+        let mut result = 0;
+        for _ in 0..count {
+            self.range >>= 1;
+            let bit = if self.code >= self.range {
+                self.code -= self.range;
+                1
+            } else {
+                0
+            };
+            self.normalize();
+            result = (result << 1) | bit;
+        }
+        result
+    }
+
+    //a number of count bits, most significant first, each modelled by the bits above it
+    fn tree(&mut self, probabilities: &mut [u16], count: usize) -> usize {
+        let mut m = 1;
+        for _ in 0..count {
+            m = (m << 1) | self.bit(&mut probabilities[m]);
+        }
+        m - (1 << count)
+    }
+
+    //like tree, least significant bit first
+    fn reverseTree(&mut self, probabilities: &mut [u16], count: usize) -> usize {
+        let mut m = 1;
+        let mut result = 0;
+        for i in 0..count {
+            let bit = self.bit(&mut probabilities[m]);
+            m = (m << 1) | bit;
+            result |= bit << i;
+        }
+        result
+    }
+}
+
+//match lengths, 2 to 273
+#[derive(Clone)]
+struct LengthDecoder {
+    choice:  u16,
+    choice2: u16,
+    low:     Vec<[u16; 8]>,
+    mid:     Vec<[u16; 8]>,
+    high:    Vec<u16>,
+}
+
+impl LengthDecoder {
+
+    fn new() -> Self {
+        Self {
+            choice:  PROBABILITY_INIT,
+            choice2: PROBABILITY_INIT,
+            low:     vec![[PROBABILITY_INIT; 8]; 16],
+            mid:     vec![[PROBABILITY_INIT; 8]; 16],
+            high:    vec![PROBABILITY_INIT; 256],
+        }
+    }
+
+    //the length minus MATCH_MIN_LEN
+    fn decode(&mut self, rc: &mut RangeDecoder, posState: usize) -> usize {
+        if rc.bit(&mut self.choice) == 0 {return rc.tree(&mut self.low[posState], 3);}
+        if rc.bit(&mut self.choice2) == 0 {return 8 + rc.tree(&mut self.mid[posState], 3);}
+        16 + rc.tree(&mut self.high, 8)
+    }
+}
+
+//the LZMA model that LZMA2 chunks share until a state reset
+struct LzmaDecoder {
+    //literal context bits, literal position bits and position bits from the properties byte
+    lc:           usize,
+    lp:           usize,
+    pb:           usize,
+
+    state:        usize,
+
+    //the last four match distances, rep[0] the most recent
+    rep:          [usize; 4],
+
+    isMatch:      Vec<u16>,
+    isRep:        Vec<u16>,
+    isRepG0:      Vec<u16>,
+    isRepG1:      Vec<u16>,
+    isRepG2:      Vec<u16>,
+    isRep0Long:   Vec<u16>,
+    literal:      Vec<u16>,
+    posSlot:      Vec<[u16; 64]>,
+    posDecoders:  Vec<u16>,
+    align:        Vec<u16>,
+    length:       LengthDecoder,
+    repLength:    LengthDecoder,
+}
+
+impl LzmaDecoder {
+
+    fn new(properties: u8) -> Result<Self, String> {
+        let properties = properties as usize;
+        if properties >= 9 * 5 * 5 {return Err("bad LZMA properties".to_string());}
+        let (lc, lp, pb) = (properties % 9, properties / 9 % 5, properties / 45);
+        if lc + lp > 4 {return Err("bad LZMA properties".to_string());}
+        let mut decoder = Self {
+            lc: lc, lp: lp, pb: pb,
+            state:       0,
+            rep:         [0; 4],
+            isMatch:     vec![],
+            isRep:       vec![],
+            isRepG0:     vec![],
+            isRepG1:     vec![],
+            isRepG2:     vec![],
+            isRep0Long:  vec![],
+            literal:     vec![],
+            posSlot:     vec![],
+            posDecoders: vec![],
+            align:       vec![],
+            length:      LengthDecoder::new(),
+            repLength:   LengthDecoder::new(),
+        };
+        decoder.reset();
+        Ok(decoder)
+    }
+
+    //forgets the state and every probability but keeps the properties
+    fn reset(&mut self) {
+        self.state = 0;
+        self.rep = [0; 4];
+        self.isMatch = vec![PROBABILITY_INIT; STATES << 4];
+        self.isRep = vec![PROBABILITY_INIT; STATES];
+        self.isRepG0 = vec![PROBABILITY_INIT; STATES];
+        self.isRepG1 = vec![PROBABILITY_INIT; STATES];
+        self.isRepG2 = vec![PROBABILITY_INIT; STATES];
+        self.isRep0Long = vec![PROBABILITY_INIT; STATES << 4];
+        self.literal = vec![PROBABILITY_INIT; 0x300 << (self.lc + self.lp)];
+        self.posSlot = vec![[PROBABILITY_INIT; 64]; 4];
+        self.posDecoders = vec![PROBABILITY_INIT; 1 + FULL_DISTANCES - END_POS_MODEL_INDEX];
+        self.align = vec![PROBABILITY_INIT; 1 << ALIGN_BITS];
+        self.length = LengthDecoder::new();
+        self.repLength = LengthDecoder::new();
+    }
+
+    //decodes one chunk of exactly unpacked bytes, out from dictionaryStart on is the dictionary
+    fn decode(&mut self, chunk: &[u8], out: &mut Vec<u8>, dictionaryStart: usize, unpacked: usize) -> Result<(), String> {
+        let mut rc = RangeDecoder::new(chunk)?;
+        let end = out.len() + unpacked;
+        while out.len() < end {
+            let position = out.len() - dictionaryStart;
+            let posState = position & ((1 << self.pb) - 1);
+
+            if rc.bit(&mut self.isMatch[(self.state << 4) + posState]) == 0 {
+                let previous = if position > 0 {out[out.len() - 1] as usize} else {0};
+                let context = ((position & ((1 << self.lp) - 1)) << self.lc) + (previous >> (8 - self.lc));
+                let probabilities = &mut self.literal[0x300 * context..0x300 * (context + 1)];
+                let mut symbol = 1;
+                //after a match the byte that followed it guides the model until they differ
+                if self.state >= 7 && self.rep[0] < position {
+                    let mut matchByte = out[out.len() - self.rep[0] - 1] as usize;
+                    while symbol < 0x100 {
+                        let matchBit = (matchByte >> 7) & 1;
+                        matchByte <<= 1;
+                        let bit = rc.bit(&mut probabilities[((1 + matchBit) << 8) + symbol]);
+                        symbol = (symbol << 1) | bit;
+                        if matchBit != bit {break;}
+                    }
+                }
+                while symbol < 0x100 {
+                    symbol = (symbol << 1) | rc.bit(&mut probabilities[symbol]);
+                }
+                out.push((symbol - 0x100) as u8);
+                self.state = if self.state < 4 {0} else if self.state < 10 {self.state - 3} else {self.state - 6};
+                continue;
+            }
+
+            let length;
+            if rc.bit(&mut self.isRep[self.state]) != 0 {
+                if position == 0 {return Err("LZMA repeat match before any data".to_string());}
+                if rc.bit(&mut self.isRepG0[self.state]) == 0 {
+                    //a single byte from the last distance
+                    if rc.bit(&mut self.isRep0Long[(self.state << 4) + posState]) == 0 {
+                        self.state = if self.state < 7 {9} else {11};
+                        let byte = out[out.len() - self.rep[0] - 1];
+                        out.push(byte);
+                        continue;
+                    }
+                } else {
+                    let distance;
+                    if rc.bit(&mut self.isRepG1[self.state]) == 0 {
+                        distance = self.rep[1];
+                    } else {
+                        if rc.bit(&mut self.isRepG2[self.state]) == 0 {
+                            distance = self.rep[2];
+                        } else {
+                            distance = self.rep[3];
+                            self.rep[3] = self.rep[2];
+                        }
+                        self.rep[2] = self.rep[1];
+                    }
+                    self.rep[1] = self.rep[0];
+                    self.rep[0] = distance;
+                }
+                length = self.repLength.decode(&mut rc, posState);
+                self.state = if self.state < 7 {8} else {11};
+            } else {
+                self.rep[3] = self.rep[2];
+                self.rep[2] = self.rep[1];
+                self.rep[1] = self.rep[0];
+                length = self.length.decode(&mut rc, posState);
+                self.state = if self.state < 7 {7} else {10};
+                self.rep[0] = self.distance(&mut rc, length);
+                //LZMA2 chunks know their size and never carry the end marker
+                if self.rep[0] == 0xffffffff {return Err("unexpected LZMA end marker".to_string());}
+            }
+
+            let length = length + MATCH_MIN_LEN;
+            if self.rep[0] >= position {return Err("LZMA match reaches before the dictionary".to_string());}
+            if out.len() + length > end {return Err("LZMA match runs past the chunk".to_string());}
+            let from = out.len() - self.rep[0] - 1;
+            //byte by byte since a match may overlap the bytes it produces
+            for i in 0..length {
+                let byte = out[from + i];
+                out.push(byte);
+            }
+        }
+        if rc.pos != chunk.len() {return Err("LZMA chunk size mismatch".to_string());}
+        Ok(())
+    }
+
+    //decodes the distance of a new match, length is the match length minus MATCH_MIN_LEN
+    fn distance(&mut self, rc: &mut RangeDecoder, length: usize) -> usize {
+        let slot = rc.tree(&mut self.posSlot[length.min(3)], 6);
+        if slot < 4 {return slot;}
+        let directBits = (slot >> 1) - 1;
+        let mut distance = (2 | (slot & 1)) << directBits;
+        if slot < END_POS_MODEL_INDEX {
+            distance += rc.reverseTree(&mut self.posDecoders[distance - slot..], directBits);
+        } else {
+            distance += rc.direct(directBits - ALIGN_BITS) << ALIGN_BITS;
+            distance += rc.reverseTree(&mut self.align, ALIGN_BITS);
+        }
+        distance & 0xffffffff
+    }
+}
+
+//reads a multibyte integer, seven bits per byte, least significant first
+fn varint(data: &[u8], pos: &mut usize) -> Result<u64, String> {
+    let mut value = 0;
+    for i in 0..9 {
+        let b = *data.get(*pos).ok_or("truncated integer")?;
+        *pos += 1;
+        value |= ((b & 0x7f) as u64) << (7 * i);
+        if b & 0x80 == 0 {return Ok(value);}
+    }
+    Err("integer too long".to_string())
+}
+
+fn u32le(bytes: &[u8]) -> u32 {
+    u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])
+}